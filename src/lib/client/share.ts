@@ -0,0 +1,35 @@
+import { decode_share_state, encode_share_state } from '$lib/wasm/pkg';
+
+// mirrors `ShareState` in wasm/src/share.rs
+export type ShareState = {
+  shader: string;
+  parameters: { [name: string]: number[] };
+  time?: number;
+  resolution?: [number, number];
+  camera?: { position: number[]; target: number[]; fov: number };
+  unknown?: { tag: number; data: number[] }[];
+};
+
+export const SHARE_PARAM = 's';
+
+// wasm must be initialized before calling these
+export const readShareState = (url: URL): ShareState | null => {
+  const encoded = url.searchParams.get(SHARE_PARAM);
+
+  if (encoded === null) {
+    return null;
+  }
+
+  try {
+    return JSON.parse(decode_share_state(encoded));
+  } catch (err) {
+    console.warn('ignoring invalid share state:', err);
+    return null;
+  }
+};
+
+export const shareUrl = (url: URL, state: ShareState) => {
+  const shared = new URL(url);
+  shared.searchParams.set(SHARE_PARAM, encode_share_state(JSON.stringify(state), true));
+  return shared;
+};
@@ -2,8 +2,17 @@
 precision highp float;
 precision highp int;
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 int channel;
 const float PI = 3.1415926;
 
@@ -132,7 +141,7 @@ float fbm21(vec2 p, float g) {
 }
 
 float base21(vec2 p) {
-    return mod(u_time, 20.0) < 10.0 ? fbm21(p, 0.5) : pnoise21(p);
+    return mod(frame.time, 20.0) < 10.0 ? fbm21(p, 0.5) : pnoise21(p);
 }
 
 float warp21(vec2 p, float g) {
@@ -146,7 +155,7 @@ float warp21(vec2 p, float g) {
 }
 
 vec3 blend(float a, float b) {
-    float time = abs(mod(0.1 * u_time, 2.0) - 1.0);
+    float time = abs(mod(0.1 * frame.time, 2.0) - 1.0);
 
     vec3[2] col2 = vec3[](
         vec3(a, a, 1),
@@ -165,10 +174,10 @@ vec3 blend(float a, float b) {
 }
 
 void main() {
-    vec2 pos = gl_FragCoord.xy / min(u_resolution.x, u_resolution.y);
+    vec2 pos = gl_FragCoord.xy / min(frame.resolution.x, frame.resolution.y);
 
-    channel = int(2.0 * gl_FragCoord.x / u_resolution.x);
-    pos = 10.0 * pos + u_time;
+    channel = int(2.0 * gl_FragCoord.x / frame.resolution.x);
+    pos = 10.0 * pos + frame.time;
 
     float a = warp21(pos, 1.0);
     float b = warp21(pos + 10.0, 1.0);
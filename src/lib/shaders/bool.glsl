@@ -2,8 +2,17 @@
 precision highp float;
 precision highp int;
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 int channel;
 const float PI = 3.1415926;
 const float TAU = 6.2831853;
@@ -98,7 +107,7 @@ float fbm21(vec2 p, float g){
     return 0.5 * val + 0.5;
 }
 float base21(vec2 p){
-    return mod(u_time, 20.0) < 10.0 ?
+    return mod(frame.time, 20.0) < 10.0 ?
     fbm21(p, 0.5) : 
     pnoise21(p);
 }
@@ -111,9 +120,9 @@ float warp21(vec2 p, float g){
 }
 
 void main(){
-    vec2 pos = gl_FragCoord.xy/min(u_resolution.x, u_resolution.y);
-    channel = int(2.0 * gl_FragCoord.x / u_resolution.x);
-    pos = 10.0 * pos + u_time;
+    vec2 pos = gl_FragCoord.xy/min(frame.resolution.x, frame.resolution.y);
+    channel = int(2.0 * gl_FragCoord.x / frame.resolution.x);
+    pos = 10.0 * pos + frame.time;
     vec2 f = vec2(warp21(pos, 1.0), warp21(pos + 10.0, 1.0));
     f -= 0.5;
     vec4 x;
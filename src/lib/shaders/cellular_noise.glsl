@@ -2,8 +2,17 @@
 precision highp float;
 precision highp int;
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 ivec2 channel;
 
 uvec3 k = uvec3(0x456789abu, 0x6789ab45u, 0x89ab4567u);
@@ -114,13 +123,13 @@ float cnoise31(vec3 p) {
 }
 
 void main() {
-    vec2 pos = gl_FragCoord.xy / min(u_resolution.x, u_resolution.y);
-    channel = ivec2(vec2(3, 2) * gl_FragCoord.xy / u_resolution.xy); 
+    vec2 pos = gl_FragCoord.xy / min(frame.resolution.x, frame.resolution.y);
+    channel = ivec2(vec2(3, 2) * gl_FragCoord.xy / frame.resolution.xy); 
     pos *= 10.0;
-    pos += u_time;
+    pos += frame.time;
     wt = channel.x == 0 ? vec4(0.2) :
          channel.x == 1 ? vec4(0.5, -1.0, 1.4, -0.1) :
          vec4(-0.3, -0.5, -1.2, 1.0);
-    fragColor = vec4(channel.y == 0 ? cnoise21(pos) : cnoise31(vec3(pos, u_time)));
+    fragColor = vec4(channel.y == 0 ? cnoise21(pos) : cnoise31(vec3(pos, frame.time)));
     fragColor.a = 1.0;
 }
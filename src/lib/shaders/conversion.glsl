@@ -2,8 +2,17 @@
 precision highp float;
 precision highp int;
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 ivec2 channel;
 const float PI = 3.1415926;
 
@@ -97,7 +106,7 @@ float fbm21(vec2 p, float g){
     return 0.5 * val + 0.5;
 }
 float base21(vec2 p){
-    return mod(u_time, 20.0) < 10.0 ?
+    return mod(frame.time, 20.0) < 10.0 ?
     fbm21(p, 0.5) : 
     pnoise21(p);
 }
@@ -109,20 +118,20 @@ float warp21(vec2 p, float g){
     return val;
 }
 float converter(float v){
-    float time = abs(mod(0.1 * u_time, 2.0) - 1.0);
+    float time = abs(mod(0.1 * frame.time, 2.0) - 1.0);
     float n = floor(8.0 * time);
     return channel == ivec2(1, 0) ? step(time, v) : 
         channel == ivec2(2, 0) ? (floor(n * v) + step(0.5, fract (n * v))) / n :
         channel == ivec2(0, 1) ? smoothstep(0.5 * (1.0 - time), 0.5 * (1.0 + time), v): 
         channel == ivec2(1, 1) ? pow(v, 2.0 * time) : 
-        channel == ivec2(2, 1) ? 0.5 * sin(4.0 *  PI * v +  u_time) + 0.5 :
+        channel == ivec2(2, 1) ? 0.5 * sin(4.0 *  PI * v +  frame.time) + 0.5 :
         v;
 }
 
 void main(){
-    vec2 pos = gl_FragCoord.xy/min(u_resolution.x, u_resolution.y);
-    channel = ivec2(vec2(3, 2) * gl_FragCoord.xy / u_resolution.xy);
-    pos = 10.0 * pos + u_time;
+    vec2 pos = gl_FragCoord.xy/min(frame.resolution.x, frame.resolution.y);
+    channel = ivec2(vec2(3, 2) * gl_FragCoord.xy / frame.resolution.xy);
+    pos = 10.0 * pos + frame.time;
     fragColor.rgb = vec3(converter(warp21(pos, 1.0)));
     fragColor.a = 1.0;
 }
\ No newline at end of file
@@ -2,8 +2,17 @@
 precision highp float;
 precision highp int;
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 
 uvec3 k = uvec3(0x456789abu, 0x6789ab45u, 0x89ab4567u);
 uvec3 u = uvec3(1, 2, 3);
@@ -57,7 +66,7 @@ float fdist(vec2 p) {
     for(float j = - 2.0; j <= 2.0; j++) {
         for(float i = - 2.0; i <= 2.0; i++) {
             vec2 grid = n + vec2(i, j);
-            vec2 jitter = sin(u_time) * (hash22(grid) - 0.5);
+            vec2 jitter = sin(frame.time) * (hash22(grid) - 0.5);
 
             dist = min(dist, distance(grid + jitter, p));
         }
@@ -66,9 +75,9 @@ float fdist(vec2 p) {
 }
 
 void main() {
-    vec2 pos = gl_FragCoord.xy / min(u_resolution.x, u_resolution.y);
+    vec2 pos = gl_FragCoord.xy / min(frame.resolution.x, frame.resolution.y);
     pos *= 10.0;
-    pos += u_time;
+    pos += frame.time;
     fragColor = vec4(fdist(pos));
     fragColor.a = 1.0;
 }
@@ -2,8 +2,17 @@
 precision highp float;
 precision highp int;
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 int channel;
 
 uvec3 k = uvec3(0x456789abu, 0x6789ab45u, 0x89ab4567u);
@@ -83,10 +92,10 @@ vec2 grad(vec2 p) {
 }
 
 void main(){
-    vec2 pos = gl_FragCoord.xy / min(u_resolution.x, u_resolution.y);
-    channel = int(2.0 * gl_FragCoord.x / u_resolution.x); 
+    vec2 pos = gl_FragCoord.xy / min(frame.resolution.x, frame.resolution.y);
+    channel = int(2.0 * gl_FragCoord.x / frame.resolution.x); 
     pos *= 10.0;
-    pos += u_time;
+    pos += frame.time;
     fragColor = channel == 0 ? vec4(fdist(pos)) : vec4(grad(pos), 1.0, 1.0);
     fragColor.a = 1.0;
 }
@@ -2,8 +2,17 @@
 precision highp float;
 precision highp int;
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 int channel;
 
 // DEF hash 
@@ -106,9 +115,9 @@ float fbm21(vec2 p, float g){
 }
 
 void main(){
-    vec2 pos = gl_FragCoord.xy / min(u_resolution.x, u_resolution.y);
-    channel = int(2.0 * gl_FragCoord.x / u_resolution.x);
-    pos = 10.0 * pos + u_time;
-    float g = abs(mod(0.2 * u_time, 2.0) - 1.0);
+    vec2 pos = gl_FragCoord.xy / min(frame.resolution.x, frame.resolution.y);
+    channel = int(2.0 * gl_FragCoord.x / frame.resolution.x);
+    pos = 10.0 * pos + frame.time;
+    float g = abs(mod(0.2 * frame.time, 2.0) - 1.0);
     fragColor = vec4(vec3(fbm21(pos, g)), 1.0);
 }
@@ -3,12 +3,21 @@ precision highp float;
 
 out vec4 fragColor;
 
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 
 
 void main() {
-    vec2 pos = gl_FragCoord.xy / u_resolution.xy;
+    vec2 pos = gl_FragCoord.xy / frame.resolution.xy;
 
-    fragColor = vec4(abs(sin(u_time)), pos, 1.0);
+    fragColor = vec4(abs(sin(frame.time)), pos, 1.0);
 }
@@ -4,8 +4,17 @@ precision highp float;
 precision highp int;
 
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 
 int channel;
 
@@ -63,9 +72,9 @@ float gnoise21(vec2 p) {
 }
 
 void main(){
-    vec2 pos = gl_FragCoord.xy / min(u_resolution.x, u_resolution.y);
-    channel = int(gl_FragCoord.x * 2.0 / u_resolution.x);
-    pos = 10.0 * pos + u_time;
+    vec2 pos = gl_FragCoord.xy / min(frame.resolution.x, frame.resolution.y);
+    channel = int(gl_FragCoord.x * 2.0 / frame.resolution.x);
+    pos = 10.0 * pos + frame.time;
     float v = gnoise21(pos);
 
     if (v > 0.85 || v < 0.15){
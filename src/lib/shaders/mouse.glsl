@@ -3,19 +3,26 @@ precision highp float;
 
 out vec4 fragColor;
 
-uniform float u_time;
-uniform vec2 u_resolution;
-uniform vec2 u_mouse_pos;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 
 
 void main() {
-    vec2 pos = gl_FragCoord.xy / u_resolution.xy;
-    vec2 mouse;
-    mouse.x = u_mouse_pos.x / u_resolution.x;
-    mouse.y = (u_resolution.y - u_mouse_pos.y) / u_resolution.y; //0.5;
+    vec2 pos = gl_FragCoord.xy / frame.resolution.xy;
+    // from the bottom left, as gl_FragCoord
+    vec2 mouse = frame.mouse.xy / frame.resolution.xy;
 
     float r = mouse.x >= pos.x ? 1.0 : 0.0;
     float g = mouse.y >= pos.y ? 1.0 : 0.0;
 
-    fragColor = vec4(r, g, abs(sin(u_time)), 1.0);
+    fragColor = vec4(r, g, abs(sin(frame.time)), 1.0);
 }
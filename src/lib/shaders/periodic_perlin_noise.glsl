@@ -2,8 +2,17 @@
 precision highp float;
 precision highp int;
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 
 const float PI = 3.1415926;
 
@@ -92,10 +101,10 @@ float periodicNoise31(vec3 p, float period) {
 }
 
 void main() {
-    vec2 pos = gl_FragCoord.xy / u_resolution.xy;
+    vec2 pos = gl_FragCoord.xy / frame.resolution.xy;
     pos = 2.0 * pos.xy - vec2(1.0);
     pos = xy2pol(pos);
-    pos = vec2(5.0 / PI, 5.0) * pos + u_time;
+    pos = vec2(5.0 / PI, 5.0) * pos + frame.time;
 
     fragColor = vec4(periodicNoise21(pos, 10.0));
     fragColor.a = 1.0;
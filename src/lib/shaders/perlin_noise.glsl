@@ -3,8 +3,17 @@ precision highp float;
 precision highp int;
 
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 
 ivec2 channel;
 
@@ -192,15 +201,15 @@ float pnoise31(vec3 p) {
 }
 
 void main(){
-    vec2 pos = gl_FragCoord.xy / min(u_resolution.x, u_resolution.y);
-    pos = 10.0 * pos + u_time;
-    channel = ivec2(2.0 * gl_FragCoord.xy / u_resolution.xy);
+    vec2 pos = gl_FragCoord.xy / min(frame.resolution.x, frame.resolution.y);
+    pos = 10.0 * pos + frame.time;
+    channel = ivec2(2.0 * gl_FragCoord.xy / frame.resolution.xy);
 
     float v = channel[0] == 0 ? 
         channel[1] == 0 ? gnoise21(pos) :
-        gnoise31(vec3(pos, u_time)) :
+        gnoise31(vec3(pos, frame.time)) :
         channel[1] == 0 ? pnoise21(pos) : 
-        pnoise31(vec3(pos, u_time));
+        pnoise31(vec3(pos, frame.time));
 
     fragColor.rgb = vec3(v);
     fragColor.a = 1.0;
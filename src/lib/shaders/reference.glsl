@@ -3,8 +3,17 @@ precision highp float;
 precision highp int;
 
 out vec4 fragColor;
-uniform float u_time;
-uniform vec2 u_resolution;
+layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
 
 ivec2 channel;
 
@@ -168,23 +177,23 @@ float gnoise31(vec3 p){
 }
 
 void main(){
-    vec2 pos = gl_FragCoord.xy / min(u_resolution.x, u_resolution.y);
-    pos = 20.0 * pos + u_time;
+    vec2 pos = gl_FragCoord.xy / min(frame.resolution.x, frame.resolution.y);
+    pos = 20.0 * pos + frame.time;
 
-    channel = ivec2(2.0 * gl_FragCoord.xy / u_resolution.xy);
+    channel = ivec2(2.0 * gl_FragCoord.xy / frame.resolution.xy);
     float v;
 
     if (channel[0] == 0){  // left ... value noise
         if (channel[1] == 0){  // left bottom
             v = vnoise21(pos);
         } else {  // left top
-            v = vnoise31(vec3(pos, u_time));
+            v = vnoise31(vec3(pos, frame.time));
         }
     } else{  // right ... gradient noise
         if (channel[1] == 0){  // right bottom
             v = gnoise21(pos);
         } else {  // right top
-            v = gnoise31(vec3(pos, u_time));
+            v = gnoise31(vec3(pos, frame.time));
         }
     }
 
@@ -0,0 +1,555 @@
+// Cameras and the controllers that move them.
+//
+// A `Camera` is a position, a target and a projection. Controllers turn
+// pointer and keyboard events into a new position and target:
+//
+//   - `OrbitController`: drag to rotate around the target, wheel to zoom,
+//     right-drag (or middle-drag) to pan.
+//   - `FlyController`: WASD to move, Q/E to go down/up, drag to look around.
+//
+// Shaders read the matrices from the frame's uniform block, see
+// `uniforms::FRAME_BLOCK`.
+
+extern crate nalgebra_glm as glm;
+
+use std::collections::HashSet;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+use crate::share::CameraState;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// `fov_y` is the vertical field of view in radians.
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    /// `height` is the height of the view volume in world units; the width
+    /// follows from the aspect ratio.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub position: glm::Vec3,
+    pub target: glm::Vec3,
+    pub up: glm::Vec3,
+    pub projection: Projection,
+    aspect: f32,
+}
+
+impl Default for Camera {
+    /// 45° perspective, 6 units in front of the origin.
+    fn default() -> Self {
+        Camera::perspective(FRAC_PI_4, 0.1, 100.0)
+            .look_at(glm::vec3(0.0, 0.0, 6.0), glm::vec3(0.0, 0.0, 0.0))
+    }
+}
+
+impl Camera {
+    pub fn perspective(fov_y: f32, near: f32, far: f32) -> Self {
+        Self::with_projection(Projection::Perspective { fov_y, near, far })
+    }
+
+    pub fn orthographic(height: f32, near: f32, far: f32) -> Self {
+        Self::with_projection(Projection::Orthographic { height, near, far })
+    }
+
+    fn with_projection(projection: Projection) -> Self {
+        Camera {
+            position: glm::vec3(0.0, 0.0, 1.0),
+            target: glm::Vec3::zeros(),
+            up: glm::Vec3::y(),
+            projection,
+            aspect: 1.0,
+        }
+    }
+
+    pub fn look_at(mut self, position: glm::Vec3, target: glm::Vec3) -> Self {
+        self.position = position;
+        self.target = target;
+        self
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    /// Call whenever the drawing buffer changes size.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    /// Unit vector from the position towards the target.
+    pub fn forward(&self) -> glm::Vec3 {
+        glm::normalize(&(self.target - self.position))
+    }
+
+    pub fn right(&self) -> glm::Vec3 {
+        glm::normalize(&glm::cross(&self.forward(), &self.up))
+    }
+
+    pub fn view_matrix(&self) -> glm::Mat4 {
+        // https://docs.rs/nalgebra-glm/latest/nalgebra_glm/fn.look_at.html
+        glm::look_at(&self.position, &self.target, &self.up)
+    }
+
+    pub fn projection_matrix(&self) -> glm::Mat4 {
+        match self.projection {
+            // https://docs.rs/nalgebra-glm/latest/nalgebra_glm/fn.perspective.html
+            Projection::Perspective { fov_y, near, far } => {
+                glm::perspective(self.aspect, fov_y, near, far)
+            }
+            // https://docs.rs/nalgebra-glm/latest/nalgebra_glm/fn.ortho.html
+            Projection::Orthographic { height, near, far } => {
+                let (w, h) = (height * self.aspect / 2.0, height / 2.0);
+                glm::ortho(-w, w, -h, h, near, far)
+            }
+        }
+    }
+
+    pub fn view_projection_matrix(&self) -> glm::Mat4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// Distances of the near and far planes.
+    pub fn depth_range(&self) -> (f32, f32) {
+        match self.projection {
+            Projection::Perspective { near, far, .. } => (near, far),
+            Projection::Orthographic { near, far, .. } => (near, far),
+        }
+    }
+
+    /// World space corners of the part of the view volume between the
+    /// distances `near` and `far`: the near face first, then the far face.
+    pub fn frustum_corners(&self, near: f32, far: f32) -> [glm::Vec3; 8] {
+        let inverse = glm::inverse(&self.view_projection_matrix());
+        let (camera_near, camera_far) = self.depth_range();
+
+        let unproject = |x: f32, y: f32, z: f32| {
+            let p = inverse * glm::vec4(x, y, z, 1.0);
+            p.xyz() / p.w
+        };
+
+        let mut corners = [glm::Vec3::zeros(); 8];
+        for (i, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .into_iter()
+            .enumerate()
+        {
+            // points at a given distance are on the line between the near
+            // and far corners, in perspective as well
+            let a = unproject(x, y, -1.0);
+            let b = unproject(x, y, 1.0);
+            let at = |d: f32| a + (b - a) * ((d - camera_near) / (camera_far - camera_near));
+
+            corners[i] = at(near);
+            corners[i + 4] = at(far);
+        }
+
+        corners
+    }
+
+    /// For share links. Orthographic cameras store a zero field of view.
+    pub fn state(&self) -> CameraState {
+        CameraState {
+            position: self.position.into(),
+            target: self.target.into(),
+            fov: match self.projection {
+                Projection::Perspective { fov_y, .. } => fov_y,
+                Projection::Orthographic { .. } => 0.0,
+            },
+        }
+    }
+
+    pub fn set_state(&mut self, state: &CameraState) {
+        self.position = glm::make_vec3(&state.position);
+        self.target = glm::make_vec3(&state.target);
+
+        if let Projection::Perspective { ref mut fov_y, .. } = self.projection {
+            if state.fov > 0.0 {
+                *fov_y = state.fov;
+            }
+        }
+    }
+}
+
+/// `MouseEvent.button` values.
+/// https://developer.mozilla.org/en-US/docs/Web/API/MouseEvent/button
+pub const BUTTON_MAIN: i16 = 0;
+pub const BUTTON_AUXILIARY: i16 = 1;
+pub const BUTTON_SECONDARY: i16 = 2;
+
+// Keep the camera off the poles, where `look_at` loses its up vector.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Drag {
+    Rotate,
+    Pan,
+}
+
+#[derive(Clone, Debug)]
+pub struct OrbitController {
+    pub target: glm::Vec3,
+    pub distance: f32,
+    /// Radians around +Y; 0 looks down -Z.
+    pub yaw: f32,
+    /// Radians above the horizon.
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians per pixel dragged.
+    pub rotate_speed: f32,
+    /// Scale applied per unit of wheel delta, as `exp(delta * zoom_speed)`.
+    pub zoom_speed: f32,
+    drag: Option<(Drag, f32, f32)>,
+}
+
+impl OrbitController {
+    pub fn new(target: glm::Vec3, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            rotate_speed: 0.005,
+            zoom_speed: 0.001,
+            drag: None,
+        }
+    }
+
+    /// Starts from wherever `camera` currently is.
+    pub fn from_camera(camera: &Camera) -> Self {
+        let offset = camera.position - camera.target;
+        let distance = glm::length(&offset).max(1e-3);
+
+        OrbitController {
+            yaw: offset.x.atan2(offset.z),
+            pitch: (offset.y / distance).clamp(-1.0, 1.0).asin(),
+            ..Self::new(camera.target, distance)
+        }
+    }
+
+    pub fn pointer_down(&mut self, button: i16, x: f32, y: f32) {
+        let drag = match button {
+            BUTTON_MAIN => Drag::Rotate,
+            BUTTON_AUXILIARY | BUTTON_SECONDARY => Drag::Pan,
+            _ => return,
+        };
+
+        self.drag = Some((drag, x, y));
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        let (drag, last_x, last_y) = match self.drag {
+            None => return,
+            Some(drag) => drag,
+        };
+        let (dx, dy) = (x - last_x, y - last_y);
+
+        match drag {
+            Drag::Rotate => {
+                self.yaw -= dx * self.rotate_speed;
+                self.pitch = (self.pitch + dy * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+            }
+            Drag::Pan => {
+                // Scaled by the distance so panning feels the same at any zoom.
+                let (right, up) = self.basis();
+                let scale = self.distance * 0.002;
+                self.target += (-right * dx + up * dy) * scale;
+            }
+        }
+
+        self.drag = Some((drag, x, y));
+    }
+
+    /// `delta` is `WheelEvent.deltaY`: positive zooms out.
+    pub fn wheel(&mut self, delta: f32) {
+        self.distance = (self.distance * (delta * self.zoom_speed).exp())
+            .clamp(self.min_distance, self.max_distance);
+    }
+
+    fn offset(&self) -> glm::Vec3 {
+        glm::vec3(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        ) * self.distance
+    }
+
+    fn basis(&self) -> (glm::Vec3, glm::Vec3) {
+        let forward = -glm::normalize(&self.offset());
+        let right = glm::normalize(&glm::cross(&forward, &glm::Vec3::y()));
+        let up = glm::cross(&right, &forward);
+        (right, up)
+    }
+
+    /// Moves `camera`. Orthographic cameras are zoomed by resizing the view
+    /// volume, matching what a 45° perspective would show at the target.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.target = self.target;
+        camera.position = self.target + self.offset();
+        camera.up = glm::Vec3::y();
+
+        if let Projection::Orthographic { ref mut height, .. } = camera.projection {
+            *height = 2.0 * self.distance * (FRAC_PI_4 / 2.0).tan();
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FlyController {
+    pub position: glm::Vec3,
+    /// Radians around +Y; 0 looks down -Z.
+    pub yaw: f32,
+    pub pitch: f32,
+    /// World units per second.
+    pub speed: f32,
+    /// Radians per pixel dragged.
+    pub look_speed: f32,
+    pressed: HashSet<String>,
+    drag: Option<(f32, f32)>,
+}
+
+impl FlyController {
+    pub fn new(position: glm::Vec3) -> Self {
+        FlyController {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 3.0,
+            look_speed: 0.003,
+            pressed: HashSet::new(),
+            drag: None,
+        }
+    }
+
+    pub fn from_camera(camera: &Camera) -> Self {
+        let forward = camera.forward();
+
+        FlyController {
+            yaw: (-forward.x).atan2(-forward.z),
+            pitch: forward.y.clamp(-1.0, 1.0).asin(),
+            ..Self::new(camera.position)
+        }
+    }
+
+    /// `code` is `KeyboardEvent.code`, e.g. "KeyW", so the layout doesn't
+    /// matter. Returns whether the key is one the controller uses.
+    pub fn key_down(&mut self, code: &str) -> bool {
+        let used = matches!(
+            code,
+            "KeyW" | "KeyA" | "KeyS" | "KeyD" | "KeyQ" | "KeyE" | "ShiftLeft" | "ShiftRight"
+        );
+
+        if used {
+            self.pressed.insert(code.to_string());
+        }
+
+        used
+    }
+
+    pub fn key_up(&mut self, code: &str) {
+        self.pressed.remove(code);
+    }
+
+    /// Drops every pressed key, e.g. when the page loses focus and the key
+    /// up events would never arrive.
+    pub fn release_all(&mut self) {
+        self.pressed.clear();
+        self.drag = None;
+    }
+
+    pub fn pointer_down(&mut self, x: f32, y: f32) {
+        self.drag = Some((x, y));
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        if let Some((last_x, last_y)) = self.drag {
+            self.yaw -= (x - last_x) * self.look_speed;
+            self.pitch = (self.pitch - (y - last_y) * self.look_speed).clamp(-MAX_PITCH, MAX_PITCH);
+            self.drag = Some((x, y));
+        }
+    }
+
+    pub fn forward(&self) -> glm::Vec3 {
+        glm::vec3(
+            -self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    /// Moves by the pressed keys over `dt` seconds. Shift runs.
+    pub fn update(&mut self, dt: f32) {
+        let key = |code: &str| self.pressed.contains(code) as i32 as f32;
+
+        let forward = self.forward();
+        let right = glm::normalize(&glm::cross(&forward, &glm::Vec3::y()));
+
+        let direction = forward * (key("KeyW") - key("KeyS"))
+            + right * (key("KeyD") - key("KeyA"))
+            + glm::Vec3::y() * (key("KeyE") - key("KeyQ"));
+
+        if direction != glm::Vec3::zeros() {
+            let run = if key("ShiftLeft") + key("ShiftRight") > 0.0 {
+                3.0
+            } else {
+                1.0
+            };
+            self.position += glm::normalize(&direction) * self.speed * run * dt;
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = self.position;
+        camera.target = self.position + self.forward();
+        camera.up = glm::Vec3::y();
+    }
+}
+
+/// Either controller, so an example can switch between them at runtime.
+#[derive(Clone, Debug)]
+pub enum Controller {
+    Orbit(OrbitController),
+    Fly(FlyController),
+}
+
+impl Controller {
+    pub fn orbit(camera: &Camera) -> Self {
+        Controller::Orbit(OrbitController::from_camera(camera))
+    }
+
+    pub fn fly(camera: &Camera) -> Self {
+        Controller::Fly(FlyController::from_camera(camera))
+    }
+
+    pub fn pointer_down(&mut self, button: i16, x: f32, y: f32) {
+        match self {
+            Controller::Orbit(orbit) => orbit.pointer_down(button, x, y),
+            Controller::Fly(fly) if button == BUTTON_MAIN => fly.pointer_down(x, y),
+            Controller::Fly(_) => {}
+        }
+    }
+
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        match self {
+            Controller::Orbit(orbit) => orbit.pointer_move(x, y),
+            Controller::Fly(fly) => fly.pointer_move(x, y),
+        }
+    }
+
+    pub fn pointer_up(&mut self) {
+        match self {
+            Controller::Orbit(orbit) => orbit.pointer_up(),
+            Controller::Fly(fly) => fly.pointer_up(),
+        }
+    }
+
+    pub fn wheel(&mut self, delta: f32) {
+        if let Controller::Orbit(orbit) = self {
+            orbit.wheel(delta);
+        }
+    }
+
+    pub fn key_down(&mut self, code: &str) -> bool {
+        match self {
+            Controller::Orbit(_) => false,
+            Controller::Fly(fly) => fly.key_down(code),
+        }
+    }
+
+    pub fn key_up(&mut self, code: &str) {
+        if let Controller::Fly(fly) = self {
+            fly.key_up(code);
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if let Controller::Fly(fly) = self {
+            fly.update(dt);
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        match self {
+            Controller::Orbit(orbit) => orbit.apply(camera),
+            Controller::Fly(fly) => fly.apply(camera),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: &glm::Vec3, b: &glm::Vec3) -> bool {
+        glm::distance(a, b) < 1e-4
+    }
+
+    #[test]
+    fn default_matches_the_old_hardcoded_view() {
+        let camera = Camera::default();
+        let old = glm::translate(&glm::Mat4::identity(), &glm::vec3(0.0, 0.0, -6.0));
+
+        assert!((camera.view_matrix() - old).abs().max() < 1e-6);
+    }
+
+    #[test]
+    fn orbit_round_trips_through_the_camera() {
+        let mut camera = Camera::default();
+        let mut orbit = OrbitController::from_camera(&camera);
+        orbit.yaw = 1.0;
+        orbit.pitch = 0.4;
+        orbit.apply(&mut camera);
+
+        let again = OrbitController::from_camera(&camera);
+        assert!((again.yaw - 1.0).abs() < 1e-5);
+        assert!((again.pitch - 0.4).abs() < 1e-5);
+        assert!((again.distance - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbit_zoom_is_clamped() {
+        let mut orbit = OrbitController::new(glm::Vec3::zeros(), 6.0);
+        orbit.wheel(-1e6);
+        assert_eq!(orbit.distance, orbit.min_distance);
+        orbit.wheel(1e6);
+        assert_eq!(orbit.distance, orbit.max_distance);
+    }
+
+    #[test]
+    fn fly_moves_along_its_view() {
+        let mut camera = Camera::default();
+        let mut fly = FlyController::from_camera(&camera);
+        assert!(close(&fly.forward(), &glm::vec3(0.0, 0.0, -1.0)));
+
+        assert!(fly.key_down("KeyW"));
+        fly.update(1.0);
+        fly.apply(&mut camera);
+        assert!(close(&camera.position, &glm::vec3(0.0, 0.0, 3.0)));
+
+        fly.key_up("KeyW");
+        fly.update(1.0);
+        assert!(close(&fly.position, &glm::vec3(0.0, 0.0, 3.0)));
+    }
+
+    #[test]
+    fn orthographic_follows_aspect() {
+        let mut camera = Camera::orthographic(2.0, 0.1, 100.0);
+        camera.resize(200, 100);
+
+        let corner = camera.projection_matrix() * glm::vec4(2.0, 1.0, -1.0, 1.0);
+        assert!((corner.x - 1.0).abs() < 1e-6);
+        assert!((corner.y - 1.0).abs() < 1e-6);
+    }
+}
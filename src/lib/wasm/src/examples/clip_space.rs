@@ -52,7 +52,7 @@ struct ClipSpaceBox {
     loc_color: WebGlUniformLocation,
 }
 
-static VERTEX_SHADER_SOURCE: &'static str = r#"
+static VERTEX_SHADER_SOURCE: &str = r#"
 // The individual position vertex
 attribute vec3 position;
 
@@ -63,7 +63,7 @@ void main() {
 }
 "#;
 
-static FRAGMENT_SHADER_SOURCE: &'static str = r#"
+static FRAGMENT_SHADER_SOURCE: &str = r#"
 precision mediump float;
 uniform vec4 color;
 
@@ -3,9 +3,10 @@ use wasm_bindgen::JsValue;
 use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlUniformLocation};
 extern crate nalgebra_glm as glm;
 
+use crate::state::GlState;
 use crate::webgl::{compile_shader, draw, link_shader_program, ShaderInfo};
 
-static VERTEX_SHADER_SOURCE: &'static str = r#"
+static VERTEX_SHADER_SOURCE: &str = r#"
   attribute vec4 aVertexPosition;
   attribute vec4 aVertexColor;
   uniform mat4 uModelViewMatrix;
@@ -17,7 +18,7 @@ static VERTEX_SHADER_SOURCE: &'static str = r#"
   }
 "#;
 
-static FRAGMENT_SHADER_SOURCE: &'static str = r#"
+static FRAGMENT_SHADER_SOURCE: &str = r#"
   varying lowp vec4 vColor;
   void main() {
     gl_FragColor = vColor;
@@ -29,8 +30,9 @@ pub fn main(
     canvas_height: f32,
     canvas_width: f32,
 ) -> Result<(), JsValue> {
-    let buffers = init_buffers(&context);
-    let program = setup_shader_program(&context)?;
+    let buffers = init_buffers(context);
+    let program = setup_shader_program(context)?;
+    let mut state = GlState::new(context);
 
     let info = ShaderInfo {
         program: &program.program,
@@ -42,7 +44,7 @@ pub fn main(
         program_model_view_matrix: &program.model_view_matrix,
     };
 
-    draw_colored_square(&context, &info, &buffers);
+    draw_colored_square(&mut state, &info, &buffers);
 
     Ok(())
 }
@@ -59,18 +61,18 @@ pub fn setup_shader_program(
     context: &WebGl2RenderingContext,
 ) -> Result<ShaderProgramInfo, JsValue> {
     let vertex_shader = compile_shader(
-        &context,
+        context,
         WebGl2RenderingContext::VERTEX_SHADER,
         VERTEX_SHADER_SOURCE,
     )?;
 
     let fragment_shader = compile_shader(
-        &context,
+        context,
         WebGl2RenderingContext::FRAGMENT_SHADER,
         FRAGMENT_SHADER_SOURCE,
     )?;
 
-    let program = link_shader_program(&context, &vertex_shader, &fragment_shader)?;
+    let program = link_shader_program(context, &vertex_shader, &fragment_shader)?;
 
     context.use_program(Some(&program));
 
@@ -148,7 +150,7 @@ pub fn init_buffers(context: &WebGl2RenderingContext) -> (WebGlBuffer, WebGlBuff
 }
 
 fn draw_colored_square(
-    context: &WebGl2RenderingContext,
+    state: &mut GlState,
     info: &ShaderInfo,
     (position_buffer, color_buffer): &(WebGlBuffer, WebGlBuffer),
 ) {
@@ -158,14 +160,14 @@ fn draw_colored_square(
     let z_far = 100.0;
 
     let projection_matrix = glm::perspective(aspect, field_of_view, z_near, z_far);
-    let vec_projection_matrix = projection_matrix.iter().map(|v| *v).collect::<Vec<_>>();
+    let vec_projection_matrix = projection_matrix.iter().copied().collect::<Vec<_>>();
 
     let model_view_matrix =
         glm::translate(&glm::Mat4::identity(), &glm::TVec3::new(-0.0, 0.0, -6.0));
-    let vec_model_view_matrix = model_view_matrix.iter().map(|v| *v).collect::<Vec<_>>();
+    let vec_model_view_matrix = model_view_matrix.iter().copied().collect::<Vec<_>>();
 
     draw(
-        context,
+        state,
         info,
         position_buffer,
         color_buffer,
@@ -0,0 +1,177 @@
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use std::f32::consts::TAU;
+
+use web_sys::HtmlCanvasElement;
+extern crate console_error_panic_hook;
+
+use crate::fluid::{Fluid, FluidOptions};
+use crate::state::GlState;
+use crate::webgl::get_context_with_canvas_by_id;
+
+/// Velocity added per unit of pointer movement, in cells per second.
+const SPLAT_FORCE: f32 = 6000.0;
+
+/// A dim color cycling with `t`, bright enough once it piles up.
+/// https://iquilezles.org/articles/palettes/
+fn palette(t: f32) -> [f32; 3] {
+    [0.0, 0.33, 0.67].map(|phase| (0.5 + 0.5 * (TAU * (t + phase)).cos()) * 0.15)
+}
+
+/// Dye stirred with the pointer, as in the mouse tutorial: `tick` gets the
+/// pointer in canvas pixels, and dragging pushes the fluid along.
+#[wasm_bindgen]
+pub struct FluidSim {
+    state: GlState,
+    canvas: HtmlCanvasElement,
+    fluid: Fluid,
+    time: Option<f64>,
+    delta: f32,
+    // last pointer position in [0, 1], from the bottom left
+    pointer: Option<[f32; 2]>,
+    pressed: bool,
+}
+
+#[wasm_bindgen]
+impl FluidSim {
+    /// `resolution` and `dye_resolution` are cells along the short side of
+    /// the canvas, e.g. 128 and 512.
+    pub fn new(id: &str, resolution: u32, dye_resolution: u32) -> Result<FluidSim, JsValue> {
+        console_error_panic_hook::set_once();
+
+        let (context, canvas) = get_context_with_canvas_by_id(id)?;
+        let mut state = GlState::new(&context);
+        resize_canvas(&canvas);
+
+        let options = FluidOptions {
+            resolution,
+            dye_resolution,
+            ..FluidOptions::default()
+        };
+        let fluid = Fluid::new(&mut state, options, aspect_of(&canvas))?;
+
+        let mut this = FluidSim {
+            state,
+            canvas,
+            fluid,
+            time: None,
+            delta: 0.0,
+            pointer: None,
+            pressed: false,
+        };
+        this.stir(8);
+
+        Ok(this)
+    }
+
+    /// Reallocates the grids; the fluid starts over.
+    pub fn set_resolution(&mut self, resolution: u32, dye_resolution: u32) -> Result<(), JsValue> {
+        let aspect = aspect_of(&self.canvas);
+        self.fluid
+            .resize(&mut self.state, resolution, dye_resolution, aspect)?;
+        self.stir(8);
+
+        Ok(())
+    }
+
+    pub fn set_pressure_iterations(&mut self, iterations: u32) {
+        self.fluid.options.pressure_iterations = iterations;
+    }
+
+    pub fn set_vorticity(&mut self, vorticity: f32) {
+        self.fluid.options.vorticity = vorticity;
+    }
+
+    /// Fading per second of the velocity and of the dye.
+    pub fn set_dissipation(&mut self, velocity: f32, dye: f32) {
+        self.fluid.options.velocity_dissipation = velocity;
+        self.fluid.options.dye_dissipation = dye;
+    }
+
+    pub fn set_splat_radius(&mut self, radius: f32) {
+        self.fluid.options.splat_radius = radius;
+    }
+
+    /// Adds `count` splats at random places, pushing in random directions.
+    pub fn stir(&mut self, count: u32) {
+        for _ in 0..count {
+            let random = || js_sys::Math::random() as f32;
+            let point = [random(), random()];
+            let force = [(random() - 0.5) * 1000.0, (random() - 0.5) * 1000.0];
+            let color = palette(random()).map(|c| c * 10.0);
+
+            self.fluid.splat(&mut self.state, point, force, color);
+        }
+    }
+
+    pub fn pointer_down(&mut self) {
+        self.pressed = true;
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.pressed = false;
+    }
+
+    /// `timestamp` in seconds; `mouse_x` and `mouse_y` in canvas pixels
+    /// from the top left, as `MouseBox::tick` takes them.
+    pub fn tick(&mut self, timestamp: f64, mouse_x: f64, mouse_y: f64) {
+        self.delta = self.time.map_or(0.0, |last| (timestamp - last) as f32);
+        self.time = Some(timestamp);
+
+        let point = [
+            mouse_x as f32 / self.canvas.width().max(1) as f32,
+            1.0 - mouse_y as f32 / self.canvas.height().max(1) as f32,
+        ];
+        let last = self.pointer.replace(point).unwrap_or(point);
+        let moved = [point[0] - last[0], point[1] - last[1]];
+
+        if self.pressed && moved != [0.0, 0.0] {
+            let force = moved.map(|d| d * SPLAT_FORCE);
+            let color = palette(timestamp as f32 * 0.1);
+
+            self.fluid.splat(&mut self.state, point, force, color);
+        }
+    }
+
+    pub fn draw(&mut self) -> Result<(), JsValue> {
+        if resize_canvas(&self.canvas) {
+            let FluidOptions {
+                resolution,
+                dye_resolution,
+                ..
+            } = self.fluid.options;
+            self.fluid.resize(
+                &mut self.state,
+                resolution,
+                dye_resolution,
+                aspect_of(&self.canvas),
+            )?;
+        }
+
+        self.fluid.step(&mut self.state, self.delta);
+        self.fluid
+            .display(&mut self.state, self.canvas.width(), self.canvas.height());
+
+        Ok(())
+    }
+}
+
+/// Matches the drawing buffer to the displayed size; true if it changed.
+fn resize_canvas(canvas: &HtmlCanvasElement) -> bool {
+    let (width, height) = (
+        canvas.client_width().max(1) as u32,
+        canvas.client_height().max(1) as u32,
+    );
+    if canvas.width() == width && canvas.height() == height {
+        return false;
+    }
+
+    canvas.set_width(width);
+    canvas.set_height(height);
+    true
+}
+
+fn aspect_of(canvas: &HtmlCanvasElement) -> f32 {
+    canvas.width().max(1) as f32 / canvas.height().max(1) as f32
+}
@@ -0,0 +1,280 @@
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use web_sys::HtmlCanvasElement;
+extern crate console_error_panic_hook;
+extern crate nalgebra_glm as glm;
+
+use crate::noise::hash::hash22;
+use crate::simulation::{Simulation, SimulationOptions, DEFAULT_DISPLAY};
+use crate::state::GlState;
+use crate::webgl::get_context_with_canvas_by_id;
+
+/// Conway's Game of Life: red is alive. Drawing brings cells to life.
+static LIFE_STEP: &str = r#"
+void main() {
+  float alive = cell(vec2(0.0)).r;
+  float neighbors = 0.0;
+  for (int y = -1; y <= 1; y++) {
+    for (int x = -1; x <= 1; x++) {
+      neighbors += cell(vec2(x, y)).r;
+    }
+  }
+  neighbors -= alive;
+
+  float next = neighbors > 2.5 && neighbors < 3.5 || alive > 0.5 && neighbors > 1.5 && neighbors < 2.5
+    ? 1.0 : 0.0;
+  if (uMouse.z > 0.5 && distance(gl_FragCoord.xy, uMouse.xy * uResolution) < 3.0) {
+    next = 1.0;
+  }
+  // green fades out after a cell dies
+  fragColor = vec4(next, max(next, cell(vec2(0.0)).g * 0.95), 0.0, 1.0);
+}
+"#;
+
+static LIFE_DISPLAY: &str = r#"
+void main() {
+  vec4 state = texture(uState, vNdc * 0.5 + 0.5);
+  fragColor = vec4(mix(vec3(0.05, 0.1, 0.2) * state.g, vec3(0.9, 1.0, 0.8), state.r), 1.0);
+}
+"#;
+
+/// Gray-Scott reaction-diffusion: red is the feed chemical, green the one
+/// it turns into. Drawing adds green.
+/// https://www.karlsims.com/rd.html
+static REACTION_DIFFUSION_STEP: &str = r#"
+uniform float uFeed; // @range(0.01, 0.1) @default(0.055) @label("Feed")
+uniform float uKill; // @range(0.04, 0.07) @default(0.062) @label("Kill")
+
+vec2 laplacian() {
+  vec2 sum = -cell(vec2(0.0)).rg;
+  sum += 0.2 * (cell(vec2(1.0, 0.0)).rg + cell(vec2(-1.0, 0.0)).rg
+              + cell(vec2(0.0, 1.0)).rg + cell(vec2(0.0, -1.0)).rg);
+  sum += 0.05 * (cell(vec2(1.0, 1.0)).rg + cell(vec2(-1.0, 1.0)).rg
+               + cell(vec2(1.0, -1.0)).rg + cell(vec2(-1.0, -1.0)).rg);
+  return sum;
+}
+
+void main() {
+  vec2 ab = cell(vec2(0.0)).rg;
+  vec2 lap = laplacian();
+  float reaction = ab.r * ab.g * ab.g;
+
+  // one unit of time per substep, the usual scale of the rates
+  float a = ab.r + lap.r - reaction + uFeed * (1.0 - ab.r);
+  float b = ab.g + 0.5 * lap.g + reaction - (uKill + uFeed) * ab.g;
+
+  if (uMouse.z > 0.5 && distance(gl_FragCoord.xy, uMouse.xy * uResolution) < 5.0) {
+    b = 1.0;
+  }
+  fragColor = vec4(clamp(vec2(a, b), 0.0, 1.0), 0.0, 1.0);
+}
+"#;
+
+static REACTION_DIFFUSION_DISPLAY: &str = r#"
+void main() {
+  vec2 ab = texture(uState, vNdc * 0.5 + 0.5).rg;
+  float t = clamp(ab.r - ab.g, 0.0, 1.0);
+  fragColor = vec4(mix(vec3(0.1, 0.0, 0.2), vec3(1.0, 0.9, 0.7), t), 1.0);
+}
+"#;
+
+/// Heat equation: red is the temperature. Drawing heats.
+static HEAT_STEP: &str = r#"
+uniform float uDiffusivity; // @range(0.0, 2.0) @default(1.0) @label("Diffusivity")
+uniform float uCooling;     // @range(0.0, 1.0) @default(0.05) @label("Cooling")
+
+void main() {
+  float t = cell(vec2(0.0)).r;
+  float lap = cell(vec2(1.0, 0.0)).r + cell(vec2(-1.0, 0.0)).r
+            + cell(vec2(0.0, 1.0)).r + cell(vec2(0.0, -1.0)).r - 4.0 * t;
+
+  // explicit Euler, stable while uDiffusivity * uDelta < 0.25
+  t += (uDiffusivity * lap - uCooling * t) * uDelta;
+
+  if (uMouse.z > 0.5 && distance(gl_FragCoord.xy, uMouse.xy * uResolution) < 6.0) {
+    t = 4.0;
+  }
+  fragColor = vec4(t, 0.0, 0.0, 1.0);
+}
+"#;
+
+static HEAT_DISPLAY: &str = r#"
+void main() {
+  float t = texture(uState, vNdc * 0.5 + 0.5).r;
+  fragColor = vec4(clamp(vec3(t, t * 0.5 - 0.2, t * 0.25 - 0.5), 0.0, 1.0), 1.0);
+}
+"#;
+
+/// A simulation on the GPU, from a preset or from shaders written against
+/// the headers of simulation.rs.
+#[wasm_bindgen]
+pub struct GpuSimulation {
+    state: GlState,
+    canvas: HtmlCanvasElement,
+    simulation: Simulation,
+    preset: String,
+    time: Option<f64>,
+    delta: f32,
+    pressed: bool,
+}
+
+#[wasm_bindgen]
+impl GpuSimulation {
+    /// `preset` is one of "life", "reaction-diffusion" or "heat".
+    pub fn new(id: &str, preset: &str, width: u32, height: u32) -> Result<GpuSimulation, JsValue> {
+        let (step, display, substeps) = match preset {
+            "life" => (LIFE_STEP, LIFE_DISPLAY, 1),
+            "reaction-diffusion" => (REACTION_DIFFUSION_STEP, REACTION_DIFFUSION_DISPLAY, 8),
+            "heat" => (HEAT_STEP, HEAT_DISPLAY, 4),
+            _ => return Err(JsValue::from(format!("Unknown preset: {}", preset))),
+        };
+
+        let mut this = Self::create(id, width, height, substeps, step, display)?;
+        this.preset = preset.to_string();
+        this.reset(0.1)?;
+
+        Ok(this)
+    }
+
+    /// `display` may be empty for `DEFAULT_DISPLAY`.
+    pub fn with_shaders(
+        id: &str,
+        width: u32,
+        height: u32,
+        step: &str,
+        display: &str,
+    ) -> Result<GpuSimulation, JsValue> {
+        let display = match display.trim() {
+            "" => DEFAULT_DISPLAY,
+            _ => display,
+        };
+
+        Self::create(id, width, height, 1, step, display)
+    }
+
+    fn create(
+        id: &str,
+        width: u32,
+        height: u32,
+        substeps: u32,
+        step: &str,
+        display: &str,
+    ) -> Result<GpuSimulation, JsValue> {
+        console_error_panic_hook::set_once();
+
+        let (context, canvas) = get_context_with_canvas_by_id(id)?;
+        let mut state = GlState::new(&context);
+
+        let options = SimulationOptions {
+            width,
+            height,
+            substeps,
+            ..SimulationOptions::default()
+        };
+        let simulation = Simulation::new(&mut state, options, step, display)?;
+
+        Ok(GpuSimulation {
+            state,
+            canvas,
+            simulation,
+            preset: String::new(),
+            time: None,
+            delta: 0.0,
+            pressed: false,
+        })
+    }
+
+    /// Seeds the state from Rust; `density` is the share of cells that
+    /// start active.
+    pub fn reset(&mut self, density: f32) -> Result<(), JsValue> {
+        let state = self.simulation.state();
+        let (width, height) = (state.width(), state.height());
+
+        let rgba = (0..width * height)
+            .flat_map(|i| {
+                let p = glm::vec2((i % width) as f32, (i / width) as f32);
+                let active = (hash22(p).x < density) as i32 as f32;
+
+                match self.preset.as_str() {
+                    // full of feed, with drops of the other chemical
+                    "reaction-diffusion" => [1.0, active, 0.0, 1.0],
+                    _ => [active, active, 0.0, 1.0],
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.simulation
+            .seed(&mut self.state, &rgba)
+            .map_err(JsValue::from)
+    }
+
+    /// The state as RGBA floats, bottom row first.
+    pub fn read_state(&self) -> Result<Vec<f32>, JsValue> {
+        self.simulation
+            .read(self.state.context())
+            .map_err(JsValue::from)
+    }
+
+    /// "byte", "half" or "float": what the state is stored as, which may be
+    /// less than the "float" asked for.
+    pub fn precision(&self) -> String {
+        self.simulation.precision().name().to_string()
+    }
+
+    pub fn set_substeps(&mut self, substeps: u32) {
+        self.simulation.set_substeps(substeps);
+    }
+
+    /// The annotated uniforms of the shaders, as a JSON array.
+    pub fn parameter_schema(&self) -> String {
+        self.simulation.parameter_schema()
+    }
+
+    pub fn set_parameter(&mut self, name: &str, values: &[f32]) -> Result<(), JsValue> {
+        self.simulation
+            .set_parameter(&mut self.state, name, values)
+            .map_err(JsValue::from)
+    }
+
+    /// `time` in seconds, e.g. the `requestAnimationFrame` timestamp / 1000.
+    pub fn tick(&mut self, time: f64) {
+        self.delta = self.time.map_or(0.0, |last| (time - last) as f32);
+        self.time = Some(time);
+    }
+
+    pub fn pointer_down(&mut self, x: f32, y: f32) {
+        self.pressed = true;
+        self.pointer_move(x, y);
+    }
+
+    /// `x` and `y` in CSS pixels from the top left of the canvas.
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        let (width, height) = (
+            self.canvas.client_width().max(1) as f32,
+            self.canvas.client_height().max(1) as f32,
+        );
+        self.simulation
+            .set_mouse(x / width, 1.0 - y / height, self.pressed);
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.pressed = false;
+        self.simulation.set_mouse(0.0, 0.0, false);
+    }
+
+    pub fn draw(&mut self) {
+        let (width, height) = (
+            self.canvas.client_width() as u32,
+            self.canvas.client_height() as u32,
+        );
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+
+        let time = self.time.unwrap_or(0.0) as f32;
+        self.simulation.step(&mut self.state, time, self.delta);
+        self.simulation.display(&mut self.state, width, height);
+    }
+}
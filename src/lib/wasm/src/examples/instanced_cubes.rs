@@ -0,0 +1,230 @@
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext, WebGlProgram};
+extern crate console_error_panic_hook;
+extern crate nalgebra_glm as glm;
+
+use std::f32::consts::{FRAC_PI_4, TAU};
+
+use crate::camera::{Camera, Controller};
+use crate::geometry::cuboid;
+use crate::instancing::Instances;
+use crate::mesh::Mesh;
+use crate::pipeline::PipelineState;
+use crate::state::GlState;
+use crate::uniforms::{FrameUniforms, SharedFrame, FRAME_BLOCK};
+use crate::webgl::{compile_shader, get_context_with_canvas_by_id, link_shader_program};
+
+/// Instances recolored per frame, through one `bufferSubData` each.
+const RECOLOR_PER_FRAME: usize = 2_000;
+
+static PIPELINE: PipelineState = PipelineState::opaque()
+    .clear_color([0.02, 0.02, 0.03, 1.0])
+    .clear_depth(1.0);
+
+fn vertex_shader() -> String {
+    format!(
+        r#"#version 300 es
+in vec3 position;
+in vec3 normal;
+in mat4 aInstanceMatrix;
+in vec4 aInstanceColor;
+in vec2 aInstanceData; // spin speed, phase
+
+{frame}
+
+out vec3 vNormal;
+out vec4 vColor;
+
+// https://en.wikipedia.org/wiki/Rodrigues%27_rotation_formula
+mat3 rotation(vec3 axis, float angle) {{
+  float c = cos(angle);
+  float s = sin(angle);
+  mat3 k = mat3(0.0, axis.z, -axis.y, -axis.z, 0.0, axis.x, axis.y, -axis.x, 0.0);
+  return mat3(1.0) + s * k + (1.0 - c) * k * k;
+}}
+
+void main() {{
+  float phase = aInstanceData.y;
+  vec3 axis = normalize(vec3(sin(phase), 1.0, cos(phase)));
+  mat3 spin = rotation(axis, frame.time * aInstanceData.x + phase);
+
+  gl_Position = frame.viewProjection * aInstanceMatrix * vec4(spin * position, 1.0);
+  vNormal = mat3(aInstanceMatrix) * spin * normal;
+  vColor = aInstanceColor;
+}}
+"#,
+        frame = FRAME_BLOCK
+    )
+}
+
+static FS_SRC: &str = r#"#version 300 es
+precision mediump float;
+in vec3 vNormal;
+in vec4 vColor;
+out vec4 fragColor;
+
+void main() {
+  vec3 light = normalize(vec3(0.4, 1.0, 0.6));
+  float diffuse = max(dot(normalize(vNormal), light), 0.0);
+  fragColor = vec4(vColor.rgb * (0.25 + 0.75 * diffuse), vColor.a);
+}
+"#;
+
+/// Hue in [0, 1) to a saturated RGB color.
+fn hue(h: f32) -> [f32; 4] {
+    let channel = |offset: f32| {
+        let k = (h * 6.0 + offset) % 6.0;
+        1.0 - (k.min(4.0 - k).clamp(0.0, 1.0))
+    };
+    [channel(5.0), channel(3.0), channel(1.0), 1.0]
+}
+
+/// A hash of `x` in [0, 1).
+fn random(x: f32) -> f32 {
+    ((x * 12.9898).sin() * 43758.547).fract().abs()
+}
+
+/// A cube of `count` spinning cubes, all drawn with one instanced call.
+#[wasm_bindgen]
+pub struct InstancedCubes {
+    state: GlState,
+    canvas: HtmlCanvasElement,
+    program: WebGlProgram,
+    mesh: Mesh,
+    instances: Instances,
+    camera: Camera,
+    frame: SharedFrame,
+    controller: Controller,
+    time: f32,
+    // next instance to recolor
+    cursor: usize,
+}
+
+#[wasm_bindgen]
+impl InstancedCubes {
+    pub fn new(id: &str, count: usize) -> Result<InstancedCubes, JsValue> {
+        console_error_panic_hook::set_once();
+
+        let (context, canvas) = get_context_with_canvas_by_id(id)?;
+        let mut state = GlState::new(&context);
+
+        let vs = compile_shader(
+            &context,
+            WebGl2RenderingContext::VERTEX_SHADER,
+            &vertex_shader(),
+        )?;
+        let fs = compile_shader(&context, WebGl2RenderingContext::FRAGMENT_SHADER, FS_SRC)?;
+        let program = link_shader_program(&context, &vs, &fs)?;
+
+        let frame = FrameUniforms::shared(&context)?;
+        frame.borrow().bind_program(&context, &program);
+
+        // the cubes fill a grid `side` cubes wide
+        let side = (count.max(1) as f32).cbrt().ceil() as usize;
+        let spacing = 1.5;
+        let center = (side - 1) as f32 * spacing / 2.0;
+
+        let mut instances = Instances::new(&mut state, count.max(1), 2)?;
+        for i in 0..count {
+            let (x, y, z) = (i % side, i / side % side, i / (side * side));
+            let position =
+                glm::vec3(x as f32, y as f32, z as f32) * spacing - glm::Vec3::repeat(center);
+            let model = glm::translate(&glm::Mat4::identity(), &position);
+
+            let index = instances.push(&model, hue(i as f32 / count as f32))?;
+            let (speed, phase) = (random(i as f32), random(i as f32 + 0.5));
+            instances.set_custom(index, &[0.5 + speed * 2.0, phase * TAU]);
+        }
+        instances.upload(&mut state)?;
+
+        let mut mesh = cuboid(1.0, 1.0, 1.0).to_mesh(&mut state)?;
+        mesh.add_vertex_buffer(instances.vertex_buffer());
+
+        let extent = side as f32 * spacing;
+        let camera = Camera::perspective(FRAC_PI_4, 0.1, extent * 8.0).look_at(
+            glm::vec3(0.0, extent * 0.5, extent * 1.6),
+            glm::Vec3::zeros(),
+        );
+        let controller = Controller::orbit(&camera);
+
+        Ok(InstancedCubes {
+            state,
+            canvas,
+            program,
+            mesh,
+            instances,
+            camera,
+            frame,
+            controller,
+            time: 0.0,
+            cursor: 0,
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// `time` in seconds.
+    pub fn tick(&mut self, time: f64) {
+        self.time = time as f32;
+    }
+
+    pub fn pointer_down(&mut self, button: i16, x: f32, y: f32) {
+        self.controller.pointer_down(button, x, y);
+    }
+
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        self.controller.pointer_move(x, y);
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.controller.pointer_up();
+    }
+
+    pub fn wheel(&mut self, delta: f32) {
+        self.controller.wheel(delta);
+    }
+
+    pub fn draw(&mut self) -> Result<(), JsValue> {
+        let (width, height) = (
+            self.canvas.client_width() as u32,
+            self.canvas.client_height() as u32,
+        );
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+        self.state.viewport(0, 0, width as i32, height as i32);
+
+        PIPELINE.clear(&mut self.state);
+        PIPELINE.apply(&mut self.state);
+
+        self.camera.resize(width, height);
+        self.controller.apply(&mut self.camera);
+        let mut frame = self.frame.borrow_mut();
+        frame.resolution = [width as f32, height as f32];
+        frame.set_camera(&self.camera);
+        frame.advance(self.state.context(), self.time)?;
+
+        // a band of new colors sweeps through the cubes; only that band is
+        // sent to the GPU
+        let count = self.instances.len();
+        let end = (self.cursor + RECOLOR_PER_FRAME).min(count);
+        for i in self.cursor..end {
+            let h = i as f32 / count as f32 + self.time * 0.05;
+            self.instances.set_color(i, hue(h.fract()));
+        }
+        self.cursor = if end == count { 0 } else { end };
+        self.instances.upload(&mut self.state)?;
+
+        self.state.use_program(Some(&self.program));
+
+        self.mesh
+            .draw_instanced(&mut self.state, &self.program, count as i32)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext};
+extern crate console_error_panic_hook;
+extern crate nalgebra_glm as glm;
+
+use crate::camera::{Camera, Controller};
+use crate::geometry;
+use crate::lighting::{Light, PhongMaterial, PhongRenderer, Shadow};
+use crate::pipeline::PipelineState;
+use crate::scene::{Material, NodeId, Scene};
+use crate::shadow::{ShadowMaps, ShadowOptions};
+use crate::state::GlState;
+use crate::utils::log;
+use crate::webgl::get_context_with_canvas_by_id;
+
+static PIPELINE: PipelineState = PipelineState::new()
+    .depth(WebGl2RenderingContext::LESS, true)
+    .clear_color([0.02, 0.02, 0.03, 1.0])
+    .clear_depth(1.0);
+
+/// A few shapes on a floor, lit by a dim sun, a point light circling them
+/// and a spot light from above. The sun and the spot light cast shadows.
+#[wasm_bindgen]
+pub struct LitShapes {
+    canvas: HtmlCanvasElement,
+    renderer: PhongRenderer,
+    scene: Scene,
+    torus: NodeId,
+    lamp_pivot: NodeId,
+    camera: Camera,
+    controller: Controller,
+    state: GlState,
+    delta: f32,
+}
+
+#[wasm_bindgen]
+impl LitShapes {
+    pub fn new(id: &str) -> Result<LitShapes, JsValue> {
+        console_error_panic_hook::set_once();
+
+        let (context, canvas) = get_context_with_canvas_by_id(id)?;
+        let mut state = GlState::new(&context);
+        let mut renderer = PhongRenderer::new(&context, 4)?;
+        renderer.shadows = Some(ShadowMaps::new(&context, ShadowOptions::default())?);
+
+        let mut scene = Scene::new();
+
+        let mut shape = |scene: &mut Scene, geometry: geometry::Geometry, color, position| {
+            let mesh = geometry.to_mesh(&mut state)?;
+            let mesh = scene.add_mesh(mesh);
+            let material = scene.add_material(PhongMaterial::colored(color));
+
+            let node = scene.add_node(None);
+            scene.attach_mesh(node, mesh);
+            scene.set_material(node, material);
+            scene.set_translation(node, position);
+
+            Ok::<_, String>(node)
+        };
+
+        shape(
+            &mut scene,
+            geometry::grid(20.0, 20.0, 1, 1),
+            [0.6, 0.6, 0.6],
+            glm::vec3(0.0, -1.0, 0.0),
+        )?;
+        let torus = shape(
+            &mut scene,
+            geometry::torus(0.8, 0.3, 48, 24),
+            [0.9, 0.3, 0.2],
+            glm::vec3(0.0, 0.3, 0.0),
+        )?;
+        shape(
+            &mut scene,
+            geometry::icosphere(0.7, 3),
+            [0.2, 0.5, 0.9],
+            glm::vec3(-2.5, -0.3, 0.5),
+        )?;
+        let capsule = shape(
+            &mut scene,
+            geometry::capsule(0.4, 1.0, 32, 8),
+            [0.3, 0.8, 0.3],
+            glm::vec3(2.5, -0.1, -0.5),
+        )?;
+        scene.set_rotation(
+            capsule,
+            glm::quat_angle_axis(0.4, &glm::vec3(0.0, 0.0, 1.0)),
+        );
+
+        // Make the capsule shiny.
+        let shiny = scene.node(capsule).material().unwrap();
+        if let Some(Material::Phong(material)) = scene.material_mut(shiny) {
+            material.specular = [1.0, 1.0, 1.0];
+            material.shininess = 128.0;
+        }
+
+        let sun = scene.add_node(None);
+        scene.attach_light(
+            sun,
+            Light::directional([1.0, 0.95, 0.9], 0.4).with_shadow(Shadow::default()),
+        );
+        scene.set_rotation(
+            sun,
+            glm::quat_angle_axis(-0.9, &glm::vec3(1.0, 0.0, 0.0))
+                * glm::quat_angle_axis(0.5, &glm::vec3(0.0, 1.0, 0.0)),
+        );
+
+        // The lamp hangs off a pivot, which spins to carry it around.
+        let pivot = scene.add_node(None);
+        let lamp = scene.add_node(Some(pivot));
+        scene.attach_light(lamp, Light::point([1.0, 0.8, 0.5], 1.5));
+        scene.set_translation(lamp, glm::vec3(3.0, 1.0, 0.0));
+
+        let spot = scene.add_node(None);
+        scene.attach_light(
+            spot,
+            Light::spot([0.6, 0.7, 1.0], 3.0, 0.25, 0.4).with_shadow(Shadow {
+                pcf_radius: 2,
+                ..Shadow::default()
+            }),
+        );
+        scene.set_translation(spot, glm::vec3(0.0, 5.0, 0.0));
+        scene.set_rotation(
+            spot,
+            glm::quat_angle_axis(-std::f32::consts::FRAC_PI_2, &glm::vec3(1.0, 0.0, 0.0)),
+        );
+
+        let camera = Camera::default().look_at(glm::vec3(0.0, 3.0, 8.0), glm::Vec3::zeros());
+        let controller = Controller::orbit(&camera);
+
+        Ok(LitShapes {
+            canvas,
+            renderer,
+            scene,
+            torus,
+            lamp_pivot: pivot,
+            camera,
+            controller,
+            state,
+            delta: 0.0,
+        })
+    }
+
+    pub fn tick(&mut self, delta: f64) {
+        self.delta = delta as f32;
+    }
+
+    pub fn pointer_down(&mut self, button: i16, x: f32, y: f32) {
+        self.controller.pointer_down(button, x, y);
+    }
+
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        self.controller.pointer_move(x, y);
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.controller.pointer_up();
+    }
+
+    pub fn wheel(&mut self, delta: f32) {
+        self.controller.wheel(delta);
+    }
+
+    pub fn draw(&mut self) {
+        let (width, height) = (
+            self.canvas.client_width() as u32,
+            self.canvas.client_height() as u32,
+        );
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+        self.state.viewport(0, 0, width as i32, height as i32);
+
+        PIPELINE.clear(&mut self.state);
+        PIPELINE.apply(&mut self.state);
+
+        self.camera.resize(width, height);
+        self.controller.apply(&mut self.camera);
+
+        self.scene.set_rotation(
+            self.torus,
+            glm::quat_angle_axis(self.delta * 0.8, &glm::vec3(0.0, 1.0, 0.0))
+                * glm::quat_angle_axis(self.delta * 0.5, &glm::vec3(1.0, 0.0, 0.0)),
+        );
+        self.scene.set_rotation(
+            self.lamp_pivot,
+            glm::quat_angle_axis(self.delta * 0.6, &glm::vec3(0.0, 1.0, 0.0)),
+        );
+
+        self.renderer
+            .render(&mut self.state, &mut self.scene, &self.camera)
+            .unwrap_or_else(|err| log(&err));
+    }
+}
@@ -3,4 +3,10 @@ pub mod colored_square;
 pub mod colored_square_rotate;
 pub mod rotating_cube;
 pub mod rotating_square;
-pub mod mouse;
\ No newline at end of file
+pub mod mouse;
+pub mod lit_shapes;
+pub mod pbr_showcase;
+pub mod particle_field;
+pub mod instanced_cubes;
+pub mod gpu_simulation;
+pub mod fluid;
\ No newline at end of file
@@ -3,21 +3,46 @@ use wasm_bindgen::prelude::*;
 // use wasm_bindgen::JsValue;
 // use wasm_bindgen::JsCast;
 
-use web_sys::{HtmlCanvasElement, WebGl2RenderingContext, WebGlUniformLocation};
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext, WebGlProgram, WebGlVertexArrayObject};
 extern crate console_error_panic_hook;
 extern crate nalgebra_glm as glm;
 
+use crate::params::ShaderParameters;
+use crate::pipeline::PipelineState;
+use crate::state::GlState;
+use crate::uniforms::{FrameUniforms, SharedFrame};
 use crate::utils::log;
 use crate::webgl::{compile_shader, get_context_with_canvas_by_id, link_shader_program};
 
+/// Depth tested as the WebGL default, for shaders drawing more than the quad.
+static PIPELINE: PipelineState = PipelineState::new().depth(WebGl2RenderingContext::LESS, true);
+
+static QUAD_POSITIONS: [f32; 12] = [
+    // Triangle 1
+    -1.0, -1.0, // left-bottom
+    1.0, -1.0, // right-bottom
+    -1.0, 1.0, // left-top
+    // Triangle 2
+    -1.0, 1.0, // left-top
+    1.0, -1.0, // right-bottom
+    1.0, 1.0, // right-top
+];
+
 #[wasm_bindgen]
 pub struct MouseBox {
     context: WebGl2RenderingContext,
     canvas: HtmlCanvasElement,
-    loc_position: u32,
+    program: WebGlProgram,
+    // the quad, fed to `position`
+    vao: WebGlVertexArrayObject,
     // loc_color: WebGlUniformLocation,
-    loc_mouse_pos: Option<WebGlUniformLocation>,
-    loc_time: Option<WebGlUniformLocation>,
+    // whether `frame.time` follows `tick`
+    dynamic: bool,
+    parameters: ShaderParameters,
+    // for shaders declaring `FRAME_BLOCK`, shared with whatever else draws
+    // on the context
+    frame: SharedFrame,
+    state: GlState,
 }
 
 #[wasm_bindgen]
@@ -27,17 +52,15 @@ impl MouseBox {
         dynamic: bool,
         vertex_shader_source: &str,
         fragment_shader_source: &str,
-    ) -> Self {
+    ) -> Result<MouseBox, JsValue> {
         console_error_panic_hook::set_once();
 
-        let (context, canvas) = get_context_with_canvas_by_id(id).unwrap_or_else(|err| {
-            log(&err);
-            panic!("Failed to compile vertex shader");
-        });
+        let (context, canvas) = get_context_with_canvas_by_id(id)?;
 
         log("MouseBox.new: context ok");
 
-        resize_of(&context, &canvas);
+        let mut state = GlState::new(&context);
+        resize_of(&mut state, &canvas);
 
         log("MouseBox.new: resize ok");
 
@@ -45,11 +68,7 @@ impl MouseBox {
             &context,
             WebGl2RenderingContext::VERTEX_SHADER,
             vertex_shader_source,
-        )
-        .unwrap_or_else(|err| {
-            log(&err);
-            panic!("Failed to compile vertex shader");
-        });
+        )?;
 
         log("MouseBox.new: vertex shader compiled");
 
@@ -57,78 +76,29 @@ impl MouseBox {
             &context,
             WebGl2RenderingContext::FRAGMENT_SHADER,
             fragment_shader_source,
-        )
-        .unwrap_or_else(|err| {
-            log(&err);
-            panic!("Failed to compile fragment shader");
-        });
+        )?;
 
         log("MouseBox.new: fragment shader compiled");
 
-        let program = link_shader_program(&context, &vertex_shader, &fragment_shader)
-            .unwrap_or_else(|err| {
-                log(&err);
-                panic!("Failed to compile link shader");
-            });
+        let program = link_shader_program(&context, &vertex_shader, &fragment_shader)?;
 
         log("MouseBox.new: shaders linked to program");
 
-        context.use_program(Some(&program));
-
-        log("MouseBox.new: use program ok");
-
-        // set resolution (if not, it will become [0.0, 0.0])
-        let loc_resolution = context.get_uniform_location(&program, "u_resolution");
-
-        log("MouseBox.new: u_resolution location ok");
-
-        match loc_resolution {
-            None => {
-                log("no location for u_resolution");
-            }
-            Some(loc) => {
-                let viewport: [f32; 2] = [canvas.width() as f32, canvas.height() as f32];
-                log("MouseBox.new: viewport ok");
-                context.uniform2fv_with_f32_array(Some(&loc), &viewport);
-                log("MouseBox.new: set viewport to u_resolution ok");
-            }
-        }
-
-        // set mouse position
-        let loc_mouse_pos = context.get_uniform_location(&program, "u_mouse_pos");
-
-        log("MouseBox.new: u_mouse_pos location ok");
-
-        match loc_mouse_pos {
-            None => {
-                log("no location for u_mouse_pos");
+        // set resolution and time (if not, they will become 0.0)
+        let frame = FrameUniforms::shared(&context)?;
+        {
+            let mut frame = frame.borrow_mut();
+            if !frame.bind_program(&context, &program) {
+                log("no Frame block");
             }
-            Some(ref loc_mouse) => {
-                let initial_pos: [f32; 2] = [0.0, 0.0];
-                context.uniform2fv_with_f32_array(Some(&loc_mouse), &initial_pos);
-                log("MouseBox.new: set initial position to u_mouse_pos ok");
+            frame.resolution = [canvas.width() as f32, canvas.height() as f32];
+            if dynamic {
+                frame.time = get_current_sec() as f32;
             }
+            frame.upload(&context)?;
         }
 
-        let loc_time = if dynamic {
-            // set time
-            let loc_time = context
-                .get_uniform_location(&program, "u_time")
-                .unwrap_or_else(|| {
-                    panic!("Failed to get uniform location: u_time");
-                });
-
-            log("MouseBox.new: u_time location ok");
-
-            let current = get_current_sec() as f32;
-            context.uniform1f(Some(&loc_time), current);
-
-            log("MouseBox.new: set time to u_time ok");
-
-            Some(loc_time)
-        } else {
-            None
-        };
+        log("MouseBox.new: frame uniforms ok");
 
         // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.get_attrib_location
         // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/getAttribLocation
@@ -146,57 +116,29 @@ impl MouseBox {
         // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/getUniformLocation
         // let loc_color = context.get_uniform_location(&program, "color").unwrap();
 
-        // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.enable
-        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/enable
-        context.enable(WebGl2RenderingContext::DEPTH_TEST);
+        PIPELINE.apply(&mut state);
 
-        log("MouseBox.new: DEPTH_TEST ok");
+        log("MouseBox.new: pipeline state ok");
 
-        MouseBox {
-            context,
-            canvas,
-            loc_position,
-            loc_mouse_pos,
-            loc_time,
-        }
-    }
-
-    fn bind_position_buffer(&self, positions: &[f32]) {
-        let position_buffer = self
-            .context
-            .create_buffer()
-            .ok_or("Failed to create buffer")
-            .unwrap();
+        // leaves the program in use
+        let parameters = ShaderParameters::new(&mut state, &program, fragment_shader_source)?;
 
-        self.context
-            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&position_buffer));
+        log("MouseBox.new: shader parameters ok");
 
-        unsafe {
-            let positions_array_buffer_view = js_sys::Float32Array::view(positions);
+        let vao = quad_of(&mut state, loc_position)?;
 
-            self.context.buffer_data_with_array_buffer_view(
-                WebGl2RenderingContext::ARRAY_BUFFER,
-                &positions_array_buffer_view,
-                WebGl2RenderingContext::STATIC_DRAW,
-            );
-        }
-
-        self.context.enable_vertex_attrib_array(self.loc_position);
-
-        let num_components = 2;
-        let data_type: u32 = WebGl2RenderingContext::FLOAT;
-        let normalize = false;
-        let stride = 0;
-        let offset = 0;
+        log("MouseBox.new: quad ok");
 
-        self.context.vertex_attrib_pointer_with_i32(
-            self.loc_position,
-            num_components,
-            data_type,
-            normalize,
-            stride,
-            offset,
-        );
+        Ok(MouseBox {
+            context,
+            canvas,
+            program,
+            vao,
+            dynamic,
+            parameters,
+            frame,
+            state,
+        })
     }
 
     // fn bind_color_buffer(&self, color: &[f32]) {
@@ -205,21 +147,13 @@ impl MouseBox {
     //     self.context.uniform4fv_with_f32_array(Some(&self.loc_color), &color);
     // }
 
-    pub fn draw(&self) {
-        resize_of(&self.context, &self.canvas);
+    pub fn draw(&mut self) {
+        resize_of(&mut self.state, &self.canvas);
 
-        let positions = [
-            // Triangle 1
-            -1.0, -1.0, // left-bottom
-            1.0, -1.0, // right-bottom
-            -1.0, 1.0, // left-top
-            // Triangle 2
-            -1.0, 1.0, // left-top
-            1.0, -1.0, // right-bottom
-            1.0, 1.0, // right-top
-        ];
+        PIPELINE.apply(&mut self.state);
+        self.state.use_program(Some(&self.program));
 
-        self.bind_position_buffer(&positions);
+        self.state.bind_vertex_array(Some(&self.vao));
 
         // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.draw_arrays
         // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/drawArrays
@@ -229,26 +163,116 @@ impl MouseBox {
             .draw_arrays(WebGl2RenderingContext::TRIANGLES, offset, vertex_count);
     }
 
-    pub fn tick(&self, timestamp: f64, mouse_x: f64, mouse_y: f64) {
-        match &self.loc_time {
-            None => {}
-            Some(loc) => {
-                let current = timestamp as f32;
-                self.context.uniform1f(Some(&loc), current);
-            }
+    /// `mouse_x` and `mouse_y` are in canvas pixels from the top left.
+    pub fn tick(&mut self, timestamp: f64, mouse_x: f64, mouse_y: f64) {
+        // the size the next `draw` renders at
+        resize_of(&mut self.state, &self.canvas);
+        let height = self.canvas.height() as f32;
+        let mut frame = self.frame.borrow_mut();
+        frame.resolution = [self.canvas.width() as f32, height];
+        // `frame.mouse` is from the bottom left, as `gl_FragCoord`
+        frame.mouse = [mouse_x as f32, height - mouse_y as f32, 0.0, 0.0];
+
+        let uploaded = match self.dynamic {
+            true => frame.advance(&self.context, timestamp as f32),
+            false => frame.upload(&self.context),
+        };
+        if let Err(err) = uploaded {
+            log(&err);
         }
 
-        match &self.loc_mouse_pos {
-            None => {}
-            Some(loc) => {
-                let next_pos: [f32; 2] = [mouse_x as f32, mouse_y as f32];
-                self.context.uniform2fv_with_f32_array(Some(&loc), &next_pos);
-            }
+        if let Err(err) = self.parameters.advance(&mut self.state, timestamp) {
+            log(&err);
         }
     }
+
+    /// The annotated uniforms of the fragment shader, as a JSON array.
+    pub fn parameter_schema(&self) -> String {
+        self.parameters.schema_json()
+    }
+
+    pub fn set_parameter(&mut self, name: &str, values: &[f32]) -> Result<(), JsValue> {
+        self.parameters
+            .set(&mut self.state, name, values)
+            .map_err(JsValue::from)
+    }
+
+    pub fn get_parameter(&self, name: &str) -> Option<Vec<f32>> {
+        self.parameters.get(name)
+    }
+
+    /// The current parameter values as a JSON preset.
+    pub fn save_preset(&self) -> String {
+        self.parameters.save_preset()
+    }
+
+    pub fn load_preset(&mut self, json: &str) -> Result<(), JsValue> {
+        self.parameters
+            .load_preset(&mut self.state, json)
+            .map_err(JsValue::from)
+    }
+
+    /// Applies the blend of two presets at `t` in [0, 1].
+    pub fn blend_presets(&mut self, from: &str, to: &str, t: f32) -> Result<(), JsValue> {
+        self.parameters
+            .blend_presets(&mut self.state, from, to, t)
+            .map_err(JsValue::from)
+    }
+
+    /// Blends from the current values to `json` over `duration` seconds,
+    /// starting at the next `tick`.
+    pub fn transition_to(&mut self, json: &str, duration: f64) -> Result<(), JsValue> {
+        self.parameters
+            .transition_to(json, duration)
+            .map_err(JsValue::from)
+    }
+}
+
+/// A vertex array feeding `QUAD_POSITIONS` to the attribute at
+/// `loc_position`, left bound.
+fn quad_of(state: &mut GlState, loc_position: u32) -> Result<WebGlVertexArrayObject, String> {
+    let context = &state.context().clone();
+    let vao = context
+        .create_vertex_array()
+        .ok_or_else(|| String::from("Unable to create vertex array object"))?;
+    let position_buffer = context
+        .create_buffer()
+        .ok_or_else(|| String::from("Failed to create buffer"))?;
+
+    state.bind_vertex_array(Some(&vao));
+    state.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&position_buffer));
+
+    unsafe {
+        let positions_array_buffer_view = js_sys::Float32Array::view(&QUAD_POSITIONS);
+
+        context.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &positions_array_buffer_view,
+            WebGl2RenderingContext::STATIC_DRAW,
+        );
+    }
+
+    context.enable_vertex_attrib_array(loc_position);
+
+    let num_components = 2;
+    let data_type: u32 = WebGl2RenderingContext::FLOAT;
+    let normalize = false;
+    let stride = 0;
+    let offset = 0;
+
+    context.vertex_attrib_pointer_with_i32(
+        loc_position,
+        num_components,
+        data_type,
+        normalize,
+        stride,
+        offset,
+    );
+
+    Ok(vao)
 }
 
-fn resize_of(context: &WebGl2RenderingContext, canvas: &HtmlCanvasElement) {
+fn resize_of(state: &mut GlState, canvas: &HtmlCanvasElement) {
     let display_width: u32 = canvas.client_width().try_into().unwrap_or_else(|_| {
         panic!("Failed to get display width");
     });
@@ -265,7 +289,7 @@ fn resize_of(context: &WebGl2RenderingContext, canvas: &HtmlCanvasElement) {
     let new_width: i32 = canvas.width().try_into().unwrap();
     let new_height: i32 = canvas.height().try_into().unwrap();
 
-    context.viewport(0, 0, new_width, new_height);
+    state.viewport(0, 0, new_width, new_height);
 }
 
 fn get_current_sec() -> f64 {
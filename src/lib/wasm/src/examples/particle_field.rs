@@ -0,0 +1,133 @@
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext};
+extern crate console_error_panic_hook;
+extern crate nalgebra_glm as glm;
+
+use crate::camera::{Camera, Controller};
+use crate::particles::{ParticleOptions, ParticleShape, ParticleSystem, CURL_FORCE};
+use crate::pipeline::PipelineState;
+use crate::state::GlState;
+use crate::webgl::get_context_with_canvas_by_id;
+
+static PIPELINE: PipelineState = PipelineState::new()
+    .depth(WebGl2RenderingContext::LESS, true)
+    .clear_color([0.0, 0.0, 0.02, 1.0])
+    .clear_depth(1.0);
+
+/// Particles swirling out of a small sphere, moved by curl noise or by a
+/// force written in GLSL.
+#[wasm_bindgen]
+pub struct ParticleField {
+    canvas: HtmlCanvasElement,
+    particles: ParticleSystem,
+    state: GlState,
+    camera: Camera,
+    controller: Controller,
+    time: Option<f64>,
+    delta: f32,
+}
+
+#[wasm_bindgen]
+impl ParticleField {
+    pub fn new(id: &str, count: usize, quads: bool) -> Result<ParticleField, JsValue> {
+        Self::with_force(id, count, quads, CURL_FORCE)
+    }
+
+    /// `force` defines `vec3 force(vec3 position, vec3 velocity, float age,
+    /// float time)`; see particles.rs for what it can use.
+    pub fn with_force(
+        id: &str,
+        count: usize,
+        quads: bool,
+        force: &str,
+    ) -> Result<ParticleField, JsValue> {
+        console_error_panic_hook::set_once();
+
+        let (context, canvas) = get_context_with_canvas_by_id(id)?;
+
+        let options = ParticleOptions {
+            count,
+            shape: match quads {
+                true => ParticleShape::Quads,
+                false => ParticleShape::Points,
+            },
+            ..ParticleOptions::default()
+        };
+        let mut state = GlState::new(&context);
+        let particles = ParticleSystem::new(&mut state, options, force)?;
+
+        let camera = Camera::default().look_at(glm::vec3(0.0, 1.0, 6.0), glm::Vec3::zeros());
+        let controller = Controller::orbit(&camera);
+
+        Ok(ParticleField {
+            canvas,
+            particles,
+            state,
+            camera,
+            controller,
+            time: None,
+            delta: 0.0,
+        })
+    }
+
+    /// The annotated uniforms of the force, as a JSON array.
+    pub fn parameter_schema(&self) -> String {
+        self.particles.parameter_schema()
+    }
+
+    pub fn set_parameter(&mut self, name: &str, values: &[f32]) -> Result<(), JsValue> {
+        self.particles
+            .set_parameter(&mut self.state, name, values)
+            .map_err(JsValue::from)
+    }
+
+    pub fn set_size(&mut self, size: f32) {
+        self.particles.options.size = size;
+    }
+
+    /// `time` in seconds, e.g. the `requestAnimationFrame` timestamp / 1000.
+    pub fn tick(&mut self, time: f64) {
+        self.delta = self.time.map_or(0.0, |last| (time - last) as f32);
+        self.time = Some(time);
+    }
+
+    pub fn pointer_down(&mut self, button: i16, x: f32, y: f32) {
+        self.controller.pointer_down(button, x, y);
+    }
+
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        self.controller.pointer_move(x, y);
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.controller.pointer_up();
+    }
+
+    pub fn wheel(&mut self, delta: f32) {
+        self.controller.wheel(delta);
+    }
+
+    pub fn draw(&mut self) {
+        let (width, height) = (
+            self.canvas.client_width() as u32,
+            self.canvas.client_height() as u32,
+        );
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+        self.state.viewport(0, 0, width as i32, height as i32);
+
+        PIPELINE.clear(&mut self.state);
+        PIPELINE.apply(&mut self.state);
+
+        self.camera.resize(width, height);
+        self.controller.apply(&mut self.camera);
+
+        let time = self.time.unwrap_or(0.0) as f32;
+        self.particles.update(&mut self.state, time, self.delta);
+        self.particles.render(&mut self.state, &self.camera);
+    }
+}
@@ -0,0 +1,284 @@
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext};
+extern crate console_error_panic_hook;
+extern crate nalgebra_glm as glm;
+
+use crate::camera::{Camera, Controller};
+use crate::geometry;
+use crate::ibl::{Environment, EnvironmentOptions};
+use crate::lighting::Light;
+use crate::loaders::gltf::parse_glb;
+use crate::loaders::hdr::{parse_hdr, HdrImage};
+use crate::pbr::{add_gltf, PbrMaterial, PbrRenderer};
+use crate::pipeline::PipelineState;
+use crate::postprocess::{EffectKind, PostProcess};
+use crate::scene::{NodeId, Scene};
+use crate::state::GlState;
+use crate::target::Precision;
+use crate::utils::log;
+use crate::webgl::get_context_with_canvas_by_id;
+
+const GRID: usize = 7;
+
+static PIPELINE: PipelineState = PipelineState::new()
+    .depth(WebGl2RenderingContext::LESS, true)
+    .clear_color([0.0, 0.0, 0.0, 1.0])
+    .clear_depth(1.0);
+
+/// A grid of spheres, metalness increasing upwards and roughness to the
+/// right, lit by an environment map. A .glb model can replace the grid.
+///
+/// Rendered into a half float target and tone mapped at the end when the
+/// browser can, with the renderer's own Reinhard otherwise.
+#[wasm_bindgen]
+pub struct PbrShowcase {
+    canvas: HtmlCanvasElement,
+    renderer: PbrRenderer,
+    post: PostProcess,
+    scene: Scene,
+    spheres: NodeId,
+    camera: Camera,
+    controller: Controller,
+    state: GlState,
+}
+
+#[wasm_bindgen]
+impl PbrShowcase {
+    /// Lit by a procedural sky.
+    pub fn new(id: &str) -> Result<PbrShowcase, JsValue> {
+        Self::with_image(id, &sky(256, 128))
+    }
+
+    /// Lit by a Radiance .hdr environment map.
+    pub fn with_hdr(id: &str, bytes: &[u8]) -> Result<PbrShowcase, JsValue> {
+        Self::with_image(id, &parse_hdr(bytes)?)
+    }
+
+    pub fn set_hdr(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let image = parse_hdr(bytes)?;
+        let environment =
+            Environment::from_hdr(self.state.context(), &image, EnvironmentOptions::default());
+        // baking binds on the context itself
+        self.state.invalidate();
+        self.renderer.environment = Some(environment?);
+
+        Ok(())
+    }
+
+    /// Replaces the spheres with the default scene of a .glb file.
+    pub fn load_glb(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let gltf = parse_glb(bytes)?;
+
+        for sphere in self.scene.node(self.spheres).children().to_vec() {
+            self.scene.detach_mesh(sphere);
+        }
+        add_gltf(&mut self.state, &mut self.scene, &gltf)?;
+
+        self.camera = self
+            .camera
+            .clone()
+            .look_at(glm::vec3(0.0, 1.0, 4.0), glm::Vec3::zeros());
+        self.controller = Controller::orbit(&self.camera);
+
+        Ok(())
+    }
+
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.renderer.exposure = exposure;
+    }
+
+    /// 0 for Reinhard, 1 for ACES, 2 for AgX; without float targets the
+    /// renderer always uses Reinhard.
+    pub fn set_tone_mapper(&mut self, operator: i32) -> Result<(), JsValue> {
+        if self.renderer.tone_map {
+            return Ok(());
+        }
+
+        self.post
+            .set_parameter(
+                &mut self.state,
+                EffectKind::ToneMapping.name(),
+                "uOperator",
+                &[operator as f32],
+            )
+            .map_err(JsValue::from)
+    }
+
+    pub fn pointer_down(&mut self, button: i16, x: f32, y: f32) {
+        self.controller.pointer_down(button, x, y);
+    }
+
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        self.controller.pointer_move(x, y);
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.controller.pointer_up();
+    }
+
+    pub fn wheel(&mut self, delta: f32) {
+        self.controller.wheel(delta);
+    }
+
+    pub fn draw(&mut self) {
+        let (width, height) = (
+            self.canvas.client_width() as u32,
+            self.canvas.client_height() as u32,
+        );
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+        self.post
+            .begin(&mut self.state, width, height)
+            .unwrap_or_else(|err| log(&err));
+
+        PIPELINE.clear(&mut self.state);
+        PIPELINE.apply(&mut self.state);
+
+        self.camera.resize(width, height);
+        self.controller.apply(&mut self.camera);
+
+        if let Some(environment) = &self.renderer.environment {
+            environment.draw_background(
+                &mut self.state,
+                &self.camera,
+                0.0,
+                self.renderer.exposure,
+                self.renderer.tone_map,
+            );
+        }
+
+        self.renderer
+            .render(&mut self.state, &mut self.scene, &self.camera)
+            .unwrap_or_else(|err| log(&err));
+
+        self.post
+            .end(&mut self.state)
+            .unwrap_or_else(|err| log(&err));
+    }
+}
+
+impl PbrShowcase {
+    fn with_image(id: &str, image: &HdrImage) -> Result<PbrShowcase, JsValue> {
+        console_error_panic_hook::set_once();
+
+        let (context, canvas) = get_context_with_canvas_by_id(id)?;
+        let mut state = GlState::new(&context);
+        let mut renderer = PbrRenderer::new(&context, 4)?;
+        renderer.environment = Some(Environment::from_hdr(
+            &context,
+            image,
+            EnvironmentOptions::default(),
+        )?);
+
+        let mut post = PostProcess::new(&context, canvas.width(), canvas.height())?;
+        post.push(&mut state, EffectKind::ToneMapping)?;
+        // tone mapped at the end instead of by the renderer
+        renderer.tone_map = post.set_precision(&mut state, Precision::Half)? == Precision::Byte;
+        post.set_enabled(EffectKind::ToneMapping.name(), !renderer.tone_map)?;
+        // offscreen targets are not antialiased like the canvas
+        post.set_samples(&mut state, 4)?;
+
+        let mut scene = Scene::new();
+        let spheres = scene.add_node(None);
+        let mesh = geometry::uv_sphere(0.4, 48, 24).to_mesh(&mut state)?;
+        let mesh = scene.add_mesh(mesh);
+
+        let spacing = 1.0;
+        let offset = (GRID - 1) as f32 * spacing / 2.0;
+
+        for row in 0..GRID {
+            for column in 0..GRID {
+                let metallic = row as f32 / (GRID - 1) as f32;
+                let roughness = column as f32 / (GRID - 1) as f32;
+                let material =
+                    scene.add_material(PbrMaterial::new([0.9, 0.6, 0.3], metallic, roughness));
+
+                let sphere = scene.add_node(Some(spheres));
+                scene.attach_mesh(sphere, mesh);
+                scene.set_material(sphere, material);
+                scene.set_translation(
+                    sphere,
+                    glm::vec3(
+                        column as f32 * spacing - offset,
+                        row as f32 * spacing - offset,
+                        0.0,
+                    ),
+                );
+            }
+        }
+
+        let sun = scene.add_node(None);
+        scene.attach_light(sun, Light::directional([1.0, 0.95, 0.9], 2.0));
+        scene.set_rotation(
+            sun,
+            glm::quat_angle_axis(-0.6, &glm::vec3(1.0, 0.0, 0.0))
+                * glm::quat_angle_axis(0.4, &glm::vec3(0.0, 1.0, 0.0)),
+        );
+
+        let camera = Camera::default().look_at(glm::vec3(0.0, 0.0, 10.0), glm::Vec3::zeros());
+        let controller = Controller::orbit(&camera);
+
+        Ok(PbrShowcase {
+            canvas,
+            renderer,
+            post,
+            scene,
+            spheres,
+            camera,
+            controller,
+            state,
+        })
+    }
+}
+
+/// An equirectangular sky: blue above, a warm horizon, dark ground and a
+/// small, very bright sun.
+fn sky(width: u32, height: u32) -> HdrImage {
+    let sun = glm::normalize(&glm::vec3(0.5, 0.6, 0.4));
+    let mut data = Vec::with_capacity((width * height * 3) as usize);
+
+    for y in 0..height {
+        // latitude, from +90° at the top row to -90° at the bottom
+        let latitude = (0.5 - (y as f32 + 0.5) / height as f32) * std::f32::consts::PI;
+
+        for x in 0..width {
+            let longitude = ((x as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+            let direction = glm::vec3(
+                latitude.cos() * longitude.cos(),
+                latitude.sin(),
+                latitude.cos() * longitude.sin(),
+            );
+
+            let up = direction.y;
+            let mut color = if up >= 0.0 {
+                glm::lerp(
+                    &glm::vec3(1.2, 1.0, 0.8),
+                    &glm::vec3(0.3, 0.5, 1.0),
+                    up.sqrt(),
+                )
+            } else {
+                glm::lerp(
+                    &glm::vec3(0.4, 0.35, 0.3),
+                    &glm::vec3(0.1, 0.1, 0.1),
+                    (-up).sqrt(),
+                )
+            };
+
+            if glm::dot(&direction, &sun) > 0.999 {
+                color += glm::vec3(200.0, 180.0, 150.0);
+            }
+
+            data.extend(color.iter());
+        }
+    }
+
+    HdrImage {
+        width,
+        height,
+        data,
+    }
+}
@@ -3,32 +3,43 @@ use wasm_bindgen::prelude::*;
 // use wasm_bindgen::JsValue;
 use wasm_bindgen::JsCast;
 
-use web_sys::{
-    HtmlCanvasElement, WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader,
-    WebGlUniformLocation,
-};
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext, WebGlProgram, WebGlShader};
 extern crate console_error_panic_hook;
 extern crate nalgebra_glm as glm;
 
-// use crate::utils::log;
-
-static VS_SRC: &'static str = r#"#version 300 es
+use crate::camera::{Camera, Controller, Projection};
+use crate::geometry::Geometry;
+use crate::loaders::gltf::parse_glb;
+use crate::loaders::obj::parse_obj;
+use crate::mesh::{AttributeType, Indices, Mesh, VertexBuffer, VertexLayout};
+use crate::pipeline::PipelineState;
+use crate::scene::{NodeId, Renderer, Scene};
+use crate::state::{GlState, StateStats};
+use crate::uniforms::{FrameUniforms, SharedFrame, FRAME_BLOCK};
+use crate::utils::log;
+
+fn vertex_shader() -> String {
+    format!(
+        r#"#version 300 es
 in vec4 aVertexPosition;
 in vec4 aVertexColor;
 
-uniform mat4 uModelViewMatrix;
-uniform mat4 uProjectionMatrix;
+{frame}
+uniform mat4 uModelMatrix;
 
 out vec4 vColor;
 
-void main() {
-  gl_Position = uProjectionMatrix * uModelViewMatrix * aVertexPosition;
+void main() {{
+  gl_Position = frame.viewProjection * uModelMatrix * aVertexPosition;
   // gl_PointSize = 4.0;
   vColor = aVertexColor;
+}}
+"#,
+        frame = FRAME_BLOCK
+    )
 }
-"#;
 
-static FS_SRC: &'static str = r#"#version 300 es
+static FS_SRC: &str = r#"#version 300 es
 precision mediump float;
 in vec4 vColor;
 out vec4 fragColor;
@@ -64,6 +75,12 @@ void main() {
 //      1.0, -1.0, 1.0,
 // ];
 
+/// Cleared to black; no culling, as loaded models may wind either way.
+static PIPELINE: PipelineState = PipelineState::new()
+    .depth(WebGl2RenderingContext::LEQUAL, true)
+    .clear_color([0.0, 0.0, 0.0, 1.0])
+    .clear_depth(1.0);
+
 static CUBE_POSITIONS: [f32; 72] = [
     // Front face: White
     -1.0, -1.0, 1.0, // debug: skyblue
@@ -127,6 +144,121 @@ fn cube_colors() -> Vec<f32> {
     // .concat()
 }
 
+/// Interleaves `CUBE_POSITIONS` and `cube_colors()` into one buffer.
+fn cube_mesh(state: &mut GlState) -> Result<Mesh, String> {
+    let colors = cube_colors();
+
+    let vertices = CUBE_POSITIONS
+        .chunks(3)
+        .zip(colors.chunks(4))
+        .flat_map(|(position, color)| position.iter().chain(color).copied())
+        .collect::<Vec<_>>();
+
+    let layout = VertexLayout::new()
+        .attribute("aVertexPosition", 3, AttributeType::Float, false)
+        .attribute("aVertexColor", 4, AttributeType::Float, false);
+
+    Mesh::new(vec![VertexBuffer::from_f32(state, &vertices, layout)?])?
+        .with_indices(state, Indices::U16(CUBE_INDICES.to_vec()))
+}
+
+/// Every mesh of an OBJ model.
+fn obj_meshes(state: &mut GlState, obj_source: &str) -> Result<Vec<Mesh>, String> {
+    let model = parse_obj(obj_source)?;
+
+    let parts = model
+        .meshes
+        .iter()
+        .map(|mesh| (&mesh.geometry, glm::Mat4::identity()))
+        .collect::<Vec<_>>();
+
+    normal_colored_meshes(state, &parts)
+}
+
+/// Every primitive reachable from the default scene of a GLB, placed by the
+/// node hierarchy.
+fn glb_meshes(state: &mut GlState, bytes: &[u8]) -> Result<Vec<Mesh>, String> {
+    let gltf = parse_glb(bytes)?;
+
+    for warning in &gltf.warnings {
+        log(warning);
+    }
+
+    let mut parts = vec![];
+    for (idx, world) in gltf.world_nodes()? {
+        if let Some(mesh) = gltf.nodes[idx].mesh.and_then(|mesh| gltf.meshes.get(mesh)) {
+            parts.extend(mesh.primitives.iter().map(|p| (&p.geometry, world)));
+        }
+    }
+
+    normal_colored_meshes(state, &parts)
+}
+
+/// Geometries moved by their transforms, then fit together into the cube's
+/// [-1, 1] box and colored by their normals.
+fn normal_colored_meshes(
+    state: &mut GlState,
+    parts: &[(&Geometry, glm::Mat4)],
+) -> Result<Vec<Mesh>, String> {
+    let transformed = parts
+        .iter()
+        .map(|(geometry, transform)| {
+            let normal_matrix = glm::mat4_to_mat3(&glm::transpose(&glm::inverse(transform)));
+
+            let positions = geometry
+                .positions
+                .iter()
+                .map(|p| (transform * glm::vec4(p[0], p[1], p[2], 1.0)).xyz())
+                .collect::<Vec<_>>();
+            let normals = geometry
+                .normals
+                .iter()
+                .map(|n| glm::normalize(&(normal_matrix * glm::make_vec3(n))))
+                .collect::<Vec<_>>();
+
+            (positions, normals, &geometry.indices)
+        })
+        .collect::<Vec<_>>();
+
+    let (min, max) = transformed
+        .iter()
+        .flat_map(|(positions, _, _)| positions.iter())
+        .fold(
+            (glm::Vec3::repeat(f32::MAX), glm::Vec3::repeat(f32::MIN)),
+            |(min, max), p| (glm::min2(&min, p), glm::max2(&max, p)),
+        );
+
+    let center = (min + max) / 2.0;
+    let extent = (max - min).max();
+    let scale = if extent > 0.0 { 2.0 / extent } else { 1.0 };
+
+    let layout = VertexLayout::new()
+        .attribute("aVertexPosition", 3, AttributeType::Float, false)
+        .attribute("aVertexColor", 4, AttributeType::Float, false);
+
+    transformed
+        .iter()
+        .map(|(positions, normals, indices)| {
+            let vertices = positions
+                .iter()
+                .zip(normals)
+                .flat_map(|(p, n)| {
+                    let p = (p - center) * scale;
+                    let c = n * 0.5 + glm::Vec3::repeat(0.5);
+                    [p.x, p.y, p.z, c.x, c.y, c.z, 1.0]
+                })
+                .collect::<Vec<_>>();
+
+            Mesh::new(vec![VertexBuffer::from_f32(
+                state,
+                &vertices,
+                layout.clone(),
+            )?])?
+            .with_indices(state, Indices::compact(indices.to_vec()))
+        })
+        .collect()
+}
+
 #[wasm_bindgen]
 pub struct RotatingCube {
     context: WebGl2RenderingContext,
@@ -135,7 +267,14 @@ pub struct RotatingCube {
     // loc_vertex_color: u32,
     // loc_model_view_matrix: WebGlUniformLocation,
     // loc_projection_matrix: WebGlUniformLocation,
-    location: Location,
+    renderer: Renderer,
+    scene: Scene,
+    spin: NodeId,
+    camera: Camera,
+    frame: SharedFrame,
+    controller: Controller,
+    state: GlState,
+    frame_stats: StateStats,
     delta: f32,
 }
 
@@ -145,21 +284,74 @@ impl RotatingCube {
         console_error_panic_hook::set_once();
 
         let (ctx, canvas) = get_context_by_id(id).unwrap();
-        let vs = compile_shader(&ctx, WebGl2RenderingContext::VERTEX_SHADER, VS_SRC).unwrap();
-        let fs = compile_shader(&ctx, WebGl2RenderingContext::FRAGMENT_SHADER, FS_SRC).unwrap();
-        let program = link_shader_program(&ctx, &vs, &fs).unwrap();
-        ctx.use_program(Some(&program));
+        let mut state = GlState::new(&ctx);
+        let meshes = vec![cube_mesh(&mut state).unwrap()];
 
-        let location = get_locations(&ctx, &program).unwrap();
+        Self::with_meshes(state, canvas, meshes)
+    }
+
+    /// Spins a Wavefront OBJ model instead of the cube, scaled to the same
+    /// size and colored by its normals.
+    pub fn with_model(id: &str, obj_source: &str) -> Result<RotatingCube, JsValue> {
+        console_error_panic_hook::set_once();
+
+        let (ctx, canvas) = get_context_by_id(id)?;
+        let mut state = GlState::new(&ctx);
+        let meshes = obj_meshes(&mut state, obj_source)?;
+
+        Ok(Self::with_meshes(state, canvas, meshes))
+    }
+
+    /// Like `with_model`, for glTF binary (.glb) bytes.
+    pub fn with_glb(id: &str, glb: &[u8]) -> Result<RotatingCube, JsValue> {
+        console_error_panic_hook::set_once();
 
-        // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.enable
-        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/enable
-        ctx.enable(WebGl2RenderingContext::DEPTH_TEST);
+        let (ctx, canvas) = get_context_by_id(id)?;
+        let mut state = GlState::new(&ctx);
+        let meshes = glb_meshes(&mut state, glb)?;
+
+        Ok(Self::with_meshes(state, canvas, meshes))
+    }
+
+    fn with_meshes(state: GlState, canvas: HtmlCanvasElement, meshes: Vec<Mesh>) -> Self {
+        let ctx = state.context().clone();
+        let vs = compile_shader(
+            &ctx,
+            WebGl2RenderingContext::VERTEX_SHADER,
+            &vertex_shader(),
+        )
+        .unwrap();
+        let fs = compile_shader(&ctx, WebGl2RenderingContext::FRAGMENT_SHADER, FS_SRC).unwrap();
+        let program = link_shader_program(&ctx, &vs, &fs).unwrap();
+        let mut renderer = Renderer::new(&ctx, &program);
+        renderer.set_pipeline(PIPELINE);
+
+        let camera = Camera::default();
+        let controller = Controller::orbit(&camera);
+        let frame = FrameUniforms::shared(&ctx).unwrap();
+        frame.borrow().bind_program(&ctx, &program);
+
+        // Every mesh hangs off one node, which is the one that rotates.
+        let mut scene = Scene::new();
+        let spin = scene.add_node(None);
+
+        for mesh in meshes {
+            let mesh = scene.add_mesh(mesh);
+            let node = scene.add_node(Some(spin));
+            scene.attach_mesh(node, mesh);
+        }
 
         RotatingCube {
             context: ctx,
             canvas,
-            location,
+            renderer,
+            scene,
+            spin,
+            camera,
+            frame,
+            controller,
+            state,
+            frame_stats: StateStats::default(),
             delta: 0.0,
         }
     }
@@ -171,169 +363,109 @@ impl RotatingCube {
     // }
 
     pub fn tick(&mut self, delta: f64) {
+        let dt = (delta as f32 - self.delta).max(0.0);
+        self.controller.update(dt);
+
         self.delta = delta as f32;
     }
 
-    pub fn draw(&self) {
-        resize_of(&self.context, &self.canvas);
-
-        // clear canvas
-        self.context.clear_color(0.0, 0.0, 0.0, 1.0); // black, fully-opaque
-        self.context.clear_depth(1.0);
-        self.context.enable(WebGl2RenderingContext::DEPTH_TEST);
-        self.context.depth_func(WebGl2RenderingContext::LEQUAL);
-
-        self.context.clear(
-            WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT,
-        );
-
-        // let vao = self.context.create_vertex_array().unwrap();
-        // self.context.bind_vertex_array(Some(&vao));
-
-        let buf_position = self.bind_array_buffer(&CUBE_POSITIONS);
-        let buf_colors = self.bind_array_buffer(&cube_colors()[..]);
-        let buf_indices = self.bind_index_buffer(&CUBE_INDICES);
-
-        self.set_vertex_attribute(self.location.vertex_position, 3, &buf_position);
-        self.set_vertex_attribute(self.location.vertex_color, 4, &buf_colors);
-
-        // why need to bind again?
-        self.context.bind_buffer(
-            WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
-            Some(&buf_indices),
-        );
-
-        // setup matrix
-        let field_of_view = 45.0 * std::f32::consts::PI / 180.0;
-        let aspect = self.canvas.client_width() as f32 / self.canvas.client_height() as f32;
-        let z_near = 0.1;
-        let z_far = 100.0;
-
-        // https://docs.rs/nalgebra-glm/latest/nalgebra_glm/fn.perspective.html
-        let projection_matrix = glm::perspective(aspect, field_of_view, z_near, z_far);
-        // let projection_matrix = glm::Mat4::identity();
-
-        // // https://docs.rs/nalgebra-glm/latest/nalgebra_glm/fn.translate.html
-        let model_view_matrix =
-            glm::translate(&glm::Mat4::identity(), &glm::TVec3::new(-0.0, 0.0, -6.0));
-        // let model_view_matrix = glm::Mat4::identity();
-
-        // https://docs.rs/nalgebra-glm/latest/nalgebra_glm/fn.rotate.html
-        let model_view_matrix = glm::rotate(
-            &model_view_matrix,
-            self.delta,
-            &glm::TVec3::new(0.0, 0.0, 1.0), // rotate around axis Z
-        );
-
-        let model_view_matrix = glm::rotate(
-            &model_view_matrix,
-            self.delta * 0.7,
-            &glm::TVec3::new(0.0, 1.0, 0.0), // rotate around axis Y
-        );
-
-        let model_view_matrix = glm::rotate(
-            &model_view_matrix,
-            self.delta * 0.3,
-            &glm::TVec3::new(1.0, 0.0, 0.0), // rotate around axis X
-        );
-
-        let vec_projection_matrix = projection_matrix.iter().map(|v| *v).collect::<Vec<_>>();
-        let vec_model_view_matrix = model_view_matrix.iter().map(|v| *v).collect::<Vec<_>>();
-
-        self.context.uniform_matrix4fv_with_f32_array(
-            Some(&self.location.projection_matrix),
-            false,
-            &vec_projection_matrix,
-        );
+    /// `button` is `MouseEvent.button`: main rotates, secondary pans (orbit
+    /// mode); main looks around (fly mode).
+    pub fn pointer_down(&mut self, button: i16, x: f32, y: f32) {
+        self.controller.pointer_down(button, x, y);
+    }
 
-        self.context.uniform_matrix4fv_with_f32_array(
-            Some(&self.location.model_view_matrix),
-            false,
-            &vec_model_view_matrix,
-        );
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        self.controller.pointer_move(x, y);
+    }
 
-        // draw
-        let offset = 0;
-        let vertex_count: i32 = CUBE_INDICES.len().try_into().unwrap();
-        let data_type = WebGl2RenderingContext::UNSIGNED_SHORT;
-        let instance_count = 1;
-
-        // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGl2RenderingContext.html#method.draw_elements_instanced_with_f64
-        self.context.draw_elements_instanced_with_i32(
-            WebGl2RenderingContext::TRIANGLES,
-            // WebGl2RenderingContext::POINTS,
-            // WebGl2RenderingContext::LINES,
-            vertex_count,
-            data_type,
-            offset,
-            instance_count,
-        );
+    pub fn pointer_up(&mut self) {
+        self.controller.pointer_up();
     }
 
-    fn bind_array_buffer(&self, array: &[f32]) -> WebGlBuffer {
-        let buffer = self.context.create_buffer().unwrap();
+    /// `WheelEvent.deltaY`.
+    pub fn wheel(&mut self, delta: f32) {
+        self.controller.wheel(delta);
+    }
 
-        self.context
-            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+    /// `KeyboardEvent.code`. Returns true when the key was used, so the page
+    /// can `preventDefault`.
+    pub fn key_down(&mut self, code: &str) -> bool {
+        self.controller.key_down(code)
+    }
 
-        unsafe {
-            let array_buffer_view = js_sys::Float32Array::view(array);
+    pub fn key_up(&mut self, code: &str) {
+        self.controller.key_up(code);
+    }
 
-            self.context.buffer_data_with_array_buffer_view(
-                WebGl2RenderingContext::ARRAY_BUFFER,
-                &array_buffer_view,
-                WebGl2RenderingContext::STATIC_DRAW,
-            );
-        }
+    /// Switches between orbiting the model and flying with WASD, starting
+    /// from the current view.
+    pub fn set_fly(&mut self, fly: bool) {
+        self.controller = if fly {
+            Controller::fly(&self.camera)
+        } else {
+            Controller::orbit(&self.camera)
+        };
+    }
 
-        buffer
+    pub fn set_orthographic(&mut self, orthographic: bool) {
+        self.camera.projection = if orthographic {
+            Projection::Orthographic {
+                height: 5.0,
+                near: 0.1,
+                far: 100.0,
+            }
+        } else {
+            Camera::default().projection
+        };
     }
 
-    fn set_vertex_attribute(&self, location: u32, num_components: i32, buffer: &WebGlBuffer) {
-        self.context.enable_vertex_attrib_array(location);
-
-        let data_type: u32 = WebGl2RenderingContext::FLOAT;
-        let normalize = false;
-        let stride = 0;
-        let offset = 0;
-
-        // why need to bind again?
-        self.context
-            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(buffer));
-
-        // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGl2RenderingContext.html#method.vertex_attrib_pointer_with_i32
-        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/vertexAttribIPointer
-        self.context.vertex_attrib_pointer_with_i32(
-            location,
-            num_components,
-            data_type,
-            normalize,
-            stride,
-            offset,
-        );
+    /// GL calls of the last frame as `[issued, skipped]`, the skipped ones
+    /// being those the state cache found redundant.
+    pub fn gl_stats(&self) -> Vec<u32> {
+        vec![self.frame_stats.issued, self.frame_stats.skipped]
     }
 
-    fn bind_index_buffer(&self, indices: &[u16]) -> WebGlBuffer {
-        let buffer = self.context.create_buffer().unwrap();
+    pub fn draw(&mut self) {
+        self.frame_stats = self.state.take_stats();
 
-        self.context
-            .bind_buffer(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, Some(&buffer));
+        resize_of(&mut self.state, &self.canvas);
 
-        unsafe {
-            let index_buffer_view = js_sys::Uint16Array::view(indices);
+        // after the first frame only `clear` reaches WebGL
+        PIPELINE.clear(&mut self.state);
+        PIPELINE.apply(&mut self.state);
 
-            self.context.buffer_data_with_array_buffer_view(
-                WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
-                &index_buffer_view,
-                WebGl2RenderingContext::STATIC_DRAW,
-            );
+        // setup camera
+        self.camera
+            .resize(self.canvas.width(), self.canvas.height());
+        self.controller.apply(&mut self.camera);
+        let mut frame = self.frame.borrow_mut();
+        frame.resolution = [self.canvas.width() as f32, self.canvas.height() as f32];
+        frame.set_camera(&self.camera);
+        if let Err(err) = frame.advance(&self.context, self.delta) {
+            log(&err);
         }
 
-        buffer
+        // https://docs.rs/nalgebra-glm/latest/nalgebra_glm/fn.quat_angle_axis.html
+        let rotation = glm::quat_angle_axis(self.delta, &glm::vec3(0.0, 0.0, 1.0)) // axis Z
+            * glm::quat_angle_axis(self.delta * 0.7, &glm::vec3(0.0, 1.0, 0.0)) // axis Y
+            * glm::quat_angle_axis(self.delta * 0.3, &glm::vec3(1.0, 0.0, 0.0)); // axis X
+
+        self.scene.set_rotation(self.spin, rotation);
+
+        // draw
+        self.renderer
+            .render(
+                &mut self.state,
+                &mut self.scene,
+                &self.camera.view_matrix(),
+                &self.camera.projection_matrix(),
+            )
+            .unwrap_or_else(|err| log(&err));
     }
 }
 
-fn resize_of(context: &WebGl2RenderingContext, canvas: &HtmlCanvasElement) {
+fn resize_of(state: &mut GlState, canvas: &HtmlCanvasElement) {
     let display_width: u32 = canvas.client_width().try_into().unwrap_or_else(|_| {
         panic!("Failed to get display width");
     });
@@ -352,7 +484,7 @@ fn resize_of(context: &WebGl2RenderingContext, canvas: &HtmlCanvasElement) {
     let new_width: i32 = canvas.width().try_into().unwrap();
     let new_height: i32 = canvas.height().try_into().unwrap();
 
-    context.viewport(0, 0, new_width, new_height);
+    state.viewport(0, 0, new_width, new_height);
 }
 
 // fn get_current_sec() -> f64 {
@@ -434,44 +566,3 @@ fn link_shader_program(
         Err(msg)
     }
 }
-
-struct Location {
-    vertex_position: u32,
-    vertex_color: u32,
-    model_view_matrix: WebGlUniformLocation,
-    projection_matrix: WebGlUniformLocation,
-}
-
-fn get_locations(
-    context: &WebGl2RenderingContext,
-    program: &WebGlProgram,
-) -> Result<Location, String> {
-    // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.get_attrib_location
-    // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/getAttribLocation
-    let loc_vertex_position: u32 = context
-        .get_attrib_location(&program, "aVertexPosition")
-        .try_into()
-        .unwrap(); //_or_else(|| String::from("Failed to get attribute location: aVertexPosition"));
-
-    let loc_vertex_color: u32 = context
-        .get_attrib_location(&program, "aVertexColor")
-        .try_into()
-        .unwrap(); // _or_else(|| String::from("Failed to get attribute location: aVertexColor"));
-
-    // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.get_uniform_location
-    // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/getUniformLocation
-    let loc_model_view_matrix = context
-        .get_uniform_location(&program, "uModelViewMatrix")
-        .unwrap(); // _or_else(|| String::from("Failed to get uniform location: uModelViewMatrix"));
-
-    let loc_projection_matrix = context
-        .get_uniform_location(&program, "uProjectionMatrix")
-        .unwrap(); // _or_else(|| String::from("Failed to get uniform location: uProjectionMatrix"));
-
-    Ok(Location {
-        vertex_position: loc_vertex_position,
-        vertex_color: loc_vertex_color,
-        model_view_matrix: loc_model_view_matrix,
-        projection_matrix: loc_projection_matrix,
-    })
-}
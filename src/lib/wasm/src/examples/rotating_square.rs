@@ -5,13 +5,13 @@ extern crate nalgebra_glm as glm;
 use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlUniformLocation};
 
 use super::colored_square::{init_buffers, setup_shader_program};
+use crate::camera::Camera;
 use crate::webgl::get_context_by_id;
 
 #[wasm_bindgen]
 pub struct RotatingSquare {
     context: WebGl2RenderingContext,
-    canvas_width: f32,
-    canvas_height: f32,
+    camera: Camera,
     position_buffer: WebGlBuffer,
     color_buffer: WebGlBuffer,
     shader_program: WebGlProgram,
@@ -30,10 +30,12 @@ impl RotatingSquare {
         let (position_buffer, color_buffer) = init_buffers(&context);
         let program = setup_shader_program(&context).unwrap();
 
+        let mut camera = Camera::default();
+        camera.resize(canvas.width as u32, canvas.height as u32);
+
         RotatingSquare {
             context,
-            canvas_width: canvas.width,
-            canvas_height: canvas.height,
+            camera,
             position_buffer,
             color_buffer,
             shader_program: program.program,
@@ -48,24 +50,16 @@ impl RotatingSquare {
     }
 
     pub fn render(&self) {
-        let field_of_view = 45.0 * std::f32::consts::PI / 180.0;
-        let aspect = self.canvas_width / self.canvas_height;
-        let z_near = 0.1;
-        let z_far = 100.0;
-
-        let projection_matrix = glm::perspective(aspect, field_of_view, z_near, z_far);
-
-        let model_view_matrix =
-            glm::translate(&glm::Mat4::identity(), &glm::TVec3::new(-0.0, 0.0, -6.0));
+        let projection_matrix = self.camera.projection_matrix();
 
         let model_view_matrix = glm::rotate(
-            &model_view_matrix,
+            &self.camera.view_matrix(),
             self.delta,
             &glm::TVec3::new(0.0, 0.0, 1.0),
         );
 
-        let vec_projection_matrix = projection_matrix.iter().map(|v| *v).collect::<Vec<_>>();
-        let vec_model_view_matrix = model_view_matrix.iter().map(|v| *v).collect::<Vec<_>>();
+        let vec_projection_matrix = projection_matrix.iter().copied().collect::<Vec<_>>();
+        let vec_model_view_matrix = model_view_matrix.iter().copied().collect::<Vec<_>>();
 
         self.draw(&vec_projection_matrix[..], &vec_model_view_matrix[..])
     }
@@ -143,13 +137,13 @@ impl RotatingSquare {
         self.context.uniform_matrix4fv_with_f32_array(
             Some(&self.shader_projection_matrix),
             false,
-            &vec_projection_matrix,
+            vec_projection_matrix,
         );
 
         self.context.uniform_matrix4fv_with_f32_array(
             Some(&self.shader_model_view_matrix),
             false,
-            &vec_model_view_matrix,
+            vec_model_view_matrix,
         );
 
         let offset = 0;
@@ -0,0 +1,592 @@
+// 2D incompressible fluid, after Stam's "Stable Fluids" (1999) and
+// "Real-Time Fluid Dynamics for Games" (2003), in the form of GPU Gems,
+// chapter 38 (https://developer.nvidia.com/gpugems/gpugems/part-vi-beyond-triangles/chapter-38-fast-fluid-dynamics-simulation-gpu).
+//
+// Velocity and pressure live on a grid of `resolution` cells along its
+// short side; the dye carried by the flow on a finer one. Every `step`:
+//
+//   1. vorticity confinement puts back the small swirls that numerical
+//      diffusion smooths out,
+//   2. the divergence of the velocity is computed and the pressure that
+//      cancels it solved for with `pressure_iterations` Jacobi iterations,
+//   3. the gradient of that pressure is subtracted, leaving the velocity
+//      divergence free,
+//   4. velocity and dye are advected backwards along the velocity
+//      (semi-Lagrangian, unconditionally stable).
+//
+// `splat` adds velocity and dye around a point, e.g. under the mouse.
+// Fields are half float textures (see simulation.rs for `PingPong`); the
+// walls of the box reflect the flow.
+
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation, WebGlVertexArrayObject};
+
+use crate::simulation::{begin_passes, PingPong};
+use crate::state::GlState;
+use crate::target::{supported_precision, Precision, RenderTarget};
+use crate::texture::Sampler;
+use crate::webgl::fullscreen_program;
+
+/// Shared by every kernel. `uTexel` is the size of one velocity cell in
+/// texture coordinates.
+static KERNEL_HEADER: &str = r#"#version 300 es
+precision highp float;
+precision highp sampler2D;
+
+uniform vec2 uTexel;
+
+in vec2 vNdc;
+out vec4 fragColor;
+
+vec2 coord() {
+  return vNdc * 0.5 + 0.5;
+}
+"#;
+
+static ADVECT_FS: &str = r#"
+uniform sampler2D uVelocity;
+uniform sampler2D uSource;
+uniform float uDelta;
+uniform float uDissipation;
+
+void main() {
+  vec2 uv = coord();
+  vec2 from = uv - uDelta * texture(uVelocity, uv).xy * uTexel;
+  fragColor = texture(uSource, from) / (1.0 + uDissipation * uDelta);
+}
+"#;
+
+static DIVERGENCE_FS: &str = r#"
+uniform sampler2D uVelocity;
+
+void main() {
+  vec2 uv = coord();
+  vec2 c = texture(uVelocity, uv).xy;
+  float l = texture(uVelocity, uv - vec2(uTexel.x, 0.0)).x;
+  float r = texture(uVelocity, uv + vec2(uTexel.x, 0.0)).x;
+  float b = texture(uVelocity, uv - vec2(0.0, uTexel.y)).y;
+  float t = texture(uVelocity, uv + vec2(0.0, uTexel.y)).y;
+
+  // no flow through the walls
+  if (uv.x - uTexel.x < 0.0) { l = -c.x; }
+  if (uv.x + uTexel.x > 1.0) { r = -c.x; }
+  if (uv.y - uTexel.y < 0.0) { b = -c.y; }
+  if (uv.y + uTexel.y > 1.0) { t = -c.y; }
+
+  fragColor = vec4(0.5 * (r - l + t - b), 0.0, 0.0, 1.0);
+}
+"#;
+
+static CURL_FS: &str = r#"
+uniform sampler2D uVelocity;
+
+void main() {
+  vec2 uv = coord();
+  float l = texture(uVelocity, uv - vec2(uTexel.x, 0.0)).y;
+  float r = texture(uVelocity, uv + vec2(uTexel.x, 0.0)).y;
+  float b = texture(uVelocity, uv - vec2(0.0, uTexel.y)).x;
+  float t = texture(uVelocity, uv + vec2(0.0, uTexel.y)).x;
+
+  fragColor = vec4(0.5 * (r - l - t + b), 0.0, 0.0, 1.0);
+}
+"#;
+
+// https://web.stanford.edu/class/cs237d/smoke.pdf, section 4
+static VORTICITY_FS: &str = r#"
+uniform sampler2D uVelocity;
+uniform sampler2D uCurl;
+uniform float uStrength;
+uniform float uDelta;
+
+void main() {
+  vec2 uv = coord();
+  float l = texture(uCurl, uv - vec2(uTexel.x, 0.0)).x;
+  float r = texture(uCurl, uv + vec2(uTexel.x, 0.0)).x;
+  float b = texture(uCurl, uv - vec2(0.0, uTexel.y)).x;
+  float t = texture(uCurl, uv + vec2(0.0, uTexel.y)).x;
+  float c = texture(uCurl, uv).x;
+
+  // towards stronger swirl, turned a quarter so that it spins it up
+  vec2 force = 0.5 * vec2(abs(t) - abs(b), abs(r) - abs(l));
+  force /= length(force) + 1e-4;
+  force *= uStrength * c;
+  force.y = -force.y;
+
+  vec2 velocity = texture(uVelocity, uv).xy + force * uDelta;
+  fragColor = vec4(clamp(velocity, -1000.0, 1000.0), 0.0, 1.0);
+}
+"#;
+
+static PRESSURE_FS: &str = r#"
+uniform sampler2D uPressure;
+uniform sampler2D uDivergence;
+
+void main() {
+  vec2 uv = coord();
+  float l = texture(uPressure, uv - vec2(uTexel.x, 0.0)).x;
+  float r = texture(uPressure, uv + vec2(uTexel.x, 0.0)).x;
+  float b = texture(uPressure, uv - vec2(0.0, uTexel.y)).x;
+  float t = texture(uPressure, uv + vec2(0.0, uTexel.y)).x;
+  float divergence = texture(uDivergence, uv).x;
+
+  fragColor = vec4((l + r + b + t - divergence) * 0.25, 0.0, 0.0, 1.0);
+}
+"#;
+
+static GRADIENT_SUBTRACT_FS: &str = r#"
+uniform sampler2D uPressure;
+uniform sampler2D uVelocity;
+
+void main() {
+  vec2 uv = coord();
+  float l = texture(uPressure, uv - vec2(uTexel.x, 0.0)).x;
+  float r = texture(uPressure, uv + vec2(uTexel.x, 0.0)).x;
+  float b = texture(uPressure, uv - vec2(0.0, uTexel.y)).x;
+  float t = texture(uPressure, uv + vec2(0.0, uTexel.y)).x;
+
+  vec2 velocity = texture(uVelocity, uv).xy - vec2(r - l, t - b);
+  fragColor = vec4(velocity, 0.0, 1.0);
+}
+"#;
+
+static SPLAT_FS: &str = r#"
+uniform sampler2D uTarget;
+uniform vec2 uPoint;
+uniform vec3 uValue;
+uniform float uRadius;
+uniform float uAspect;
+
+void main() {
+  vec2 p = coord() - uPoint;
+  p.x *= uAspect;
+  vec3 splat = exp(-dot(p, p) / uRadius) * uValue;
+  fragColor = vec4(texture(uTarget, coord()).xyz + splat, 1.0);
+}
+"#;
+
+static SCALE_FS: &str = r#"
+uniform sampler2D uTarget;
+uniform float uScale;
+
+void main() {
+  fragColor = uScale * texture(uTarget, coord());
+}
+"#;
+
+static DISPLAY_FS: &str = r#"
+uniform sampler2D uDye;
+
+void main() {
+  vec3 color = texture(uDye, coord()).rgb;
+  fragColor = vec4(color / (1.0 + max(color.r, max(color.g, color.b)) * 0.1), 1.0);
+}
+"#;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluidOptions {
+    /// Cells of velocity and pressure along the short side.
+    pub resolution: u32,
+    /// Cells of dye along the short side.
+    pub dye_resolution: u32,
+    pub pressure_iterations: u32,
+    /// Strength of vorticity confinement; 0 lets swirls die out.
+    pub vorticity: f32,
+    /// How fast the velocity fades, per second.
+    pub velocity_dissipation: f32,
+    /// How fast the dye fades, per second.
+    pub dye_dissipation: f32,
+    /// Share of the pressure kept as the first guess of the next solve.
+    pub pressure_decay: f32,
+    /// Size of a splat: the variance of its gaussian, in hundredths of the
+    /// canvas height squared.
+    pub splat_radius: f32,
+}
+
+impl Default for FluidOptions {
+    fn default() -> Self {
+        FluidOptions {
+            resolution: 128,
+            dye_resolution: 512,
+            pressure_iterations: 20,
+            vorticity: 30.0,
+            velocity_dissipation: 0.2,
+            dye_dissipation: 1.0,
+            pressure_decay: 0.8,
+            splat_radius: 0.25,
+        }
+    }
+}
+
+/// A fullscreen program and the locations of the uniforms it was asked
+/// for.
+struct Kernel {
+    program: WebGlProgram,
+    locations: Vec<(&'static str, Option<WebGlUniformLocation>)>,
+}
+
+impl Kernel {
+    fn new(
+        context: &WebGl2RenderingContext,
+        body: &str,
+        uniforms: &[&'static str],
+    ) -> Result<Self, String> {
+        let program = fullscreen_program(context, &format!("{}{}", KERNEL_HEADER, body))?;
+
+        let locations = ["uTexel"]
+            .iter()
+            .chain(uniforms)
+            .map(|&name| (name, context.get_uniform_location(&program, name)))
+            .collect();
+
+        Ok(Kernel { program, locations })
+    }
+
+    fn location(&self, name: &str) -> Option<&WebGlUniformLocation> {
+        self.locations
+            .iter()
+            .find(|(n, _)| *n == name)
+            .and_then(|(_, location)| location.as_ref())
+    }
+
+    /// Uses the program, drawing into `output`.
+    fn begin(&self, state: &mut GlState, output: &RenderTarget, texel: [f32; 2]) {
+        output.bind_cached(state);
+        state.use_program(Some(&self.program));
+        state
+            .context()
+            .uniform2f(self.location("uTexel"), texel[0], texel[1]);
+    }
+
+    /// Binds `target` to texture `unit` for the sampler `name`.
+    fn texture(&self, state: &mut GlState, name: &str, unit: u32, target: &RenderTarget) {
+        state.bind_texture(
+            unit,
+            WebGl2RenderingContext::TEXTURE_2D,
+            Some(target.texture()),
+        );
+        state.context().uniform1i(self.location(name), unit as i32);
+    }
+
+    fn draw(&self, context: &WebGl2RenderingContext) {
+        context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+    }
+}
+
+/// The textures of the simulation, sized from the options.
+struct Fields {
+    velocity: PingPong,
+    dye: PingPong,
+    pressure: PingPong,
+    divergence: RenderTarget,
+    curl: RenderTarget,
+}
+
+impl Fields {
+    fn new(
+        context: &WebGl2RenderingContext,
+        options: &FluidOptions,
+        aspect: f32,
+        format: u32,
+    ) -> Result<Self, String> {
+        let (width, height) = grid_size(options.resolution, aspect);
+        let (dye_width, dye_height) = grid_size(options.dye_resolution, aspect);
+
+        let linear = Sampler::clamped();
+        let nearest = Sampler {
+            mag_filter: WebGl2RenderingContext::NEAREST,
+            min_filter: WebGl2RenderingContext::NEAREST,
+            ..linear
+        };
+        let single = |sampler| -> Result<RenderTarget, String> {
+            let target = RenderTarget::new(context, width, height, format, false)?;
+            target.set_sampler(context, sampler);
+            Ok(target)
+        };
+
+        let fields = Fields {
+            velocity: PingPong::new(context, width, height, format, linear)?,
+            dye: PingPong::new(context, dye_width, dye_height, format, linear)?,
+            pressure: PingPong::new(context, width, height, format, nearest)?,
+            divergence: single(nearest)?,
+            curl: single(nearest)?,
+        };
+        fields.velocity.fill(context, [0.0; 4]);
+        fields.dye.fill(context, [0.0; 4]);
+        fields.pressure.fill(context, [0.0; 4]);
+
+        Ok(fields)
+    }
+
+    fn delete(self, context: &WebGl2RenderingContext) {
+        self.velocity.delete(context);
+        self.dye.delete(context);
+        self.pressure.delete(context);
+        self.divergence.delete(context);
+        self.curl.delete(context);
+    }
+}
+
+pub struct Fluid {
+    pub options: FluidOptions,
+    fields: Fields,
+    format: u32,
+    aspect: f32,
+    advect: Kernel,
+    divergence: Kernel,
+    curl: Kernel,
+    vorticity: Kernel,
+    pressure: Kernel,
+    gradient_subtract: Kernel,
+    splat: Kernel,
+    scale: Kernel,
+    display: Kernel,
+    vao: WebGlVertexArrayObject,
+}
+
+impl Fluid {
+    /// `aspect` is width / height of the simulated box, usually of the
+    /// canvas. Needs float render targets (EXT_color_buffer_float).
+    pub fn new(state: &mut GlState, options: FluidOptions, aspect: f32) -> Result<Self, String> {
+        let context = state.context();
+        let precision = supported_precision(context, Precision::Half);
+        if precision == Precision::Byte {
+            return Err(String::from(
+                "The fluid simulation needs float render targets (EXT_color_buffer_float)",
+            ));
+        }
+        let format = precision.format();
+        let aspect = aspect.max(1e-3);
+
+        let fields = Fields::new(context, &options, aspect, format);
+        // allocating binds the textures on the context itself
+        state.invalidate();
+        let context = state.context();
+
+        Ok(Fluid {
+            fields: fields?,
+            options,
+            format,
+            aspect,
+            advect: Kernel::new(
+                context,
+                ADVECT_FS,
+                &["uVelocity", "uSource", "uDelta", "uDissipation"],
+            )?,
+            divergence: Kernel::new(context, DIVERGENCE_FS, &["uVelocity"])?,
+            curl: Kernel::new(context, CURL_FS, &["uVelocity"])?,
+            vorticity: Kernel::new(
+                context,
+                VORTICITY_FS,
+                &["uVelocity", "uCurl", "uStrength", "uDelta"],
+            )?,
+            pressure: Kernel::new(context, PRESSURE_FS, &["uPressure", "uDivergence"])?,
+            gradient_subtract: Kernel::new(
+                context,
+                GRADIENT_SUBTRACT_FS,
+                &["uPressure", "uVelocity"],
+            )?,
+            splat: Kernel::new(
+                context,
+                SPLAT_FS,
+                &["uTarget", "uPoint", "uValue", "uRadius", "uAspect"],
+            )?,
+            scale: Kernel::new(context, SCALE_FS, &["uTarget", "uScale"])?,
+            display: Kernel::new(context, DISPLAY_FS, &["uDye"])?,
+            vao: context
+                .create_vertex_array()
+                .ok_or_else(|| String::from("Unable to create vertex array object"))?,
+        })
+    }
+
+    /// Size of the velocity grid in cells.
+    pub fn grid_size(&self) -> (u32, u32) {
+        (self.fields.velocity.width(), self.fields.velocity.height())
+    }
+
+    /// Reallocates the fields for new resolutions or a new aspect ratio,
+    /// starting over from still, clear fluid.
+    pub fn resize(
+        &mut self,
+        state: &mut GlState,
+        resolution: u32,
+        dye_resolution: u32,
+        aspect: f32,
+    ) -> Result<(), String> {
+        let aspect = aspect.max(1e-3);
+        if (resolution, dye_resolution) == (self.options.resolution, self.options.dye_resolution)
+            && aspect == self.aspect
+        {
+            return Ok(());
+        }
+
+        self.options.resolution = resolution;
+        self.options.dye_resolution = dye_resolution;
+        self.aspect = aspect;
+
+        let context = &state.context().clone();
+        let fields = Fields::new(context, &self.options, aspect, self.format);
+        state.invalidate();
+        std::mem::replace(&mut self.fields, fields?).delete(context);
+
+        Ok(())
+    }
+
+    /// Adds `force` (in cells per second) to the velocity and `color` to
+    /// the dye around `point`, in [0, 1] from the bottom left.
+    pub fn splat(
+        &mut self,
+        state: &mut GlState,
+        point: [f32; 2],
+        force: [f32; 2],
+        color: [f32; 3],
+    ) {
+        let context = &state.context().clone();
+        begin_passes(state, &self.vao);
+        let texel = self.texel();
+        // widened with the aspect so that splats look the same on wide canvases
+        let radius = (self.options.splat_radius / 100.0) * self.aspect.max(1.0);
+        let kernel = &self.splat;
+
+        for (field, value) in [
+            (&mut self.fields.velocity, [force[0], force[1], 0.0]),
+            (&mut self.fields.dye, color),
+        ] {
+            kernel.begin(state, field.write(), texel);
+            kernel.texture(state, "uTarget", 0, field.read());
+            context.uniform2f(kernel.location("uPoint"), point[0], point[1]);
+            context.uniform3f(kernel.location("uValue"), value[0], value[1], value[2]);
+            context.uniform1f(kernel.location("uRadius"), radius);
+            context.uniform1f(kernel.location("uAspect"), self.aspect);
+            kernel.draw(context);
+            field.swap();
+        }
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+    }
+
+    /// Advances by `delta` seconds, capped to keep the solve stable. Leaves
+    /// no framebuffer bound.
+    pub fn step(&mut self, state: &mut GlState, delta: f32) {
+        let context = &state.context().clone();
+        let delta = delta.clamp(0.0, 1.0 / 30.0);
+        begin_passes(state, &self.vao);
+        let texel = self.texel();
+        let Fields {
+            velocity,
+            dye,
+            pressure,
+            divergence,
+            curl,
+        } = &mut self.fields;
+
+        // 1. vorticity confinement
+        self.curl.begin(state, curl, texel);
+        self.curl.texture(state, "uVelocity", 0, velocity.read());
+        self.curl.draw(context);
+
+        let kernel = &self.vorticity;
+        kernel.begin(state, velocity.write(), texel);
+        kernel.texture(state, "uVelocity", 0, velocity.read());
+        kernel.texture(state, "uCurl", 1, curl);
+        context.uniform1f(kernel.location("uStrength"), self.options.vorticity);
+        context.uniform1f(kernel.location("uDelta"), delta);
+        kernel.draw(context);
+        velocity.swap();
+
+        // 2. pressure solve
+        self.divergence.begin(state, divergence, texel);
+        self.divergence
+            .texture(state, "uVelocity", 0, velocity.read());
+        self.divergence.draw(context);
+
+        let kernel = &self.scale;
+        kernel.begin(state, pressure.write(), texel);
+        kernel.texture(state, "uTarget", 0, pressure.read());
+        context.uniform1f(kernel.location("uScale"), self.options.pressure_decay);
+        kernel.draw(context);
+        pressure.swap();
+
+        let kernel = &self.pressure;
+        for _ in 0..self.options.pressure_iterations {
+            kernel.begin(state, pressure.write(), texel);
+            kernel.texture(state, "uPressure", 0, pressure.read());
+            kernel.texture(state, "uDivergence", 1, divergence);
+            kernel.draw(context);
+            pressure.swap();
+        }
+
+        // 3. projection
+        let kernel = &self.gradient_subtract;
+        kernel.begin(state, velocity.write(), texel);
+        kernel.texture(state, "uPressure", 0, pressure.read());
+        kernel.texture(state, "uVelocity", 1, velocity.read());
+        kernel.draw(context);
+        velocity.swap();
+
+        // 4. advection, of the velocity by itself, then of the dye
+        let kernel = &self.advect;
+        kernel.begin(state, velocity.write(), texel);
+        kernel.texture(state, "uVelocity", 0, velocity.read());
+        kernel.texture(state, "uSource", 1, velocity.read());
+        context.uniform1f(kernel.location("uDelta"), delta);
+        context.uniform1f(
+            kernel.location("uDissipation"),
+            self.options.velocity_dissipation,
+        );
+        kernel.draw(context);
+        velocity.swap();
+
+        kernel.begin(state, dye.write(), texel);
+        kernel.texture(state, "uVelocity", 0, velocity.read());
+        kernel.texture(state, "uSource", 1, dye.read());
+        context.uniform1f(
+            kernel.location("uDissipation"),
+            self.options.dye_dissipation,
+        );
+        kernel.draw(context);
+        dye.swap();
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+    }
+
+    /// Draws the dye over the whole canvas of `width` by `height`.
+    pub fn display(&self, state: &mut GlState, width: u32, height: u32) {
+        let context = &state.context().clone();
+        begin_passes(state, &self.vao);
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        state.viewport(0, 0, width as i32, height as i32);
+        state.use_program(Some(&self.display.program));
+        self.display
+            .texture(state, "uDye", 0, self.fields.dye.read());
+        self.display.draw(context);
+    }
+
+    fn texel(&self) -> [f32; 2] {
+        let (width, height) = self.grid_size();
+        [1.0 / width as f32, 1.0 / height as f32]
+    }
+}
+
+/// Cells along each side for `resolution` cells along the short one.
+fn grid_size(resolution: u32, aspect: f32) -> (u32, u32) {
+    let resolution = resolution.max(1);
+    let long = (resolution as f32 * aspect.max(1.0 / aspect)).round() as u32;
+
+    match aspect >= 1.0 {
+        true => (long, resolution),
+        false => (resolution, long),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_keeps_the_short_side() {
+        assert_eq!(grid_size(128, 1.0), (128, 128));
+        assert_eq!(grid_size(128, 16.0 / 9.0), (228, 128));
+        assert_eq!(grid_size(128, 0.5), (128, 256));
+        assert_eq!(grid_size(0, 2.0), (2, 1));
+    }
+}
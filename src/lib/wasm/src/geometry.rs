@@ -0,0 +1,711 @@
+// Procedural geometry, built on the CPU and uploaded as an interleaved
+// `Mesh` with the attributes
+//
+//   in vec3 position;
+//   in vec3 normal;
+//   in vec4 tangent;  // w: handedness of the bitangent
+//   in vec2 uv;
+//
+// Y is up and front faces wind counter-clockwise. Shapes of revolution
+// (sphere, cylinder, cone, capsule, torus) are lathed around the Y axis
+// and duplicate their seam so that UVs don't wrap.
+
+use crate::mesh::{AttributeType, Indices, Mesh, VertexBuffer, VertexLayout};
+use crate::state::GlState;
+
+use std::f32::consts::{PI, TAU};
+
+#[derive(Clone, Debug, Default)]
+pub struct Geometry {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 4]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .attribute("position", 3, AttributeType::Float, false)
+            .attribute("normal", 3, AttributeType::Float, false)
+            .attribute("tangent", 4, AttributeType::Float, false)
+            .attribute("uv", 2, AttributeType::Float, false)
+    }
+
+    /// Vertices interleaved as in `Geometry::layout`.
+    pub fn interleaved(&self) -> Vec<f32> {
+        (0..self.vertex_count())
+            .flat_map(|i| {
+                self.positions[i]
+                    .into_iter()
+                    .chain(self.normals[i])
+                    .chain(self.tangents[i])
+                    .chain(self.uvs[i])
+            })
+            .collect()
+    }
+
+    pub fn to_mesh(&self, state: &mut GlState) -> Result<Mesh, String> {
+        let vertices = VertexBuffer::from_f32(state, &self.interleaved(), Self::layout())?;
+
+        Mesh::new(vec![vertices])?.with_indices(state, Indices::compact(self.indices.clone()))
+    }
+
+    fn push(&mut self, position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normalize(normal));
+        self.uvs.push(uv);
+        (self.positions.len() - 1) as u32
+    }
+
+    /// Quads between a `(columns + 1) x (rows + 1)` block of vertices
+    /// starting at `first`, laid out row by row.
+    fn push_quads(&mut self, first: u32, columns: u32, rows: u32) {
+        for j in 0..rows {
+            for i in 0..columns {
+                let a = first + j * (columns + 1) + i;
+                let b = a + 1;
+                let c = a + columns + 1;
+                let d = c + 1;
+                self.indices.extend([a, b, d, a, d, c]);
+            }
+        }
+    }
+
+    /// Drops zero-area triangles, e.g. at the poles of a sphere.
+    fn remove_degenerate(&mut self) {
+        let positions = &self.positions;
+
+        self.indices = self
+            .indices
+            .chunks(3)
+            .filter(|t| {
+                let [a, b, c] = [0, 1, 2].map(|k| positions[t[k] as usize]);
+                length(cross(sub(b, a), sub(c, a))) > 1e-10
+            })
+            .flatten()
+            .copied()
+            .collect();
+    }
+
+    /// Area-weighted vertex normals, for sources that come without them.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![[0.0; 3]; self.vertex_count()];
+
+        for t in self.indices.chunks(3) {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| i as usize);
+            let p = &self.positions;
+            // twice the area, pointing out of the front face
+            let face = cross(sub(p[b], p[a]), sub(p[c], p[a]));
+
+            for i in [a, b, c] {
+                normals[i] = add(normals[i], face);
+            }
+        }
+
+        self.normals = normals
+            .into_iter()
+            .map(|n| {
+                if length(n) > 0.0 {
+                    normalize(n)
+                } else {
+                    [0.0, 1.0, 0.0]
+                }
+            })
+            .collect();
+    }
+
+    /// Per-vertex tangents from the UV layout (Lengyel's method),
+    /// orthogonalized against the normals.
+    pub fn compute_tangents(&mut self) {
+        let count = self.vertex_count();
+        let mut tangents = vec![[0.0; 3]; count];
+        let mut bitangents = vec![[0.0; 3]; count];
+
+        for t in self.indices.chunks(3) {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| i as usize);
+
+            let e1 = sub(self.positions[b], self.positions[a]);
+            let e2 = sub(self.positions[c], self.positions[a]);
+            let [du1, dv1] = [
+                self.uvs[b][0] - self.uvs[a][0],
+                self.uvs[b][1] - self.uvs[a][1],
+            ];
+            let [du2, dv2] = [
+                self.uvs[c][0] - self.uvs[a][0],
+                self.uvs[c][1] - self.uvs[a][1],
+            ];
+
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            let r = 1.0 / det;
+
+            let tangent = scale(sub(scale(e1, dv2), scale(e2, dv1)), r);
+            let bitangent = scale(sub(scale(e2, du1), scale(e1, du2)), r);
+
+            for i in [a, b, c] {
+                tangents[i] = add(tangents[i], tangent);
+                bitangents[i] = add(bitangents[i], bitangent);
+            }
+        }
+
+        self.tangents = (0..count)
+            .map(|i| {
+                let n = self.normals[i];
+                let t = sub(tangents[i], scale(n, dot(n, tangents[i])));
+
+                let t = if length(t) > 1e-6 {
+                    normalize(t)
+                } else {
+                    any_perpendicular(n)
+                };
+
+                let w = if dot(cross(n, t), bitangents[i]) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+
+                [t[0], t[1], t[2], w]
+            })
+            .collect();
+    }
+
+    fn finish(mut self) -> Self {
+        self.remove_degenerate();
+        self.compute_tangents();
+        self
+    }
+}
+
+/// A `width` x `height` rectangle in the XY plane facing +Z, split into
+/// `subdivisions` x `subdivisions` quads.
+pub fn plane(width: f32, height: f32, subdivisions: u32) -> Geometry {
+    let n = subdivisions.max(1);
+    let mut geometry = Geometry::default();
+
+    for j in 0..=n {
+        for i in 0..=n {
+            let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+            geometry.push(
+                [(u - 0.5) * width, (v - 0.5) * height, 0.0],
+                [0.0, 0.0, 1.0],
+                [u, v],
+            );
+        }
+    }
+
+    geometry.push_quads(0, n, n);
+    geometry.finish()
+}
+
+/// A `width` x `depth` ground grid in the XZ plane facing +Y, with
+/// `columns` x `rows` quads, meant to be displaced in a vertex shader.
+pub fn grid(width: f32, depth: f32, columns: u32, rows: u32) -> Geometry {
+    let (columns, rows) = (columns.max(1), rows.max(1));
+    let mut geometry = Geometry::default();
+
+    for j in 0..=rows {
+        for i in 0..=columns {
+            let (u, v) = (i as f32 / columns as f32, j as f32 / rows as f32);
+            // v runs towards -Z so that the grid faces +Y
+            geometry.push(
+                [(u - 0.5) * width, 0.0, (0.5 - v) * depth],
+                [0.0, 1.0, 0.0],
+                [u, v],
+            );
+        }
+    }
+
+    geometry.push_quads(0, columns, rows);
+    geometry.finish()
+}
+
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Geometry {
+    let rings = rings.max(2);
+
+    let profile = (0..=rings)
+        .map(|k| {
+            let v = k as f32 / rings as f32;
+            let theta = v * PI - PI / 2.0;
+            ProfilePoint::new(
+                radius * theta.cos(),
+                radius * theta.sin(),
+                theta.cos(),
+                theta.sin(),
+                v,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    lathe(&profile, segments)
+}
+
+/// A box centered on the origin, one quad per face so that each face has
+/// its own normals and UVs.
+pub fn cuboid(width: f32, height: f32, depth: f32) -> Geometry {
+    let half = [width / 2.0, height / 2.0, depth / 2.0];
+    let mut geometry = Geometry::default();
+
+    // normal, then the directions of u and v, with u x v = normal
+    let faces: [[[f32; 3]; 3]; 6] = [
+        [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+        [[-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+        [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+        [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        [[0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    for [normal, u, v] in faces {
+        let first = geometry.vertex_count() as u32;
+
+        for j in 0..=1 {
+            for i in 0..=1 {
+                let (s, t) = (i as f32 * 2.0 - 1.0, j as f32 * 2.0 - 1.0);
+                let position = [0, 1, 2].map(|k| (normal[k] + s * u[k] + t * v[k]) * half[k]);
+                geometry.push(position, normal, [i as f32, j as f32]);
+            }
+        }
+
+        geometry.push_quads(first, 1, 1);
+    }
+
+    geometry.finish()
+}
+
+/// A subdivided icosahedron. UVs are spherical, so the texture wraps across
+/// the seam at -Z.
+pub fn icosphere(radius: f32, subdivisions: u32) -> Geometry {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+
+    let mut vertices = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .map(normalize)
+    .to_vec();
+
+    let mut faces: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = std::collections::HashMap::new();
+        let mut midpoint = |a: u32, b: u32| -> u32 {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let m = scale(add(vertices[a as usize], vertices[b as usize]), 0.5);
+                vertices.push(normalize(m));
+                (vertices.len() - 1) as u32
+            })
+        };
+
+        faces = faces
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut geometry = Geometry::default();
+
+    for n in vertices {
+        let u = 0.5 + n[0].atan2(n[2]) / TAU;
+        let v = 0.5 + n[1].clamp(-1.0, 1.0).asin() / PI;
+        geometry.push(scale(n, radius), n, [u, v]);
+    }
+
+    geometry.indices = faces.into_iter().flatten().collect();
+    geometry.finish()
+}
+
+/// A ring of `radius` (center to tube center) around the Y axis.
+pub fn torus(radius: f32, tube_radius: f32, segments: u32, tube_segments: u32) -> Geometry {
+    let tube_segments = tube_segments.max(3);
+
+    let profile = (0..=tube_segments)
+        .map(|k| {
+            let v = k as f32 / tube_segments as f32;
+            let theta = v * TAU;
+            ProfilePoint::new(
+                radius + tube_radius * theta.cos(),
+                tube_radius * theta.sin(),
+                theta.cos(),
+                theta.sin(),
+                v,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    lathe(&profile, segments)
+}
+
+/// A capped cylinder of `height` centered at the origin.
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> Geometry {
+    frustum(radius, radius, height, segments)
+}
+
+/// A capped cone of `height` centered at the origin, apex up.
+pub fn cone(radius: f32, height: f32, segments: u32) -> Geometry {
+    frustum(radius, 0.0, height, segments)
+}
+
+/// A cylinder of `length` with hemispheres of `radius` on both ends, so
+/// `length + 2 * radius` tall overall. `rings` is per hemisphere.
+pub fn capsule(radius: f32, length: f32, segments: u32, rings: u32) -> Geometry {
+    let rings = rings.max(1);
+    let total = length + PI * radius;
+
+    let hemisphere = |from: f32, y: f32, arc: f32| {
+        (0..=rings).map(move |k| {
+            let theta = from + k as f32 / rings as f32 * PI / 2.0;
+            let s = arc + (theta - from) * radius;
+            ProfilePoint::new(
+                radius * theta.cos(),
+                y + radius * theta.sin(),
+                theta.cos(),
+                theta.sin(),
+                s / total,
+            )
+        })
+    };
+
+    let profile = hemisphere(-PI / 2.0, -length / 2.0, 0.0)
+        .chain(hemisphere(0.0, length / 2.0, PI / 2.0 * radius + length))
+        .collect::<Vec<_>>();
+
+    lathe(&profile, segments)
+}
+
+fn frustum(bottom_radius: f32, top_radius: f32, height: f32, segments: u32) -> Geometry {
+    let segments = segments.max(3);
+
+    // side normals lean by the slope of the side
+    let (normal_radial, normal_y) = (height, bottom_radius - top_radius);
+
+    let side = [
+        ProfilePoint::new(bottom_radius, -height / 2.0, normal_radial, normal_y, 0.0),
+        ProfilePoint::new(top_radius, height / 2.0, normal_radial, normal_y, 1.0),
+    ];
+
+    let mut geometry = lathe_into(Geometry::default(), &side, segments);
+
+    for (radius, y, normal_y) in [
+        (bottom_radius, -height / 2.0, -1.0),
+        (top_radius, height / 2.0, 1.0),
+    ] {
+        if radius <= 0.0 {
+            continue;
+        }
+
+        let center = geometry.push([0.0, y, 0.0], [0.0, normal_y, 0.0], [0.5, 0.5]);
+
+        for i in 0..=segments {
+            let phi = i as f32 / segments as f32 * TAU;
+            let (sin, cos) = phi.sin_cos();
+            // mirrored on the bottom so the texture reads right from below
+            let u = 0.5 + 0.5 * sin * normal_y;
+            geometry.push(
+                [radius * sin, y, radius * cos],
+                [0.0, normal_y, 0.0],
+                [u, 0.5 + 0.5 * cos],
+            );
+        }
+
+        for i in 0..segments {
+            let (a, b) = (center + 1 + i, center + 2 + i);
+            if normal_y > 0.0 {
+                geometry.indices.extend([center, a, b]);
+            } else {
+                geometry.indices.extend([center, b, a]);
+            }
+        }
+    }
+
+    geometry.finish()
+}
+
+/// A point of a profile curve in the (radial, Y) half-plane; the outward
+/// side is on the right when walking it with Y going up.
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    normal: [f32; 2],
+    v: f32,
+}
+
+impl ProfilePoint {
+    fn new(radius: f32, y: f32, normal_radial: f32, normal_y: f32, v: f32) -> Self {
+        ProfilePoint {
+            radius,
+            y,
+            normal: [normal_radial, normal_y],
+            v,
+        }
+    }
+}
+
+fn lathe(profile: &[ProfilePoint], segments: u32) -> Geometry {
+    lathe_into(Geometry::default(), profile, segments.max(3)).finish()
+}
+
+/// Revolves `profile` around the Y axis, `segments + 1` vertices per point.
+fn lathe_into(mut geometry: Geometry, profile: &[ProfilePoint], segments: u32) -> Geometry {
+    let first = geometry.vertex_count() as u32;
+
+    for point in profile {
+        // cos(±PI / 2) isn't quite zero; make the poles collapse exactly
+        let radius = if point.radius.abs() < 1e-6 {
+            0.0
+        } else {
+            point.radius
+        };
+
+        for i in 0..=segments {
+            let u = i as f32 / segments as f32;
+            let (sin, cos) = (u * TAU).sin_cos();
+            let [normal_radial, normal_y] = point.normal;
+
+            geometry.push(
+                [radius * sin, point.y, radius * cos],
+                [normal_radial * sin, normal_y, normal_radial * cos],
+                [u, point.v],
+            );
+        }
+    }
+
+    geometry.push_quads(first, segments, profile.len() as u32 - 1);
+    geometry
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = length(a);
+    if len > 0.0 {
+        scale(a, 1.0 / len)
+    } else {
+        a
+    }
+}
+
+fn any_perpendicular(n: [f32; 3]) -> [f32; 3] {
+    let axis = if n[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+
+    normalize(cross(axis, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(geometry: &Geometry) {
+        let count = geometry.vertex_count();
+
+        assert_eq!(geometry.normals.len(), count);
+        assert_eq!(geometry.tangents.len(), count);
+        assert_eq!(geometry.uvs.len(), count);
+
+        assert!(!geometry.indices.is_empty());
+        assert_eq!(geometry.indices.len() % 3, 0);
+        assert!(geometry.indices.iter().all(|&i| (i as usize) < count));
+
+        for (n, t) in geometry.normals.iter().zip(&geometry.tangents) {
+            assert!((length(*n) - 1.0).abs() < 1e-4, "normal {:?}", n);
+
+            let t3 = [t[0], t[1], t[2]];
+            assert!((length(t3) - 1.0).abs() < 1e-4, "tangent {:?}", t);
+            assert!(
+                dot(*n, t3).abs() < 1e-4,
+                "tangent {:?} vs normal {:?}",
+                t,
+                n
+            );
+            assert!(t[3] == 1.0 || t[3] == -1.0);
+        }
+
+        // counter-clockwise: every face agrees with its vertex normals
+        for t in geometry.indices.chunks(3) {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| i as usize);
+            let p = &geometry.positions;
+            let face = cross(sub(p[b], p[a]), sub(p[c], p[a]));
+            let n = add(
+                add(geometry.normals[a], geometry.normals[b]),
+                geometry.normals[c],
+            );
+            assert!(dot(face, n) > 0.0, "triangle {:?} winds clockwise", t);
+        }
+    }
+
+    #[test]
+    fn plane_counts() {
+        let geometry = plane(2.0, 1.0, 4);
+        check(&geometry);
+        assert_eq!(geometry.vertex_count(), 25);
+        assert_eq!(geometry.indices.len(), 4 * 4 * 6);
+    }
+
+    #[test]
+    fn grid_counts() {
+        let geometry = grid(10.0, 10.0, 64, 32);
+        check(&geometry);
+        assert_eq!(geometry.vertex_count(), 65 * 33);
+        assert_eq!(geometry.indices.len(), 64 * 32 * 6);
+        assert!(geometry.normals.iter().all(|n| n == &[0.0, 1.0, 0.0]));
+    }
+
+    #[test]
+    fn cuboid_counts() {
+        let geometry = cuboid(1.0, 2.0, 3.0);
+        check(&geometry);
+        assert_eq!(geometry.vertex_count(), 24);
+        assert_eq!(geometry.indices.len(), 36);
+        assert!(geometry
+            .positions
+            .iter()
+            .all(|p| p[0].abs() == 0.5 && p[1].abs() == 1.0 && p[2].abs() == 1.5));
+    }
+
+    #[test]
+    fn uv_sphere_counts() {
+        let geometry = uv_sphere(2.0, 16, 8);
+        check(&geometry);
+        assert_eq!(geometry.vertex_count(), 17 * 9);
+        // the pole rows lose one triangle per quad
+        assert_eq!(geometry.indices.len(), (16 * 8 * 2 - 2 * 16) * 3);
+        assert!(geometry
+            .positions
+            .iter()
+            .all(|p| (length(*p) - 2.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn icosphere_counts() {
+        for (subdivisions, vertices) in [(0, 12), (1, 42), (2, 162), (3, 642)] {
+            let geometry = icosphere(1.0, subdivisions);
+            check(&geometry);
+            assert_eq!(geometry.vertex_count(), vertices);
+            assert_eq!(geometry.indices.len(), 20 * 4usize.pow(subdivisions) * 3);
+        }
+    }
+
+    #[test]
+    fn torus_counts() {
+        let geometry = torus(1.0, 0.25, 24, 12);
+        check(&geometry);
+        assert_eq!(geometry.vertex_count(), 25 * 13);
+        assert_eq!(geometry.indices.len(), 24 * 12 * 6);
+    }
+
+    #[test]
+    fn cylinder_counts() {
+        let geometry = cylinder(1.0, 2.0, 12);
+        check(&geometry);
+        // side + two caps of center and ring
+        assert_eq!(geometry.vertex_count(), 13 * 2 + 2 * (1 + 13));
+        assert_eq!(geometry.indices.len(), (12 * 2 + 2 * 12) * 3);
+    }
+
+    #[test]
+    fn cone_counts() {
+        let geometry = cone(1.0, 2.0, 12);
+        check(&geometry);
+        // side + bottom cap; the apex collapses one triangle per quad
+        assert_eq!(geometry.vertex_count(), 13 * 2 + 1 + 13);
+        assert_eq!(geometry.indices.len(), (12 + 12) * 3);
+    }
+
+    #[test]
+    fn capsule_counts() {
+        let geometry = capsule(0.5, 1.0, 16, 4);
+        check(&geometry);
+        assert_eq!(geometry.vertex_count(), 17 * 10);
+        assert_eq!(geometry.indices.len(), (16 * 9 * 2 - 2 * 16) * 3);
+
+        let (min, max) = geometry
+            .positions
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), p| {
+                (min.min(p[1]), max.max(p[1]))
+            });
+        assert!((min + 1.0).abs() < 1e-4 && (max - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn interleaved_matches_layout() {
+        let geometry = plane(1.0, 1.0, 1);
+        let stride = Geometry::layout().stride() as usize / 4;
+        assert_eq!(
+            geometry.interleaved().len(),
+            geometry.vertex_count() * stride
+        );
+    }
+}
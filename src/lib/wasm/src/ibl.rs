@@ -0,0 +1,718 @@
+// Image-based lighting: an equirectangular HDR turned, on the GPU, into
+//
+//   - an environment cube map (for the background),
+//   - an irradiance cube map (diffuse light from every direction),
+//   - a prefiltered cube map whose mip levels hold the environment blurred
+//     for increasing roughness (GGX importance sampling),
+//   - and the split-sum BRDF lookup table (scale and bias on F0, indexed by
+//     N.V and roughness).
+//
+// See Karis, "Real Shading in Unreal Engine 4" (SIGGRAPH 2013) and
+// https://learnopengl.com/PBR/IBL/Specular-IBL
+//
+// Rendering into half float targets needs EXT_color_buffer_float; without
+// it everything is stored as 8-bit, clamping the environment to [0, 1].
+//
+// Baking runs on the context itself and unbinds what it used, so a
+// `GlState` has to be invalidated after loading an environment; drawing the
+// background goes through it.
+
+extern crate nalgebra_glm as glm;
+
+use web_sys::{
+    WebGl2RenderingContext, WebGlFramebuffer, WebGlProgram, WebGlTexture, WebGlUniformLocation,
+    WebGlVertexArrayObject,
+};
+
+use crate::camera::Camera;
+use crate::loaders::hdr::HdrImage;
+use crate::pbr::TONE_MAPPING;
+use crate::pipeline::PipelineState;
+use crate::state::GlState;
+use crate::target::{supported_precision, Precision};
+use crate::texture::{Sampler, Texture};
+use crate::webgl::fullscreen_program;
+
+// the background writes no depth and ignores it, staying behind whatever is
+// drawn after it
+static BACKGROUND_PIPELINE: PipelineState = PipelineState::new();
+
+static EQUIRECTANGULAR_FS: &str = r#"#version 300 es
+precision highp float;
+
+uniform sampler2D uEquirectangular;
+uniform mat3 uFace;
+
+in vec2 vNdc;
+out vec4 fragColor;
+
+const float PI = 3.14159265359;
+
+void main() {
+  vec3 d = normalize(uFace * vec3(vNdc, -1.0));
+  // the top row of the image comes first, at v = 0
+  vec2 uv = vec2(atan(d.z, d.x) / (2.0 * PI) + 0.5, 0.5 - asin(clamp(d.y, -1.0, 1.0)) / PI);
+  fragColor = vec4(textureLod(uEquirectangular, uv, 0.0).rgb, 1.0);
+}
+"#;
+
+static IRRADIANCE_FS: &str = r#"#version 300 es
+precision highp float;
+
+uniform samplerCube uEnvironment;
+uniform mat3 uFace;
+uniform float uSourceLod;
+
+in vec2 vNdc;
+out vec4 fragColor;
+
+const float PI = 3.14159265359;
+const float STEP = 0.05;
+
+void main() {
+  vec3 n = normalize(uFace * vec3(vNdc, -1.0));
+  vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+  vec3 right = normalize(cross(up, n));
+  up = cross(n, right);
+
+  vec3 sum = vec3(0.0);
+  float count = 0.0;
+
+  for (float phi = 0.0; phi < 2.0 * PI; phi += STEP) {
+    for (float theta = 0.0; theta < 0.5 * PI; theta += STEP) {
+      vec3 t = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+      vec3 s = t.x * right + t.y * up + t.z * n;
+      sum += textureLod(uEnvironment, s, uSourceLod).rgb * cos(theta) * sin(theta);
+      count += 1.0;
+    }
+  }
+
+  fragColor = vec4(PI * sum / count, 1.0);
+}
+"#;
+
+static GGX_SAMPLING: &str = r#"
+const float PI = 3.14159265359;
+
+float radicalInverse(uint bits) {
+  bits = (bits << 16u) | (bits >> 16u);
+  bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+  bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+  bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+  bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+  return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(uint i, uint n) {
+  return vec2(float(i) / float(n), radicalInverse(i));
+}
+
+// A half vector around `n`, distributed like GGX with `roughness`.
+vec3 importanceSampleGGX(vec2 xi, vec3 n, float roughness) {
+  float a = roughness * roughness;
+  float phi = 2.0 * PI * xi.x;
+  float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+  float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+  vec3 h = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+
+  vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+  vec3 tangent = normalize(cross(up, n));
+  vec3 bitangent = cross(n, tangent);
+  return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+}
+"#;
+
+fn prefilter_fs() -> String {
+    format!(
+        r#"#version 300 es
+precision highp float;
+
+uniform samplerCube uEnvironment;
+uniform mat3 uFace;
+uniform float uRoughness;
+uniform float uResolution;
+
+in vec2 vNdc;
+out vec4 fragColor;
+{GGX_SAMPLING}
+const uint SAMPLE_COUNT = 256u;
+
+void main() {{
+  // assume the view direction is the normal, as the split sum does
+  vec3 n = normalize(uFace * vec3(vNdc, -1.0));
+  vec3 v = n;
+
+  float a = uRoughness * uRoughness;
+  // solid angle of one texel of the source
+  float texel = 4.0 * PI / (6.0 * uResolution * uResolution);
+
+  vec3 sum = vec3(0.0);
+  float weight = 0.0;
+
+  for (uint i = 0u; i < SAMPLE_COUNT; i++) {{
+    vec3 h = importanceSampleGGX(hammersley(i, SAMPLE_COUNT), n, uRoughness);
+    vec3 l = normalize(2.0 * dot(v, h) * h - v);
+    float nl = dot(n, l);
+
+    if (nl > 0.0) {{
+      // read from a blurrier level where samples are sparse, against
+      // fireflies (Colbert & Krivanek, GPU Gems 3, ch. 20)
+      float nh = max(dot(n, h), 0.0);
+      float d = nh * nh * (a * a - 1.0) + 1.0;
+      float D = a * a / (PI * d * d);
+      float pdf = D / 4.0 + 0.0001;
+      float saSample = 1.0 / (float(SAMPLE_COUNT) * pdf + 0.0001);
+      float lod = uRoughness == 0.0 ? 0.0 : 0.5 * log2(saSample / texel);
+
+      sum += textureLod(uEnvironment, l, lod).rgb * nl;
+      weight += nl;
+    }}
+  }}
+
+  fragColor = vec4(sum / weight, 1.0);
+}}
+"#
+    )
+}
+
+fn brdf_fs() -> String {
+    format!(
+        r#"#version 300 es
+precision highp float;
+
+in vec2 vNdc;
+out vec4 fragColor;
+{GGX_SAMPLING}
+const uint SAMPLE_COUNT = 512u;
+
+// Smith with Schlick-GGX, k remapped for IBL
+float geometry(float nv, float nl, float roughness) {{
+  float k = roughness * roughness / 2.0;
+  return nv / (nv * (1.0 - k) + k) * nl / (nl * (1.0 - k) + k);
+}}
+
+void main() {{
+  vec2 uv = vNdc * 0.5 + 0.5;
+  float nv = max(uv.x, 0.001);
+  float roughness = uv.y;
+
+  vec3 v = vec3(sqrt(1.0 - nv * nv), 0.0, nv);
+  vec3 n = vec3(0.0, 0.0, 1.0);
+
+  float scale = 0.0;
+  float bias = 0.0;
+
+  for (uint i = 0u; i < SAMPLE_COUNT; i++) {{
+    vec3 h = importanceSampleGGX(hammersley(i, SAMPLE_COUNT), n, roughness);
+    vec3 l = normalize(2.0 * dot(v, h) * h - v);
+
+    float nl = max(l.z, 0.0);
+    float nh = max(h.z, 0.0);
+    float vh = max(dot(v, h), 0.0);
+
+    if (nl > 0.0) {{
+      float visibility = geometry(nv, nl, roughness) * vh / (nh * nv);
+      float fresnel = pow(1.0 - vh, 5.0);
+
+      scale += (1.0 - fresnel) * visibility;
+      bias += fresnel * visibility;
+    }}
+  }}
+
+  fragColor = vec4(scale / float(SAMPLE_COUNT), bias / float(SAMPLE_COUNT), 0.0, 1.0);
+}}
+"#
+    )
+}
+
+fn skybox_fs() -> String {
+    format!(
+        r#"#version 300 es
+precision highp float;
+
+uniform samplerCube uEnvironment;
+uniform mat4 uInverseViewProjection;
+uniform float uLod;
+{TONE_MAPPING}
+in vec2 vNdc;
+out vec4 fragColor;
+
+void main() {{
+  vec4 far = uInverseViewProjection * vec4(vNdc, 1.0, 1.0);
+  vec3 d = normalize(far.xyz / far.w);
+  fragColor = vec4(toneMap(textureLod(uEnvironment, d, uLod).rgb), 1.0);
+}}
+"#
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnvironmentOptions {
+    /// Edge of each face of the environment cube map.
+    pub cube_size: u32,
+    pub irradiance_size: u32,
+    pub prefiltered_size: u32,
+    /// Roughness 0 to 1 is spread over this many mip levels.
+    pub prefiltered_levels: u32,
+    pub brdf_lut_size: u32,
+}
+
+impl Default for EnvironmentOptions {
+    fn default() -> Self {
+        EnvironmentOptions {
+            cube_size: 512,
+            irradiance_size: 32,
+            prefiltered_size: 128,
+            prefiltered_levels: 5,
+            brdf_lut_size: 256,
+        }
+    }
+}
+
+pub struct Environment {
+    cube: WebGlTexture,
+    irradiance: WebGlTexture,
+    prefiltered: WebGlTexture,
+    prefiltered_levels: u32,
+    brdf_lut: WebGlTexture,
+    skybox: Skybox,
+}
+
+impl Environment {
+    pub fn from_hdr(
+        context: &WebGl2RenderingContext,
+        image: &HdrImage,
+        options: EnvironmentOptions,
+    ) -> Result<Self, String> {
+        // `textureLod` at level 0 only: mipmaps would show the seam where
+        // `atan` wraps around
+        let sampler = Sampler {
+            min_filter: WebGl2RenderingContext::LINEAR,
+            wrap_t: WebGl2RenderingContext::CLAMP_TO_EDGE,
+            ..Sampler::default()
+        };
+        let texture =
+            Texture::from_rgb_f32(context, image.width, image.height, &image.data, sampler)?;
+
+        let environment = Self::from_equirectangular(context, &texture, options);
+        context.delete_texture(Some(texture.texture()));
+
+        environment
+    }
+
+    /// `equirectangular` is sampled at level 0 with its top row at v = 0.
+    pub fn from_equirectangular(
+        context: &WebGl2RenderingContext,
+        equirectangular: &Texture,
+        options: EnvironmentOptions,
+    ) -> Result<Self, String> {
+        let format = supported_precision(context, Precision::Half).format();
+
+        let cube_levels = mip_levels(options.cube_size);
+        let prefiltered_levels = options
+            .prefiltered_levels
+            .clamp(1, mip_levels(options.prefiltered_size));
+
+        let baker = Baker::new(context)?;
+
+        // environment, with mipmaps for the filtering passes to read from
+        let cube = create_cube(context, options.cube_size, cube_levels, format)?;
+        let program = baker.program(EQUIRECTANGULAR_FS)?;
+        equirectangular.bind(
+            context,
+            0,
+            baker.uniform(&program, "uEquirectangular").as_ref(),
+        );
+        baker.render_cube(&program, &cube, options.cube_size, 0, |_| {});
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_CUBE_MAP, Some(&cube));
+        context.generate_mipmap(WebGl2RenderingContext::TEXTURE_CUBE_MAP);
+
+        // irradiance, read from a level of about 64x64: plenty for such a blur
+        let irradiance = create_cube(context, options.irradiance_size, 1, format)?;
+        let program = baker.program(IRRADIANCE_FS)?;
+        baker.bind_cube(&program, "uEnvironment", &cube);
+        let source_lod = (options.cube_size as f32 / 64.0).log2().max(0.0);
+        context.uniform1f(baker.uniform(&program, "uSourceLod").as_ref(), source_lod);
+        baker.render_cube(&program, &irradiance, options.irradiance_size, 0, |_| {});
+
+        // specular, one roughness per level
+        let prefiltered = create_cube(
+            context,
+            options.prefiltered_size,
+            prefiltered_levels,
+            format,
+        )?;
+        let program = baker.program(&prefilter_fs())?;
+        baker.bind_cube(&program, "uEnvironment", &cube);
+        context.uniform1f(
+            baker.uniform(&program, "uResolution").as_ref(),
+            options.cube_size as f32,
+        );
+        let loc_roughness = baker.uniform(&program, "uRoughness");
+        for level in 0..prefiltered_levels {
+            let roughness = level as f32 / (prefiltered_levels - 1).max(1) as f32;
+            let size = (options.prefiltered_size >> level).max(1);
+            baker.render_cube(&program, &prefiltered, size, level as i32, |context| {
+                context.uniform1f(loc_roughness.as_ref(), roughness);
+            });
+        }
+
+        let brdf_lut = create_texture_2d(context, options.brdf_lut_size, format)?;
+        let program = baker.program(&brdf_fs())?;
+        baker.render_2d(&program, &brdf_lut, options.brdf_lut_size);
+
+        // `baker` cleans up when dropped, here or on an early return
+        drop(baker);
+
+        Ok(Environment {
+            cube,
+            irradiance,
+            prefiltered,
+            prefiltered_levels,
+            brdf_lut,
+            skybox: Skybox::new(context)?,
+        })
+    }
+
+    pub fn cube(&self) -> &WebGlTexture {
+        &self.cube
+    }
+
+    pub fn irradiance(&self) -> &WebGlTexture {
+        &self.irradiance
+    }
+
+    pub fn prefiltered(&self) -> &WebGlTexture {
+        &self.prefiltered
+    }
+
+    pub fn prefiltered_levels(&self) -> u32 {
+        self.prefiltered_levels
+    }
+
+    pub fn brdf_lut(&self) -> &WebGlTexture {
+        &self.brdf_lut
+    }
+
+    /// Fills the viewport with the environment as seen by `camera`. Call
+    /// right after clearing; it draws without depth testing, and leaves that
+    /// `PipelineState` applied. `blur` is a mip level of the environment
+    /// cube map, 0 for sharp. As for
+    /// `PbrRenderer::tone_map`, leave `tone_map` off when drawing into an
+    /// HDR target that is tone mapped later.
+    pub fn draw_background(
+        &self,
+        state: &mut GlState,
+        camera: &Camera,
+        blur: f32,
+        exposure: f32,
+        tone_map: bool,
+    ) {
+        self.skybox
+            .draw(state, &self.cube, camera, blur, exposure, tone_map);
+    }
+}
+
+struct Skybox {
+    program: WebGlProgram,
+    vao: WebGlVertexArrayObject,
+    loc_environment: Option<WebGlUniformLocation>,
+    loc_inverse_view_projection: Option<WebGlUniformLocation>,
+    loc_lod: Option<WebGlUniformLocation>,
+    loc_exposure: Option<WebGlUniformLocation>,
+    loc_tone_map: Option<WebGlUniformLocation>,
+}
+
+impl Skybox {
+    fn new(context: &WebGl2RenderingContext) -> Result<Self, String> {
+        let program = fullscreen_program(context, &skybox_fs())?;
+        let location = |name: &str| context.get_uniform_location(&program, name);
+
+        Ok(Skybox {
+            loc_environment: location("uEnvironment"),
+            loc_inverse_view_projection: location("uInverseViewProjection"),
+            loc_lod: location("uLod"),
+            loc_exposure: location("uExposure"),
+            loc_tone_map: location("uToneMap"),
+            vao: context
+                .create_vertex_array()
+                .ok_or_else(|| String::from("Unable to create vertex array object"))?,
+            program,
+        })
+    }
+
+    fn draw(
+        &self,
+        state: &mut GlState,
+        cube: &WebGlTexture,
+        camera: &Camera,
+        lod: f32,
+        exposure: f32,
+        tone_map: bool,
+    ) {
+        let context = &state.context().clone();
+
+        // only the rotation of the view matters
+        let mut view = camera.view_matrix();
+        view.set_column(3, &glm::vec4(0.0, 0.0, 0.0, 1.0));
+        let inverse = glm::inverse(&(camera.projection_matrix() * view));
+
+        BACKGROUND_PIPELINE.apply(state);
+        state.use_program(Some(&self.program));
+        context.uniform_matrix4fv_with_f32_array(
+            self.loc_inverse_view_projection.as_ref(),
+            false,
+            inverse.as_slice(),
+        );
+        context.uniform1f(self.loc_lod.as_ref(), lod);
+        context.uniform1f(self.loc_exposure.as_ref(), exposure);
+        context.uniform1i(self.loc_tone_map.as_ref(), tone_map as i32);
+
+        state.bind_texture(0, WebGl2RenderingContext::TEXTURE_CUBE_MAP, Some(cube));
+        context.uniform1i(self.loc_environment.as_ref(), 0);
+
+        state.bind_vertex_array(Some(&self.vao));
+        context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+    }
+}
+
+/// Runs the fullscreen passes that fill the IBL textures. Unbinds and
+/// deletes what it created when dropped, even after a failed pass.
+struct Baker<'a> {
+    context: &'a WebGl2RenderingContext,
+    framebuffer: WebGlFramebuffer,
+    vao: WebGlVertexArrayObject,
+    programs: std::cell::RefCell<Vec<WebGlProgram>>,
+}
+
+impl<'a> Baker<'a> {
+    fn new(context: &'a WebGl2RenderingContext) -> Result<Self, String> {
+        let framebuffer = context
+            .create_framebuffer()
+            .ok_or_else(|| String::from("Unable to create framebuffer"))?;
+        let vao = context
+            .create_vertex_array()
+            .ok_or_else(|| String::from("Unable to create vertex array object"))?;
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&framebuffer));
+        context.bind_vertex_array(Some(&vao));
+
+        Ok(Baker {
+            context,
+            framebuffer,
+            vao,
+            programs: Default::default(),
+        })
+    }
+
+    /// Compiled with `FULLSCREEN_VS`, in use, and deleted on drop.
+    fn program(&self, fragment_shader_source: &str) -> Result<WebGlProgram, String> {
+        let program = fullscreen_program(self.context, fragment_shader_source)?;
+        self.context.use_program(Some(&program));
+        self.programs.borrow_mut().push(program.clone());
+
+        Ok(program)
+    }
+
+    fn uniform(&self, program: &WebGlProgram, name: &str) -> Option<WebGlUniformLocation> {
+        self.context.get_uniform_location(program, name)
+    }
+
+    fn bind_cube(&self, program: &WebGlProgram, name: &str, cube: &WebGlTexture) {
+        self.context
+            .active_texture(WebGl2RenderingContext::TEXTURE0);
+        self.context
+            .bind_texture(WebGl2RenderingContext::TEXTURE_CUBE_MAP, Some(cube));
+        self.context
+            .uniform1i(self.uniform(program, name).as_ref(), 0);
+    }
+
+    /// Draws each face of `level` of `target`, with `uFace` turning the
+    /// face's NDC into a direction.
+    fn render_cube<F>(
+        &self,
+        program: &WebGlProgram,
+        target: &WebGlTexture,
+        size: u32,
+        level: i32,
+        mut per_face: F,
+    ) where
+        F: FnMut(&WebGl2RenderingContext),
+    {
+        let loc_face = self.uniform(program, "uFace");
+        self.context.viewport(0, 0, size as i32, size as i32);
+
+        for (face, rotation) in face_rotations().iter().enumerate() {
+            // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/framebufferTexture2D
+            self.context.framebuffer_texture_2d(
+                WebGl2RenderingContext::FRAMEBUFFER,
+                WebGl2RenderingContext::COLOR_ATTACHMENT0,
+                WebGl2RenderingContext::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                Some(target),
+                level,
+            );
+
+            self.context.uniform_matrix3fv_with_f32_array(
+                loc_face.as_ref(),
+                false,
+                rotation.as_slice(),
+            );
+            per_face(self.context);
+
+            self.context
+                .draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+        }
+    }
+
+    fn render_2d(&self, _program: &WebGlProgram, target: &WebGlTexture, size: u32) {
+        self.context.framebuffer_texture_2d(
+            WebGl2RenderingContext::FRAMEBUFFER,
+            WebGl2RenderingContext::COLOR_ATTACHMENT0,
+            WebGl2RenderingContext::TEXTURE_2D,
+            Some(target),
+            0,
+        );
+        self.context.viewport(0, 0, size as i32, size as i32);
+        self.context
+            .draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+    }
+}
+
+impl Drop for Baker<'_> {
+    fn drop(&mut self) {
+        let context = self.context;
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        context.bind_vertex_array(None);
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_CUBE_MAP, None);
+        context.use_program(None);
+
+        context.delete_framebuffer(Some(&self.framebuffer));
+        context.delete_vertex_array(Some(&self.vao));
+        for program in self.programs.borrow().iter() {
+            context.delete_program(Some(program));
+        }
+    }
+}
+
+/// For each cube face, in `TEXTURE_CUBE_MAP_POSITIVE_X + i` order, the
+/// rotation from the view space of a 90° camera looking at that face to
+/// world space.
+fn face_rotations() -> [glm::Mat3; 6] {
+    let face = |direction: glm::Vec3, up: glm::Vec3| {
+        let view = glm::look_at(&glm::Vec3::zeros(), &direction, &up);
+        glm::transpose(&glm::mat4_to_mat3(&view))
+    };
+
+    [
+        face(glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+        face(glm::vec3(-1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+        face(glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, 1.0)),
+        face(glm::vec3(0.0, -1.0, 0.0), glm::vec3(0.0, 0.0, -1.0)),
+        face(glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, -1.0, 0.0)),
+        face(glm::vec3(0.0, 0.0, -1.0), glm::vec3(0.0, -1.0, 0.0)),
+    ]
+}
+
+fn mip_levels(size: u32) -> u32 {
+    32 - size.max(1).leading_zeros()
+}
+
+fn create_cube(
+    context: &WebGl2RenderingContext,
+    size: u32,
+    levels: u32,
+    format: u32,
+) -> Result<WebGlTexture, String> {
+    let texture = context
+        .create_texture()
+        .ok_or_else(|| String::from("Unable to create texture"))?;
+    let target = WebGl2RenderingContext::TEXTURE_CUBE_MAP;
+
+    context.bind_texture(target, Some(&texture));
+    // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/texStorage2D
+    context.tex_storage_2d(target, levels as i32, format, size as i32, size as i32);
+
+    let min_filter = if levels > 1 {
+        WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR
+    } else {
+        WebGl2RenderingContext::LINEAR
+    };
+    Sampler {
+        min_filter,
+        ..Sampler::clamped()
+    }
+    .apply(context, target);
+    context.tex_parameteri(
+        target,
+        WebGl2RenderingContext::TEXTURE_WRAP_R,
+        WebGl2RenderingContext::CLAMP_TO_EDGE as i32,
+    );
+
+    context.bind_texture(target, None);
+
+    Ok(texture)
+}
+
+fn create_texture_2d(
+    context: &WebGl2RenderingContext,
+    size: u32,
+    format: u32,
+) -> Result<WebGlTexture, String> {
+    let texture = context
+        .create_texture()
+        .ok_or_else(|| String::from("Unable to create texture"))?;
+    let target = WebGl2RenderingContext::TEXTURE_2D;
+
+    context.bind_texture(target, Some(&texture));
+    context.tex_storage_2d(target, 1, format, size as i32, size as i32);
+    Sampler::clamped().apply(context, target);
+    context.bind_texture(target, None);
+
+    Ok(texture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faces_look_along_their_axes() {
+        let forward = glm::vec3(0.0, 0.0, -1.0);
+        let expected = [
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(-1.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(0.0, -1.0, 0.0),
+            glm::vec3(0.0, 0.0, 1.0),
+            glm::vec3(0.0, 0.0, -1.0),
+        ];
+
+        for (rotation, expected) in face_rotations().iter().zip(expected) {
+            assert!(glm::distance(&(rotation * forward), &expected) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn mip_chain_lengths() {
+        assert_eq!(mip_levels(1), 1);
+        assert_eq!(mip_levels(128), 8);
+        assert_eq!(mip_levels(100), 7);
+    }
+
+    #[test]
+    fn shaders_avoid_reserved_words() {
+        use crate::webgl::reserved_words_in;
+
+        let sources = [
+            EQUIRECTANGULAR_FS.to_string(),
+            IRRADIANCE_FS.to_string(),
+            prefilter_fs(),
+            brdf_fs(),
+            skybox_fs(),
+        ];
+        for source in &sources {
+            assert_eq!(reserved_words_in(source), Vec::<&str>::new());
+        }
+    }
+}
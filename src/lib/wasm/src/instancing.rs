@@ -0,0 +1,205 @@
+// Per-instance attributes for drawing many copies of a mesh in one call.
+//
+// `Instances` keeps a CPU copy of one interleaved buffer with a divisor of
+// 1, holding for every instance
+//
+//   in mat4 aInstanceMatrix;  // model matrix, 4 consecutive locations
+//   in vec4 aInstanceColor;
+//   in vec4 aInstanceData;    // 1 to 4 custom floats, if any
+//
+// Add it to a mesh and draw with the instance count:
+//
+//   let mut instances = Instances::new(&mut state, 100_000, 0)?;
+//   instances.push(&model, [1.0, 0.5, 0.2, 1.0])?;
+//   mesh.add_vertex_buffer(instances.vertex_buffer());
+//   ...
+//   instances.upload(&mut state)?;
+//   mesh.draw_instanced(&mut state, &program, instances.len() as i32)?;
+//
+// Setters only mark the instances they touch; `upload` sends the smallest
+// range covering them with `bufferSubData`, so changing a few instances
+// of a large buffer costs little.
+
+use std::ops::Range;
+
+extern crate nalgebra_glm as glm;
+
+use crate::mesh::{AttributeType, VertexBuffer, VertexLayout};
+use crate::state::GlState;
+
+pub const INSTANCE_MATRIX: &str = "aInstanceMatrix";
+pub const INSTANCE_COLOR: &str = "aInstanceColor";
+pub const INSTANCE_DATA: &str = "aInstanceData";
+
+const MATRIX_FLOATS: usize = 16;
+const COLOR_FLOATS: usize = 4;
+
+pub struct Instances {
+    buffer: VertexBuffer,
+    data: Vec<f32>,
+    custom: usize,
+    capacity: usize,
+    len: usize,
+    // instances changed since the last upload
+    dirty: Option<Range<usize>>,
+}
+
+impl Instances {
+    /// Room for `capacity` instances with `custom` (0 to 4) extra floats
+    /// each. The buffer never grows.
+    pub fn new(state: &mut GlState, capacity: usize, custom: usize) -> Result<Self, String> {
+        if custom > 4 {
+            return Err(format!(
+                "At most 4 custom floats per instance, got {}",
+                custom
+            ));
+        }
+        if capacity == 0 {
+            return Err(String::from("Instances need a capacity"));
+        }
+
+        let data = vec![0.0; capacity * Self::floats_per_instance(custom)];
+        let buffer = VertexBuffer::dynamic_f32(state, &data, Self::layout(custom))?;
+
+        Ok(Instances {
+            buffer,
+            data,
+            custom,
+            capacity,
+            len: 0,
+            dirty: None,
+        })
+    }
+
+    /// The attributes of one instance, advancing once per instance.
+    pub fn layout(custom: usize) -> VertexLayout {
+        let layout = VertexLayout::new()
+            .matrix(INSTANCE_MATRIX, 4, 4)
+            .attribute(INSTANCE_COLOR, 4, AttributeType::Float, false)
+            .with_divisor(1);
+
+        match custom {
+            0 => layout,
+            n => layout.attribute(INSTANCE_DATA, n as i32, AttributeType::Float, false),
+        }
+    }
+
+    fn floats_per_instance(custom: usize) -> usize {
+        MATRIX_FLOATS + COLOR_FLOATS + custom
+    }
+
+    /// A handle to the GL buffer, for `Mesh::add_vertex_buffer`.
+    pub fn vertex_buffer(&self) -> VertexBuffer {
+        self.buffer.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Forgets every instance; the buffer keeps its contents until they
+    /// are overwritten.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.dirty = None;
+    }
+
+    /// Appends an instance and returns its index.
+    pub fn push(&mut self, matrix: &glm::Mat4, color: [f32; 4]) -> Result<usize, String> {
+        if self.len == self.capacity {
+            return Err(format!("All {} instances are in use", self.capacity));
+        }
+
+        let index = self.len;
+        self.len += 1;
+        self.set_matrix(index, matrix);
+        self.set_color(index, color);
+        self.set_custom(index, &[0.0; 4][..self.custom]);
+
+        Ok(index)
+    }
+
+    /// Panics if `index` is not below `len`, like slice indexing.
+    pub fn set_matrix(&mut self, index: usize, matrix: &glm::Mat4) {
+        // nalgebra stores matrices column-major, as GLSL expects
+        self.write(index, 0, matrix.as_slice());
+    }
+
+    pub fn set_color(&mut self, index: usize, color: [f32; 4]) {
+        self.write(index, MATRIX_FLOATS, &color);
+    }
+
+    /// `values` has as many floats as `custom` in `new`.
+    pub fn set_custom(&mut self, index: usize, values: &[f32]) {
+        assert_eq!(values.len(), self.custom, "custom floats per instance");
+        self.write(index, MATRIX_FLOATS + COLOR_FLOATS, values);
+    }
+
+    fn write(&mut self, index: usize, offset: usize, values: &[f32]) {
+        assert!(index < self.len, "instance {} of {}", index, self.len);
+
+        let start = index * Self::floats_per_instance(self.custom) + offset;
+        self.data[start..start + values.len()].copy_from_slice(values);
+        self.dirty = Some(merge(self.dirty.take(), index..index + 1));
+    }
+
+    /// Sends the instances changed since the last upload.
+    pub fn upload(&mut self, state: &mut GlState) -> Result<(), String> {
+        let Some(dirty) = self.dirty.take() else {
+            return Ok(());
+        };
+
+        let floats = Self::floats_per_instance(self.custom);
+        self.buffer.update_f32(
+            state,
+            dirty.start as i32,
+            &self.data[dirty.start * floats..dirty.end * floats],
+        )
+    }
+}
+
+/// The smallest range covering both.
+fn merge(range: Option<Range<usize>>, other: Range<usize>) -> Range<usize> {
+    match range {
+        Some(range) => range.start.min(other.start)..range.end.max(other.end),
+        None => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_spans_matrix_columns() {
+        let layout = Instances::layout(2);
+        let attributes = layout.attributes();
+
+        assert_eq!(attributes[0].columns, 4);
+        assert_eq!(attributes[0].size(), 64);
+        assert_eq!(attributes[1].offset, 64);
+        assert_eq!(attributes[2].offset, 80);
+        assert_eq!(layout.stride(), 88);
+        assert_eq!(layout.divisor(), 1);
+        assert_eq!(Instances::layout(0).stride(), 80);
+    }
+
+    #[test]
+    fn dirty_ranges_merge() {
+        let range = merge(None, 5..6);
+        assert_eq!(range, 5..6);
+
+        let range = merge(Some(range), 2..3);
+        assert_eq!(range, 2..6);
+
+        assert_eq!(merge(Some(range), 3..4), 2..6);
+    }
+}
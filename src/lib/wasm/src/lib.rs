@@ -1,8 +1,32 @@
 extern crate wasm_bindgen;
 use wasm_bindgen::prelude::*;
 
+pub mod camera;
 mod examples;
+pub mod fluid;
+pub mod geometry;
+pub mod ibl;
+pub mod instancing;
+pub mod lighting;
+pub mod loaders;
+pub mod mesh;
+pub mod noise;
+pub mod pbr;
+pub mod pipeline;
+mod params;
+pub mod particles;
+pub mod postprocess;
+mod preset;
+mod registry;
 mod rtg;
+pub mod scene;
+pub mod shadow;
+pub mod simulation;
+mod share;
+pub mod state;
+pub mod target;
+pub mod texture;
+pub mod uniforms;
 mod utils;
 mod webgl;
 use crate::examples::colored_square::main as draw_colored_square;
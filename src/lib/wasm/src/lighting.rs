@@ -0,0 +1,533 @@
+// Blinn-Phong lighting for scene graphs.
+//
+// Lights are attached to scene nodes (`Scene::attach_light`) and materials
+// to the nodes showing meshes (`Scene::set_material`). `PhongRenderer`
+// generates a program for up to `max_lights` lights and draws every mesh
+// with it. Meshes need "position" and "normal" attributes, as produced by
+// `Geometry::to_mesh`.
+//
+// Lighting is computed in view space: light positions and directions are
+// moved there in Rust, and so is the normal matrix (the inverse transpose
+// of the model-view matrix), set by `scene::Renderer` as `uNormalMatrix`.
+
+extern crate nalgebra_glm as glm;
+
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+use crate::camera::Camera;
+use crate::loaders::obj::Material as ObjMaterial;
+use crate::scene::{Material, Renderer, Scene};
+use crate::shadow::{shadow_declarations, ShadowMaps, ShadowUniforms};
+use crate::state::GlState;
+use crate::webgl::{compile_shader, link_shader_program};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Parallel rays along the node's -Z axis, e.g. the sun.
+    Directional,
+    /// Shines in every direction from the node's origin.
+    Point,
+    /// A cone along the node's -Z axis. Full intensity within
+    /// `inner_angle`, fading out to nothing at `outer_angle` (radians, from
+    /// the axis).
+    Spot { inner_angle: f32, outer_angle: f32 },
+}
+
+impl LightKind {
+    // Matches the #defines in the fragment shader.
+    fn id(&self) -> i32 {
+        match self {
+            LightKind::Directional => 0,
+            LightKind::Point => 1,
+            LightKind::Spot { .. } => 2,
+        }
+    }
+}
+
+/// Intensity is divided by `constant + linear * d + quadratic * d * d` at
+/// distance `d`. Directional lights don't attenuate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for Attenuation {
+    /// Falls to about a tenth at 15 units.
+    fn default() -> Self {
+        Attenuation {
+            constant: 1.0,
+            linear: 0.09,
+            quadratic: 0.032,
+        }
+    }
+}
+
+/// How a light casts shadows, see `shadow::ShadowMaps`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shadow {
+    /// Subtracted from the depth of the fragment, in the light's [0, 1]
+    /// depth range, against shadow acne.
+    pub bias: f32,
+    /// Fragments are looked up this far (in world units) along their
+    /// normal, against acne on surfaces at grazing angles.
+    pub normal_bias: f32,
+    /// Percentage-closer filtering over `(2r + 1)²` texels; 0 for hard
+    /// edges. At most `shadow::MAX_PCF_RADIUS`.
+    pub pcf_radius: u32,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Shadow {
+            bias: 0.0005,
+            normal_bias: 0.02,
+            pcf_radius: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub attenuation: Attenuation,
+    /// Only directional and spot lights cast shadows.
+    pub shadow: Option<Shadow>,
+}
+
+impl Light {
+    pub fn directional(color: [f32; 3], intensity: f32) -> Self {
+        Self::new(LightKind::Directional, color, intensity)
+    }
+
+    pub fn point(color: [f32; 3], intensity: f32) -> Self {
+        Self::new(LightKind::Point, color, intensity)
+    }
+
+    pub fn spot(color: [f32; 3], intensity: f32, inner_angle: f32, outer_angle: f32) -> Self {
+        let kind = LightKind::Spot {
+            inner_angle: inner_angle.min(outer_angle),
+            outer_angle,
+        };
+
+        Self::new(kind, color, intensity)
+    }
+
+    fn new(kind: LightKind, color: [f32; 3], intensity: f32) -> Self {
+        Light {
+            kind,
+            color,
+            intensity,
+            attenuation: Attenuation::default(),
+            shadow: None,
+        }
+    }
+
+    pub fn with_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Self {
+        self.attenuation = Attenuation {
+            constant,
+            linear,
+            quadratic,
+        };
+        self
+    }
+
+    pub fn with_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhongMaterial {
+    /// Multiplied by the renderer's ambient light.
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    /// Specular exponent; higher is shinier and tighter.
+    pub shininess: f32,
+    pub opacity: f32,
+}
+
+impl Default for PhongMaterial {
+    fn default() -> Self {
+        PhongMaterial {
+            ambient: [0.8, 0.8, 0.8],
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [0.5, 0.5, 0.5],
+            shininess: 32.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl PhongMaterial {
+    /// Same color for ambient and diffuse.
+    pub fn colored(color: [f32; 3]) -> Self {
+        PhongMaterial {
+            ambient: color,
+            diffuse: color,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&ObjMaterial> for PhongMaterial {
+    /// Exporters often write `Ka 0 0 0`, which would leave shadows pitch
+    /// black; the diffuse color stands in for it then.
+    fn from(material: &ObjMaterial) -> Self {
+        let ambient = if material.ambient.iter().all(|&c| c == 0.0) {
+            material.diffuse
+        } else {
+            material.ambient
+        };
+
+        PhongMaterial {
+            ambient,
+            diffuse: material.diffuse,
+            specular: material.specular,
+            shininess: material.shininess.max(1.0),
+            opacity: material.opacity,
+        }
+    }
+}
+
+static VS_SRC: &str = r#"#version 300 es
+in vec3 position;
+in vec3 normal;
+
+uniform mat4 uModelViewMatrix;
+uniform mat4 uProjectionMatrix;
+uniform mat3 uNormalMatrix;
+
+out vec3 vPosition;
+out vec3 vNormal;
+
+void main() {
+  vec4 p = uModelViewMatrix * vec4(position, 1.0);
+  vPosition = p.xyz;
+  vNormal = uNormalMatrix * normal;
+  gl_Position = uProjectionMatrix * p;
+}
+"#;
+
+/// Declares `uLights`, `uLightCount` and
+///
+///   // How much of `light` reaches `position` (view space), and from where.
+///   float lightIncidence(Light light, vec3 position, out vec3 l);
+///
+/// for shaders lit by `LightUniforms`.
+pub fn light_declarations(max_lights: usize) -> String {
+    format!(
+        r#"#define MAX_LIGHTS {max_lights}
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+struct Light {{
+  int kind;
+  vec3 color;      // premultiplied by intensity
+  vec3 position;   // view space
+  vec3 direction;  // view space
+  vec3 attenuation;  // constant, linear, quadratic
+  vec2 cone;       // cos(inner angle), cos(outer angle)
+}};
+
+uniform Light uLights[MAX_LIGHTS];
+uniform int uLightCount;
+
+float lightIncidence(Light light, vec3 position, out vec3 l) {{
+  if (light.kind == DIRECTIONAL) {{
+    l = -light.direction;
+    return 1.0;
+  }}
+
+  vec3 toLight = light.position - position;
+  float d = length(toLight);
+  l = toLight / d;
+  float strength = 1.0 / dot(light.attenuation, vec3(1.0, d, d * d));
+
+  if (light.kind == SPOT) {{
+    strength *= smoothstep(light.cone.y, light.cone.x, dot(-l, light.direction));
+  }}
+
+  return strength;
+}}
+"#
+    )
+}
+
+/// The Blinn-Phong fragment shader for up to `max_lights` lights.
+pub fn phong_fragment_shader(max_lights: usize) -> String {
+    format!(
+        r#"#version 300 es
+precision highp float;
+
+{lights}
+{shadows}
+uniform vec3 uAmbientLight;
+
+uniform vec3 uAmbient;
+uniform vec3 uDiffuse;
+uniform vec3 uSpecular;
+uniform float uShininess;
+uniform float uOpacity;
+
+in vec3 vPosition;
+in vec3 vNormal;
+
+out vec4 fragColor;
+
+void main() {{
+  vec3 n = normalize(vNormal);
+  if (!gl_FrontFacing) n = -n;
+  vec3 v = normalize(-vPosition);
+
+  vec3 color = uAmbientLight * uAmbient;
+
+  for (int i = 0; i < MAX_LIGHTS; i++) {{
+    if (i >= uLightCount) break;
+
+    vec3 l;
+    float strength = lightIncidence(uLights[i], vPosition, l);
+    strength *= shadowFactor(i, vPosition, n);
+
+    float diffuse = max(dot(n, l), 0.0);
+    float specular = 0.0;
+    if (diffuse > 0.0) {{
+      vec3 h = normalize(l + v);
+      specular = pow(max(dot(n, h), 0.0), uShininess);
+    }}
+
+    color += strength * uLights[i].color * (uDiffuse * diffuse + uSpecular * specular);
+  }}
+
+  fragColor = vec4(color, uOpacity);
+}}
+"#,
+        lights = light_declarations(max_lights),
+        shadows = shadow_declarations(),
+    )
+}
+
+struct LightLocations {
+    kind: Option<WebGlUniformLocation>,
+    color: Option<WebGlUniformLocation>,
+    position: Option<WebGlUniformLocation>,
+    direction: Option<WebGlUniformLocation>,
+    attenuation: Option<WebGlUniformLocation>,
+    cone: Option<WebGlUniformLocation>,
+}
+
+/// Uploads a scene's lights to the uniforms of `light_declarations`.
+pub struct LightUniforms {
+    lights: Vec<LightLocations>,
+    loc_light_count: Option<WebGlUniformLocation>,
+}
+
+impl LightUniforms {
+    pub fn new(
+        context: &WebGl2RenderingContext,
+        program: &WebGlProgram,
+        max_lights: usize,
+    ) -> Self {
+        let location = |name: &str| context.get_uniform_location(program, name);
+
+        let lights = (0..max_lights)
+            .map(|i| {
+                let field = |field: &str| location(&format!("uLights[{}].{}", i, field));
+
+                LightLocations {
+                    kind: field("kind"),
+                    color: field("color"),
+                    position: field("position"),
+                    direction: field("direction"),
+                    attenuation: field("attenuation"),
+                    cone: field("cone"),
+                }
+            })
+            .collect();
+
+        LightUniforms {
+            lights,
+            loc_light_count: location("uLightCount"),
+        }
+    }
+
+    /// Lights past `max_lights`, in node order, are ignored. The program
+    /// must be in use and the scene updated.
+    pub fn upload(&self, context: &WebGl2RenderingContext, scene: &Scene, view: &glm::Mat4) {
+        let mut count = 0;
+
+        for ((node, light), loc) in scene.lights().zip(&self.lights) {
+            let position = (view * node.world_position().push(1.0)).xyz();
+            let direction = glm::normalize(&(view * node.world_direction().push(0.0)).xyz());
+            let color = glm::make_vec3(&light.color) * light.intensity;
+            let attenuation = light.attenuation;
+            let cone = match light.kind {
+                LightKind::Spot {
+                    inner_angle,
+                    outer_angle,
+                } => [inner_angle.cos(), outer_angle.cos()],
+                _ => [1.0, 0.0],
+            };
+
+            context.uniform1i(loc.kind.as_ref(), light.kind.id());
+            context.uniform3fv_with_f32_array(loc.color.as_ref(), color.as_slice());
+            context.uniform3fv_with_f32_array(loc.position.as_ref(), position.as_slice());
+            context.uniform3fv_with_f32_array(loc.direction.as_ref(), direction.as_slice());
+            context.uniform3f(
+                loc.attenuation.as_ref(),
+                attenuation.constant,
+                attenuation.linear,
+                attenuation.quadratic,
+            );
+            context.uniform2fv_with_f32_array(loc.cone.as_ref(), &cone);
+
+            count += 1;
+        }
+
+        context.uniform1i(self.loc_light_count.as_ref(), count);
+    }
+}
+
+struct MaterialLocations {
+    ambient: Option<WebGlUniformLocation>,
+    diffuse: Option<WebGlUniformLocation>,
+    specular: Option<WebGlUniformLocation>,
+    shininess: Option<WebGlUniformLocation>,
+    opacity: Option<WebGlUniformLocation>,
+}
+
+pub struct PhongRenderer {
+    renderer: Renderer,
+    lights: LightUniforms,
+    shadow_uniforms: ShadowUniforms,
+    loc_ambient_light: Option<WebGlUniformLocation>,
+    material: MaterialLocations,
+    pub ambient_light: [f32; 3],
+    /// Shadows of the lights with a `Shadow`; none without.
+    pub shadows: Option<ShadowMaps>,
+}
+
+impl PhongRenderer {
+    /// Lights past `max_lights`, in node order, are ignored.
+    pub fn new(context: &WebGl2RenderingContext, max_lights: usize) -> Result<Self, String> {
+        let max_lights = max_lights.max(1);
+
+        let vs = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, VS_SRC)?;
+        let fs = compile_shader(
+            context,
+            WebGl2RenderingContext::FRAGMENT_SHADER,
+            &phong_fragment_shader(max_lights),
+        )?;
+        let program = link_shader_program(context, &vs, &fs)?;
+
+        let location = |name: &str| context.get_uniform_location(&program, name);
+
+        Ok(PhongRenderer {
+            lights: LightUniforms::new(context, &program, max_lights),
+            shadow_uniforms: ShadowUniforms::new(context, &program, max_lights)?,
+            loc_ambient_light: location("uAmbientLight"),
+            material: MaterialLocations {
+                ambient: location("uAmbient"),
+                diffuse: location("uDiffuse"),
+                specular: location("uSpecular"),
+                shininess: location("uShininess"),
+                opacity: location("uOpacity"),
+            },
+            renderer: Renderer::new(context, &program),
+            ambient_light: [0.1, 0.1, 0.1],
+            shadows: None,
+        })
+    }
+
+    pub fn program(&self) -> &WebGlProgram {
+        self.renderer.program()
+    }
+
+    pub fn render(
+        &self,
+        state: &mut GlState,
+        scene: &mut Scene,
+        camera: &Camera,
+    ) -> Result<(), String> {
+        let context = &state.context().clone();
+        let view = camera.view_matrix();
+        let shadows = match &self.shadows {
+            Some(shadows) => Some(shadows.render(state, scene, camera)?),
+            None => None,
+        };
+
+        scene.update();
+        state.use_program(Some(self.program()));
+
+        context.uniform3fv_with_f32_array(self.loc_ambient_light.as_ref(), &self.ambient_light);
+        self.lights.upload(context, scene, &view);
+        self.shadow_uniforms
+            .upload(state, scene, shadows.as_ref(), &view);
+
+        self.renderer.render_with(
+            state,
+            scene,
+            &view,
+            &camera.projection_matrix(),
+            |_, scene, id| {
+                let material = match scene.node(id).material().and_then(|m| scene.material(m)) {
+                    Some(Material::Phong(material)) => *material,
+                    _ => PhongMaterial::default(),
+                };
+
+                self.upload_material(context, &material);
+            },
+        )
+    }
+
+    fn upload_material(&self, context: &WebGl2RenderingContext, material: &PhongMaterial) {
+        let loc = &self.material;
+
+        context.uniform3fv_with_f32_array(loc.ambient.as_ref(), &material.ambient);
+        context.uniform3fv_with_f32_array(loc.diffuse.as_ref(), &material.diffuse);
+        context.uniform3fv_with_f32_array(loc.specular.as_ref(), &material.specular);
+        context.uniform1f(loc.shininess.as_ref(), material.shininess);
+        context.uniform1f(loc.opacity.as_ref(), material.opacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::obj::parse_mtl;
+
+    #[test]
+    fn lights_follow_their_nodes() {
+        let mut scene = Scene::new();
+        let arm = scene.add_node(None);
+        let spot = scene.add_node(Some(arm));
+
+        scene.attach_light(spot, Light::spot([1.0; 3], 1.0, 0.2, 0.4));
+        scene.set_translation(arm, glm::vec3(0.0, 5.0, 0.0));
+        scene.set_rotation(
+            arm,
+            glm::quat_angle_axis(-std::f32::consts::FRAC_PI_2, &glm::vec3(1.0, 0.0, 0.0)),
+        );
+        scene.update();
+
+        let (node, light) = scene.lights().next().unwrap();
+        assert!(matches!(light.kind, LightKind::Spot { .. }));
+        assert!(glm::distance(&node.world_position(), &glm::vec3(0.0, 5.0, 0.0)) < 1e-5);
+        assert!(glm::distance(&node.world_direction(), &glm::vec3(0.0, -1.0, 0.0)) < 1e-5);
+    }
+
+    #[test]
+    fn materials_from_mtl() {
+        let materials = parse_mtl("newmtl red\nKa 0 0 0\nKd 0.8 0.1 0.1\nNs 0\n").unwrap();
+        let material = PhongMaterial::from(&materials["red"]);
+
+        assert_eq!(material.ambient, [0.8, 0.1, 0.1]);
+        assert_eq!(material.shininess, 1.0);
+    }
+}
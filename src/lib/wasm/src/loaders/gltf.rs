@@ -0,0 +1,1191 @@
+// glTF 2.0 binary (.glb), parsed from bytes handed over by the caller.
+//
+// Buffers must live in the BIN chunk or in `data:` URIs; nothing is fetched.
+// Triangle primitives become indexed `Geometry` (normals and tangents are
+// generated when missing), materials keep their metallic-roughness factors
+// and texture references, and images keep their encoded bytes for the
+// caller to decode. Unsupported extensions, primitive modes and external
+// URIs are skipped and reported in `Gltf::warnings`.
+//
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+
+extern crate nalgebra_glm as glm;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+
+use crate::geometry::Geometry;
+
+const GLB_MAGIC: u32 = 0x4654_6c67; // "glTF"
+const CHUNK_JSON: u32 = 0x4e4f_534a; // "JSON"
+const CHUNK_BIN: u32 = 0x004e_4942; // "BIN\0"
+
+const MODE_TRIANGLES: u32 = 4;
+
+// Components of an accessor without a buffer view, which are zeros taking no
+// space in the file.
+const MAX_ZEROED_COMPONENTS: usize = 1 << 24;
+
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_materials_emissive_strength"];
+
+pub struct Gltf {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub images: Vec<GltfImage>,
+    pub nodes: Vec<GltfNode>,
+    /// Root nodes of each scene.
+    pub scenes: Vec<Vec<usize>>,
+    /// The scene to show by default.
+    pub scene: Option<usize>,
+    pub warnings: Vec<String>,
+}
+
+impl Gltf {
+    /// Root nodes of the default scene, or of the first one.
+    pub fn roots(&self) -> &[usize] {
+        self.scenes
+            .get(self.scene.unwrap_or(0))
+            .map(|roots| &roots[..])
+            .unwrap_or(&[])
+    }
+
+    /// Every node under `roots`, parents first, with its world matrix.
+    /// Fails on a node reached twice, which a valid file can't have since
+    /// the hierarchy is a forest, so cycles don't loop forever.
+    pub fn world_nodes(&self) -> Result<Vec<(usize, glm::Mat4)>, String> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut nodes = vec![];
+        let mut stack = self
+            .roots()
+            .iter()
+            .map(|&node| (node, glm::Mat4::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((idx, parent)) = stack.pop() {
+            let node = self
+                .nodes
+                .get(idx)
+                .ok_or_else(|| format!("Node {} does not exist", idx))?;
+            if std::mem::replace(&mut visited[idx], true) {
+                return Err(format!("Node {} is reached twice", idx));
+            }
+
+            let world = parent * node.local_matrix();
+            stack.extend(node.children.iter().map(|&child| (child, world)));
+            nodes.push((idx, world));
+        }
+
+        Ok(nodes)
+    }
+}
+
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+pub struct GltfPrimitive {
+    pub geometry: Geometry,
+    pub material: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbrMaterial {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Roughness in G, metalness in B.
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<usize>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfTexture {
+    pub image: Option<usize>,
+    pub mag_filter: Option<u32>,
+    pub min_filter: Option<u32>,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+}
+
+pub struct GltfImage {
+    pub name: Option<String>,
+    pub mime_type: Option<String>,
+    /// Encoded (PNG, JPEG, ...) bytes; empty if the image is external.
+    pub data: Vec<u8>,
+    pub uri: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+    pub translation: [f32; 3],
+    /// Quaternion, x y z w.
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl GltfNode {
+    pub fn local_matrix(&self) -> glm::Mat4 {
+        let [x, y, z, w] = self.rotation;
+        let rotation = glm::quat_to_mat4(&glm::quat(x, y, z, w));
+
+        glm::translation(&glm::make_vec3(&self.translation))
+            * rotation
+            * glm::scaling(&glm::make_vec3(&self.scale))
+    }
+}
+
+pub fn parse_glb(bytes: &[u8]) -> Result<Gltf, String> {
+    let (json, bin) = split_glb(bytes)?;
+
+    let document: Document =
+        serde_json::from_slice(json).map_err(|err| format!("Invalid glTF JSON: {}", err))?;
+
+    Loader { document, bin }.load()
+}
+
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let u32_at = |offset: usize| -> Result<u32, String> {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| String::from("Truncated GLB"))
+    };
+
+    if u32_at(0)? != GLB_MAGIC {
+        return Err(String::from("Not a GLB file"));
+    }
+
+    let version = u32_at(4)?;
+    if version != 2 {
+        return Err(format!("Unsupported glTF version: {}", version));
+    }
+
+    let length = (u32_at(8)? as usize).min(bytes.len());
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+
+    while offset + 8 <= length {
+        let truncated = || String::from("Truncated GLB chunk");
+
+        let chunk_length = u32_at(offset)? as usize;
+        let chunk_type = u32_at(offset + 4)?;
+        let chunk_end = (offset + 8)
+            .checked_add(chunk_length)
+            .ok_or_else(truncated)?;
+        let data = bytes.get(offset + 8..chunk_end).ok_or_else(truncated)?;
+
+        match chunk_type {
+            CHUNK_JSON if json.is_none() => json = Some(data),
+            CHUNK_BIN if bin.is_none() => bin = Some(data),
+            // unknown chunks must be ignored
+            _ => {}
+        }
+
+        // chunks are 4-byte aligned
+        offset = chunk_end
+            .checked_next_multiple_of(4)
+            .ok_or_else(truncated)?;
+    }
+
+    let json = json.ok_or_else(|| String::from("GLB has no JSON chunk"))?;
+
+    Ok((json, bin))
+}
+
+struct Loader<'a> {
+    document: Document,
+    bin: Option<&'a [u8]>,
+}
+
+impl<'a> Loader<'a> {
+    fn load(self) -> Result<Gltf, String> {
+        let mut warnings = vec![];
+
+        for extension in &self.document.extensions_used {
+            if !SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+                let required = self.document.extensions_required.contains(extension);
+                warnings.push(format!(
+                    "Skipping unsupported extension {}{}",
+                    extension,
+                    if required { " (required)" } else { "" }
+                ));
+            }
+        }
+
+        let buffers = self
+            .document
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(idx, buffer)| self.buffer_data(idx, buffer))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let meshes = self
+            .document
+            .meshes
+            .iter()
+            .enumerate()
+            .map(|(idx, mesh)| self.load_mesh(idx, mesh, &buffers, &mut warnings))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let materials = self.document.materials.iter().map(load_material).collect();
+
+        let textures = self
+            .document
+            .textures
+            .iter()
+            .map(|texture| {
+                let sampler = texture
+                    .sampler
+                    .and_then(|s| self.document.samplers.get(s))
+                    .cloned()
+                    .unwrap_or_default();
+
+                GltfTexture {
+                    image: texture.source,
+                    mag_filter: sampler.mag_filter,
+                    min_filter: sampler.min_filter,
+                    wrap_s: sampler.wrap_s,
+                    wrap_t: sampler.wrap_t,
+                }
+            })
+            .collect();
+
+        let images = self
+            .document
+            .images
+            .iter()
+            .enumerate()
+            .map(|(idx, image)| self.load_image(idx, image, &buffers, &mut warnings))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let nodes = self.document.nodes.iter().map(load_node).collect();
+
+        let scenes = self
+            .document
+            .scenes
+            .iter()
+            .map(|scene| scene.nodes.clone())
+            .collect();
+
+        Ok(Gltf {
+            meshes,
+            materials,
+            textures,
+            images,
+            nodes,
+            scenes,
+            scene: self.document.scene,
+            warnings,
+        })
+    }
+
+    fn buffer_data(&self, idx: usize, buffer: &Buffer) -> Result<Vec<u8>, String> {
+        let data = match &buffer.uri {
+            // the first buffer without a URI is the BIN chunk
+            None if idx == 0 => self
+                .bin
+                .ok_or_else(|| String::from("Buffer 0 refers to a missing BIN chunk"))?
+                .to_vec(),
+            None => return Err(format!("Buffer {} has no data", idx)),
+            Some(uri) => decode_data_uri(uri).ok_or_else(|| {
+                format!(
+                    "Buffer {} is external ({}); only GLB-embedded and data: buffers are supported",
+                    idx, uri
+                )
+            })??,
+        };
+
+        if data.len() < buffer.byte_length {
+            return Err(format!(
+                "Buffer {} is {} bytes, expected {}",
+                idx,
+                data.len(),
+                buffer.byte_length
+            ));
+        }
+
+        Ok(data)
+    }
+
+    fn load_mesh(
+        &self,
+        idx: usize,
+        mesh: &MeshDef,
+        buffers: &[Vec<u8>],
+        warnings: &mut Vec<String>,
+    ) -> Result<GltfMesh, String> {
+        let mut primitives = vec![];
+
+        for (p, primitive) in mesh.primitives.iter().enumerate() {
+            if primitive.mode != MODE_TRIANGLES {
+                warnings.push(format!(
+                    "Skipping mesh {} primitive {}: mode {} is not supported",
+                    idx, p, primitive.mode
+                ));
+                continue;
+            }
+
+            let read = |name: &str, components: usize| -> Result<Option<Vec<f32>>, String> {
+                match primitive.attributes.get(name) {
+                    None => Ok(None),
+                    Some(&accessor) => {
+                        let values = self.read_accessor(accessor, buffers)?;
+                        if values.components != components {
+                            return Err(format!(
+                                "Mesh {} primitive {}: {} must have {} components",
+                                idx, p, name, components
+                            ));
+                        }
+                        Ok(Some(values.data))
+                    }
+                }
+            };
+
+            let positions = read("POSITION", 3)?
+                .ok_or_else(|| format!("Mesh {} primitive {} has no POSITION", idx, p))?;
+            let normals = read("NORMAL", 3)?;
+            let tangents = read("TANGENT", 4)?;
+            let uvs = read("TEXCOORD_0", 2)?;
+
+            let count = positions.len() / 3;
+
+            let mut geometry = Geometry {
+                positions: positions.chunks(3).map(|c| [c[0], c[1], c[2]]).collect(),
+                normals: normals
+                    .as_ref()
+                    .map(|n| n.chunks(3).map(|c| [c[0], c[1], c[2]]).collect())
+                    .unwrap_or_else(|| vec![[0.0; 3]; count]),
+                tangents: tangents
+                    .as_ref()
+                    .map(|t| t.chunks(4).map(|c| [c[0], c[1], c[2], c[3]]).collect())
+                    .unwrap_or_default(),
+                uvs: uvs
+                    .map(|uv| uv.chunks(2).map(|c| [c[0], c[1]]).collect())
+                    .unwrap_or_else(|| vec![[0.0; 2]; count]),
+                indices: match primitive.indices {
+                    Some(accessor) => self.read_indices(accessor, buffers)?,
+                    None => (0..count as u32).collect(),
+                },
+            };
+
+            if geometry.normals.len() != count
+                || geometry.uvs.len() != count
+                || (tangents.is_some() && geometry.tangents.len() != count)
+            {
+                return Err(format!(
+                    "Mesh {} primitive {}: attribute counts differ",
+                    idx, p
+                ));
+            }
+
+            if geometry.indices.iter().any(|&i| i as usize >= count) {
+                return Err(format!("Mesh {} primitive {}: index out of range", idx, p));
+            }
+
+            if normals.is_none() {
+                geometry.compute_normals();
+            }
+            if tangents.is_none() {
+                geometry.compute_tangents();
+            }
+
+            primitives.push(GltfPrimitive {
+                geometry,
+                material: primitive.material,
+            });
+        }
+
+        Ok(GltfMesh {
+            name: mesh.name.clone(),
+            primitives,
+        })
+    }
+
+    /// Every element of an accessor as f32, normalized if flagged.
+    fn read_accessor(&self, idx: usize, buffers: &[Vec<u8>]) -> Result<Accessor, String> {
+        let elements = self.elements(idx, buffers)?;
+        let mut data = Vec::with_capacity(elements.count * elements.components);
+
+        for i in 0..elements.count {
+            for c in 0..elements.components {
+                let b = elements.component(i, c);
+                let value = match elements.component_type {
+                    5120 => {
+                        let v = b[0] as i8 as f32;
+                        if elements.normalized {
+                            (v / 127.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    5121 => {
+                        let v = b[0] as f32;
+                        if elements.normalized {
+                            v / 255.0
+                        } else {
+                            v
+                        }
+                    }
+                    5122 => {
+                        let v = i16::from_le_bytes([b[0], b[1]]) as f32;
+                        if elements.normalized {
+                            (v / 32767.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    5123 => {
+                        let v = u16::from_le_bytes([b[0], b[1]]) as f32;
+                        if elements.normalized {
+                            v / 65535.0
+                        } else {
+                            v
+                        }
+                    }
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                };
+                data.push(value);
+            }
+        }
+
+        Ok(Accessor {
+            components: elements.components,
+            data,
+        })
+    }
+
+    /// An index accessor, read as integers so that indices above 2^24 stay
+    /// exact.
+    fn read_indices(&self, idx: usize, buffers: &[Vec<u8>]) -> Result<Vec<u32>, String> {
+        let elements = self.elements(idx, buffers)?;
+
+        if elements.components != 1 || elements.normalized {
+            return Err(format!(
+                "Accessor {}: indices must be unnormalized scalars",
+                idx
+            ));
+        }
+
+        (0..elements.count)
+            .map(|i| {
+                let b = elements.component(i, 0);
+                match elements.component_type {
+                    5121 => Ok(b[0] as u32),
+                    5123 => Ok(u16::from_le_bytes([b[0], b[1]]) as u32),
+                    5125 => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    other => Err(format!(
+                        "Accessor {}: component type {} can't hold indices",
+                        idx, other
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    /// Where the elements of an accessor are, checked to lie within its
+    /// buffer view.
+    fn elements<'b>(&self, idx: usize, buffers: &'b [Vec<u8>]) -> Result<Elements<'b>, String> {
+        let accessor = self
+            .document
+            .accessors
+            .get(idx)
+            .ok_or_else(|| format!("Accessor {} does not exist", idx))?;
+
+        if accessor.sparse.is_some() {
+            return Err(format!(
+                "Accessor {}: sparse accessors are not supported",
+                idx
+            ));
+        }
+
+        let components = match accessor.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            other => return Err(format!("Accessor {}: unknown type {}", idx, other)),
+        };
+
+        let size = match accessor.component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            other => {
+                return Err(format!(
+                    "Accessor {}: unknown component type {}",
+                    idx, other
+                ))
+            }
+        };
+
+        let overrun = || format!("Accessor {} overruns its buffer view", idx);
+
+        let mut elements = Elements {
+            components,
+            size,
+            component_type: accessor.component_type,
+            normalized: accessor.normalized,
+            count: accessor.count,
+            bytes: &[],
+            stride: 0,
+        };
+
+        // no buffer view: all zeros, which costs memory without costing file
+        // size, so the count is capped
+        let view = match accessor.buffer_view {
+            None => {
+                match accessor.count.checked_mul(components) {
+                    Some(total) if total <= MAX_ZEROED_COMPONENTS => {}
+                    _ => return Err(format!("Accessor {}: too many elements", idx)),
+                }
+                return Ok(elements);
+            }
+            Some(view) => self
+                .document
+                .buffer_views
+                .get(view)
+                .ok_or_else(|| format!("Buffer view {} does not exist", view))?,
+        };
+
+        let buffer = buffers
+            .get(view.buffer)
+            .ok_or_else(|| format!("Buffer {} does not exist", view.buffer))?;
+
+        let element = components * size;
+        let stride = view.byte_stride.unwrap_or(element);
+        if stride < element {
+            return Err(format!(
+                "Accessor {}: stride {} is less than an element ({} bytes)",
+                idx, stride, element
+            ));
+        }
+
+        let start = view
+            .byte_offset
+            .checked_add(accessor.byte_offset)
+            .ok_or_else(overrun)?;
+        let view_end = view
+            .byte_offset
+            .checked_add(view.byte_length)
+            .ok_or_else(overrun)?;
+
+        if accessor.count > 0 {
+            let end = (accessor.count - 1)
+                .checked_mul(stride)
+                .and_then(|last| last.checked_add(start))
+                .and_then(|last| last.checked_add(element))
+                .ok_or_else(overrun)?;
+            if end > view_end || end > buffer.len() {
+                return Err(overrun());
+            }
+        }
+
+        elements.bytes = &buffer[start.min(buffer.len())..];
+        elements.stride = stride;
+        Ok(elements)
+    }
+
+    fn load_image(
+        &self,
+        idx: usize,
+        image: &ImageDef,
+        buffers: &[Vec<u8>],
+        warnings: &mut Vec<String>,
+    ) -> Result<GltfImage, String> {
+        let mut data = vec![];
+        let mut mime_type = image.mime_type.clone();
+
+        if let Some(view) = image.buffer_view {
+            let view = self
+                .document
+                .buffer_views
+                .get(view)
+                .ok_or_else(|| format!("Buffer view {} does not exist", view))?;
+            let buffer = buffers
+                .get(view.buffer)
+                .ok_or_else(|| format!("Buffer {} does not exist", view.buffer))?;
+
+            data = view
+                .byte_offset
+                .checked_add(view.byte_length)
+                .and_then(|end| buffer.get(view.byte_offset..end))
+                .ok_or_else(|| format!("Image {} overruns its buffer", idx))?
+                .to_vec();
+        } else if let Some(uri) = &image.uri {
+            match decode_data_uri(uri) {
+                Some(decoded) => {
+                    data = decoded?;
+                    mime_type = mime_type.or_else(|| {
+                        uri.strip_prefix("data:")
+                            .and_then(|rest| rest.split([';', ',']).next())
+                            .map(|mime| mime.to_string())
+                    });
+                }
+                None => warnings.push(format!("Skipping external image {}: {}", idx, uri)),
+            }
+        }
+
+        Ok(GltfImage {
+            name: image.name.clone(),
+            mime_type,
+            data,
+            uri: image.uri.clone(),
+        })
+    }
+}
+
+struct Accessor {
+    components: usize,
+    data: Vec<f32>,
+}
+
+/// The bytes of an accessor, from its first element on. Empty when the
+/// accessor has no buffer view, meaning zeros.
+struct Elements<'b> {
+    components: usize,
+    /// Bytes per component.
+    size: usize,
+    component_type: u32,
+    normalized: bool,
+    count: usize,
+    bytes: &'b [u8],
+    stride: usize,
+}
+
+impl Elements<'_> {
+    /// Component `c` of element `i`, which `Loader::elements` checked to be
+    /// in bounds.
+    fn component(&self, i: usize, c: usize) -> &[u8] {
+        if self.bytes.is_empty() {
+            return &[0; 4];
+        }
+        let at = i * self.stride + c * self.size;
+        &self.bytes[at..at + self.size]
+    }
+}
+
+/// `None` if `uri` is not a `data:` URI.
+fn decode_data_uri(uri: &str) -> Option<Result<Vec<u8>, String>> {
+    let rest = uri.strip_prefix("data:")?;
+
+    let (header, payload) = match rest.split_once(',') {
+        Some(parts) => parts,
+        None => return Some(Err(String::from("Malformed data URI"))),
+    };
+
+    if !header.ends_with(";base64") {
+        return Some(Err(String::from("Only base64 data URIs are supported")));
+    }
+
+    Some(
+        STANDARD
+            .decode(payload)
+            .map_err(|err| format!("Invalid base64 in data URI: {}", err)),
+    )
+}
+
+fn load_material(material: &MaterialDef) -> PbrMaterial {
+    let pbr = material.pbr_metallic_roughness.clone().unwrap_or_default();
+
+    let emissive_strength = material
+        .extensions
+        .get("KHR_materials_emissive_strength")
+        .and_then(|ext| ext.get("emissiveStrength"))
+        .and_then(|s| s.as_f64())
+        .unwrap_or(1.0) as f32;
+
+    PbrMaterial {
+        name: material.name.clone(),
+        base_color_factor: pbr.base_color_factor,
+        base_color_texture: pbr.base_color_texture.map(|t| t.index),
+        metallic_factor: pbr.metallic_factor,
+        roughness_factor: pbr.roughness_factor,
+        metallic_roughness_texture: pbr.metallic_roughness_texture.map(|t| t.index),
+        normal_texture: material.normal_texture.as_ref().map(|t| t.index),
+        normal_scale: material
+            .normal_texture
+            .as_ref()
+            .map(|t| t.scale)
+            .unwrap_or(1.0),
+        occlusion_texture: material.occlusion_texture.as_ref().map(|t| t.index),
+        occlusion_strength: material
+            .occlusion_texture
+            .as_ref()
+            .map(|t| t.strength)
+            .unwrap_or(1.0),
+        emissive_factor: material.emissive_factor.map(|c| c * emissive_strength),
+        emissive_texture: material.emissive_texture.as_ref().map(|t| t.index),
+        alpha_mode: material.alpha_mode,
+        alpha_cutoff: material.alpha_cutoff,
+        double_sided: material.double_sided,
+    }
+}
+
+fn load_node(node: &NodeDef) -> GltfNode {
+    let (translation, rotation, scale) = match &node.matrix {
+        Some(m) => decompose(&glm::make_mat4(m)),
+        None => (
+            node.translation.unwrap_or([0.0; 3]),
+            node.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]),
+            node.scale.unwrap_or([1.0; 3]),
+        ),
+    };
+
+    GltfNode {
+        name: node.name.clone(),
+        mesh: node.mesh,
+        children: node.children.clone(),
+        translation,
+        rotation,
+        scale,
+    }
+}
+
+/// TRS of an affine matrix without shear, as glTF requires for `matrix`.
+fn decompose(m: &glm::Mat4) -> ([f32; 3], [f32; 4], [f32; 3]) {
+    let translation = [m[(0, 3)], m[(1, 3)], m[(2, 3)]];
+
+    let mut basis = glm::mat4_to_mat3(m);
+    let mut scale = [0, 1, 2].map(|c| basis.column(c).norm());
+
+    // a mirrored basis shows up as a negative scale
+    if basis.determinant() < 0.0 {
+        scale[0] = -scale[0];
+    }
+
+    for (c, s) in scale.iter().enumerate() {
+        if *s != 0.0 {
+            let column = basis.column(c) / *s;
+            basis.set_column(c, &column);
+        }
+    }
+
+    let q = glm::mat3_to_quat(&basis);
+
+    (translation, [q.i, q.j, q.k, q.w], scale)
+}
+
+// The subset of the glTF JSON schema read above.
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    #[serde(default)]
+    extensions_used: Vec<String>,
+    #[serde(default)]
+    extensions_required: Vec<String>,
+    #[serde(default)]
+    accessors: Vec<AccessorDef>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    #[serde(default)]
+    meshes: Vec<MeshDef>,
+    #[serde(default)]
+    materials: Vec<MaterialDef>,
+    #[serde(default)]
+    textures: Vec<TextureDef>,
+    #[serde(default)]
+    samplers: Vec<SamplerDef>,
+    #[serde(default)]
+    images: Vec<ImageDef>,
+    #[serde(default)]
+    nodes: Vec<NodeDef>,
+    #[serde(default)]
+    scenes: Vec<SceneDef>,
+    scene: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessorDef {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Deserialize)]
+struct MeshDef {
+    name: Option<String>,
+    primitives: Vec<PrimitiveDef>,
+}
+
+#[derive(Deserialize)]
+struct PrimitiveDef {
+    attributes: std::collections::HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "default_mode")]
+    mode: u32,
+}
+
+fn default_mode() -> u32 {
+    MODE_TRIANGLES
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialDef {
+    name: Option<String>,
+    pbr_metallic_roughness: Option<PbrDef>,
+    normal_texture: Option<NormalTextureInfo>,
+    occlusion_texture: Option<OcclusionTextureInfo>,
+    emissive_texture: Option<TextureInfo>,
+    #[serde(default)]
+    emissive_factor: [f32; 3],
+    #[serde(default)]
+    alpha_mode: AlphaMode,
+    #[serde(default = "default_alpha_cutoff")]
+    alpha_cutoff: f32,
+    #[serde(default)]
+    double_sided: bool,
+    #[serde(default)]
+    extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+fn default_alpha_cutoff() -> f32 {
+    0.5
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct PbrDef {
+    base_color_factor: [f32; 4],
+    base_color_texture: Option<TextureInfo>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    metallic_roughness_texture: Option<TextureInfo>,
+}
+
+impl Default for PbrDef {
+    fn default() -> Self {
+        PbrDef {
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct TextureInfo {
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct NormalTextureInfo {
+    index: usize,
+    #[serde(default = "one")]
+    scale: f32,
+}
+
+#[derive(Deserialize)]
+struct OcclusionTextureInfo {
+    index: usize,
+    #[serde(default = "one")]
+    strength: f32,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+struct TextureDef {
+    sampler: Option<usize>,
+    source: Option<usize>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct SamplerDef {
+    mag_filter: Option<u32>,
+    min_filter: Option<u32>,
+    wrap_s: u32,
+    wrap_t: u32,
+}
+
+impl Default for SamplerDef {
+    fn default() -> Self {
+        SamplerDef {
+            mag_filter: None,
+            min_filter: None,
+            wrap_s: 10497, // REPEAT
+            wrap_t: 10497,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageDef {
+    name: Option<String>,
+    uri: Option<String>,
+    mime_type: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct NodeDef {
+    name: Option<String>,
+    mesh: Option<usize>,
+    #[serde(default)]
+    children: Vec<usize>,
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize)]
+struct SceneDef {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path, err))
+    }
+
+    #[test]
+    fn loads_triangle() {
+        let gltf = parse_glb(&fixture("triangle.glb")).unwrap();
+
+        assert_eq!(gltf.meshes.len(), 1);
+        // the LINES primitive is skipped
+        assert_eq!(gltf.meshes[0].primitives.len(), 1);
+
+        let geometry = &gltf.meshes[0].primitives[0].geometry;
+        assert_eq!(
+            geometry.positions,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(geometry.indices, vec![0, 1, 2]);
+        // generated, facing +Z
+        assert!(geometry.normals.iter().all(|n| n == &[0.0, 0.0, 1.0]));
+        assert_eq!(geometry.tangents.len(), 3);
+
+        let material = &gltf.materials[0];
+        assert_eq!(material.base_color_factor, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(material.metallic_factor, 0.2);
+        assert_eq!(material.roughness_factor, 0.7);
+        assert_eq!(gltf.meshes[0].primitives[0].material, Some(0));
+
+        assert_eq!(gltf.roots(), &[0]);
+        assert_eq!(gltf.nodes[0].children, vec![1]);
+        assert_eq!(gltf.nodes[0].translation, [0.0, 1.0, 0.0]);
+        assert_eq!(gltf.nodes[1].translation, [3.0, 0.0, 0.0]);
+        assert_eq!(gltf.nodes[1].scale, [2.0, 2.0, 2.0]);
+        assert_eq!(gltf.nodes[1].rotation, [0.0, 0.0, 0.0, 1.0]);
+
+        assert_eq!(gltf.warnings.len(), 2);
+        assert!(gltf.warnings[0].contains("KHR_materials_clearcoat"));
+        assert!(gltf.warnings[1].contains("mode 1"));
+    }
+
+    /// A GLB of `json` and, if not empty, a BIN chunk of `bin`.
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+
+        let mut chunks = vec![];
+        chunks.extend((json.len() as u32).to_le_bytes());
+        chunks.extend(CHUNK_JSON.to_le_bytes());
+        chunks.extend(json);
+        if !bin.is_empty() {
+            chunks.extend((bin.len() as u32).to_le_bytes());
+            chunks.extend(CHUNK_BIN.to_le_bytes());
+            chunks.extend(bin);
+        }
+
+        let mut bytes = vec![];
+        bytes.extend(GLB_MAGIC.to_le_bytes());
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend((12 + chunks.len() as u32).to_le_bytes());
+        bytes.extend(chunks);
+        bytes
+    }
+
+    fn loader(json: &str, bin: &'static [u8]) -> Loader<'static> {
+        Loader {
+            document: serde_json::from_str(json).unwrap(),
+            bin: Some(bin),
+        }
+    }
+
+    #[test]
+    fn chunk_lengths_are_checked() {
+        let mut bytes = glb("{}", &[]);
+        // JSON chunk claiming 4 GiB
+        bytes[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_glb(&bytes).is_err());
+    }
+
+    #[test]
+    fn accessors_stay_within_their_views() {
+        let accessor = |extra: &str| {
+            format!(
+                r#"{{
+                    "buffers": [{{ "byteLength": 16 }}],
+                    "bufferViews": [{{ "buffer": 0, "byteLength": 16 }}],
+                    "accessors": [{{ "bufferView": 0, "componentType": 5126, "type": "SCALAR", {} }}]
+                }}"#,
+                extra
+            )
+        };
+        let buffers = [vec![0; 16]];
+
+        let fits = loader(&accessor(r#""count": 4"#), &[0; 16]);
+        assert_eq!(fits.read_accessor(0, &buffers).unwrap().data.len(), 4);
+
+        for extra in [
+            r#""count": 5"#,
+            r#""count": 1, "byteOffset": 16"#,
+            r#""count": 2, "byteOffset": 18446744073709551615"#,
+            r#""count": 18446744073709551615"#,
+        ] {
+            let json = accessor(extra);
+            let loader = loader(&json, &[0; 16]);
+            assert!(loader.read_accessor(0, &buffers).is_err(), "{}", extra);
+        }
+    }
+
+    #[test]
+    fn strides_and_zeroed_accessors_are_bounded() {
+        let json = r#"{
+            "bufferViews": [{ "buffer": 0, "byteLength": 64, "byteStride": 0 }],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "type": "VEC4", "count": 1000000 },
+                { "componentType": 5126, "type": "MAT4", "count": 1000000000 },
+                { "componentType": 5126, "type": "VEC3", "count": 2 }
+            ]
+        }"#;
+        let loader = loader(json, &[0; 64]);
+        let buffers = [vec![0; 64]];
+
+        assert!(loader.read_accessor(0, &buffers).is_err());
+        assert!(loader.read_accessor(1, &buffers).is_err());
+        assert_eq!(loader.read_accessor(2, &buffers).unwrap().data, [0.0; 6]);
+    }
+
+    #[test]
+    fn indices_are_read_exactly() {
+        let json = r#"{
+            "bufferViews": [{ "buffer": 0, "byteLength": 8 }],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5125, "type": "SCALAR", "count": 2 },
+                { "bufferView": 0, "componentType": 5126, "type": "SCALAR", "count": 2 }
+            ]
+        }"#;
+        // 2^24 + 1 and u32::MAX, which an f32 would round
+        let mut bin = vec![];
+        bin.extend(16_777_217u32.to_le_bytes());
+        bin.extend(u32::MAX.to_le_bytes());
+        let buffers = [bin];
+
+        let loader = loader(json, &[]);
+        assert_eq!(
+            loader.read_indices(0, &buffers).unwrap(),
+            [16_777_217, u32::MAX]
+        );
+        assert!(loader.read_indices(1, &buffers).is_err());
+    }
+
+    #[test]
+    fn node_cycles_are_errors() {
+        let json = r#"{
+            "nodes": [{ "children": [1] }, { "children": [0] }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let gltf = parse_glb(&glb(json, &[])).unwrap();
+        assert!(gltf.world_nodes().is_err());
+
+        let json = r#"{
+            "nodes": [{ "children": [1], "translation": [1, 0, 0] }, { "translation": [0, 2, 0] }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let gltf = parse_glb(&glb(json, &[])).unwrap();
+        let nodes = gltf.world_nodes().unwrap();
+        assert_eq!(
+            nodes.iter().map(|(idx, _)| *idx).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(
+            nodes[1].1 * glm::vec4(0.0, 0.0, 0.0, 1.0),
+            glm::vec4(1.0, 2.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse_glb(b"not a glb at all").is_err());
+
+        let mut truncated = fixture("triangle.glb");
+        truncated.truncate(40);
+        assert!(parse_glb(&truncated).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+// Radiance RGBE (.hdr) images, as used for equirectangular environment
+// maps.
+//
+// Both flat and run-length encoded scanlines are read. Only the standard
+// `-Y height +X width` orientation (top row first) is supported, which is
+// what practically every .hdr file in the wild uses.
+//
+// https://paulbourke.net/dataformats/pic/
+
+// Larger than any texture WebGL takes, and a bound on what a small RLE file
+// can make us allocate.
+const MAX_DIMENSION: u32 = 16384;
+const MAX_PIXELS: usize = 1 << 25;
+
+#[derive(Clone, Debug)]
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    /// Linear RGB, top row first.
+    pub data: Vec<f32>,
+}
+
+impl HdrImage {
+    /// `None` outside of the image.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[f32; 3]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let i = (y as usize)
+            .checked_mul(self.width as usize)?
+            .checked_add(x as usize)?
+            .checked_mul(3)?;
+        self.data.get(i..i + 3).map(|p| [p[0], p[1], p[2]])
+    }
+}
+
+pub fn parse_hdr(bytes: &[u8]) -> Result<HdrImage, String> {
+    let mut pos = 0;
+    let mut line = || -> Result<&str, String> {
+        let end = bytes[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| String::from("Unexpected end of header"))?;
+        let text = std::str::from_utf8(&bytes[pos..pos + end])
+            .map_err(|_| String::from("Header is not text"))?;
+        pos += end + 1;
+        Ok(text.trim_end_matches('\r'))
+    };
+
+    let magic = line()?;
+    if magic != "#?RADIANCE" && magic != "#?RGBE" {
+        return Err(String::from("Not a Radiance HDR file"));
+    }
+
+    loop {
+        let header = line()?;
+        if header.is_empty() {
+            break;
+        }
+        if let Some(format) = header.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(format!("Unsupported format: {}", format));
+            }
+        }
+    }
+
+    let resolution = line()?.split_whitespace().collect::<Vec<_>>();
+    let (height, width) = match resolution[..] {
+        ["-Y", height, "+X", width] => (
+            height.parse::<u32>().map_err(|e| e.to_string())?,
+            width.parse::<u32>().map_err(|e| e.to_string())?,
+        ),
+        _ => return Err(format!("Unsupported orientation: {}", resolution.join(" "))),
+    };
+
+    let pixels = (width as usize).saturating_mul(height as usize);
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!("Unsupported size: {}x{}", width, height));
+    }
+    if pixels > MAX_PIXELS {
+        return Err(format!("Image too large: {}x{}", width, height));
+    }
+
+    // each scanline takes at least its RLE header and a two byte run per
+    // channel for every 127 pixels, or 4 bytes per pixel when flat
+    let min_scanline = (width as usize * 4).min(4 + 8 * (width as usize).div_ceil(127));
+    if (height as usize) * min_scanline > bytes.len() - pos {
+        return Err(format!(
+            "{} bytes are too few for {}x{}",
+            bytes.len() - pos,
+            width,
+            height
+        ));
+    }
+
+    let mut reader = Reader {
+        bytes,
+        pos,
+        width: width as usize,
+    };
+    let mut data = Vec::with_capacity(pixels * 3);
+    let mut scanline = vec![[0u8; 4]; width as usize];
+
+    for y in 0..height {
+        reader
+            .scanline(&mut scanline)
+            .map_err(|err| format!("Scanline {}: {}", y, err))?;
+        data.extend(scanline.iter().flat_map(|&rgbe| rgbe_to_f32(rgbe)));
+    }
+
+    Ok(HdrImage {
+        width,
+        height,
+        data,
+    })
+}
+
+fn rgbe_to_f32([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+
+    // 2^(e - 128) / 256
+    let scale = f32::powi(2.0, e as i32 - 136);
+    [r as f32 * scale, g as f32 * scale, b as f32 * scale]
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    width: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| String::from("Unexpected end of data"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn scanline(&mut self, out: &mut [[u8; 4]]) -> Result<(), String> {
+        // RLE scanlines start with 2, 2 and the width; widths outside
+        // [8, 0x7fff] can't be run-length encoded
+        let rle = (8..0x8000).contains(&self.width)
+            && self.bytes.get(self.pos..self.pos + 2) == Some(&[2, 2])
+            && self.bytes.get(self.pos + 2).is_some_and(|b| b & 0x80 == 0);
+
+        if !rle {
+            for pixel in out.iter_mut() {
+                *pixel = [self.byte()?, self.byte()?, self.byte()?, self.byte()?];
+            }
+            return Ok(());
+        }
+
+        self.pos += 2;
+        let width = (self.byte()? as usize) << 8 | self.byte()? as usize;
+        if width != self.width {
+            return Err(format!("Width is {} instead of {}", width, self.width));
+        }
+
+        // each channel is encoded separately
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < self.width {
+                let count = self.byte()? as usize;
+
+                let (run, count) = if count > 128 {
+                    (Some(self.byte()?), count - 128)
+                } else {
+                    (None, count)
+                };
+
+                if count == 0 || x + count > self.width {
+                    return Err(String::from("Bad run length"));
+                }
+
+                for pixel in &mut out[x..x + count] {
+                    pixel[channel] = match run {
+                        Some(value) => value,
+                        None => self.byte()?,
+                    };
+                }
+                x += count;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: u32, height: u32) -> Vec<u8> {
+        format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            height, width
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn flat_scanlines() {
+        let mut bytes = header(2, 1);
+        bytes.extend([128, 64, 0, 129, 0, 0, 0, 0]);
+
+        let image = parse_hdr(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixel(0, 0), Some([1.0, 0.5, 0.0]));
+        assert_eq!(image.pixel(1, 0), Some([0.0, 0.0, 0.0]));
+        assert_eq!(image.pixel(2, 0), None);
+        assert_eq!(image.pixel(0, 1), None);
+    }
+
+    #[test]
+    fn rle_scanlines() {
+        let mut bytes = header(8, 1);
+        bytes.extend([2, 2, 0, 8]);
+        // R: a run of 8
+        bytes.extend([128 + 8, 128]);
+        // G: 8 literals
+        bytes.extend([8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        // B: two runs
+        bytes.extend([128 + 4, 0, 128 + 4, 128]);
+        // E
+        bytes.extend([128 + 8, 128]);
+
+        let image = parse_hdr(&bytes).unwrap();
+        assert_eq!(image.pixel(0, 0), Some([0.5, 0.0, 0.0]));
+        assert_eq!(image.pixel(7, 0), Some([0.5, 112.0 / 256.0, 0.5]));
+    }
+
+    #[test]
+    fn rejects_truncated_and_foreign_files() {
+        let mut bytes = header(2, 2);
+        bytes.extend([128, 64, 0, 129]);
+        assert!(parse_hdr(&bytes).is_err());
+
+        // long enough, but with an empty run
+        let mut bytes = header(8, 1);
+        bytes.extend([2, 2, 0, 8, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(parse_hdr(&bytes).unwrap_err().starts_with("Scanline 0"));
+
+        assert!(parse_hdr(b"\x89PNG\r\n").is_err());
+    }
+
+    #[test]
+    fn sizes_are_checked_before_allocating() {
+        // a header promising more pixels than the bytes could hold
+        let mut bytes = header(16384, 2048);
+        bytes.extend([2, 2, 64, 0]);
+        assert!(parse_hdr(&bytes).unwrap_err().contains("too few"));
+
+        for (width, height) in [(0, 1), (16385, 1), (16384, 16384)] {
+            let mut bytes = header(width, height);
+            bytes.resize(bytes.len() + (1 << 20), 0);
+            assert!(parse_hdr(&bytes).is_err(), "{}x{}", width, height);
+        }
+    }
+}
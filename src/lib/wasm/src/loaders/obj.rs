@@ -0,0 +1,575 @@
+// Wavefront OBJ and MTL, parsed from text handed over by the caller.
+//
+// Supported OBJ statements: v, vt, vn, f (any polygon, triangulated by ear
+// clipping, with negative indices), o, g, usemtl and mtllib. Others (s, l,
+// p, curves, ...) are ignored. A new mesh starts whenever the object, group
+// or material changes, and identical v/vt/vn triples are shared within it.
+//
+// Vertices without a normal get an area-weighted one; every mesh gets tangents
+// (see geometry.rs).
+
+extern crate nalgebra_glm as glm;
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::geometry::Geometry;
+
+pub struct ObjMesh {
+    pub object: Option<String>,
+    pub group: Option<String>,
+    /// Name of a material in one of the `material_libraries`.
+    pub material: Option<String>,
+    pub geometry: Geometry,
+}
+
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    /// File names from `mtllib`, for the caller to fetch and `parse_mtl`.
+    pub material_libraries: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub opacity: f32,
+    pub diffuse_texture: Option<String>,
+    pub normal_texture: Option<String>,
+}
+
+impl Material {
+    fn new(name: &str) -> Self {
+        Material {
+            name: name.to_string(),
+            ambient: [0.0; 3],
+            diffuse: [0.8; 3],
+            specular: [0.0; 3],
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+struct MeshBuilder {
+    object: Option<String>,
+    group: Option<String>,
+    material: Option<String>,
+    vertices: HashMap<VertexKey, u32>,
+    geometry: Geometry,
+    // vertices without `vn`
+    missing_normals: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn new(object: Option<String>, group: Option<String>, material: Option<String>) -> Self {
+        MeshBuilder {
+            object,
+            group,
+            material,
+            vertices: HashMap::new(),
+            geometry: Geometry::default(),
+            missing_normals: vec![],
+        }
+    }
+
+    fn vertex(&mut self, key: VertexKey, data: &ObjData) -> u32 {
+        if let Some(&index) = self.vertices.get(&key) {
+            return index;
+        }
+
+        let (v, vt, vn) = key;
+        let geometry = &mut self.geometry;
+
+        geometry.positions.push(data.positions[v]);
+        geometry
+            .uvs
+            .push(vt.map(|vt| data.texcoords[vt]).unwrap_or([0.0; 2]));
+        geometry
+            .normals
+            .push(vn.map(|vn| data.normals[vn]).unwrap_or([0.0; 3]));
+
+        let index = (geometry.positions.len() - 1) as u32;
+        if vn.is_none() {
+            self.missing_normals.push(index);
+        }
+        self.vertices.insert(key, index);
+        index
+    }
+
+    fn finish(mut self) -> Option<ObjMesh> {
+        if self.geometry.indices.is_empty() {
+            return None;
+        }
+
+        if !self.missing_normals.is_empty() {
+            // only fill in the missing ones, the file's normals win
+            let given = std::mem::take(&mut self.geometry.normals);
+            self.geometry.compute_normals();
+            let computed = std::mem::replace(&mut self.geometry.normals, given);
+
+            for &i in &self.missing_normals {
+                self.geometry.normals[i as usize] = computed[i as usize];
+            }
+        }
+        self.geometry.compute_tangents();
+
+        Some(ObjMesh {
+            object: self.object,
+            group: self.group,
+            material: self.material,
+            geometry: self.geometry,
+        })
+    }
+}
+
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+}
+
+pub fn parse_obj(source: &str) -> Result<ObjModel, String> {
+    let mut data = ObjData::default();
+    let mut meshes = vec![];
+    let mut material_libraries = vec![];
+    let mut current = MeshBuilder::new(None, None, None);
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_number = idx + 1;
+        let err = |msg: String| format!("line {}: {}", line_number, msg);
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+
+        let keyword = match tokens.next() {
+            None => continue,
+            Some(keyword) => keyword,
+        };
+        let args = tokens.collect::<Vec<_>>();
+
+        match keyword {
+            "v" => {
+                // optional w, or r g b vertex colors, are ignored
+                let v = parse_floats(&args, 3, 7).map_err(err)?;
+                data.positions.push([v[0], v[1], v[2]]);
+            }
+            "vt" => {
+                let v = parse_floats(&args, 1, 3).map_err(err)?;
+                data.texcoords
+                    .push([v[0], v.get(1).copied().unwrap_or(0.0)]);
+            }
+            "vn" => {
+                let v = parse_floats(&args, 3, 3).map_err(err)?;
+                data.normals.push([v[0], v[1], v[2]]);
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err(format!(
+                        "face needs at least 3 vertices, got {}",
+                        args.len()
+                    )));
+                }
+
+                let keys = args
+                    .iter()
+                    .map(|arg| parse_face_vertex(arg, &data))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(err)?;
+
+                let polygon = keys.iter().map(|k| data.positions[k.0]).collect::<Vec<_>>();
+
+                for triangle in triangulate(&polygon) {
+                    for k in triangle {
+                        let index = current.vertex(keys[k], &data);
+                        current.geometry.indices.push(index);
+                    }
+                }
+            }
+            "o" | "g" | "usemtl" => {
+                let name = if args.is_empty() {
+                    None
+                } else {
+                    Some(args.join(" "))
+                };
+
+                let (mut object, mut group, mut material) = (
+                    current.object.clone(),
+                    current.group.clone(),
+                    current.material.clone(),
+                );
+
+                match keyword {
+                    // a new object starts without a group
+                    "o" => (object, group) = (name, None),
+                    "g" => group = name,
+                    _ => material = name,
+                }
+
+                let next = MeshBuilder::new(object, group, material);
+                meshes.extend(std::mem::replace(&mut current, next).finish());
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(err(String::from("mtllib needs a file name")));
+                }
+                material_libraries.extend(args.iter().map(|s| s.to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    meshes.extend(current.finish());
+
+    Ok(ObjModel {
+        meshes,
+        material_libraries,
+    })
+}
+
+/// Materials of an MTL file by name.
+pub fn parse_mtl(source: &str) -> Result<BTreeMap<String, Material>, String> {
+    let mut materials = BTreeMap::new();
+    let mut current: Option<Material> = None;
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_number = idx + 1;
+        let err = |msg: String| format!("line {}: {}", line_number, msg);
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+
+        let keyword = match tokens.next() {
+            None => continue,
+            Some(keyword) => keyword,
+        };
+        let args = tokens.collect::<Vec<_>>();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(err(String::from("newmtl needs a name")));
+            }
+            if let Some(material) = current.replace(Material::new(&args.join(" "))) {
+                materials.insert(material.name.clone(), material);
+            }
+            continue;
+        }
+
+        let material = match current.as_mut() {
+            Some(material) => material,
+            None => return Err(err(format!("{} before any newmtl", keyword))),
+        };
+
+        // texture statements may carry options (`-s 1 1 1`), the file comes last
+        let texture = || {
+            args.last()
+                .map(|s| s.to_string())
+                .ok_or_else(|| err(format!("{} needs a file name", keyword)))
+        };
+
+        match keyword {
+            "Ka" => material.ambient = parse_color(&args).map_err(err)?,
+            "Kd" => material.diffuse = parse_color(&args).map_err(err)?,
+            "Ks" => material.specular = parse_color(&args).map_err(err)?,
+            "Ns" => material.shininess = parse_floats(&args, 1, 1).map_err(err)?[0],
+            "d" => material.opacity = parse_floats(&args, 1, 1).map_err(err)?[0],
+            "Tr" => material.opacity = 1.0 - parse_floats(&args, 1, 1).map_err(err)?[0],
+            "map_Kd" => material.diffuse_texture = Some(texture()?),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_texture = Some(texture()?),
+            _ => {}
+        }
+    }
+
+    if let Some(material) = current {
+        materials.insert(material.name.clone(), material);
+    }
+
+    Ok(materials)
+}
+
+fn parse_floats(args: &[&str], min: usize, max: usize) -> Result<Vec<f32>, String> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {
+            format!("{}", min)
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(format!("expected {} numbers, got {}", expected, args.len()));
+    }
+
+    args.iter()
+        .map(|arg| {
+            arg.parse::<f32>()
+                .map_err(|_| format!("invalid number: {}", arg))
+        })
+        .collect()
+}
+
+fn parse_color(args: &[&str]) -> Result<[f32; 3], String> {
+    if args.first() == Some(&"spectral") || args.first() == Some(&"xyz") {
+        return Err(format!("unsupported color: {}", args[0]));
+    }
+
+    // a single value means gray
+    let c = parse_floats(args, 1, 3)?;
+    match c[..] {
+        [r, g, b] => Ok([r, g, b]),
+        [v] => Ok([v; 3]),
+        _ => Err(format!("expected 1 or 3 numbers, got {}", c.len())),
+    }
+}
+
+/// `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolved to 0-based indices.
+fn parse_face_vertex(arg: &str, data: &ObjData) -> Result<VertexKey, String> {
+    let mut parts = arg.split('/');
+
+    let v = resolve_index(parts.next(), data.positions.len(), "vertex")?
+        .ok_or_else(|| format!("missing vertex index in {}", arg))?;
+    let vt = resolve_index(parts.next(), data.texcoords.len(), "texture coordinate")?;
+    let vn = resolve_index(parts.next(), data.normals.len(), "normal")?;
+
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex: {}", arg));
+    }
+
+    Ok((v, vt, vn))
+}
+
+fn resolve_index(part: Option<&str>, count: usize, what: &str) -> Result<Option<usize>, String> {
+    let part = match part {
+        None | Some("") => return Ok(None),
+        Some(part) => part,
+    };
+
+    let index = part
+        .parse::<i64>()
+        .map_err(|_| format!("invalid {} index: {}", what, part))?;
+
+    // 1-based, or relative to the end when negative
+    let resolved = match index {
+        0 => None,
+        i if i > 0 => Some(i - 1),
+        i => Some(count as i64 + i),
+    };
+
+    match resolved {
+        Some(i) if i >= 0 && (i as usize) < count => Ok(Some(i as usize)),
+        _ => Err(format!(
+            "{} index {} out of range ({} defined)",
+            what, index, count
+        )),
+    }
+}
+
+/// Splits a (possibly concave) planar polygon into triangles of indices into
+/// `polygon`, keeping its winding.
+fn triangulate(polygon: &[[f32; 3]]) -> Vec<[usize; 3]> {
+    let n = polygon.len();
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    let points = polygon
+        .iter()
+        .map(|p| glm::vec3(p[0], p[1], p[2]))
+        .collect::<Vec<_>>();
+
+    // Newell's method, robust for non-planar and concave polygons
+    let normal = (0..n).fold(glm::Vec3::zeros(), |acc, i| {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        acc + glm::vec3(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        )
+    });
+
+    // project onto the plane the polygon faces most, counter-clockwise
+    let axis = normal.iamax();
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let sign = if normal[axis] < 0.0 { -1.0 } else { 1.0 };
+    let flat = points
+        .iter()
+        .map(|p| glm::vec2(p[u], p[v] * sign))
+        .collect::<Vec<_>>();
+
+    let cross = |o: glm::Vec2, a: glm::Vec2, b: glm::Vec2| {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    };
+
+    let mut remaining = (0..n).collect::<Vec<_>>();
+    let mut triangles = vec![];
+
+    while remaining.len() > 3 {
+        let len = remaining.len();
+
+        let ear = (0..len).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + len - 1) % len],
+                remaining[i],
+                remaining[(i + 1) % len],
+            );
+
+            if cross(flat[a], flat[b], flat[c]) <= 0.0 {
+                return false;
+            }
+
+            remaining.iter().all(|&p| {
+                p == a
+                    || p == b
+                    || p == c
+                    || cross(flat[a], flat[b], flat[p]) < 0.0
+                    || cross(flat[b], flat[c], flat[p]) < 0.0
+                    || cross(flat[c], flat[a], flat[p]) < 0.0
+            })
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + len - 1) % len],
+                    remaining[i],
+                    remaining[(i + 1) % len],
+                ]);
+                remaining.remove(i);
+            }
+            // degenerate or self-intersecting: fan out the rest
+            None => break,
+        }
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 1
+vn 0 0 1
+";
+
+    #[test]
+    fn faces_share_identical_vertices() {
+        let source = format!("{}f 1/1/1 2/1/1 3/2/1\nf 1/1/1 3/2/1 4/2/1\n", QUAD);
+        let model = parse_obj(&source).unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        let geometry = &model.meshes[0].geometry;
+        assert_eq!(geometry.indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(geometry.vertex_count(), 4);
+        assert_eq!(geometry.uvs[2], [1.0, 1.0]);
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_end() {
+        let source = format!("{}f -4//-1 -3//-1 -2//-1\n", QUAD);
+        let geometry = &parse_obj(&source).unwrap().meshes[0].geometry;
+
+        assert_eq!(
+            geometry.positions,
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]
+        );
+
+        for face in ["f -5 1 2", "f 1 2 5", "f 1/3 2 3", "f 0 1 2", "f 1 2"] {
+            let source = format!("{}{}\n", QUAD, face);
+            assert!(parse_obj(&source).is_err(), "{}", face);
+        }
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        // convex quad: two triangles with the winding of the face
+        let source = format!("{}f 1 2 3 4\n", QUAD);
+        let geometry = &parse_obj(&source).unwrap().meshes[0].geometry;
+        assert_eq!(geometry.indices.len(), 6);
+
+        // concave L shape: no triangle may cover the notch at (1.5, 1.5)
+        let l_shape = [
+            [0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 2.0, 0.0],
+            [0.0, 2.0, 0.0],
+        ];
+        let triangles = triangulate(&l_shape);
+        assert_eq!(triangles.len(), 4);
+
+        for [a, b, c] in triangles {
+            let (a, b, c) = (l_shape[a], l_shape[b], l_shape[c]);
+            let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+            assert!(area > 0.0, "winding flipped");
+
+            let centroid = [(a[0] + b[0] + c[0]) / 3.0, (a[1] + b[1] + c[1]) / 3.0];
+            assert!(
+                !(centroid[0] > 1.0 && centroid[1] > 1.0),
+                "covers the notch"
+            );
+        }
+    }
+
+    #[test]
+    fn only_missing_normals_are_computed() {
+        // the file's normal points away from the face normal on purpose
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vn 1 0 0
+f 1//1 2 3
+";
+        let geometry = &parse_obj(source).unwrap().meshes[0].geometry;
+
+        assert_eq!(geometry.normals[0], [1.0, 0.0, 0.0]);
+        assert_eq!(geometry.normals[1], [0.0, 0.0, 1.0]);
+        assert_eq!(geometry.normals[2], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn groups_and_materials_split_meshes() {
+        let source = format!(
+            "mtllib a.mtl\n{}o first\nf 1 2 3\nusemtl red\nf 1 3 4\ng side\nf 1 2 4\n",
+            QUAD
+        );
+        let model = parse_obj(&source).unwrap();
+
+        assert_eq!(model.material_libraries, ["a.mtl"]);
+        let names = model
+            .meshes
+            .iter()
+            .map(|m| {
+                (
+                    m.object.as_deref(),
+                    m.group.as_deref(),
+                    m.material.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                (Some("first"), None, None),
+                (Some("first"), None, Some("red")),
+                (Some("first"), Some("side"), Some("red")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,693 @@
+// Meshes: vertex buffers described by a declarative `VertexLayout`, plus an
+// optional index buffer.
+//
+//   let layout = VertexLayout::new()
+//       .attribute("aVertexPosition", 3, AttributeType::Float, false)
+//       .attribute("aVertexColor", 4, AttributeType::Float, false)
+//       .integer_attribute("aMaterialId", 1, AttributeType::UnsignedShort);
+//   let mesh = Mesh::new(vec![VertexBuffer::from_f32(&mut state, &vertices, layout)?])?
+//       .with_indices(&mut state, Indices::U16(indices))?;
+//   mesh.draw(&mut state, &program)?;
+//
+// Buffers and VAOs are bound through the `GlState`, and left bound, so that
+// drawing the mesh again skips the bind.
+//
+// Attributes sharing a buffer are interleaved; pass one `VertexBuffer` per
+// attribute for separate buffers. A layout with a divisor steps once per
+// instance rather than once per vertex, which is how `Instances` feeds
+// per-instance matrices and colors. Attributes are matched to the program by
+// name, and the resulting vertex array objects are cached per set of
+// attribute locations, so programs that agree on locations share a VAO.
+
+use std::collections::HashMap;
+
+use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlVertexArrayObject};
+
+use crate::state::GlState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeType {
+    Byte,
+    UnsignedByte,
+    Short,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+    Float,
+}
+
+impl AttributeType {
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            AttributeType::Byte => WebGl2RenderingContext::BYTE,
+            AttributeType::UnsignedByte => WebGl2RenderingContext::UNSIGNED_BYTE,
+            AttributeType::Short => WebGl2RenderingContext::SHORT,
+            AttributeType::UnsignedShort => WebGl2RenderingContext::UNSIGNED_SHORT,
+            AttributeType::Int => WebGl2RenderingContext::INT,
+            AttributeType::UnsignedInt => WebGl2RenderingContext::UNSIGNED_INT,
+            AttributeType::Float => WebGl2RenderingContext::FLOAT,
+        }
+    }
+
+    /// Size of one component in bytes.
+    pub fn size(&self) -> i32 {
+        match self {
+            AttributeType::Byte | AttributeType::UnsignedByte => 1,
+            AttributeType::Short | AttributeType::UnsignedShort => 2,
+            AttributeType::Int | AttributeType::UnsignedInt | AttributeType::Float => 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexAttribute {
+    /// Name of the `in` variable in the vertex shader.
+    pub name: String,
+    pub components: i32,
+    pub kind: AttributeType,
+    pub normalized: bool,
+    /// Read as integers by an `int`/`uint` (or `ivecN`/`uvecN`) input,
+    /// rather than converted to float.
+    pub integer: bool,
+    /// Byte offset from the start of a vertex.
+    pub offset: i32,
+    /// Consecutive locations taken by the attribute, e.g. 4 for a `mat4`,
+    /// each one a column of `components` values.
+    pub columns: i32,
+}
+
+impl VertexAttribute {
+    /// Bytes taken by one column.
+    pub fn column_size(&self) -> i32 {
+        self.components * self.kind.size()
+    }
+
+    /// Bytes taken by the whole attribute.
+    pub fn size(&self) -> i32 {
+        self.column_size() * self.columns
+    }
+}
+
+/// How the vertices of one buffer are laid out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+    stride: Option<i32>,
+    divisor: u32,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        VertexLayout::default()
+    }
+
+    /// Appends an attribute right after the previous one.
+    pub fn attribute(
+        self,
+        name: &str,
+        components: i32,
+        kind: AttributeType,
+        normalized: bool,
+    ) -> Self {
+        let offset = self
+            .attributes
+            .last()
+            .map(|a| a.offset + a.size())
+            .unwrap_or(0);
+
+        self.attribute_at(name, components, kind, normalized, offset)
+    }
+
+    /// Appends an attribute for an integer input, e.g. `in uvec4 aJoints;`.
+    /// `kind` must be an integer type.
+    ///
+    /// https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/vertexAttribIPointer
+    pub fn integer_attribute(mut self, name: &str, components: i32, kind: AttributeType) -> Self {
+        self = self.attribute(name, components, kind, false);
+        if let Some(attribute) = self.attributes.last_mut() {
+            attribute.integer = true;
+        }
+        self
+    }
+
+    /// Appends a float matrix attribute of `columns` columns with `rows`
+    /// rows each, e.g. `matrix("aModel", 4, 4)` for a `mat4`.
+    ///
+    /// https://registry.khronos.org/OpenGL-Refpages/es3.0/html/glVertexAttribPointer.xhtml
+    pub fn matrix(mut self, name: &str, columns: i32, rows: i32) -> Self {
+        self = self.attribute(name, rows, AttributeType::Float, false);
+        if let Some(attribute) = self.attributes.last_mut() {
+            attribute.columns = columns;
+        }
+        self
+    }
+
+    pub fn attribute_at(
+        mut self,
+        name: &str,
+        components: i32,
+        kind: AttributeType,
+        normalized: bool,
+        offset: i32,
+    ) -> Self {
+        self.attributes.push(VertexAttribute {
+            name: name.to_string(),
+            components,
+            kind,
+            normalized,
+            integer: false,
+            offset,
+            columns: 1,
+        });
+        self
+    }
+
+    /// Overrides the stride, e.g. to skip padding or unused attributes.
+    pub fn with_stride(mut self, stride: i32) -> Self {
+        self.stride = Some(stride);
+        self
+    }
+
+    /// Advances the attributes once every `divisor` instances instead of
+    /// once per vertex.
+    ///
+    /// https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/vertexAttribDivisor
+    pub fn with_divisor(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+
+    pub fn divisor(&self) -> u32 {
+        self.divisor
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    /// Checks what WebGL would reject when the attributes are set up.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.attributes.is_empty() || self.stride() <= 0 {
+            return Err(String::from("Vertex layout has no attributes"));
+        }
+
+        for attribute in &self.attributes {
+            if !(1..=4).contains(&attribute.components) {
+                return Err(format!(
+                    "{} has {} components, not 1 to 4",
+                    attribute.name, attribute.components
+                ));
+            }
+            if attribute.integer && attribute.kind == AttributeType::Float {
+                return Err(format!("{} is integer but of float type", attribute.name));
+            }
+            if attribute.integer && attribute.normalized {
+                return Err(format!("{} is integer and normalized", attribute.name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bytes per vertex; by default the end of the last attribute.
+    pub fn stride(&self) -> i32 {
+        self.stride.unwrap_or_else(|| {
+            self.attributes
+                .iter()
+                .map(|a| a.offset + a.size())
+                .max()
+                .unwrap_or(0)
+        })
+    }
+}
+
+/// A handle to a vertex buffer; clones share the same GL buffer.
+#[derive(Clone)]
+pub struct VertexBuffer {
+    buffer: WebGlBuffer,
+    layout: VertexLayout,
+    vertex_count: i32,
+}
+
+impl VertexBuffer {
+    pub fn from_f32(
+        state: &mut GlState,
+        data: &[f32],
+        layout: VertexLayout,
+    ) -> Result<Self, String> {
+        // https://rustwasm.github.io/wasm-bindgen/api/js_sys/struct.Float32Array.html#method.view
+        // `view` is only valid until the next allocation, so upload right away
+        let view = unsafe { js_sys::Float32Array::view(data) };
+
+        Self::upload(
+            state,
+            &view,
+            data.len() * 4,
+            layout,
+            WebGl2RenderingContext::STATIC_DRAW,
+        )
+    }
+
+    /// Like `from_f32`, but hinted for frequent `update_f32` calls.
+    pub fn dynamic_f32(
+        state: &mut GlState,
+        data: &[f32],
+        layout: VertexLayout,
+    ) -> Result<Self, String> {
+        let view = unsafe { js_sys::Float32Array::view(data) };
+
+        Self::upload(
+            state,
+            &view,
+            data.len() * 4,
+            layout,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        )
+    }
+
+    /// For layouts mixing component types, e.g. float positions and
+    /// normalized `UnsignedByte` colors.
+    pub fn from_bytes(
+        state: &mut GlState,
+        data: &[u8],
+        layout: VertexLayout,
+    ) -> Result<Self, String> {
+        let view = unsafe { js_sys::Uint8Array::view(data) };
+
+        Self::upload(
+            state,
+            &view,
+            data.len(),
+            layout,
+            WebGl2RenderingContext::STATIC_DRAW,
+        )
+    }
+
+    fn upload(
+        state: &mut GlState,
+        view: &js_sys::Object,
+        byte_length: usize,
+        layout: VertexLayout,
+        usage: u32,
+    ) -> Result<Self, String> {
+        layout.validate()?;
+        let stride = layout.stride();
+
+        let buffer = state
+            .context()
+            .create_buffer()
+            .ok_or_else(|| String::from("Unable to create vertex buffer"))?;
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/bufferData
+        state.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+        state.context().buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            view,
+            usage,
+        );
+
+        Ok(VertexBuffer {
+            buffer,
+            vertex_count: byte_length as i32 / stride,
+            layout,
+        })
+    }
+
+    /// Overwrites part of the buffer, starting at `first_vertex`, without
+    /// reallocating it. `data` must fit in the buffer.
+    pub fn update_f32(
+        &self,
+        state: &mut GlState,
+        first_vertex: i32,
+        data: &[f32],
+    ) -> Result<(), String> {
+        let offset = first_vertex * self.layout.stride();
+        let end = offset as usize + data.len() * 4;
+        if first_vertex < 0 || end > (self.vertex_count * self.layout.stride()) as usize {
+            return Err(format!(
+                "Update of {} bytes at {} overflows the vertex buffer",
+                data.len() * 4,
+                offset
+            ));
+        }
+
+        let view = unsafe { js_sys::Float32Array::view(data) };
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/bufferSubData
+        state.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&self.buffer));
+        state
+            .context()
+            .buffer_sub_data_with_i32_and_array_buffer_view(
+                WebGl2RenderingContext::ARRAY_BUFFER,
+                offset,
+                &view,
+            );
+
+        Ok(())
+    }
+
+    pub fn buffer(&self) -> &WebGlBuffer {
+        &self.buffer
+    }
+
+    pub fn vertex_count(&self) -> i32 {
+        self.vertex_count
+    }
+
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+}
+
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The narrowest index type holding every index.
+    pub fn compact(indices: Vec<u32>) -> Self {
+        if indices.iter().all(|&i| i <= u16::MAX as u32) {
+            Indices::U16(indices.into_iter().map(|i| i as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+}
+
+struct IndexBuffer {
+    buffer: WebGlBuffer,
+    kind: u32,
+    count: i32,
+}
+
+pub struct Mesh {
+    vertex_buffers: Vec<VertexBuffer>,
+    index_buffer: Option<IndexBuffer>,
+    mode: u32,
+    // attribute locations by program, and VAOs by attribute locations
+    locations: Vec<(WebGlProgram, Vec<i32>)>,
+    vaos: HashMap<Vec<i32>, WebGlVertexArrayObject>,
+}
+
+impl Mesh {
+    pub fn new(vertex_buffers: Vec<VertexBuffer>) -> Result<Self, String> {
+        if vertex_buffers.is_empty() {
+            return Err(String::from("Mesh needs at least one vertex buffer"));
+        }
+
+        Ok(Mesh {
+            vertex_buffers,
+            index_buffer: None,
+            mode: WebGl2RenderingContext::TRIANGLES,
+            locations: vec![],
+            vaos: HashMap::new(),
+        })
+    }
+
+    pub fn with_indices(mut self, state: &mut GlState, indices: Indices) -> Result<Self, String> {
+        let context = &state.context().clone();
+        let buffer = context
+            .create_buffer()
+            .ok_or_else(|| String::from("Unable to create index buffer"))?;
+
+        // bound outside of any VAO; `vao_for` records it in each VAO
+        state.bind_vertex_array(None);
+        state.bind_buffer(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, Some(&buffer));
+
+        let kind = unsafe {
+            match &indices {
+                Indices::U16(indices) => {
+                    context.buffer_data_with_array_buffer_view(
+                        WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+                        &js_sys::Uint16Array::view(indices),
+                        WebGl2RenderingContext::STATIC_DRAW,
+                    );
+                    WebGl2RenderingContext::UNSIGNED_SHORT
+                }
+                Indices::U32(indices) => {
+                    context.buffer_data_with_array_buffer_view(
+                        WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+                        &js_sys::Uint32Array::view(indices),
+                        WebGl2RenderingContext::STATIC_DRAW,
+                    );
+                    WebGl2RenderingContext::UNSIGNED_INT
+                }
+            }
+        };
+
+        self.index_buffer = Some(IndexBuffer {
+            buffer,
+            kind,
+            count: indices.len() as i32,
+        });
+        self.vaos.clear();
+
+        Ok(self)
+    }
+
+    /// Adds another buffer, e.g. per-instance attributes.
+    pub fn add_vertex_buffer(&mut self, vertex_buffer: VertexBuffer) {
+        self.vertex_buffers.push(vertex_buffer);
+        self.locations.clear();
+        self.vaos.clear();
+    }
+
+    /// `TRIANGLES` by default.
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Indices if indexed, vertices otherwise.
+    pub fn element_count(&self) -> i32 {
+        match &self.index_buffer {
+            Some(index_buffer) => index_buffer.count,
+            None => self
+                .vertex_buffers
+                .iter()
+                .filter(|b| b.layout.divisor == 0)
+                .map(|b| b.vertex_count)
+                .min()
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn draw(&mut self, state: &mut GlState, program: &WebGlProgram) -> Result<(), String> {
+        self.draw_instanced(state, program, 1)
+    }
+
+    /// Leaves the VAO bound, so that drawing the mesh again skips the bind.
+    pub fn draw_instanced(
+        &mut self,
+        state: &mut GlState,
+        program: &WebGlProgram,
+        instance_count: i32,
+    ) -> Result<(), String> {
+        let vao = self.vao_for(state, program)?;
+        state.bind_vertex_array(Some(&vao));
+        self.draw_bound(state.context(), instance_count);
+
+        Ok(())
+    }
+
+    fn draw_bound(&self, context: &WebGl2RenderingContext, instance_count: i32) {
+        let count = self.element_count();
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/drawElementsInstanced
+        match &self.index_buffer {
+            Some(index_buffer) => context.draw_elements_instanced_with_i32(
+                self.mode,
+                count,
+                index_buffer.kind,
+                0,
+                instance_count,
+            ),
+            None => context.draw_arrays_instanced(self.mode, 0, count, instance_count),
+        }
+    }
+
+    fn vao_for(
+        &mut self,
+        state: &mut GlState,
+        program: &WebGlProgram,
+    ) -> Result<WebGlVertexArrayObject, String> {
+        let context = &state.context().clone();
+        let locations = self.locations_of(context, program);
+
+        if let Some(vao) = self.vaos.get(&locations) {
+            return Ok(vao.clone());
+        }
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/createVertexArray
+        let vao = context
+            .create_vertex_array()
+            .ok_or_else(|| String::from("Unable to create vertex array object"))?;
+
+        state.bind_vertex_array(Some(&vao));
+
+        let attributes = self
+            .vertex_buffers
+            .iter()
+            .flat_map(|b| b.layout.attributes.iter().map(move |a| (b, a)));
+
+        for ((vertex_buffer, attribute), &location) in attributes.zip(&locations) {
+            // not used by this program
+            if location < 0 {
+                continue;
+            }
+            state.bind_buffer(
+                WebGl2RenderingContext::ARRAY_BUFFER,
+                Some(&vertex_buffer.buffer),
+            );
+
+            // a matrix takes one location per column
+            for column in 0..attribute.columns {
+                let location = (location + column) as u32;
+                context.enable_vertex_attrib_array(location);
+
+                let stride = vertex_buffer.layout.stride();
+                let offset = attribute.offset + column * attribute.column_size();
+
+                // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/vertexAttribPointer
+                if attribute.integer {
+                    context.vertex_attrib_i_pointer_with_i32(
+                        location,
+                        attribute.components,
+                        attribute.kind.gl_type(),
+                        stride,
+                        offset,
+                    );
+                } else {
+                    context.vertex_attrib_pointer_with_i32(
+                        location,
+                        attribute.components,
+                        attribute.kind.gl_type(),
+                        attribute.normalized,
+                        stride,
+                        offset,
+                    );
+                }
+                context.vertex_attrib_divisor(location, vertex_buffer.layout.divisor);
+            }
+        }
+
+        if let Some(index_buffer) = &self.index_buffer {
+            state.bind_buffer(
+                WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+                Some(&index_buffer.buffer),
+            );
+        }
+
+        self.vaos.insert(locations, vao.clone());
+
+        Ok(vao)
+    }
+
+    fn locations_of(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        program: &WebGlProgram,
+    ) -> Vec<i32> {
+        if let Some((_, locations)) = self.locations.iter().find(|(p, _)| p == program) {
+            return locations.clone();
+        }
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/getAttribLocation
+        let locations = self
+            .vertex_buffers
+            .iter()
+            .flat_map(|b| b.layout.attributes.iter())
+            .map(|a| context.get_attrib_location(program, &a.name))
+            .collect::<Vec<_>>();
+
+        self.locations.push((program.clone(), locations.clone()));
+
+        locations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(layout: &VertexLayout) -> Vec<i32> {
+        layout.attributes().iter().map(|a| a.offset).collect()
+    }
+
+    #[test]
+    fn attributes_follow_each_other() {
+        let layout = VertexLayout::new()
+            .attribute("aPosition", 3, AttributeType::Float, false)
+            .attribute("aColor", 4, AttributeType::UnsignedByte, true)
+            .attribute("aUv", 2, AttributeType::UnsignedShort, true);
+
+        assert_eq!(offsets(&layout), [0, 12, 16]);
+        assert_eq!(layout.stride(), 20);
+        assert_eq!(layout.with_stride(32).stride(), 32);
+    }
+
+    #[test]
+    fn matrices_take_a_column_per_location() {
+        let layout = VertexLayout::new()
+            .matrix("aModel", 4, 4)
+            .attribute("aColor", 4, AttributeType::Float, false)
+            .with_divisor(1);
+
+        let model = &layout.attributes()[0];
+        assert_eq!(
+            (model.columns, model.column_size(), model.size()),
+            (4, 16, 64)
+        );
+        assert_eq!(offsets(&layout), [0, 64]);
+        assert_eq!(layout.stride(), 80);
+        assert_eq!(layout.divisor(), 1);
+    }
+
+    #[test]
+    fn explicit_offsets_set_the_stride() {
+        let layout = VertexLayout::new()
+            .attribute_at("aNormal", 3, AttributeType::Float, false, 12)
+            .attribute_at("aPosition", 3, AttributeType::Float, false, 0);
+
+        assert_eq!(layout.stride(), 24);
+    }
+
+    #[test]
+    fn integer_attributes_are_validated() {
+        let layout = VertexLayout::new()
+            .attribute("aPosition", 3, AttributeType::Float, false)
+            .integer_attribute("aJoints", 4, AttributeType::UnsignedByte);
+
+        let joints = &layout.attributes()[1];
+        assert!(joints.integer && !joints.normalized);
+        assert_eq!(joints.offset, 12);
+        assert!(layout.validate().is_ok());
+
+        let float = VertexLayout::new().integer_attribute("aId", 1, AttributeType::Float);
+        assert!(float.validate().is_err());
+
+        let mut normalized = VertexLayout::new().integer_attribute("aId", 1, AttributeType::Int);
+        normalized.attributes[0].normalized = true;
+        assert!(normalized.validate().is_err());
+    }
+
+    #[test]
+    fn empty_or_oversized_layouts_are_errors() {
+        assert!(VertexLayout::new().validate().is_err());
+
+        let wide = VertexLayout::new().attribute("aWide", 5, AttributeType::Float, false);
+        assert!(wide.validate().is_err());
+    }
+}
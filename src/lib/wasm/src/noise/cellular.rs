@@ -0,0 +1,125 @@
+// Port of `cellular_noise.glsl`: distances to the four nearest feature
+// points, combined with a weight vector.
+
+extern crate nalgebra_glm as glm;
+
+use super::glsl::{floor2, floor3, modulo, sign, step};
+use super::hash::{hash22, hash33};
+
+/// Inserts `v` into the ascending `list`, dropping the largest entry.
+pub fn sort(list: glm::Vec4, v: f32) -> glm::Vec4 {
+    let res = [
+        step(v, list.x) != 0.0,
+        step(v, list.y) != 0.0,
+        step(v, list.z) != 0.0,
+        step(v, list.w) != 0.0,
+    ];
+
+    if res[0] {
+        glm::vec4(v, list.x, list.y, list.z)
+    } else if res[1] {
+        glm::vec4(list.x, v, list.y, list.z)
+    } else if res[2] {
+        glm::vec4(list.x, list.y, v, list.z)
+    } else if res[3] {
+        glm::vec4(list.x, list.y, list.z, v)
+    } else {
+        list
+    }
+}
+
+/// Offset of the `j`-th cell row to visit: 0, +1, -1, +2, -2.
+fn row_offset(j: f32) -> f32 {
+    sign(modulo(j, 2.0) - 0.5) * (j * 0.5).ceil()
+}
+
+pub fn fdist24(p: glm::Vec2) -> glm::Vec4 {
+    let n = floor2(p) + glm::vec2(0.5, 0.5);
+    let d = glm::vec2(1.5 - (p.x - n.x).abs(), 1.5 - (p.y - n.y).abs());
+    let mut dist4 = glm::Vec4::from_element(glm::length(&d));
+
+    for j in 0..=4 {
+        let mut grid = glm::Vec2::zeros();
+        grid.y = n.y + row_offset(j as f32);
+
+        if (grid.y - p.y).abs() - 0.5 > dist4.w {
+            continue;
+        }
+
+        for i in -2..=2 {
+            grid.x = n.x + i as f32;
+            let jitter = hash22(grid) - glm::vec2(0.5, 0.5);
+            dist4 = sort(dist4, glm::length(&(grid + jitter - p)));
+        }
+    }
+
+    dist4
+}
+
+pub fn fdist34(p: glm::Vec3) -> glm::Vec4 {
+    let n = floor3(p) + glm::vec3(0.5, 0.5, 0.5);
+    let d = glm::vec3(
+        1.5 - (p.x - n.x).abs(),
+        1.5 - (p.y - n.y).abs(),
+        1.5 - (p.z - n.z).abs(),
+    );
+    let mut dist4 = glm::Vec4::from_element(glm::length(&d));
+
+    for k in 0..=4 {
+        let mut grid = glm::Vec3::zeros();
+        grid.z = n.z + row_offset(k as f32);
+
+        if (grid.z - p.z).abs() - 0.5 > dist4.w {
+            continue;
+        }
+
+        for j in 0..=4 {
+            grid.y = n.y + row_offset(j as f32);
+
+            if (grid.y - p.y).abs() - 0.5 > dist4.w {
+                continue;
+            }
+
+            for i in -2..=2 {
+                grid.x = n.x + i as f32;
+                let jitter = hash33(grid) - glm::vec3(0.5, 0.5, 0.5);
+                dist4 = sort(dist4, glm::length(&(grid + jitter - p)));
+            }
+        }
+    }
+
+    dist4
+}
+
+pub fn cnoise21(p: glm::Vec2, wt: glm::Vec4) -> f32 {
+    glm::dot(&wt, &fdist24(p)).abs()
+}
+
+pub fn cnoise31(p: glm::Vec3, wt: glm::Vec4) -> f32 {
+    glm::dot(&wt, &fdist34(p)).abs()
+}
+
+/// `main()` of `cellular_noise.glsl`: three weightings side by side,
+/// 2D noise in the bottom row and 3D noise in the top row.
+pub fn fragment(frag_coord: glm::Vec2, resolution: glm::Vec2, time: f32) -> glm::Vec4 {
+    let pos = frag_coord / resolution.x.min(resolution.y);
+    let channel = [
+        (3.0 * frag_coord.x / resolution.x) as i32,
+        (2.0 * frag_coord.y / resolution.y) as i32,
+    ];
+    let pos = pos * 10.0 + glm::vec2(time, time);
+
+    let wt = match channel[0] {
+        0 => glm::Vec4::from_element(0.2),
+        1 => glm::vec4(0.5, -1.0, 1.4, -0.1),
+        _ => glm::vec4(-0.3, -0.5, -1.2, 1.0),
+    };
+
+    let v = if channel[1] == 0 {
+        cnoise21(pos, wt)
+    } else {
+        cnoise31(glm::vec3(pos.x, pos.y, time), wt)
+    };
+
+    glm::vec4(v, v, v, 1.0)
+}
@@ -0,0 +1,74 @@
+// Port of `frac_brownian_motion.glsl`: fractional Brownian motion over
+// value noise or Perlin noise.
+
+extern crate nalgebra_glm as glm;
+
+use super::glsl::{fade, floor2, fract2, mix, modulo};
+use super::hash::hash21;
+use super::perlin::pnoise21;
+
+pub const OCTAVES: usize = 4;
+pub const LACUNARITY: f32 = 2.01;
+
+/// The noise summed by `fbm21`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Base {
+    Value,
+    Perlin,
+}
+
+pub fn vnoise21(p: glm::Vec2) -> f32 {
+    let n = floor2(p);
+    let mut v = [0.0; 4];
+
+    for j in 0..2 {
+        for i in 0..2 {
+            v[i + 2 * j] = hash21(n + glm::vec2(i as f32, j as f32));
+        }
+    }
+
+    let f = fract2(p);
+    let f = glm::vec2(fade(f.x), fade(f.y));
+
+    mix(mix(v[0], v[1], f[0]), mix(v[2], v[3], f[0]), f[1])
+}
+
+pub fn base21(p: glm::Vec2, base: Base) -> f32 {
+    match base {
+        Base::Value => vnoise21(p) - 0.5,
+        Base::Perlin => pnoise21(p) - 0.5,
+    }
+}
+
+/// `g` is the gain: the amplitude ratio between successive octaves.
+pub fn fbm21(p: glm::Vec2, g: f32, base: Base) -> f32 {
+    let mut val = 0.0;
+    let mut amp = 1.0;
+    let mut freq = 1.0;
+
+    for _ in 0..OCTAVES {
+        val += amp * base21(freq * p, base);
+        amp *= g;
+        freq *= LACUNARITY;
+    }
+
+    0.5 * val + 0.5
+}
+
+/// `main()` of `frac_brownian_motion.glsl`: value noise on the left,
+/// Perlin noise on the right, with the gain oscillating over time.
+pub fn fragment(frag_coord: glm::Vec2, resolution: glm::Vec2, time: f32) -> glm::Vec4 {
+    let pos = frag_coord / resolution.x.min(resolution.y);
+    let channel = (2.0 * frag_coord.x / resolution.x) as i32;
+    let pos = 10.0 * pos + glm::vec2(time, time);
+    let g = (modulo(0.2 * time, 2.0) - 1.0).abs();
+
+    let base = if channel == 0 {
+        Base::Value
+    } else {
+        Base::Perlin
+    };
+    let v = fbm21(pos, g, base);
+
+    glm::vec4(v, v, v, 1.0)
+}
@@ -0,0 +1,58 @@
+// GLSL built-ins, written out with the same operation order as the
+// GLSL ES 3.00 spec so the ports round the same way the shaders do.
+
+extern crate nalgebra_glm as glm;
+
+pub fn fract(x: f32) -> f32 {
+    x - x.floor()
+}
+
+/// `mod(x, y)`, which differs from `%` for negative operands.
+pub fn modulo(x: f32, y: f32) -> f32 {
+    x - y * (x / y).floor()
+}
+
+pub fn mix(x: f32, y: f32, a: f32) -> f32 {
+    x * (1.0 - a) + y * a
+}
+
+/// `sign(x)`, which unlike `f32::signum` returns 0.0 for 0.0.
+pub fn sign(x: f32) -> f32 {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+pub fn step(edge: f32, x: f32) -> f32 {
+    if x < edge {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+/// The quintic interpolation curve `6t^5 - 15t^4 + 10t^3` used by every
+/// noise in the library.
+pub fn fade(f: f32) -> f32 {
+    f * f * f * (10.0 - 15.0 * f + 6.0 * f * f)
+}
+
+pub fn floor2(p: glm::Vec2) -> glm::Vec2 {
+    glm::vec2(p.x.floor(), p.y.floor())
+}
+
+pub fn floor3(p: glm::Vec3) -> glm::Vec3 {
+    glm::vec3(p.x.floor(), p.y.floor(), p.z.floor())
+}
+
+pub fn fract2(p: glm::Vec2) -> glm::Vec2 {
+    glm::vec2(fract(p.x), fract(p.y))
+}
+
+pub fn fract3(p: glm::Vec3) -> glm::Vec3 {
+    glm::vec3(fract(p.x), fract(p.y), fract(p.z))
+}
@@ -0,0 +1,115 @@
+// Port of the hash functions shared by the noise shaders
+// (`perlin_noise.glsl`, `cellular_noise.glsl`, `voronoi.glsl`, ...).
+//
+// GLSL `uint` arithmetic wraps, hence the explicit `wrapping_mul`.
+
+extern crate nalgebra_glm as glm;
+
+const K: [u32; 3] = [0x456789ab, 0x6789ab45, 0x89ab4567];
+const U: [u32; 3] = [1, 2, 3];
+const UINT_MAX: f32 = u32::MAX as f32;
+
+pub fn uhash11(n: u32) -> u32 {
+    let mut n = n;
+    n ^= n << U[0];
+    n ^= n >> U[0];
+    n = n.wrapping_mul(K[0]);
+    n ^= n << U[0];
+    n.wrapping_mul(K[0])
+}
+
+pub fn uhash22(n: [u32; 2]) -> [u32; 2] {
+    // every swizzle on the right-hand side reads `n` before the assignment
+    let [mut x, mut y] = n;
+    (x, y) = (x ^ (y << U[0]), y ^ (x << U[1]));
+    (x, y) = (x ^ (y >> U[0]), y ^ (x >> U[1]));
+    (x, y) = (x.wrapping_mul(K[0]), y.wrapping_mul(K[1]));
+    (x, y) = (x ^ (y << U[0]), y ^ (x << U[1]));
+    [x.wrapping_mul(K[0]), y.wrapping_mul(K[1])]
+}
+
+pub fn uhash33(n: [u32; 3]) -> [u32; 3] {
+    let [mut x, mut y, mut z] = n;
+    (x, y, z) = (x ^ (y << U[0]), y ^ (z << U[1]), z ^ (x << U[2]));
+    (x, y, z) = (x ^ (y >> U[0]), y ^ (z >> U[1]), z ^ (x >> U[2]));
+    (x, y, z) = (
+        x.wrapping_mul(K[0]),
+        y.wrapping_mul(K[1]),
+        z.wrapping_mul(K[2]),
+    );
+    (x, y, z) = (x ^ (y << U[0]), y ^ (z << U[1]), z ^ (x << U[2]));
+    [
+        x.wrapping_mul(K[0]),
+        y.wrapping_mul(K[1]),
+        z.wrapping_mul(K[2]),
+    ]
+}
+
+pub fn hash11(p: f32) -> f32 {
+    uhash11(p.to_bits()) as f32 / UINT_MAX
+}
+
+pub fn hash21(p: glm::Vec2) -> f32 {
+    uhash22(bits2(p))[0] as f32 / UINT_MAX
+}
+
+pub fn hash31(p: glm::Vec3) -> f32 {
+    uhash33(bits3(p))[0] as f32 / UINT_MAX
+}
+
+pub fn hash22(p: glm::Vec2) -> glm::Vec2 {
+    let [x, y] = uhash22(bits2(p));
+    glm::vec2(x as f32 / UINT_MAX, y as f32 / UINT_MAX)
+}
+
+pub fn hash33(p: glm::Vec3) -> glm::Vec3 {
+    let [x, y, z] = uhash33(bits3(p));
+    glm::vec3(
+        x as f32 / UINT_MAX,
+        y as f32 / UINT_MAX,
+        z as f32 / UINT_MAX,
+    )
+}
+
+/// `floatBitsToUint(vec2)`
+pub fn bits2(p: glm::Vec2) -> [u32; 2] {
+    [p.x.to_bits(), p.y.to_bits()]
+}
+
+/// `floatBitsToUint(vec3)`
+pub fn bits3(p: glm::Vec3) -> [u32; 3] {
+    [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // from a transcription of the GLSL in Python, with 32-bit wrapping
+    #[test]
+    fn hashes_match_the_shader() {
+        assert_eq!(uhash11(0), 0);
+        assert_eq!(uhash11(1), 0xfab7d7fe);
+        assert_eq!(uhash11(0xdeadbeef), 0xdf374db7);
+
+        assert_eq!(uhash22([1, 2]), [0xa4f733ec, 0x36481657]);
+        assert_eq!(
+            uhash22(bits2(glm::vec2(1.5, -2.0))),
+            [0xe3e00000, 0x76700000]
+        );
+
+        assert_eq!(uhash33([1, 2, 3]), [0x96401bba, 0xe6f70ea8, 0xdf3bf0eb]);
+    }
+
+    #[test]
+    fn float_hashes_are_unit_range() {
+        for i in 0..1000 {
+            let p = glm::vec2(i as f32 * 0.37 - 100.0, i as f32 * -1.3);
+            let h = hash22(p);
+
+            assert!((0.0..=1.0).contains(&hash21(p)));
+            assert!((0.0..=1.0).contains(&h.x) && (0.0..=1.0).contains(&h.y));
+            assert_eq!(h, hash22(p));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! CPU ports of the GLSL noise library in `src/lib/shaders`.
+//!
+//! Each submodule mirrors one shader: the same hash functions, fade curve
+//! and octave count, evaluated in `f32` with the GLSL operation order.
+//! The `fragment` function of each module reproduces the shader's `main()`,
+//! so a texture can be generated without a GPU or compared against a
+//! `readPixels` of the real shader.
+
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+extern crate nalgebra_glm as glm;
+
+pub mod cellular;
+pub mod fbm;
+pub mod glsl;
+pub mod hash;
+pub mod periodic;
+pub mod perlin;
+pub mod voronoi;
+
+/// `main()` of a shader: `gl_FragCoord.xy`, `frame.resolution`, `frame.time` -> `fragColor`.
+pub type Fragment = fn(glm::Vec2, glm::Vec2, f32) -> glm::Vec4;
+
+/// Looks up the port of a shader by its file name, without `.glsl`.
+pub fn fragment_by_name(name: &str) -> Option<Fragment> {
+    match name {
+        "perlin_noise" => Some(perlin::fragment),
+        "periodic_perlin_noise" => Some(periodic::fragment),
+        "cellular_noise" => Some(cellular::fragment),
+        "voronoi" => Some(voronoi::fragment),
+        "frac_brownian_motion" => Some(fbm::fragment),
+        _ => None,
+    }
+}
+
+/// Evaluates `fragment` at every pixel centre and returns RGBA8 pixels,
+/// bottom row first like `readPixels`.
+pub fn render(width: u32, height: u32, time: f32, fragment: Fragment) -> Vec<u8> {
+    let resolution = glm::vec2(width as f32, height as f32);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let frag_coord = glm::vec2(x as f32 + 0.5, y as f32 + 0.5);
+            let color = fragment(frag_coord, resolution, time);
+
+            pixels.extend(color.iter().map(|c| to_unorm8(*c)));
+        }
+    }
+
+    pixels
+}
+
+/// The largest per-channel difference between two RGBA8 images,
+/// or `None` if their sizes differ.
+pub fn max_difference(expected: &[u8], actual: &[u8]) -> Option<u8> {
+    if expected.len() != actual.len() {
+        return None;
+    }
+
+    Some(
+        expected
+            .iter()
+            .zip(actual)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0),
+    )
+}
+
+/// Whether a GPU readback matches the CPU reference within `tolerance`
+/// steps per channel.
+pub fn matches(expected: &[u8], actual: &[u8], tolerance: u8) -> bool {
+    max_difference(expected, actual).is_some_and(|diff| diff <= tolerance)
+}
+
+/// Float to normalized 8-bit, as the GL spec converts on write.
+fn to_unorm8(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Renders the named noise shader on the CPU into RGBA8 pixels
+/// (bottom row first).
+#[wasm_bindgen]
+pub fn noise_texture(name: &str, width: u32, height: u32, time: f32) -> Result<Vec<u8>, JsValue> {
+    let fragment = fragment_by_name(name)
+        .ok_or_else(|| JsValue::from(format!("Unknown noise shader: {}", name)))?;
+
+    Ok(render(width, height, time, fragment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glsl::{fade, fract, modulo, sign};
+    use super::*;
+
+    fn samples() -> impl Iterator<Item = glm::Vec2> {
+        (0..400).map(|i| glm::vec2((i % 20) as f32 * 0.173 - 1.7, (i / 20) as f32 * 0.291 - 2.9))
+    }
+
+    #[test]
+    fn builtins_follow_glsl() {
+        assert_eq!(modulo(-1.0, 3.0), 2.0);
+        assert_eq!(fract(-0.25), 0.75);
+        assert_eq!(sign(0.0), 0.0);
+        assert_eq!((fade(0.0), fade(0.5), fade(1.0)), (0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn gradient_noises_are_half_on_the_lattice() {
+        for p in [
+            glm::vec2(0.0, 0.0),
+            glm::vec2(3.0, -7.0),
+            glm::vec2(-12.0, 5.0),
+        ] {
+            let p3 = glm::vec3(p.x, p.y, 2.0);
+
+            assert_eq!(perlin::gnoise21(p), 0.5);
+            assert_eq!(perlin::pnoise21(p), 0.5);
+            assert_eq!(perlin::gnoise31(p3), 0.5);
+            assert_eq!(perlin::pnoise31(p3), 0.5);
+            assert_eq!(periodic::periodic_noise21(p, 4.0), 0.5);
+        }
+    }
+
+    #[test]
+    fn noises_stay_in_range() {
+        for p in samples() {
+            for v in [
+                perlin::gnoise21(p),
+                perlin::pnoise21(p),
+                fbm::vnoise21(p),
+                fbm::fbm21(p, 0.5, fbm::Base::Perlin),
+            ] {
+                assert!((0.0..=1.0).contains(&v), "{} at {:?}", v, p);
+            }
+        }
+    }
+
+    #[test]
+    fn periodic_noise_repeats() {
+        for p in samples() {
+            let shifted = p + glm::vec2(4.0, -8.0);
+            let (a, b) = (
+                periodic::periodic_noise21(p, 4.0),
+                periodic::periodic_noise21(shifted, 4.0),
+            );
+
+            // the fractional part of `p` rounds slightly differently once shifted
+            assert!((a - b).abs() < 1e-5, "{} != {} at {:?}", a, b, p);
+        }
+    }
+
+    #[test]
+    fn cellular_distances_are_sorted() {
+        for p in samples() {
+            let d = cellular::fdist24(p);
+            assert!(d.x <= d.y && d.y <= d.z && d.z <= d.w, "{:?} at {:?}", d, p);
+        }
+    }
+
+    #[test]
+    fn voronoi_picks_a_nearby_cell() {
+        for p in samples() {
+            let id = voronoi::voronoi2(p);
+            assert!((id - p).abs().max() <= 1.5, "{:?} for {:?}", id, p);
+        }
+    }
+
+    #[test]
+    fn rendering_is_deterministic() {
+        for name in [
+            "perlin_noise",
+            "periodic_perlin_noise",
+            "cellular_noise",
+            "voronoi",
+            "frac_brownian_motion",
+        ] {
+            let fragment = fragment_by_name(name).unwrap();
+            let a = render(16, 8, 1.5, fragment);
+
+            assert_eq!(a.len(), 16 * 8 * 4);
+            assert!(matches(&a, &render(16, 8, 1.5, fragment), 0));
+        }
+        assert!(fragment_by_name("nope").is_none());
+    }
+}
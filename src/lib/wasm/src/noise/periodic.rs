@@ -0,0 +1,93 @@
+// Port of `periodic_perlin_noise.glsl`: Perlin noise whose lattice wraps
+// around every `period` cells, hashed with chained `uhash11` calls.
+
+extern crate nalgebra_glm as glm;
+
+use super::glsl::{fade, floor2, floor3, fract2, fract3, mix, modulo};
+use super::hash::{bits2, bits3, uhash11};
+use super::perlin::{gradient2, gradient3, trilinear, GRADIENT3_SCALE};
+
+// the shader's own truncated value, not `std::f32::consts::PI`
+#[allow(clippy::approx_constant)]
+const PI: f32 = 3.1415926;
+
+pub fn gtable2(lattice: glm::Vec2, p: glm::Vec2) -> f32 {
+    let [x, y] = bits2(lattice);
+    let idx = uhash11(uhash11(x).wrapping_add(y)) >> 29;
+    gradient2(idx, p)
+}
+
+pub fn periodic_noise21(p: glm::Vec2, period: f32) -> f32 {
+    let n = floor2(p);
+    let f = fract2(p);
+    let mut v = [0.0; 4];
+
+    for j in 0..2 {
+        for i in 0..2 {
+            let corner = glm::vec2(i as f32, j as f32);
+            let lattice = n + corner;
+            let lattice = glm::vec2(modulo(lattice.x, period), modulo(lattice.y, period));
+            v[i + 2 * j] = gtable2(lattice, f - corner);
+        }
+    }
+
+    let f = glm::vec2(fade(f.x), fade(f.y));
+
+    0.5 * mix(mix(v[0], v[1], f[0]), mix(v[2], v[3], f[0]), f[1]) + 0.5
+}
+
+pub fn gtable3(lattice: glm::Vec3, p: glm::Vec3) -> f32 {
+    let [x, y, z] = bits3(lattice);
+    let idx = uhash11(uhash11(uhash11(x).wrapping_add(y)).wrapping_add(z)) >> 28;
+    gradient3(idx, p)
+}
+
+pub fn periodic_noise31(p: glm::Vec3, period: f32) -> f32 {
+    let n = floor3(p);
+    let f = fract3(p);
+    let mut v = [0.0; 8];
+
+    for k in 0..2 {
+        for j in 0..2 {
+            for i in 0..2 {
+                let corner = glm::vec3(i as f32, j as f32, k as f32);
+                let lattice = n + corner;
+                let lattice = glm::vec3(
+                    modulo(lattice.x, period),
+                    modulo(lattice.y, period),
+                    modulo(lattice.z, period),
+                );
+                v[i + 2 * j + 4 * k] = gtable3(lattice, f - corner) * GRADIENT3_SCALE;
+            }
+        }
+    }
+
+    let f = glm::vec3(fade(f.x), fade(f.y), fade(f.z));
+
+    0.5 * trilinear(&v, f) + 0.5
+}
+
+pub fn atan2(y: f32, x: f32) -> f32 {
+    if x == 0.0 {
+        super::glsl::sign(y) * PI / 2.0
+    } else {
+        y.atan2(x)
+    }
+}
+
+/// Cartesian to polar, as `(angle, radius)`.
+pub fn xy2pol(xy: glm::Vec2) -> glm::Vec2 {
+    glm::vec2(atan2(xy.x, xy.y), glm::length(&xy))
+}
+
+/// `main()` of `periodic_perlin_noise.glsl`: noise wrapped around the centre.
+pub fn fragment(frag_coord: glm::Vec2, resolution: glm::Vec2, time: f32) -> glm::Vec4 {
+    let pos = glm::vec2(frag_coord.x / resolution.x, frag_coord.y / resolution.y);
+    let pos = 2.0 * pos - glm::vec2(1.0, 1.0);
+    let pos = xy2pol(pos);
+    let pos = glm::vec2(5.0 / PI * pos.x, 5.0 * pos.y) + glm::vec2(time, time);
+
+    let v = periodic_noise21(pos, 10.0);
+
+    glm::vec4(v, v, v, 1.0)
+}
@@ -0,0 +1,170 @@
+// Port of `perlin_noise.glsl`: gradient noise (`gnoise*`) with hashed
+// gradients and Perlin noise (`pnoise*`) with gradient tables.
+
+extern crate nalgebra_glm as glm;
+
+use super::glsl::{fade, floor2, floor3, fract2, fract3, mix};
+use super::hash::{bits2, bits3, hash22, hash33, uhash22, uhash33};
+
+// Scales the 3D gradients, which have length sqrt(2), back to unit length.
+#[allow(clippy::approx_constant)]
+pub(super) const GRADIENT3_SCALE: f32 = 0.70710678;
+
+pub fn gnoise21(p: glm::Vec2) -> f32 {
+    let n = floor2(p);
+    let mut g = [glm::Vec2::zeros(); 4];
+
+    for j in 0..2 {
+        for i in 0..2 {
+            let h = hash22(n + glm::vec2(i as f32, j as f32));
+            g[i + 2 * j] = glm::normalize(&(h - glm::vec2(0.5, 0.5)));
+        }
+    }
+
+    let f = fract2(p);
+    let mut v = [0.0; 4];
+
+    for j in 0..2 {
+        for i in 0..2 {
+            v[i + 2 * j] = glm::dot(&g[i + 2 * j], &(f - glm::vec2(i as f32, j as f32)));
+        }
+    }
+
+    let f = glm::vec2(fade(f.x), fade(f.y));
+
+    0.5 * mix(mix(v[0], v[1], f[0]), mix(v[2], v[3], f[0]), f[1]) + 0.5
+}
+
+pub fn gnoise31(p: glm::Vec3) -> f32 {
+    let n = floor3(p);
+    let mut g = [glm::Vec3::zeros(); 8];
+
+    for k in 0..2 {
+        for j in 0..2 {
+            for i in 0..2 {
+                let h = hash33(n + glm::vec3(i as f32, j as f32, k as f32));
+                g[i + 2 * j + 4 * k] = glm::normalize(&(h - glm::vec3(0.5, 0.5, 0.5)));
+            }
+        }
+    }
+
+    let f = fract3(p);
+    let mut v = [0.0; 8];
+
+    for k in 0..2 {
+        for j in 0..2 {
+            for i in 0..2 {
+                let corner = glm::vec3(i as f32, j as f32, k as f32);
+                v[i + 2 * j + 4 * k] = glm::dot(&g[i + 2 * j + 4 * k], &(f - corner));
+            }
+        }
+    }
+
+    let f = glm::vec3(fade(f.x), fade(f.y), fade(f.z));
+
+    0.5 * trilinear(&v, f) + 0.5
+}
+
+pub fn gtable2(lattice: glm::Vec2, p: glm::Vec2) -> f32 {
+    let idx = uhash22(bits2(lattice))[0] >> 29;
+    gradient2(idx, p)
+}
+
+pub fn pnoise21(p: glm::Vec2) -> f32 {
+    let n = floor2(p);
+    let f = fract2(p);
+    let mut v = [0.0; 4];
+
+    for j in 0..2 {
+        for i in 0..2 {
+            let corner = glm::vec2(i as f32, j as f32);
+            v[i + 2 * j] = gtable2(n + corner, f - corner);
+        }
+    }
+
+    let f = glm::vec2(fade(f.x), fade(f.y));
+
+    0.5 * mix(mix(v[0], v[1], f[0]), mix(v[2], v[3], f[0]), f[1]) + 0.5
+}
+
+pub fn gtable3(lattice: glm::Vec3, p: glm::Vec3) -> f32 {
+    let idx = uhash33(bits3(lattice))[0] >> 28;
+    gradient3(idx, p)
+}
+
+pub fn pnoise31(p: glm::Vec3) -> f32 {
+    let n = floor3(p);
+    let f = fract3(p);
+    let mut v = [0.0; 8];
+
+    for k in 0..2 {
+        for j in 0..2 {
+            for i in 0..2 {
+                let corner = glm::vec3(i as f32, j as f32, k as f32);
+                v[i + 2 * j + 4 * k] = gtable3(n + corner, f - corner) * GRADIENT3_SCALE;
+            }
+        }
+    }
+
+    let f = glm::vec3(fade(f.x), fade(f.y), fade(f.z));
+
+    0.5 * trilinear(&v, f) + 0.5
+}
+
+/// `main()` of `perlin_noise.glsl`: one noise per quadrant of the canvas.
+pub fn fragment(frag_coord: glm::Vec2, resolution: glm::Vec2, time: f32) -> glm::Vec4 {
+    let pos = frag_coord / resolution.x.min(resolution.y);
+    let pos = 10.0 * pos + glm::vec2(time, time);
+    let channel = [
+        (2.0 * frag_coord.x / resolution.x) as i32,
+        (2.0 * frag_coord.y / resolution.y) as i32,
+    ];
+
+    let v = match channel {
+        [0, 0] => gnoise21(pos),
+        [0, _] => gnoise31(glm::vec3(pos.x, pos.y, time)),
+        [_, 0] => pnoise21(pos),
+        _ => pnoise31(glm::vec3(pos.x, pos.y, time)),
+    };
+
+    glm::vec4(v, v, v, 1.0)
+}
+
+// The gradient table lookups, shared with the periodic variants
+// which only differ in how `idx` is hashed.
+// The literals are the shader's, digit for digit.
+
+#[allow(clippy::excessive_precision)]
+pub(super) fn gradient2(idx: u32, p: glm::Vec2) -> f32 {
+    let u = 0.92387953 * if idx < 4 { p.x } else { p.y }; // cos(pi/8)
+    let v = 0.38268343 * if idx < 4 { p.y } else { p.x }; // sin(pi/8)
+
+    (if idx & 1 == 0 { u } else { -u }) + (if idx & 2 == 0 { v } else { -v })
+}
+
+pub(super) fn gradient3(idx: u32, p: glm::Vec3) -> f32 {
+    let u = if idx < 8 { p.x } else { p.y };
+    let v = if idx < 4 {
+        p.y
+    } else if idx == 12 || idx == 14 {
+        p.x
+    } else {
+        p.z
+    };
+
+    (if idx & 1 == 0 { u } else { -u }) + (if idx & 2 == 0 { v } else { -v })
+}
+
+pub(super) fn trilinear(v: &[f32; 8], f: glm::Vec3) -> f32 {
+    let mut w = [0.0; 2];
+
+    for (i, w) in w.iter_mut().enumerate() {
+        *w = mix(
+            mix(v[4 * i], v[4 * i + 1], f[0]),
+            mix(v[4 * i + 2], v[4 * i + 3], f[0]),
+            f[1],
+        );
+    }
+
+    mix(w[0], w[1], f[2])
+}
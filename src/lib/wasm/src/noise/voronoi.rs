@@ -0,0 +1,92 @@
+// Port of `voronoi.glsl`: the lattice point of the nearest jittered
+// feature point, used as a cell id.
+
+extern crate nalgebra_glm as glm;
+
+use super::glsl::{modulo, sign};
+use super::hash::{hash22, hash33};
+
+pub fn voronoi2(p: glm::Vec2) -> glm::Vec2 {
+    let n = glm::vec2((p.x + 0.5).floor(), (p.y + 0.5).floor());
+    let mut dist = 2.0f32.sqrt();
+    let mut id = glm::Vec2::zeros();
+
+    for j in 0..=2 {
+        let j = j as f32;
+        let mut grid = glm::Vec2::zeros();
+        grid.y = n.y + sign(modulo(j, 2.0) - 0.5) * (j * 0.5).ceil();
+
+        if (grid.y - p.y).abs() - 0.5 > dist {
+            continue;
+        }
+
+        for i in -1..=1 {
+            grid.x = n.x + i as f32;
+            let jitter = hash22(grid) - glm::vec2(0.5, 0.5);
+
+            if glm::length(&(grid + jitter - p)) <= dist {
+                dist = glm::length(&(grid + jitter - p));
+                id = grid;
+            }
+        }
+    }
+
+    id
+}
+
+pub fn voronoi3(p: glm::Vec3) -> glm::Vec3 {
+    let n = glm::vec3(
+        (p.x + 0.5).floor(),
+        (p.y + 0.5).floor(),
+        (p.z + 0.5).floor(),
+    );
+    let mut dist = 3.0f32.sqrt();
+    let mut id = glm::Vec3::zeros();
+
+    for k in 0..=2 {
+        let k = k as f32;
+        let mut grid = glm::Vec3::zeros();
+        grid.z = n.z + sign(modulo(k, 2.0) - 0.5) * (k * 0.5).ceil();
+
+        if (grid.z - p.z).abs() - 0.5 > dist {
+            continue;
+        }
+
+        for j in 0..=2 {
+            let j = j as f32;
+            grid.y = n.y + sign(modulo(j, 2.0) - 0.5) * (j * 0.5).ceil();
+
+            if (grid.y - p.y).abs() - 0.5 > dist {
+                continue;
+            }
+
+            for i in -1..=1 {
+                grid.x = n.x + i as f32;
+                let jitter = hash33(grid) - glm::vec3(0.5, 0.5, 0.5);
+
+                if glm::length(&(grid + jitter - p)) <= dist {
+                    dist = glm::length(&(grid + jitter - p));
+                    id = grid;
+                }
+            }
+        }
+    }
+
+    id
+}
+
+/// `main()` of `voronoi.glsl`: 2D cells on the left, 3D cells on the right.
+pub fn fragment(frag_coord: glm::Vec2, resolution: glm::Vec2, time: f32) -> glm::Vec4 {
+    let pos = frag_coord / resolution.x.min(resolution.y);
+    let channel = (2.0 * frag_coord.x / resolution.x) as i32;
+    let pos = pos * 10.0 + glm::vec2(time, time);
+
+    let rgb = if channel == 0 {
+        let h = hash22(voronoi2(pos));
+        glm::vec3(h.x, h.y, 1.0)
+    } else {
+        hash33(voronoi3(glm::vec3(pos.x, pos.y, time)))
+    };
+
+    glm::vec4(rgb.x, rgb.y, rgb.z, 1.0)
+}
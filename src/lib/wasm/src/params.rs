@@ -0,0 +1,650 @@
+// Shader parameters declared by annotating uniforms in GLSL:
+//
+//   uniform float u_scale; // @range(0.1, 10.0) @default(2.0) @label("Scale")
+//   uniform vec3 u_tint;   // @color @default(1.0, 0.5, 0.0) @group("Look")
+//
+// Supported annotations:
+//   @range(min, max)   bounds applied to every component
+//   @step(step)        slider increment
+//   @default(v, ...)   one value per component
+//   @label("text")     display name (defaults to the uniform name)
+//   @group("text")     groups parameters in the UI
+//   @color             show a color picker (vec3/vec4 only)
+//
+// Uniforms without annotations, and uniform blocks such as `FRAME_BLOCK`,
+// are ignored.
+
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+use crate::preset::{Preset, Transition};
+use crate::state::GlState;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UniformType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    Bool,
+}
+
+impl UniformType {
+    fn parse(glsl_type: &str) -> Option<Self> {
+        match glsl_type {
+            "float" => Some(UniformType::Float),
+            "vec2" => Some(UniformType::Vec2),
+            "vec3" => Some(UniformType::Vec3),
+            "vec4" => Some(UniformType::Vec4),
+            "int" => Some(UniformType::Int),
+            "bool" => Some(UniformType::Bool),
+            _ => None,
+        }
+    }
+
+    pub fn components(&self) -> usize {
+        match self {
+            UniformType::Float | UniformType::Int | UniformType::Bool => 1,
+            UniformType::Vec2 => 2,
+            UniformType::Vec3 => 3,
+            UniformType::Vec4 => 4,
+        }
+    }
+}
+
+/// One entry of the parameter schema handed to the UI.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: UniformType,
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step: Option<f32>,
+    pub default: Vec<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub color: bool,
+}
+
+impl Parameter {
+    /// Clamps `values` into `@range`, and rounds them for `int`/`bool`.
+    pub fn normalize(&self, values: &[f32]) -> Vec<f32> {
+        values
+            .iter()
+            .map(|v| {
+                let v = match (self.min, self.max) {
+                    (Some(min), Some(max)) => v.clamp(min, max),
+                    _ => *v,
+                };
+
+                match self.kind {
+                    UniformType::Int => v.round(),
+                    UniformType::Bool => (v != 0.0) as i32 as f32,
+                    _ => v,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Collects the annotated uniforms of a shader, in declaration order.
+pub fn parse_parameters(source: &str) -> Result<Vec<Parameter>, String> {
+    let mut parameters: Vec<Parameter> = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+
+        let parameter = parse_line(line).map_err(|msg| format!("line {}: {}", line_number, msg))?;
+
+        if let Some(parameter) = parameter {
+            if parameters.iter().any(|p| p.name == parameter.name) {
+                return Err(format!(
+                    "line {}: duplicate parameter '{}'",
+                    line_number, parameter.name
+                ));
+            }
+
+            parameters.push(parameter);
+        }
+    }
+
+    Ok(parameters)
+}
+
+fn parse_line(line: &str) -> Result<Option<Parameter>, String> {
+    let line = line.trim();
+
+    if !line.starts_with("uniform ") {
+        return Ok(None);
+    }
+
+    let (declaration, comment) = match line.split_once("//") {
+        Some((declaration, comment)) => (declaration, comment),
+        None => return Ok(None),
+    };
+
+    if !comment.contains('@') {
+        return Ok(None);
+    }
+
+    let declaration = declaration
+        .trim()
+        .strip_suffix(';')
+        .ok_or_else(|| String::from("expected ';' after uniform declaration"))?;
+
+    let tokens = declaration
+        .split_whitespace()
+        .skip(1) // uniform
+        .filter(|t| !matches!(*t, "lowp" | "mediump" | "highp"))
+        .collect::<Vec<_>>();
+
+    let (glsl_type, name) = match tokens[..] {
+        [glsl_type, name] => (glsl_type, name),
+        _ => return Err(format!("cannot parse declaration '{}'", declaration)),
+    };
+
+    if name.contains('[') {
+        return Err(format!("array uniform '{}' cannot be a parameter", name));
+    }
+
+    let kind = UniformType::parse(glsl_type)
+        .ok_or_else(|| format!("type '{}' of '{}' cannot be a parameter", glsl_type, name))?;
+
+    let mut parameter = Parameter {
+        name: name.to_string(),
+        kind,
+        label: name.to_string(),
+        min: None,
+        max: None,
+        step: None,
+        default: vec![],
+        group: None,
+        color: false,
+    };
+
+    for annotation in parse_annotations(comment)? {
+        apply_annotation(&mut parameter, annotation)?;
+    }
+
+    if parameter.default.is_empty() {
+        let initial = parameter.min.unwrap_or(0.0);
+        parameter.default = vec![initial; kind.components()];
+    }
+
+    if parameter.step.is_none() {
+        parameter.step = match (kind, parameter.min, parameter.max) {
+            (UniformType::Int, _, _) => Some(1.0),
+            (UniformType::Bool, _, _) => None,
+            (_, Some(min), Some(max)) => Some((max - min) / 100.0),
+            _ => None,
+        };
+    }
+
+    parameter.default = parameter.normalize(&parameter.default);
+
+    Ok(Some(parameter))
+}
+
+struct Annotation {
+    name: String,
+    args: Vec<Arg>,
+}
+
+enum Arg {
+    Number(f32),
+    Text(String),
+}
+
+fn parse_annotations(comment: &str) -> Result<Vec<Annotation>, String> {
+    let mut annotations = vec![];
+    let mut chars = comment.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        if c != '@' {
+            // plain comment text after the annotations
+            break;
+        }
+
+        let mut name = String::new();
+        while let Some(c) = chars
+            .peek()
+            .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+        {
+            name.push(*c);
+            chars.next();
+        }
+
+        if name.is_empty() {
+            return Err(String::from("expected annotation name after '@'"));
+        }
+
+        let mut args = vec![];
+
+        if chars.peek() == Some(&'(') {
+            chars.next();
+
+            let mut raw = String::new();
+            let mut in_string = false;
+            let mut closed = false;
+
+            for c in chars.by_ref() {
+                match c {
+                    '"' => {
+                        in_string = !in_string;
+                        raw.push(c);
+                    }
+                    ')' if !in_string => {
+                        closed = true;
+                        break;
+                    }
+                    _ => raw.push(c),
+                }
+            }
+
+            if !closed {
+                return Err(format!("unclosed '(' in @{}", name));
+            }
+
+            args = split_args(&raw)
+                .into_iter()
+                .map(|arg| parse_arg(&arg).map_err(|msg| format!("@{}: {}", name, msg)))
+                .collect::<Result<Vec<_>, _>>()?;
+        }
+
+        annotations.push(Annotation { name, args });
+    }
+
+    Ok(annotations)
+}
+
+fn split_args(raw: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut in_string = false;
+
+    for c in raw.chars() {
+        match c {
+            '"' => {
+                in_string = !in_string;
+                current.push(c);
+            }
+            ',' if !in_string => args.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+
+    if !current.trim().is_empty() || !args.is_empty() {
+        args.push(current);
+    }
+
+    args
+}
+
+fn parse_arg(arg: &str) -> Result<Arg, String> {
+    let arg = arg.trim();
+
+    if let Some(text) = arg.strip_prefix('"') {
+        return text
+            .strip_suffix('"')
+            .map(|text| Arg::Text(text.to_string()))
+            .ok_or_else(|| format!("unterminated string {}", arg));
+    }
+
+    arg.parse::<f32>()
+        .map(Arg::Number)
+        .map_err(|_| format!("expected a number, got '{}'", arg))
+}
+
+fn apply_annotation(parameter: &mut Parameter, annotation: Annotation) -> Result<(), String> {
+    let Annotation { name, args } = annotation;
+
+    let numbers = || {
+        args.iter()
+            .map(|arg| match arg {
+                Arg::Number(n) => Ok(*n),
+                Arg::Text(t) => Err(format!("@{}: expected a number, got \"{}\"", name, t)),
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let text = || match &args[..] {
+        [Arg::Text(t)] => Ok(t.clone()),
+        _ => Err(format!("@{} takes one string", name)),
+    };
+
+    match name.as_str() {
+        "range" => match numbers()?[..] {
+            [min, max] if min < max => {
+                parameter.min = Some(min);
+                parameter.max = Some(max);
+            }
+            [_, _] => return Err(String::from("@range: min must be less than max")),
+            _ => return Err(String::from("@range takes two numbers")),
+        },
+        "step" => match numbers()?[..] {
+            [step] if step > 0.0 => parameter.step = Some(step),
+            _ => return Err(String::from("@step takes one positive number")),
+        },
+        "default" => {
+            let values = numbers()?;
+            let components = parameter.kind.components();
+
+            if values.len() != components {
+                return Err(format!(
+                    "@default of '{}' needs {} value(s), got {}",
+                    parameter.name,
+                    components,
+                    values.len()
+                ));
+            }
+
+            parameter.default = values;
+        }
+        "label" => parameter.label = text()?,
+        "group" => parameter.group = Some(text()?),
+        "color" => {
+            if !matches!(parameter.kind, UniformType::Vec3 | UniformType::Vec4) {
+                return Err(String::from("@color only applies to vec3 and vec4"));
+            }
+            parameter.color = true;
+        }
+        _ => return Err(format!("unknown annotation @{}", name)),
+    }
+
+    Ok(())
+}
+
+/// The parameters of a linked program, with their current values.
+pub struct ShaderParameters {
+    program: WebGlProgram,
+    parameters: Vec<Parameter>,
+    locations: Vec<Option<WebGlUniformLocation>>,
+    values: Vec<Vec<f32>>,
+    transition: Option<Transition>,
+}
+
+impl ShaderParameters {
+    /// Parses `fragment_shader_source` and uploads every default to `program`,
+    /// which is left in use.
+    pub fn new(
+        state: &mut GlState,
+        program: &WebGlProgram,
+        fragment_shader_source: &str,
+    ) -> Result<Self, String> {
+        let parameters = parse_parameters(fragment_shader_source)?;
+        let context = state.context();
+
+        let locations = parameters
+            .iter()
+            .map(|p| context.get_uniform_location(program, &p.name))
+            .collect();
+
+        let values = parameters.iter().map(|p| p.default.clone()).collect();
+
+        let shader_parameters = ShaderParameters {
+            program: program.clone(),
+            parameters,
+            locations,
+            values,
+            transition: None,
+        };
+
+        state.use_program(Some(&shader_parameters.program));
+        for index in 0..shader_parameters.parameters.len() {
+            shader_parameters.upload(state.context(), index);
+        }
+
+        Ok(shader_parameters)
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    pub fn schema_json(&self) -> String {
+        serde_json::to_string(&self.parameters).unwrap_or_else(|_| String::from("[]"))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Vec<f32>> {
+        self.index_of(name).map(|index| self.values[index].clone())
+    }
+
+    /// Sets `name` to `values`, clamped to its range, and uploads it to the
+    /// program, which is left in use.
+    pub fn set(&mut self, state: &mut GlState, name: &str, values: &[f32]) -> Result<(), String> {
+        let index = self
+            .index_of(name)
+            .ok_or_else(|| format!("Unknown parameter: {}", name))?;
+
+        let parameter = &self.parameters[index];
+        let components = parameter.kind.components();
+
+        if values.len() != components {
+            return Err(format!(
+                "Parameter {} needs {} value(s), got {}",
+                name,
+                components,
+                values.len()
+            ));
+        }
+
+        self.values[index] = parameter.normalize(values);
+
+        state.use_program(Some(&self.program));
+        self.upload(state.context(), index);
+
+        Ok(())
+    }
+
+    /// Captures the current value of every parameter.
+    pub fn preset(&self) -> Preset {
+        let values = self
+            .parameters
+            .iter()
+            .zip(&self.values)
+            .map(|(p, v)| (p.name.clone(), v.clone()))
+            .collect();
+
+        Preset::new(values)
+    }
+
+    /// Sets every parameter named in `preset`; values for parameters the
+    /// shader doesn't declare are skipped.
+    pub fn apply_preset(&mut self, state: &mut GlState, preset: &Preset) -> Result<(), String> {
+        for (name, values) in &preset.values {
+            if self.index_of(name).is_some() {
+                self.set(state, name, values)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The current values as a JSON preset.
+    pub fn save_preset(&self) -> String {
+        self.preset().to_json()
+    }
+
+    /// Applies a JSON preset, cancelling any transition.
+    pub fn load_preset(&mut self, state: &mut GlState, json: &str) -> Result<(), String> {
+        let preset = Preset::from_json(json)?;
+
+        self.transition = None;
+        self.apply_preset(state, &preset)
+    }
+
+    /// Applies the blend of two JSON presets at `t` in [0, 1].
+    pub fn blend_presets(
+        &mut self,
+        state: &mut GlState,
+        from: &str,
+        to: &str,
+        t: f32,
+    ) -> Result<(), String> {
+        let from = Preset::from_json(from)?;
+        let to = Preset::from_json(to)?;
+        let preset = Preset::interpolate(&from, &to, t, &self.parameters)?;
+
+        self.apply_preset(state, &preset)
+    }
+
+    /// Blends from the current values to a JSON preset over `duration`
+    /// seconds, starting at the next `advance`.
+    pub fn transition_to(&mut self, json: &str, duration: f64) -> Result<(), String> {
+        let to = Preset::from_json(json)?;
+
+        self.transition = Some(Transition::new(self.preset(), to, duration));
+
+        Ok(())
+    }
+
+    /// Moves the transition, if any, to `timestamp` in seconds.
+    pub fn advance(&mut self, state: &mut GlState, timestamp: f64) -> Result<(), String> {
+        let transition = match &mut self.transition {
+            None => return Ok(()),
+            Some(transition) => transition,
+        };
+
+        let preset = transition.at(timestamp, &self.parameters);
+        if transition.is_finished(timestamp) || preset.is_err() {
+            self.transition = None;
+        }
+
+        self.apply_preset(state, &preset?)
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.parameters.iter().position(|p| p.name == name)
+    }
+
+    fn upload(&self, context: &WebGl2RenderingContext, index: usize) {
+        // unused uniforms are optimized away and have no location
+        let loc = match &self.locations[index] {
+            None => return,
+            Some(loc) => loc,
+        };
+
+        let v = &self.values[index];
+
+        match self.parameters[index].kind {
+            UniformType::Float => context.uniform1f(Some(loc), v[0]),
+            UniformType::Vec2 => context.uniform2fv_with_f32_array(Some(loc), v),
+            UniformType::Vec3 => context.uniform3fv_with_f32_array(Some(loc), v),
+            UniformType::Vec4 => context.uniform4fv_with_f32_array(Some(loc), v),
+            UniformType::Int | UniformType::Bool => context.uniform1i(Some(loc), v[0] as i32),
+        }
+    }
+}
+
+/// Returns the parameter schema of a fragment shader as JSON, so the UI
+/// can be built before the shader is compiled.
+#[wasm_bindgen]
+pub fn parameter_schema(fragment_shader_source: &str) -> Result<String, JsValue> {
+    let parameters = parse_parameters(fragment_shader_source).map_err(JsValue::from)?;
+
+    serde_json::to_string(&parameters).map_err(|err| JsValue::from(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_one(line: &str) -> Result<Parameter, String> {
+        parse_parameters(line)?
+            .pop()
+            .ok_or_else(|| String::from("no parameter"))
+    }
+
+    #[test]
+    fn annotations_fill_the_schema() {
+        let source = r#"
+uniform float u_time;
+uniform highp float u_scale; // @range(0.1, 10.0) @default(2.0) @label("Scale") then prose
+uniform vec3 u_tint;   // @color @default(1.0, 0.5, 0.0) @group("Look")
+uniform int u_steps;   // @range(1, 8)
+uniform bool u_invert; // @default(1)
+"#;
+        let parameters = parse_parameters(source).unwrap();
+        let names = parameters
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["u_scale", "u_tint", "u_steps", "u_invert"]);
+
+        let scale = &parameters[0];
+        assert_eq!(scale.kind, UniformType::Float);
+        assert_eq!((scale.min, scale.max), (Some(0.1), Some(10.0)));
+        assert_eq!(scale.default, [2.0]);
+        assert_eq!(scale.label, "Scale");
+        assert_eq!(scale.step, Some((10.0 - 0.1) / 100.0));
+
+        let tint = &parameters[1];
+        assert!(tint.color);
+        assert_eq!(tint.group.as_deref(), Some("Look"));
+        assert_eq!(tint.label, "u_tint");
+
+        let steps = &parameters[2];
+        assert_eq!((steps.default.clone(), steps.step), (vec![1.0], Some(1.0)));
+
+        assert_eq!(parameters[3].default, [1.0]);
+    }
+
+    #[test]
+    fn malformed_annotations_are_errors() {
+        for (line, expected) in [
+            (
+                "uniform float a; // @range(1.0)",
+                "@range takes two numbers",
+            ),
+            ("uniform float a; // @range(2.0, 1.0)", "min must be less"),
+            (
+                "uniform vec2 a; // @default(1.0)",
+                "needs 2 value(s), got 1",
+            ),
+            ("uniform float a; // @color", "@color only applies"),
+            ("uniform float a; // @slider", "unknown annotation @slider"),
+            ("uniform float a; // @label(\"open)", "unclosed '('"),
+            ("uniform float a; // @step(x)", "expected a number"),
+            ("uniform float a; // @", "expected annotation name"),
+            ("uniform mat4 a; // @default(1.0)", "type 'mat4'"),
+            ("uniform float a[2]; // @default(1.0)", "array uniform"),
+            ("uniform float a // @default(1.0)", "expected ';'"),
+        ] {
+            let err = parse_one(line).unwrap_err();
+            assert!(err.contains(expected), "{:?}: {}", line, err);
+        }
+    }
+
+    #[test]
+    fn errors_name_the_line_and_duplicates() {
+        let source = "uniform float a; // @default(1.0)\nuniform float a; // @default(2.0)";
+        assert_eq!(
+            parse_parameters(source).unwrap_err(),
+            "line 2: duplicate parameter 'a'"
+        );
+    }
+
+    #[test]
+    fn defaults_are_normalized() {
+        let clamped = parse_one("uniform float a; // @range(0.0, 1.0) @default(5.0)").unwrap();
+        assert_eq!(clamped.default, [1.0]);
+
+        let rounded = parse_one("uniform int n; // @default(2.6)").unwrap();
+        assert_eq!(rounded.default, [3.0]);
+        assert_eq!(rounded.normalize(&[-1.2]), [-1.0]);
+    }
+}
@@ -0,0 +1,632 @@
+// Particles simulated entirely on the GPU with transform feedback.
+//
+// Each particle is two vec4 in a vertex buffer: position and age, then
+// velocity and lifetime. Every `update` runs a vertex shader over the
+// particles of one buffer with rasterization off, capturing its outputs
+// into the other buffer
+// (https://developer.mozilla.org/en-US/docs/Web/API/WebGLTransformFeedback);
+// the two buffers then swap roles. `render` draws the latest buffer as
+// point sprites or camera facing quads, added onto the frame.
+//
+// The motion comes from a GLSL function written by the user,
+//
+//   vec3 force(vec3 position, vec3 velocity, float age, float time)
+//
+// returning an acceleration. It can call the noise of `perlin_noise.glsl`
+// (hash33, gnoise31, pnoise31, ...) and `curlNoise`, and declare annotated
+// uniforms (see params.rs), which become parameters of the system.
+//
+// A particle is born when its age passes its lifetime: at a random point
+// of the emitter sphere, with a random velocity and lifetime. Negative ages
+// count down to a first birth, which staggers the start.
+
+use wasm_bindgen::JsCast;
+use web_sys::{
+    WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlTransformFeedback,
+    WebGlUniformLocation, WebGlVertexArrayObject,
+};
+
+use crate::camera::Camera;
+use crate::params::ShaderParameters;
+use crate::pipeline::{Blend, PipelineState};
+use crate::state::GlState;
+use crate::uniforms::FRAME_BLOCK;
+use crate::webgl::{compile_shader, link_shader_program, link_transform_feedback_program};
+
+/// Floats per particle: position, age, velocity, lifetime.
+const STRIDE: usize = 8;
+
+const LOCATION_POSITION_AGE: u32 = 0;
+const LOCATION_VELOCITY_LIFE: u32 = 1;
+
+/// Added onto the frame, tested against its depth but not written to it.
+static RENDER_PIPELINE: PipelineState = PipelineState::transparent(Blend::ADDITIVE);
+
+/// Swirls around without ever converging: the curl of a noise field is
+/// divergence free.
+pub static CURL_FORCE: &str = r#"
+uniform float uCurlScale;    // @range(0.05, 2.0) @default(0.4) @label("Noise scale")
+uniform float uCurlStrength; // @range(0.0, 20.0) @default(4.0) @label("Strength")
+uniform float uGravity;      // @range(-5.0, 5.0) @default(0.0) @label("Gravity")
+
+vec3 force(vec3 position, vec3 velocity, float age, float time) {
+  vec3 curl = curlNoise(position * uCurlScale + vec3(0.0, 0.0, time * 0.1));
+  return curl * uCurlStrength + vec3(0.0, -uGravity, 0.0);
+}
+"#;
+
+static PERLIN_NOISE: &str = include_str!("../../shaders/perlin_noise.glsl");
+
+static CURL_NOISE: &str = r#"
+// https://www.cs.ubc.ca/~rbridson/docs/bridson-siggraph2007-curlnoise.pdf
+// The potential is three decorrelated copies of pnoise31.
+vec3 potential(vec3 p) {
+  return vec3(
+    pnoise31(p),
+    pnoise31(p + vec3(31.416, -47.853, 12.793)),
+    pnoise31(p + vec3(-12.345, 73.19, -91.12))
+  ) - 0.5;
+}
+
+vec3 curlNoise(vec3 p) {
+  const float e = 0.01;
+  vec3 dx = (potential(p + vec3(e, 0.0, 0.0)) - potential(p - vec3(e, 0.0, 0.0))) / (2.0 * e);
+  vec3 dy = (potential(p + vec3(0.0, e, 0.0)) - potential(p - vec3(0.0, e, 0.0))) / (2.0 * e);
+  vec3 dz = (potential(p + vec3(0.0, 0.0, e)) - potential(p - vec3(0.0, 0.0, e))) / (2.0 * e);
+
+  return vec3(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x);
+}
+"#;
+
+/// The hash and noise functions of `perlin_noise.glsl`, without its
+/// version, inputs, outputs and `main`, followed by `vec3 curlNoise(vec3)`.
+pub fn noise_library() -> String {
+    let body = PERLIN_NOISE.replace(FRAME_BLOCK, "");
+    let body = body
+        .split("void main(")
+        .next()
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            !(line.starts_with("#version")
+                || line.starts_with("precision")
+                || line.starts_with("out ")
+                || line.starts_with("uniform ")
+                || line.starts_with("ivec2 channel"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("{}\n{}", body, CURL_NOISE)
+}
+
+fn update_vertex_shader(force: &str) -> String {
+    format!(
+        r#"#version 300 es
+precision highp float;
+precision highp int;
+
+layout(location = 0) in vec4 aPositionAge;
+layout(location = 1) in vec4 aVelocityLife;
+
+uniform float uTime;
+uniform float uDelta;
+uniform vec3 uEmitter;
+uniform float uEmitterRadius;
+uniform vec3 uVelocity;
+uniform float uVelocitySpread;
+uniform vec2 uLifetime;
+uniform float uDrag;
+
+out vec4 vPositionAge;
+out vec4 vVelocityLife;
+
+{noise}
+{force}
+
+void main() {{
+  vec3 position = aPositionAge.xyz;
+  float age = aPositionAge.w + uDelta;
+  vec3 velocity = aVelocityLife.xyz;
+  float life = aVelocityLife.w;
+
+  if (age >= life) {{
+    vec3 r = hash33(vec3(float(gl_VertexID), uTime, 1.0));
+    vec3 s = hash33(r + vec3(uTime));
+
+    // uniform in the sphere: a direction, and the cube root for the radius
+    vec3 direction = normalize(vec3(
+      sqrt(1.0 - (2.0 * r.x - 1.0) * (2.0 * r.x - 1.0)) * cos(6.2831853 * r.y),
+      sqrt(1.0 - (2.0 * r.x - 1.0) * (2.0 * r.x - 1.0)) * sin(6.2831853 * r.y),
+      2.0 * r.x - 1.0
+    ));
+    position = uEmitter + direction * uEmitterRadius * pow(r.z, 1.0 / 3.0);
+    velocity = uVelocity + (s - 0.5) * 2.0 * uVelocitySpread;
+    life = mix(uLifetime.x, uLifetime.y, s.z);
+    age = 0.0;
+  }} else if (age >= 0.0) {{
+    velocity += force(position, velocity, age, uTime) * uDelta;
+    velocity *= exp(-uDrag * uDelta);
+    position += velocity * uDelta;
+  }}
+
+  vPositionAge = vec4(position, age);
+  vVelocityLife = vec4(velocity, life);
+}}
+"#,
+        noise = noise_library(),
+        force = force,
+    )
+}
+
+// Never runs: rasterization is off while updating, but a program needs one.
+static UPDATE_FS: &str = r#"#version 300 es
+precision highp float;
+out vec4 fragColor;
+void main() {
+  fragColor = vec4(0.0);
+}
+"#;
+
+static RENDER_VS: &str = r#"#version 300 es
+precision highp float;
+
+layout(location = 0) in vec4 aPositionAge;
+layout(location = 1) in vec4 aVelocityLife;
+
+uniform mat4 uViewMatrix;
+uniform mat4 uProjectionMatrix;
+uniform float uSize;
+uniform float uViewportHeight;
+uniform bool uQuads;
+
+out vec2 vCorner;
+out float vT;
+
+void main() {
+  float age = aPositionAge.w;
+  float life = aVelocityLife.w;
+
+  // not born yet: outside of the clip volume
+  if (age < 0.0 || life <= 0.0) {
+    gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+    gl_PointSize = 0.0;
+    return;
+  }
+
+  vT = clamp(age / life, 0.0, 1.0);
+  vec4 view = uViewMatrix * vec4(aPositionAge.xyz, 1.0);
+
+  if (uQuads) {
+    // two triangles, corners from the vertex index
+    const vec2 corners[6] = vec2[6](
+      vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+      vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+    );
+    vCorner = corners[gl_VertexID];
+    view.xy += vCorner * uSize * 0.5;
+    gl_Position = uProjectionMatrix * view;
+  } else {
+    vCorner = vec2(0.0);
+    gl_Position = uProjectionMatrix * view;
+    // world size to pixels, as a quad of the same size would be
+    gl_PointSize = uSize * uProjectionMatrix[1][1] * uViewportHeight * 0.5 / gl_Position.w;
+  }
+}
+"#;
+
+static RENDER_FS: &str = r#"#version 300 es
+precision highp float;
+
+uniform vec4 uColorStart;
+uniform vec4 uColorEnd;
+uniform bool uQuads;
+
+in vec2 vCorner;
+in float vT;
+out vec4 fragColor;
+
+void main() {
+  vec2 p = uQuads ? vCorner : gl_PointCoord * 2.0 - 1.0;
+  float falloff = 1.0 - smoothstep(0.0, 1.0, dot(p, p));
+  if (falloff <= 0.0) discard;
+
+  vec4 color = mix(uColorStart, uColorEnd, vT);
+  // additive: premultiplied, fading in and out
+  float fade = smoothstep(0.0, 0.1, vT) * (1.0 - smoothstep(0.7, 1.0, vT));
+  fragColor = vec4(color.rgb * color.a * falloff * fade, 1.0);
+}
+"#;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleShape {
+    /// `gl_PointSize` sprites; sizes are capped by the driver, often at 64
+    /// or 256 pixels.
+    Points,
+    /// Instanced camera facing quads, of any size.
+    Quads,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParticleOptions {
+    pub count: usize,
+    pub shape: ParticleShape,
+    /// Center and radius of the sphere particles are born in.
+    pub emitter: [f32; 3],
+    pub emitter_radius: f32,
+    /// Initial velocity, plus up to `velocity_spread` along each axis.
+    pub velocity: [f32; 3],
+    pub velocity_spread: f32,
+    /// Lifetimes are picked between the two, in seconds.
+    pub lifetime: [f32; 2],
+    /// Fraction of the velocity lost per second, roughly.
+    pub drag: f32,
+    /// Diameter in world units.
+    pub size: f32,
+    /// Colors at birth and at death; alpha scales the brightness.
+    pub color_start: [f32; 4],
+    pub color_end: [f32; 4],
+}
+
+impl Default for ParticleOptions {
+    fn default() -> Self {
+        ParticleOptions {
+            count: 100_000,
+            shape: ParticleShape::Points,
+            emitter: [0.0; 3],
+            emitter_radius: 0.5,
+            velocity: [0.0, 0.5, 0.0],
+            velocity_spread: 0.5,
+            lifetime: [2.0, 6.0],
+            drag: 0.5,
+            size: 0.03,
+            color_start: [1.0, 0.6, 0.2, 0.6],
+            color_end: [0.2, 0.3, 1.0, 0.2],
+        }
+    }
+}
+
+struct UpdateLocations {
+    time: Option<WebGlUniformLocation>,
+    delta: Option<WebGlUniformLocation>,
+    emitter: Option<WebGlUniformLocation>,
+    emitter_radius: Option<WebGlUniformLocation>,
+    velocity: Option<WebGlUniformLocation>,
+    velocity_spread: Option<WebGlUniformLocation>,
+    lifetime: Option<WebGlUniformLocation>,
+    drag: Option<WebGlUniformLocation>,
+}
+
+struct RenderLocations {
+    view_matrix: Option<WebGlUniformLocation>,
+    projection_matrix: Option<WebGlUniformLocation>,
+    size: Option<WebGlUniformLocation>,
+    viewport_height: Option<WebGlUniformLocation>,
+    quads: Option<WebGlUniformLocation>,
+    color_start: Option<WebGlUniformLocation>,
+    color_end: Option<WebGlUniformLocation>,
+}
+
+pub struct ParticleSystem {
+    pub options: ParticleOptions,
+    update_program: WebGlProgram,
+    render_program: WebGlProgram,
+    parameters: ShaderParameters,
+    update_locations: UpdateLocations,
+    render_locations: RenderLocations,
+    buffers: [WebGlBuffer; 2],
+    // the same buffers as vertex attributes, per instance for quads
+    point_vaos: [WebGlVertexArrayObject; 2],
+    quad_vaos: [WebGlVertexArrayObject; 2],
+    transform_feedback: WebGlTransformFeedback,
+    // the buffer holding the latest state
+    current: usize,
+}
+
+impl ParticleSystem {
+    /// `force` defines `vec3 force(vec3 position, vec3 velocity, float age,
+    /// float time)`, e.g. `CURL_FORCE`. `options.count` is fixed from here
+    /// on; the other options can change between frames.
+    pub fn new(state: &mut GlState, options: ParticleOptions, force: &str) -> Result<Self, String> {
+        let context = &state.context().clone();
+        let source = update_vertex_shader(force);
+        let vs = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, &source)?;
+        let fs = compile_shader(context, WebGl2RenderingContext::FRAGMENT_SHADER, UPDATE_FS)?;
+        let update_program =
+            link_transform_feedback_program(context, &vs, &fs, &["vPositionAge", "vVelocityLife"])?;
+        // annotations are only looked for in the user's part
+        let parameters = ShaderParameters::new(state, &update_program, force)?;
+
+        let vs = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, RENDER_VS)?;
+        let fs = compile_shader(context, WebGl2RenderingContext::FRAGMENT_SHADER, RENDER_FS)?;
+        let render_program = link_shader_program(context, &vs, &fs)?;
+
+        let location =
+            |program: &WebGlProgram, name: &str| context.get_uniform_location(program, name);
+        let update_locations = UpdateLocations {
+            time: location(&update_program, "uTime"),
+            delta: location(&update_program, "uDelta"),
+            emitter: location(&update_program, "uEmitter"),
+            emitter_radius: location(&update_program, "uEmitterRadius"),
+            velocity: location(&update_program, "uVelocity"),
+            velocity_spread: location(&update_program, "uVelocitySpread"),
+            lifetime: location(&update_program, "uLifetime"),
+            drag: location(&update_program, "uDrag"),
+        };
+        let render_locations = RenderLocations {
+            view_matrix: location(&render_program, "uViewMatrix"),
+            projection_matrix: location(&render_program, "uProjectionMatrix"),
+            size: location(&render_program, "uSize"),
+            viewport_height: location(&render_program, "uViewportHeight"),
+            quads: location(&render_program, "uQuads"),
+            color_start: location(&render_program, "uColorStart"),
+            color_end: location(&render_program, "uColorEnd"),
+        };
+
+        let initial = initial_state(options.count, options.lifetime[1]);
+        let buffers = [
+            create_state_buffer(context, &initial)?,
+            create_state_buffer(context, &initial)?,
+        ];
+        let point_vaos = [
+            create_state_vao(context, &buffers[0], 0)?,
+            create_state_vao(context, &buffers[1], 0)?,
+        ];
+        let quad_vaos = [
+            create_state_vao(context, &buffers[0], 1)?,
+            create_state_vao(context, &buffers[1], 1)?,
+        ];
+        let transform_feedback = context
+            .create_transform_feedback()
+            .ok_or_else(|| String::from("Unable to create transform feedback"))?;
+        // the buffers and vertex arrays were bound on the context itself
+        state.invalidate();
+
+        Ok(ParticleSystem {
+            options,
+            update_program,
+            render_program,
+            parameters,
+            update_locations,
+            render_locations,
+            buffers,
+            point_vaos,
+            quad_vaos,
+            transform_feedback,
+            current: 0,
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.options.count
+    }
+
+    /// The annotated uniforms of the force, as a JSON array.
+    pub fn parameter_schema(&self) -> String {
+        self.parameters.schema_json()
+    }
+
+    pub fn set_parameter(
+        &mut self,
+        state: &mut GlState,
+        name: &str,
+        values: &[f32],
+    ) -> Result<(), String> {
+        self.parameters.set(state, name, values)
+    }
+
+    /// Advances every particle by `delta` seconds; `time` is the time since
+    /// the start, in seconds.
+    pub fn update(&mut self, state: &mut GlState, time: f32, delta: f32) {
+        let context = &state.context().clone();
+        let options = &self.options;
+        let locations = &self.update_locations;
+        let next = 1 - self.current;
+
+        state.use_program(Some(&self.update_program));
+        context.uniform1f(locations.time.as_ref(), time);
+        // long frames (a hidden tab) would throw particles far away
+        context.uniform1f(locations.delta.as_ref(), delta.clamp(0.0, 0.1));
+        context.uniform3fv_with_f32_array(locations.emitter.as_ref(), &options.emitter);
+        context.uniform1f(locations.emitter_radius.as_ref(), options.emitter_radius);
+        context.uniform3fv_with_f32_array(locations.velocity.as_ref(), &options.velocity);
+        context.uniform1f(locations.velocity_spread.as_ref(), options.velocity_spread);
+        context.uniform2fv_with_f32_array(locations.lifetime.as_ref(), &options.lifetime);
+        context.uniform1f(locations.drag.as_ref(), options.drag);
+
+        state.bind_vertex_array(Some(&self.point_vaos[self.current]));
+        context.bind_transform_feedback(
+            WebGl2RenderingContext::TRANSFORM_FEEDBACK,
+            Some(&self.transform_feedback),
+        );
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/bindBufferBase
+        context.bind_buffer_base(
+            WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER,
+            0,
+            Some(&self.buffers[next]),
+        );
+
+        state.enable(WebGl2RenderingContext::RASTERIZER_DISCARD);
+        context.begin_transform_feedback(WebGl2RenderingContext::POINTS);
+        context.draw_arrays(WebGl2RenderingContext::POINTS, 0, options.count as i32);
+        context.end_transform_feedback();
+        state.disable(WebGl2RenderingContext::RASTERIZER_DISCARD);
+
+        // a buffer cannot be captured into and read as attributes at once
+        context.bind_buffer_base(WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER, 0, None);
+        context.bind_transform_feedback(WebGl2RenderingContext::TRANSFORM_FEEDBACK, None);
+        state.bind_vertex_array(None);
+
+        self.current = next;
+    }
+
+    /// Adds the particles onto the bound framebuffer. They test against
+    /// its depth but don't write to it; see `RENDER_PIPELINE`.
+    pub fn render(&self, state: &mut GlState, camera: &Camera) {
+        let context = &state.context().clone();
+        let options = &self.options;
+        let locations = &self.render_locations;
+        let quads = options.shape == ParticleShape::Quads;
+
+        let viewport = context
+            .get_parameter(WebGl2RenderingContext::VIEWPORT)
+            .ok()
+            .and_then(|viewport| viewport.dyn_into::<js_sys::Int32Array>().ok())
+            .map(|viewport| viewport.to_vec())
+            .unwrap_or_default();
+        let viewport_height = viewport.get(3).copied().unwrap_or(1) as f32;
+
+        RENDER_PIPELINE.apply(state);
+        state.use_program(Some(&self.render_program));
+        context.uniform_matrix4fv_with_f32_array(
+            locations.view_matrix.as_ref(),
+            false,
+            camera.view_matrix().as_slice(),
+        );
+        context.uniform_matrix4fv_with_f32_array(
+            locations.projection_matrix.as_ref(),
+            false,
+            camera.projection_matrix().as_slice(),
+        );
+        context.uniform1f(locations.size.as_ref(), options.size);
+        context.uniform1f(locations.viewport_height.as_ref(), viewport_height);
+        context.uniform1i(locations.quads.as_ref(), quads as i32);
+        context.uniform4fv_with_f32_array(locations.color_start.as_ref(), &options.color_start);
+        context.uniform4fv_with_f32_array(locations.color_end.as_ref(), &options.color_end);
+
+        if quads {
+            state.bind_vertex_array(Some(&self.quad_vaos[self.current]));
+            // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/drawArraysInstanced
+            context.draw_arrays_instanced(
+                WebGl2RenderingContext::TRIANGLES,
+                0,
+                6,
+                options.count as i32,
+            );
+        } else {
+            state.bind_vertex_array(Some(&self.point_vaos[self.current]));
+            context.draw_arrays(WebGl2RenderingContext::POINTS, 0, options.count as i32);
+        }
+
+        state.bind_vertex_array(None);
+    }
+}
+
+/// Every particle at the origin, waiting to be born: the i-th one after
+/// `i / count` of `max_lifetime`, so that births are spread out instead of
+/// all happening on the first frame.
+pub fn initial_state(count: usize, max_lifetime: f32) -> Vec<f32> {
+    let mut state = vec![0.0; count * STRIDE];
+
+    for (i, particle) in state.chunks_exact_mut(STRIDE).enumerate() {
+        // age; a lifetime of 0 makes it born as soon as the age reaches 0
+        particle[3] = -max_lifetime * i as f32 / count as f32;
+    }
+
+    state
+}
+
+fn create_state_buffer(
+    context: &WebGl2RenderingContext,
+    state: &[f32],
+) -> Result<WebGlBuffer, String> {
+    let buffer = context
+        .create_buffer()
+        .ok_or_else(|| String::from("Unable to create buffer"))?;
+
+    context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+    unsafe {
+        let view = js_sys::Float32Array::view(state);
+
+        // written by the GPU every frame, read back only by drawing
+        context.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &view,
+            WebGl2RenderingContext::DYNAMIC_COPY,
+        );
+    }
+    context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
+
+    Ok(buffer)
+}
+
+/// The state as the two vec4 attributes, advancing per vertex (`divisor`
+/// 0) or per instance (1).
+fn create_state_vao(
+    context: &WebGl2RenderingContext,
+    buffer: &WebGlBuffer,
+    divisor: u32,
+) -> Result<WebGlVertexArrayObject, String> {
+    let vao = context
+        .create_vertex_array()
+        .ok_or_else(|| String::from("Unable to create vertex array object"))?;
+    let stride = (STRIDE * 4) as i32;
+
+    context.bind_vertex_array(Some(&vao));
+    context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(buffer));
+
+    for (location, offset) in [(LOCATION_POSITION_AGE, 0), (LOCATION_VELOCITY_LIFE, 16)] {
+        context.enable_vertex_attrib_array(location);
+        context.vertex_attrib_pointer_with_i32(
+            location,
+            4,
+            WebGl2RenderingContext::FLOAT,
+            false,
+            stride,
+            offset,
+        );
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/vertexAttribDivisor
+        context.vertex_attrib_divisor(location, divisor);
+    }
+
+    context.bind_vertex_array(None);
+    context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
+
+    Ok(vao)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::parse_parameters;
+
+    #[test]
+    fn noise_library_has_no_entry_point() {
+        let library = noise_library();
+
+        assert!(library.contains("float pnoise31(vec3 p)"));
+        assert!(library.contains("vec3 hash33(vec3 p)"));
+        assert!(library.contains("vec3 curlNoise(vec3 p)"));
+        assert!(!library.contains("void main"));
+        assert!(!library.contains("#version"));
+        assert!(!library.contains("uniform Frame"));
+    }
+
+    #[test]
+    fn births_are_staggered() {
+        let state = initial_state(4, 2.0);
+
+        assert_eq!(state.len(), 4 * STRIDE);
+        let ages = state
+            .chunks_exact(STRIDE)
+            .map(|particle| particle[3])
+            .collect::<Vec<_>>();
+        assert_eq!(ages, vec![0.0, -0.5, -1.0, -1.5]);
+        // lifetimes start at 0
+        assert!(state
+            .chunks_exact(STRIDE)
+            .all(|particle| particle[7] == 0.0));
+    }
+
+    #[test]
+    fn curl_force_parameters() {
+        let names = parse_parameters(CURL_FORCE)
+            .unwrap()
+            .into_iter()
+            .map(|parameter| parameter.name)
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["uCurlScale", "uCurlStrength", "uGravity"]);
+    }
+}
@@ -0,0 +1,770 @@
+// Metallic-roughness physically based shading, as specified by glTF 2.0.
+//
+// Direct light uses the Cook-Torrance BRDF: GGX distribution, Smith
+// geometry with the Schlick-GGX approximation and Schlick's Fresnel, with
+// the lights of `lighting::light_declarations`. Ambient light comes from an
+// `ibl::Environment` when one is set (irradiance for diffuse, prefiltered
+// environment and BRDF lookup table for specular), and is a flat color
+// otherwise.
+//
+// Meshes need "position" and "normal" attributes; "uv" and "tangent" are
+// used for texture maps and normal mapping when present, as produced by
+// `Geometry::to_mesh` and the glTF loader.
+//
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#appendix-b-brdf-implementation
+// https://learnopengl.com/PBR/Theory
+
+extern crate nalgebra_glm as glm;
+
+use std::collections::HashMap;
+
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+use crate::camera::Camera;
+use crate::ibl::Environment;
+use crate::lighting::{light_declarations, LightUniforms};
+use crate::loaders::gltf::{AlphaMode, Gltf, GltfTexture, PbrMaterial as GltfMaterial};
+use crate::scene::{Material, NodeId, Renderer, Scene};
+use crate::shadow::{shadow_declarations, ShadowMaps, ShadowUniforms};
+use crate::state::GlState;
+use crate::texture::{decode_png, Sampler, Texture};
+use crate::webgl::{compile_shader, link_shader_program};
+
+#[derive(Clone, Debug)]
+pub struct PbrMaterial {
+    /// Linear RGBA; alpha is opacity.
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    /// Fragments with a lower alpha are discarded (glTF's MASK mode).
+    pub alpha_cutoff: Option<f32>,
+    /// sRGB; multiplied by `base_color`.
+    pub base_color_map: Option<Texture>,
+    /// Roughness in G, metalness in B; multiplied by the factors.
+    pub metallic_roughness_map: Option<Texture>,
+    /// Tangent space; needs tangents on the mesh.
+    pub normal_map: Option<Texture>,
+    /// Ambient occlusion in R.
+    pub occlusion_map: Option<Texture>,
+    /// sRGB; multiplied by `emissive`.
+    pub emissive_map: Option<Texture>,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        PbrMaterial {
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: [0.0, 0.0, 0.0],
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: None,
+            base_color_map: None,
+            metallic_roughness_map: None,
+            normal_map: None,
+            occlusion_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
+impl PbrMaterial {
+    pub fn new(color: [f32; 3], metallic: f32, roughness: f32) -> Self {
+        let [r, g, b] = color;
+
+        PbrMaterial {
+            base_color: [r, g, b, 1.0],
+            metallic,
+            roughness,
+            ..Self::default()
+        }
+    }
+}
+
+static VS_SRC: &str = r#"#version 300 es
+in vec3 position;
+in vec3 normal;
+in vec4 tangent;
+in vec2 uv;
+
+uniform mat4 uModelViewMatrix;
+uniform mat4 uProjectionMatrix;
+uniform mat3 uNormalMatrix;
+
+out vec3 vPosition;
+out vec3 vNormal;
+out vec4 vTangent;
+out vec2 vUv;
+
+void main() {
+  vec4 p = uModelViewMatrix * vec4(position, 1.0);
+  vPosition = p.xyz;
+  vNormal = uNormalMatrix * normal;
+  vTangent = vec4(mat3(uModelViewMatrix) * tangent.xyz, tangent.w);
+  vUv = uv;
+  gl_Position = uProjectionMatrix * p;
+}
+"#;
+
+/// `vec3 toneMap(vec3 color)`: exposure, then Reinhard and gamma when
+/// `uToneMap` is set. Leave it off when rendering into an HDR target that
+/// is tone mapped later.
+pub(crate) static TONE_MAPPING: &str = r#"
+uniform float uExposure;
+uniform bool uToneMap;
+
+vec3 toneMap(vec3 color) {
+  color *= uExposure;
+  if (uToneMap) {
+    color = color / (color + 1.0);
+    color = pow(color, vec3(1.0 / 2.2));
+  }
+  return color;
+}
+"#;
+
+/// The metallic-roughness fragment shader for up to `max_lights` lights.
+pub fn pbr_fragment_shader(max_lights: usize) -> String {
+    format!(
+        r#"#version 300 es
+precision highp float;
+
+{lights}
+{shadows}
+{tone_mapping}
+uniform vec3 uAmbientLight;
+
+uniform bool uHasEnvironment;
+uniform samplerCube uIrradiance;
+uniform samplerCube uPrefiltered;
+uniform sampler2D uBrdfLut;
+uniform float uPrefilteredLevels;
+uniform float uEnvironmentIntensity;
+uniform mat3 uViewToWorld;
+
+uniform vec4 uBaseColor;
+uniform float uMetallic;
+uniform float uRoughness;
+uniform vec3 uEmissive;
+uniform float uNormalScale;
+uniform float uOcclusionStrength;
+uniform float uAlphaCutoff;
+
+uniform sampler2D uBaseColorMap;
+uniform sampler2D uMetallicRoughnessMap;
+uniform sampler2D uNormalMap;
+uniform sampler2D uOcclusionMap;
+uniform sampler2D uEmissiveMap;
+
+in vec3 vPosition;
+in vec3 vNormal;
+in vec4 vTangent;
+in vec2 vUv;
+
+out vec4 fragColor;
+
+const float PI = 3.14159265359;
+
+float distributionGGX(float nh, float roughness) {{
+  float a = roughness * roughness;
+  float a2 = a * a;
+  float d = nh * nh * (a2 - 1.0) + 1.0;
+  return a2 / (PI * d * d);
+}}
+
+float geometrySchlickGGX(float nx, float k) {{
+  return nx / (nx * (1.0 - k) + k);
+}}
+
+float geometrySmith(float nv, float nl, float roughness) {{
+  float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+  return geometrySchlickGGX(nv, k) * geometrySchlickGGX(nl, k);
+}}
+
+vec3 fresnelSchlick(float cosTheta, vec3 f0) {{
+  return f0 + (1.0 - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}}
+
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 f0, float roughness) {{
+  return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}}
+
+vec3 surfaceNormal() {{
+  vec3 n = normalize(vNormal);
+  if (!gl_FrontFacing) n = -n;
+
+  // meshes without tangents read (0, 0, 0, 1)
+  if (dot(vTangent.xyz, vTangent.xyz) < 1e-8) return n;
+
+  vec3 t = normalize(vTangent.xyz - n * dot(n, vTangent.xyz));
+  vec3 b = cross(n, t) * vTangent.w;
+  vec3 m = texture(uNormalMap, vUv).xyz * 2.0 - 1.0;
+  m.xy *= uNormalScale;
+
+  return normalize(mat3(t, b, n) * m);
+}}
+
+void main() {{
+  vec4 base = uBaseColor * texture(uBaseColorMap, vUv);
+  if (base.a < uAlphaCutoff) discard;
+
+  vec4 metallicRoughness = texture(uMetallicRoughnessMap, vUv);
+  float metallic = clamp(uMetallic * metallicRoughness.b, 0.0, 1.0);
+  // very low roughness makes the highlight of point lights vanish
+  float roughness = clamp(uRoughness * metallicRoughness.g, 0.04, 1.0);
+
+  vec3 n = surfaceNormal();
+  vec3 v = normalize(-vPosition);
+  float nv = max(dot(n, v), 1e-4);
+
+  vec3 f0 = mix(vec3(0.04), base.rgb, metallic);
+  vec3 color = vec3(0.0);
+
+  for (int i = 0; i < MAX_LIGHTS; i++) {{
+    if (i >= uLightCount) break;
+
+    vec3 l;
+    float strength = lightIncidence(uLights[i], vPosition, l);
+    float nl = dot(n, l);
+    if (nl <= 0.0) continue;
+    strength *= shadowFactor(i, vPosition, n);
+
+    vec3 h = normalize(l + v);
+    vec3 f = fresnelSchlick(max(dot(h, v), 0.0), f0);
+    float d = distributionGGX(max(dot(n, h), 0.0), roughness);
+    float g = geometrySmith(nv, nl, roughness);
+
+    vec3 specular = d * g * f / (4.0 * nv * nl);
+    vec3 diffuse = (1.0 - f) * (1.0 - metallic) * base.rgb / PI;
+
+    color += (diffuse + specular) * uLights[i].color * strength * nl;
+  }}
+
+  vec3 ambient;
+  if (uHasEnvironment) {{
+    vec3 nw = uViewToWorld * n;
+    vec3 r = uViewToWorld * reflect(-v, n);
+
+    vec3 f = fresnelSchlickRoughness(nv, f0, roughness);
+    vec3 diffuse = (1.0 - f) * (1.0 - metallic) * base.rgb * texture(uIrradiance, nw).rgb;
+
+    vec3 prefiltered = textureLod(uPrefiltered, r, roughness * (uPrefilteredLevels - 1.0)).rgb;
+    vec2 brdf = texture(uBrdfLut, vec2(nv, roughness)).rg;
+    vec3 specular = prefiltered * (f * brdf.x + brdf.y);
+
+    ambient = (diffuse + specular) * uEnvironmentIntensity;
+  }} else {{
+    ambient = uAmbientLight * base.rgb;
+  }}
+
+  float occlusion = mix(1.0, texture(uOcclusionMap, vUv).r, uOcclusionStrength);
+  color += ambient * occlusion;
+  color += uEmissive * texture(uEmissiveMap, vUv).rgb;
+
+  fragColor = vec4(toneMap(color), base.a);
+}}
+"#,
+        lights = light_declarations(max_lights),
+        shadows = shadow_declarations(),
+        tone_mapping = TONE_MAPPING,
+    )
+}
+
+// Texture units of the material maps, then of the environment.
+const UNIT_BASE_COLOR: u32 = 0;
+const UNIT_METALLIC_ROUGHNESS: u32 = 1;
+const UNIT_NORMAL: u32 = 2;
+const UNIT_OCCLUSION: u32 = 3;
+const UNIT_EMISSIVE: u32 = 4;
+const UNIT_IRRADIANCE: u32 = 5;
+const UNIT_PREFILTERED: u32 = 6;
+const UNIT_BRDF_LUT: u32 = 7;
+
+struct MaterialLocations {
+    base_color: Option<WebGlUniformLocation>,
+    metallic: Option<WebGlUniformLocation>,
+    roughness: Option<WebGlUniformLocation>,
+    emissive: Option<WebGlUniformLocation>,
+    normal_scale: Option<WebGlUniformLocation>,
+    occlusion_strength: Option<WebGlUniformLocation>,
+    alpha_cutoff: Option<WebGlUniformLocation>,
+    base_color_map: Option<WebGlUniformLocation>,
+    metallic_roughness_map: Option<WebGlUniformLocation>,
+    normal_map: Option<WebGlUniformLocation>,
+    occlusion_map: Option<WebGlUniformLocation>,
+    emissive_map: Option<WebGlUniformLocation>,
+}
+
+struct EnvironmentLocations {
+    has_environment: Option<WebGlUniformLocation>,
+    irradiance: Option<WebGlUniformLocation>,
+    prefiltered: Option<WebGlUniformLocation>,
+    brdf_lut: Option<WebGlUniformLocation>,
+    prefiltered_levels: Option<WebGlUniformLocation>,
+    intensity: Option<WebGlUniformLocation>,
+    view_to_world: Option<WebGlUniformLocation>,
+}
+
+pub struct PbrRenderer {
+    renderer: Renderer,
+    lights: LightUniforms,
+    shadow_uniforms: ShadowUniforms,
+    material: MaterialLocations,
+    environment_locations: EnvironmentLocations,
+    loc_ambient_light: Option<WebGlUniformLocation>,
+    loc_exposure: Option<WebGlUniformLocation>,
+    loc_tone_map: Option<WebGlUniformLocation>,
+    // stand-ins for missing maps
+    white: Texture,
+    flat_normal: Texture,
+    /// Image-based ambient light; `ambient_light` is used without one.
+    pub environment: Option<Environment>,
+    pub environment_intensity: f32,
+    /// Shadows of the lights with a `Shadow`; none without.
+    pub shadows: Option<ShadowMaps>,
+    pub ambient_light: [f32; 3],
+    pub exposure: f32,
+    /// Reinhard and gamma correction on output; turn off when rendering
+    /// into a floating point target.
+    pub tone_map: bool,
+}
+
+impl PbrRenderer {
+    /// Lights past `max_lights`, in node order, are ignored.
+    pub fn new(context: &WebGl2RenderingContext, max_lights: usize) -> Result<Self, String> {
+        let max_lights = max_lights.max(1);
+
+        let vs = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, VS_SRC)?;
+        let fs = compile_shader(
+            context,
+            WebGl2RenderingContext::FRAGMENT_SHADER,
+            &pbr_fragment_shader(max_lights),
+        )?;
+        let program = link_shader_program(context, &vs, &fs)?;
+
+        let location = |name: &str| context.get_uniform_location(&program, name);
+
+        Ok(PbrRenderer {
+            lights: LightUniforms::new(context, &program, max_lights),
+            shadow_uniforms: ShadowUniforms::new(context, &program, max_lights)?,
+            material: MaterialLocations {
+                base_color: location("uBaseColor"),
+                metallic: location("uMetallic"),
+                roughness: location("uRoughness"),
+                emissive: location("uEmissive"),
+                normal_scale: location("uNormalScale"),
+                occlusion_strength: location("uOcclusionStrength"),
+                alpha_cutoff: location("uAlphaCutoff"),
+                base_color_map: location("uBaseColorMap"),
+                metallic_roughness_map: location("uMetallicRoughnessMap"),
+                normal_map: location("uNormalMap"),
+                occlusion_map: location("uOcclusionMap"),
+                emissive_map: location("uEmissiveMap"),
+            },
+            environment_locations: EnvironmentLocations {
+                has_environment: location("uHasEnvironment"),
+                irradiance: location("uIrradiance"),
+                prefiltered: location("uPrefiltered"),
+                brdf_lut: location("uBrdfLut"),
+                prefiltered_levels: location("uPrefilteredLevels"),
+                intensity: location("uEnvironmentIntensity"),
+                view_to_world: location("uViewToWorld"),
+            },
+            loc_ambient_light: location("uAmbientLight"),
+            loc_exposure: location("uExposure"),
+            loc_tone_map: location("uToneMap"),
+            renderer: Renderer::new(context, &program),
+            white: Texture::solid(context, [255, 255, 255, 255])?,
+            flat_normal: Texture::solid(context, [128, 128, 255, 255])?,
+            environment: None,
+            environment_intensity: 1.0,
+            shadows: None,
+            ambient_light: [0.03, 0.03, 0.03],
+            exposure: 1.0,
+            tone_map: true,
+        })
+    }
+
+    pub fn program(&self) -> &WebGlProgram {
+        self.renderer.program()
+    }
+
+    pub fn render(
+        &self,
+        state: &mut GlState,
+        scene: &mut Scene,
+        camera: &Camera,
+    ) -> Result<(), String> {
+        let context = &state.context().clone();
+        let view = camera.view_matrix();
+        let shadows = match &self.shadows {
+            Some(shadows) => Some(shadows.render(state, scene, camera)?),
+            None => None,
+        };
+
+        scene.update();
+        state.use_program(Some(self.program()));
+
+        context.uniform3fv_with_f32_array(self.loc_ambient_light.as_ref(), &self.ambient_light);
+        context.uniform1f(self.loc_exposure.as_ref(), self.exposure);
+        context.uniform1i(self.loc_tone_map.as_ref(), self.tone_map as i32);
+        self.lights.upload(context, scene, &view);
+        self.shadow_uniforms
+            .upload(state, scene, shadows.as_ref(), &view);
+        self.upload_environment(state, &view);
+
+        let default_material = PbrMaterial::default();
+
+        self.renderer.render_with(
+            state,
+            scene,
+            &view,
+            &camera.projection_matrix(),
+            |state, scene, id| {
+                let material = match scene.node(id).material().and_then(|m| scene.material(m)) {
+                    Some(Material::Pbr(material)) => material,
+                    _ => &default_material,
+                };
+
+                self.upload_material(state, material);
+            },
+        )
+    }
+
+    fn upload_environment(&self, state: &mut GlState, view: &glm::Mat4) {
+        let context = &state.context().clone();
+        let loc = &self.environment_locations;
+
+        // the environment samplers must point at cube maps even when unused
+        context.uniform1i(loc.irradiance.as_ref(), UNIT_IRRADIANCE as i32);
+        context.uniform1i(loc.prefiltered.as_ref(), UNIT_PREFILTERED as i32);
+        context.uniform1i(loc.brdf_lut.as_ref(), UNIT_BRDF_LUT as i32);
+
+        let environment = match &self.environment {
+            None => {
+                context.uniform1i(loc.has_environment.as_ref(), 0);
+                return;
+            }
+            Some(environment) => environment,
+        };
+
+        let textures = [
+            (
+                UNIT_IRRADIANCE,
+                WebGl2RenderingContext::TEXTURE_CUBE_MAP,
+                environment.irradiance(),
+            ),
+            (
+                UNIT_PREFILTERED,
+                WebGl2RenderingContext::TEXTURE_CUBE_MAP,
+                environment.prefiltered(),
+            ),
+            (
+                UNIT_BRDF_LUT,
+                WebGl2RenderingContext::TEXTURE_2D,
+                environment.brdf_lut(),
+            ),
+        ];
+        for (unit, target, texture) in textures {
+            state.bind_texture(unit, target, Some(texture));
+        }
+
+        // the view matrix is a rotation and a translation, so the inverse of
+        // its rotation is the transpose
+        let view_to_world = glm::transpose(&glm::mat4_to_mat3(view));
+
+        context.uniform1i(loc.has_environment.as_ref(), 1);
+        context.uniform1f(
+            loc.prefiltered_levels.as_ref(),
+            environment.prefiltered_levels() as f32,
+        );
+        context.uniform1f(loc.intensity.as_ref(), self.environment_intensity);
+        context.uniform_matrix3fv_with_f32_array(
+            loc.view_to_world.as_ref(),
+            false,
+            view_to_world.as_slice(),
+        );
+    }
+
+    fn upload_material(&self, state: &mut GlState, material: &PbrMaterial) {
+        let context = &state.context().clone();
+        let loc = &self.material;
+
+        context.uniform4fv_with_f32_array(loc.base_color.as_ref(), &material.base_color);
+        context.uniform1f(loc.metallic.as_ref(), material.metallic);
+        context.uniform1f(loc.roughness.as_ref(), material.roughness);
+        context.uniform3fv_with_f32_array(loc.emissive.as_ref(), &material.emissive);
+        context.uniform1f(loc.normal_scale.as_ref(), material.normal_scale);
+        context.uniform1f(loc.occlusion_strength.as_ref(), material.occlusion_strength);
+        context.uniform1f(
+            loc.alpha_cutoff.as_ref(),
+            material.alpha_cutoff.unwrap_or(0.0),
+        );
+
+        let maps = [
+            (
+                UNIT_BASE_COLOR,
+                &material.base_color_map,
+                &loc.base_color_map,
+                &self.white,
+            ),
+            (
+                UNIT_METALLIC_ROUGHNESS,
+                &material.metallic_roughness_map,
+                &loc.metallic_roughness_map,
+                &self.white,
+            ),
+            (
+                UNIT_NORMAL,
+                &material.normal_map,
+                &loc.normal_map,
+                &self.flat_normal,
+            ),
+            (
+                UNIT_OCCLUSION,
+                &material.occlusion_map,
+                &loc.occlusion_map,
+                &self.white,
+            ),
+            (
+                UNIT_EMISSIVE,
+                &material.emissive_map,
+                &loc.emissive_map,
+                &self.white,
+            ),
+        ];
+
+        for (unit, map, location, fallback) in maps {
+            let map = map.as_ref().unwrap_or(fallback);
+            state.bind_texture(
+                unit,
+                WebGl2RenderingContext::TEXTURE_2D,
+                Some(map.texture()),
+            );
+            context.uniform1i(location.as_ref(), unit as i32);
+        }
+    }
+}
+
+/// Adds the default scene of `gltf` to `scene`: a node per glTF node, with
+/// a child per primitive holding its mesh and material. Returns the new
+/// root nodes.
+///
+/// Only PNG images are decoded; textures in other formats are left out,
+/// with a warning in the log.
+pub fn add_gltf(
+    state: &mut GlState,
+    scene: &mut Scene,
+    gltf: &Gltf,
+) -> Result<Vec<NodeId>, String> {
+    let context = &state.context().clone();
+    let mut textures = TextureCache {
+        context,
+        gltf,
+        loaded: HashMap::new(),
+    };
+
+    let materials = gltf
+        .materials
+        .iter()
+        .map(|material| {
+            let material = pbr_material(material, &mut textures)?;
+            Ok(scene.add_material(material))
+        })
+        .collect::<Result<Vec<_>, String>>();
+    // uploading the textures binds them on the context itself
+    state.invalidate();
+    let materials = materials?;
+
+    let meshes = gltf
+        .meshes
+        .iter()
+        .map(|mesh| {
+            mesh.primitives
+                .iter()
+                .map(|primitive| {
+                    let mesh = scene.add_mesh(primitive.geometry.to_mesh(state)?);
+                    Ok((
+                        mesh,
+                        primitive.material.and_then(|m| materials.get(m).copied()),
+                    ))
+                })
+                .collect::<Result<Vec<_>, String>>()
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut roots = Vec::new();
+    // a node reached twice means a cycle, see `Gltf::world_nodes`
+    let mut visited = vec![false; gltf.nodes.len()];
+    let mut stack = gltf
+        .roots()
+        .iter()
+        .map(|&index| (index, None))
+        .collect::<Vec<_>>();
+
+    while let Some((index, parent)) = stack.pop() {
+        let source = gltf
+            .nodes
+            .get(index)
+            .ok_or_else(|| format!("Node {} does not exist", index))?;
+        if std::mem::replace(&mut visited[index], true) {
+            return Err(format!("Node {} is reached twice", index));
+        }
+
+        let id = scene.add_node(parent);
+        if parent.is_none() {
+            roots.push(id);
+        }
+
+        let [x, y, z, w] = source.rotation;
+        scene.set_translation(id, glm::make_vec3(&source.translation));
+        scene.set_rotation(id, glm::quat(x, y, z, w));
+        scene.set_scale(id, glm::make_vec3(&source.scale));
+        if let Some(name) = &source.name {
+            scene.set_name(id, name);
+        }
+
+        for &(mesh, material) in source
+            .mesh
+            .and_then(|m| meshes.get(m))
+            .into_iter()
+            .flatten()
+        {
+            let primitive = scene.add_node(Some(id));
+            scene.attach_mesh(primitive, mesh);
+            if let Some(material) = material {
+                scene.set_material(primitive, material);
+            }
+        }
+
+        stack.extend(source.children.iter().map(|&child| (child, Some(id))));
+    }
+
+    for warning in &textures.gltf.warnings {
+        crate::utils::log(warning);
+    }
+
+    Ok(roots)
+}
+
+fn pbr_material(
+    material: &GltfMaterial,
+    textures: &mut TextureCache,
+) -> Result<PbrMaterial, String> {
+    Ok(PbrMaterial {
+        base_color: material.base_color_factor,
+        metallic: material.metallic_factor,
+        roughness: material.roughness_factor,
+        emissive: material.emissive_factor,
+        normal_scale: material.normal_scale,
+        occlusion_strength: material.occlusion_strength,
+        alpha_cutoff: match material.alpha_mode {
+            AlphaMode::Mask => Some(material.alpha_cutoff),
+            _ => None,
+        },
+        base_color_map: textures.get(material.base_color_texture, true)?,
+        metallic_roughness_map: textures.get(material.metallic_roughness_texture, false)?,
+        normal_map: textures.get(material.normal_texture, false)?,
+        occlusion_map: textures.get(material.occlusion_texture, false)?,
+        emissive_map: textures.get(material.emissive_texture, true)?,
+    })
+}
+
+/// glTF textures, uploaded once per color space.
+struct TextureCache<'a> {
+    context: &'a WebGl2RenderingContext,
+    gltf: &'a Gltf,
+    loaded: HashMap<(usize, bool), Option<Texture>>,
+}
+
+impl TextureCache<'_> {
+    fn get(&mut self, index: Option<usize>, srgb: bool) -> Result<Option<Texture>, String> {
+        let index = match index {
+            None => return Ok(None),
+            Some(index) => index,
+        };
+
+        if let Some(texture) = self.loaded.get(&(index, srgb)) {
+            return Ok(texture.clone());
+        }
+
+        let texture = self.load(index, srgb)?;
+        self.loaded.insert((index, srgb), texture.clone());
+
+        Ok(texture)
+    }
+
+    fn load(&self, index: usize, srgb: bool) -> Result<Option<Texture>, String> {
+        let source = self
+            .gltf
+            .textures
+            .get(index)
+            .ok_or_else(|| format!("Texture {} does not exist", index))?;
+        let image = match source.image.and_then(|i| self.gltf.images.get(i)) {
+            None => return Ok(None),
+            Some(image) => image,
+        };
+
+        if image.mime_type.as_deref().unwrap_or("image/png") != "image/png" || image.data.is_empty()
+        {
+            crate::utils::log(&format!(
+                "Texture {}: only embedded PNG images are supported",
+                index
+            ));
+            return Ok(None);
+        }
+
+        let (width, height, pixels) = decode_png(&image.data)?;
+        let texture =
+            Texture::from_rgba8(self.context, width, height, &pixels, srgb, sampler(source))?;
+
+        Ok(Some(texture))
+    }
+}
+
+fn sampler(texture: &GltfTexture) -> Sampler {
+    let default = Sampler::default();
+
+    Sampler {
+        mag_filter: texture.mag_filter.unwrap_or(default.mag_filter),
+        min_filter: texture.min_filter.unwrap_or(default.min_filter),
+        wrap_s: texture.wrap_s,
+        wrap_t: texture.wrap_t,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samplers_follow_gltf() {
+        let texture = GltfTexture {
+            image: Some(0),
+            mag_filter: Some(WebGl2RenderingContext::NEAREST),
+            min_filter: None,
+            wrap_s: WebGl2RenderingContext::CLAMP_TO_EDGE,
+            wrap_t: WebGl2RenderingContext::REPEAT,
+        };
+
+        let sampler = sampler(&texture);
+        assert_eq!(sampler.mag_filter, WebGl2RenderingContext::NEAREST);
+        assert_eq!(
+            sampler.min_filter,
+            WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR
+        );
+        assert_eq!(sampler.wrap_s, WebGl2RenderingContext::CLAMP_TO_EDGE);
+    }
+
+    #[test]
+    fn shaders_avoid_reserved_words() {
+        use crate::webgl::reserved_words_in;
+
+        assert!(reserved_words_in(VS_SRC).is_empty());
+        assert!(reserved_words_in(&pbr_fragment_shader(4)).is_empty());
+    }
+}
@@ -0,0 +1,453 @@
+// Fixed-function state as values: depth, blending, culling, masks, polygon
+// offset, stencil and clearing, described once per kind of draw instead of
+// set call by call.
+//
+// A `PipelineState` is built with `const fn`s, so it can live in a `static`
+// next to the shaders it goes with. `apply` sets all of it through `GlState`,
+// whose cache turns the description into only the calls that differ from
+// what the previous draw left behind.
+// https://developer.mozilla.org/en-US/docs/Web/API/WebGL_API/WebGL_best_practices
+use web_sys::WebGl2RenderingContext as Gl;
+
+use crate::state::{GlContext, GlState};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Depth {
+    pub func: u32,
+    pub write: bool,
+}
+
+/// Equations and factors of `blendEquationSeparate` and `blendFuncSeparate`.
+/// https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/blendFuncSeparate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Blend {
+    pub color_equation: u32,
+    pub alpha_equation: u32,
+    pub src_color: u32,
+    pub dst_color: u32,
+    pub src_alpha: u32,
+    pub dst_alpha: u32,
+}
+
+impl Blend {
+    const fn add(src_color: u32, dst_color: u32, src_alpha: u32, dst_alpha: u32) -> Blend {
+        Blend {
+            color_equation: Gl::FUNC_ADD,
+            alpha_equation: Gl::FUNC_ADD,
+            src_color,
+            dst_color,
+            src_alpha,
+            dst_alpha,
+        }
+    }
+
+    /// Straight alpha: `src * a + dst * (1 - a)`.
+    pub const ALPHA: Blend = Blend::add(
+        Gl::SRC_ALPHA,
+        Gl::ONE_MINUS_SRC_ALPHA,
+        Gl::ONE,
+        Gl::ONE_MINUS_SRC_ALPHA,
+    );
+
+    /// For colors already multiplied by their alpha: `src + dst * (1 - a)`.
+    pub const PREMULTIPLIED: Blend = Blend::add(
+        Gl::ONE,
+        Gl::ONE_MINUS_SRC_ALPHA,
+        Gl::ONE,
+        Gl::ONE_MINUS_SRC_ALPHA,
+    );
+
+    /// Light adding up, e.g. particles: `src * a + dst`.
+    pub const ADDITIVE: Blend = Blend::add(Gl::SRC_ALPHA, Gl::ONE, Gl::ONE, Gl::ONE);
+
+    /// Darkening, e.g. shadows or tints: `src * dst`, keeping the alpha.
+    pub const MULTIPLY: Blend = Blend::add(Gl::DST_COLOR, Gl::ZERO, Gl::ZERO, Gl::ONE);
+}
+
+/// The same test and operations for front and back faces.
+/// https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/stencilOp
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stencil {
+    pub func: u32,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub fail: u32,
+    pub depth_fail: u32,
+    pub pass: u32,
+    pub write_mask: u32,
+}
+
+impl Stencil {
+    /// Writes `reference` wherever something is drawn, e.g. to mark an area.
+    pub const fn write(reference: i32) -> Stencil {
+        Stencil {
+            func: Gl::ALWAYS,
+            reference,
+            read_mask: 0xff,
+            fail: Gl::KEEP,
+            depth_fail: Gl::KEEP,
+            pass: Gl::REPLACE,
+            write_mask: 0xff,
+        }
+    }
+
+    /// Draws only where the stencil equals `reference`, leaving it as is.
+    pub const fn equal(reference: i32) -> Stencil {
+        Stencil {
+            func: Gl::EQUAL,
+            reference,
+            read_mask: 0xff,
+            fail: Gl::KEEP,
+            depth_fail: Gl::KEEP,
+            pass: Gl::KEEP,
+            write_mask: 0x00,
+        }
+    }
+}
+
+/// Depth pushed back by `factor` times the slope of the polygon plus
+/// `units` of depth resolution, e.g. against shadow acne.
+/// https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/polygonOffset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolygonOffset {
+    pub factor: f32,
+    pub units: f32,
+}
+
+/// Values `clear` fills the buffers with; `None` leaves a buffer alone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearValues {
+    pub color: Option<[f32; 4]>,
+    pub depth: Option<f32>,
+    pub stencil: Option<i32>,
+}
+
+/// Everything a draw needs set besides its program and buffers. `new` is the
+/// WebGL default: no depth test, blending, culling or stencil test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PipelineState {
+    depth: Option<Depth>,
+    blend: Option<Blend>,
+    cull: Option<u32>,
+    color_mask: [bool; 4],
+    polygon_offset: Option<PolygonOffset>,
+    stencil: Option<Stencil>,
+    clear: ClearValues,
+}
+
+impl Default for PipelineState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PipelineState {
+    pub const fn new() -> Self {
+        PipelineState {
+            depth: None,
+            blend: None,
+            cull: None,
+            color_mask: [true; 4],
+            polygon_offset: None,
+            stencil: None,
+            clear: ClearValues {
+                color: None,
+                depth: None,
+                stencil: None,
+            },
+        }
+    }
+
+    /// Depth tested with LESS and written, culling back faces: solid 3D.
+    pub const fn opaque() -> Self {
+        Self::new().depth(Gl::LESS, true).cull(Gl::BACK)
+    }
+
+    /// Blended with `blend` over what is drawn, depth tested but not
+    /// written, so transparent surfaces do not hide each other.
+    pub const fn transparent(blend: Blend) -> Self {
+        Self::new().depth(Gl::LEQUAL, false).blend(blend)
+    }
+
+    pub const fn depth(self, func: u32, write: bool) -> Self {
+        PipelineState {
+            depth: Some(Depth { func, write }),
+            ..self
+        }
+    }
+
+    pub const fn no_depth(self) -> Self {
+        PipelineState {
+            depth: None,
+            ..self
+        }
+    }
+
+    pub const fn blend(self, blend: Blend) -> Self {
+        PipelineState {
+            blend: Some(blend),
+            ..self
+        }
+    }
+
+    /// `face` is BACK, FRONT or FRONT_AND_BACK.
+    pub const fn cull(self, face: u32) -> Self {
+        PipelineState {
+            cull: Some(face),
+            ..self
+        }
+    }
+
+    pub const fn no_cull(self) -> Self {
+        PipelineState { cull: None, ..self }
+    }
+
+    /// Which of red, green, blue and alpha are written.
+    pub const fn color_mask(self, color_mask: [bool; 4]) -> Self {
+        PipelineState { color_mask, ..self }
+    }
+
+    pub const fn polygon_offset(self, factor: f32, units: f32) -> Self {
+        PipelineState {
+            polygon_offset: Some(PolygonOffset { factor, units }),
+            ..self
+        }
+    }
+
+    pub const fn stencil(self, stencil: Stencil) -> Self {
+        PipelineState {
+            stencil: Some(stencil),
+            ..self
+        }
+    }
+
+    pub const fn clear_color(self, rgba: [f32; 4]) -> Self {
+        PipelineState {
+            clear: ClearValues {
+                color: Some(rgba),
+                ..self.clear
+            },
+            ..self
+        }
+    }
+
+    pub const fn clear_depth(self, depth: f32) -> Self {
+        PipelineState {
+            clear: ClearValues {
+                depth: Some(depth),
+                ..self.clear
+            },
+            ..self
+        }
+    }
+
+    pub const fn clear_stencil(self, stencil: i32) -> Self {
+        PipelineState {
+            clear: ClearValues {
+                stencil: Some(stencil),
+                ..self.clear
+            },
+            ..self
+        }
+    }
+
+    pub fn clear_values(&self) -> ClearValues {
+        self.clear
+    }
+
+    /// The buffers `clear` clears, as the mask `clear()` takes.
+    pub fn clear_mask(&self) -> u32 {
+        let ClearValues {
+            color,
+            depth,
+            stencil,
+        } = self.clear;
+
+        [
+            (color.is_some(), Gl::COLOR_BUFFER_BIT),
+            (depth.is_some(), Gl::DEPTH_BUFFER_BIT),
+            (stencil.is_some(), Gl::STENCIL_BUFFER_BIT),
+        ]
+        .iter()
+        .filter(|(clear, _)| *clear)
+        .fold(0, |mask, (_, bit)| mask | bit)
+    }
+
+    /// Sets the context up for drawing with this state.
+    pub fn apply<C: GlContext>(&self, state: &mut GlState<C>) {
+        match self.depth {
+            Some(Depth { func, write }) => {
+                state.enable(Gl::DEPTH_TEST);
+                state.depth_func(func);
+                state.depth_mask(write);
+            }
+            None => state.disable(Gl::DEPTH_TEST),
+        }
+
+        match self.blend {
+            Some(blend) => {
+                state.enable(Gl::BLEND);
+                state.blend_equation(blend.color_equation, blend.alpha_equation);
+                state.blend_func(
+                    blend.src_color,
+                    blend.dst_color,
+                    blend.src_alpha,
+                    blend.dst_alpha,
+                );
+            }
+            None => state.disable(Gl::BLEND),
+        }
+
+        match self.cull {
+            Some(face) => {
+                state.enable(Gl::CULL_FACE);
+                state.cull_face(face);
+            }
+            None => state.disable(Gl::CULL_FACE),
+        }
+
+        state.color_mask(self.color_mask);
+
+        match self.polygon_offset {
+            Some(PolygonOffset { factor, units }) => {
+                state.enable(Gl::POLYGON_OFFSET_FILL);
+                state.polygon_offset(factor, units);
+            }
+            None => state.disable(Gl::POLYGON_OFFSET_FILL),
+        }
+
+        match self.stencil {
+            Some(stencil) => {
+                state.enable(Gl::STENCIL_TEST);
+                state.stencil_func(stencil.func, stencil.reference, stencil.read_mask);
+                state.stencil_op(stencil.fail, stencil.depth_fail, stencil.pass);
+                state.stencil_mask(stencil.write_mask);
+            }
+            None => state.disable(Gl::STENCIL_TEST),
+        }
+    }
+
+    /// Clears the buffers that have clear values. Clearing goes through the
+    /// write masks, so the ones it needs are opened first; call `apply`
+    /// after it.
+    pub fn clear<C: GlContext>(&self, state: &mut GlState<C>) {
+        let ClearValues {
+            color,
+            depth,
+            stencil,
+        } = self.clear;
+
+        if let Some([r, g, b, a]) = color {
+            state.color_mask([true; 4]);
+            state.clear_color(r, g, b, a);
+        }
+        if let Some(depth) = depth {
+            state.depth_mask(true);
+            state.clear_depth(depth);
+        }
+        if let Some(stencil) = stencil {
+            state.stencil_mask(0xff);
+            state.clear_stencil(stencil);
+        }
+
+        let mask = self.clear_mask();
+        if mask != 0 {
+            state.clear(mask);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::recorder::Recorder;
+
+    #[test]
+    fn clear_mask_follows_clear_values() {
+        assert_eq!(PipelineState::new().clear_mask(), 0);
+
+        let color_depth = PipelineState::opaque()
+            .clear_color([0.0, 0.0, 0.0, 1.0])
+            .clear_depth(1.0);
+        assert_eq!(
+            color_depth.clear_mask(),
+            Gl::COLOR_BUFFER_BIT | Gl::DEPTH_BUFFER_BIT
+        );
+
+        let stencil = PipelineState::new().clear_stencil(0);
+        assert_eq!(stencil.clear_mask(), Gl::STENCIL_BUFFER_BIT);
+    }
+
+    #[test]
+    fn builders_change_one_field() {
+        let transparent = PipelineState::transparent(Blend::ADDITIVE);
+        let culled = transparent.cull(Gl::BACK);
+
+        assert_eq!(culled.blend, Some(Blend::ADDITIVE));
+        assert_eq!(
+            culled.depth,
+            Some(Depth {
+                func: Gl::LEQUAL,
+                write: false
+            })
+        );
+        assert_eq!(culled.no_cull(), transparent);
+    }
+
+    #[test]
+    fn applying_again_changes_nothing() {
+        let recorder = Recorder::default();
+        let mut state = GlState::new(&recorder);
+        let opaque = PipelineState::opaque();
+
+        opaque.apply(&mut state);
+        let first = recorder.take();
+        opaque.apply(&mut state);
+
+        assert!(recorder.take().is_empty());
+        assert_eq!(state.take_stats().issued as usize, first.len());
+
+        // only what differs reaches the context
+        PipelineState::transparent(Blend::ALPHA)
+            .cull(Gl::BACK)
+            .apply(&mut state);
+        assert_eq!(
+            recorder.take(),
+            [
+                format!("depth_func {:#x}", Gl::LEQUAL),
+                String::from("depth_mask false"),
+                format!("enable {:#x}", Gl::BLEND),
+                format!("blend_equation {:#x} {:#x}", Gl::FUNC_ADD, Gl::FUNC_ADD),
+                format!(
+                    "blend_func {:#x} {:#x} {:#x} {:#x}",
+                    Gl::SRC_ALPHA,
+                    Gl::ONE_MINUS_SRC_ALPHA,
+                    Gl::ONE,
+                    Gl::ONE_MINUS_SRC_ALPHA
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn polygon_offset_comes_with_its_capability() {
+        let recorder = Recorder::default();
+        let mut state = GlState::new(&recorder);
+        let depth_pass = PipelineState::new()
+            .depth(Gl::LESS, true)
+            .polygon_offset(2.0, 1.0);
+
+        PipelineState::new().apply(&mut state);
+        recorder.take();
+
+        depth_pass.apply(&mut state);
+        let calls = recorder.take();
+        assert!(calls.contains(&format!("enable {:#x}", Gl::POLYGON_OFFSET_FILL)));
+        assert!(calls.contains(&String::from("polygon_offset 2 1")));
+
+        PipelineState::new().apply(&mut state);
+        assert!(recorder
+            .take()
+            .contains(&format!("disable {:#x}", Gl::POLYGON_OFFSET_FILL)));
+    }
+}
@@ -0,0 +1,1147 @@
+// Screen-space effects applied, in order, to a rendered frame.
+//
+//   let mut post = PostProcess::new(&context, width, height)?;
+//   post.push(&mut state, EffectKind::Bloom)?;
+//   post.push(&mut state, EffectKind::Vignette)?;
+//
+//   // every frame
+//   post.begin(&mut state, width, height)?;  // draw the scene after this
+//   ...
+//   post.end(&mut state)?;                   // effects, onto the canvas
+//
+// The scene is drawn into a target with a depth buffer; each enabled effect
+// then reads the previous result and writes into one of two ping-pong
+// targets, the last one straight into the canvas. Effects are fullscreen
+// passes whose knobs are annotated uniforms (see params.rs), set by name
+// like the parameters of a `GlBox` shader. The passes draw through the
+// `GlState` with `PASS_PIPELINE`, which is left applied.
+//
+// With float targets (`set_precision`) the frame keeps values above 1 until
+// the end of the chain: the chain is then linear, tone mapping brings it
+// into range and the last pass encodes it to sRGB for the canvas.
+
+use web_sys::{
+    WebGl2RenderingContext, WebGlProgram, WebGlTexture, WebGlUniformLocation,
+    WebGlVertexArrayObject,
+};
+
+use crate::params::ShaderParameters;
+use crate::pipeline::{Blend, PipelineState};
+use crate::state::GlState;
+use crate::target::{supported_precision, Precision, RenderTarget};
+use crate::webgl::fullscreen_program;
+
+/// Fullscreen passes: no depth test, blending or culling.
+static PASS_PIPELINE: PipelineState = PipelineState::new();
+
+// bloom levels are added onto the next larger one
+static UPSAMPLE_PIPELINE: PipelineState = PipelineState::new().blend(Blend::ADDITIVE);
+
+/// Declarations shared by every effect: the frame so far, the size of the
+/// output in pixels and the time in seconds. Effects write their result
+/// with `finish`, which encodes it to sRGB on the last pass of a linear
+/// chain.
+static EFFECT_HEADER: &str = r#"#version 300 es
+precision highp float;
+
+uniform sampler2D uInput;
+uniform vec2 uResolution;
+uniform float uTime;
+// the chain holds linear values, see `PostProcess::set_precision`
+uniform bool uLinear;
+uniform bool uEncodeSrgb;
+
+in vec2 vNdc;
+out vec4 fragColor;
+
+// https://en.wikipedia.org/wiki/SRGB#Transformation
+vec3 linearToSrgb(vec3 c) {
+  c = clamp(c, 0.0, 1.0);
+  return mix(12.92 * c, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+vec4 finish(vec3 color) {
+  return vec4(uEncodeSrgb ? linearToSrgb(color) : color, 1.0);
+}
+"#;
+
+static BLOOM_FS: &str = r#"
+uniform sampler2D uBloom;
+
+uniform float uThreshold; // @range(0.0, 4.0) @default(0.8) @label("Threshold")
+uniform float uKnee;      // @range(0.0, 1.0) @default(0.5) @label("Knee")
+uniform float uIntensity; // @range(0.0, 4.0) @default(0.8) @label("Intensity")
+uniform float uRadius;    // @range(0.2, 2.0) @default(1.0) @label("Radius")
+
+void main() {
+  vec2 uv = vNdc * 0.5 + 0.5;
+  fragColor = finish(texture(uInput, uv).rgb + texture(uBloom, uv).rgb * uIntensity);
+}
+"#;
+
+static TONE_MAPPING_FS: &str = r#"
+uniform float uExposure; // @range(0.0, 8.0) @default(1.0) @label("Exposure")
+uniform int uOperator;   // @range(0, 2) @default(1) @label("Reinhard / ACES / AgX")
+
+// Narkowicz, "ACES Filmic Tone Mapping Curve" (2015)
+vec3 aces(vec3 x) {
+  return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// After Sobotka's AgX, in the polynomial fit by Wrensch ("Minimal AgX
+// implementation", 2023). Its curve outputs display values, so they are
+// decoded back to linear for the sRGB encoding at the end of the chain.
+vec3 agx(vec3 x) {
+  const mat3 inset = mat3(
+    0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+    0.0784335999999992, 0.878468636469772, 0.0784336,
+    0.0792237451477643, 0.0791661274605434, 0.879142973793104
+  );
+  const mat3 outset = mat3(
+    1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+    -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+    -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+  );
+  const float minEv = -12.47393;
+  const float maxEv = 4.026069;
+
+  x = inset * max(x, 1e-10);
+  x = clamp((log2(x) - minEv) / (maxEv - minEv), 0.0, 1.0);
+
+  vec3 x2 = x * x;
+  vec3 x4 = x2 * x2;
+  x = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x
+    + 0.4298 * x2 + 0.1191 * x - 0.00232;
+
+  return pow(max(outset * x, 0.0), vec3(2.2));
+}
+
+void main() {
+  vec3 color = texture(uInput, vNdc * 0.5 + 0.5).rgb * uExposure;
+
+  if (uOperator == 0) {
+    color = color / (color + 1.0);
+  } else if (uOperator == 1) {
+    color = aces(color);
+  } else {
+    color = agx(color);
+  }
+
+  fragColor = finish(color);
+}
+"#;
+
+static VIGNETTE_FS: &str = r#"
+uniform float uIntensity; // @range(0.0, 1.0) @default(0.5) @label("Intensity")
+uniform float uRadius;    // @range(0.0, 1.5) @default(0.75) @label("Radius")
+uniform float uSoftness;  // @range(0.01, 1.0) @default(0.45) @label("Softness")
+
+void main() {
+  vec2 uv = vNdc * 0.5 + 0.5;
+  // 0 at the center, 1 at the corners
+  float d = length(vNdc) / sqrt(2.0);
+  float shade = smoothstep(uRadius, uRadius - uSoftness, d);
+  fragColor = finish(texture(uInput, uv).rgb * mix(1.0, shade, uIntensity));
+}
+"#;
+
+static CHROMATIC_ABERRATION_FS: &str = r#"
+uniform float uStrength; // @range(0.0, 0.02) @default(0.004) @label("Strength")
+
+void main() {
+  vec2 uv = vNdc * 0.5 + 0.5;
+  // stronger towards the edges, like a lens
+  vec2 offset = (uv - 0.5) * uStrength;
+  fragColor = finish(vec3(
+    texture(uInput, uv + offset).r,
+    texture(uInput, uv).g,
+    texture(uInput, uv - offset).b
+  ));
+}
+"#;
+
+// After Lottes, "FXAA" (NVIDIA, 2009); the small, single pass variant.
+static FXAA_FS: &str = r#"
+uniform float uSpanMax;   // @range(1.0, 16.0) @default(8.0) @label("Span")
+uniform float uReduceMul; // @range(0.0, 0.5) @default(0.125) @label("Reduce")
+
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+const float REDUCE_MIN = 1.0 / 128.0;
+
+vec3 at(vec2 uv) {
+  return texture(uInput, uv).rgb;
+}
+
+void main() {
+  vec2 uv = vNdc * 0.5 + 0.5;
+  vec2 px = 1.0 / uResolution;
+
+  float nw = dot(at(uv + vec2(-1.0, -1.0) * px), LUMA);
+  float ne = dot(at(uv + vec2(1.0, -1.0) * px), LUMA);
+  float sw = dot(at(uv + vec2(-1.0, 1.0) * px), LUMA);
+  float se = dot(at(uv + vec2(1.0, 1.0) * px), LUMA);
+  float m = dot(at(uv), LUMA);
+
+  float lumaMin = min(m, min(min(nw, ne), min(sw, se)));
+  float lumaMax = max(m, max(max(nw, ne), max(sw, se)));
+
+  // across the edge
+  vec2 dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+  float reduce = max((nw + ne + sw + se) * 0.25 * uReduceMul, REDUCE_MIN);
+  float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+  dir = clamp(dir * scale, -uSpanMax, uSpanMax) * px;
+
+  vec3 a = 0.5 * (at(uv + dir * (1.0 / 3.0 - 0.5)) + at(uv + dir * (2.0 / 3.0 - 0.5)));
+  vec3 b = a * 0.5 + 0.25 * (at(uv - dir * 0.5) + at(uv + dir * 0.5));
+  float lumaB = dot(b, LUMA);
+
+  fragColor = finish(lumaB < lumaMin || lumaB > lumaMax ? a : b);
+}
+"#;
+
+static FILM_GRAIN_FS: &str = r#"
+uniform float uIntensity; // @range(0.0, 0.5) @default(0.08) @label("Intensity")
+uniform float uSize;      // @range(1.0, 4.0) @default(1.5) @label("Size")
+uniform float uSpeed;     // @range(0.0, 60.0) @default(24.0) @label("Frames per second")
+
+float hash(vec2 p) {
+  vec3 q = fract(vec3(p.xyx) * 0.1031);
+  q += dot(q, q.yzx + 33.33);
+  return fract((q.x + q.y) * q.z);
+}
+
+void main() {
+  vec2 uv = vNdc * 0.5 + 0.5;
+  vec3 color = texture(uInput, uv).rgb;
+
+  float frame = mod(floor(uTime * uSpeed), 256.0);
+  float grain = hash(floor(gl_FragCoord.xy / uSize) + frame * vec2(37.0, 17.0)) - 0.5;
+
+  // less visible in highlights, as on film
+  float luma = dot(color, vec3(0.299, 0.587, 0.114));
+  color += grain * uIntensity * (1.0 - 0.5 * luma);
+
+  fragColor = finish(color);
+}
+"#;
+
+static COLOR_GRADING_FS: &str = r#"
+uniform highp sampler3D uLut;
+uniform float uLutSize;
+
+uniform float uIntensity; // @range(0.0, 1.0) @default(1.0) @label("Intensity")
+
+vec3 srgbToLinear(vec3 c) {
+  return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+void main() {
+  vec3 color = texture(uInput, vNdc * 0.5 + 0.5).rgb;
+
+  // LUTs are made for display values
+  vec3 display = uLinear ? linearToSrgb(color) : clamp(color, 0.0, 1.0);
+
+  // sample at texel centers, so that the ends of the LUT map to 0 and 1
+  vec3 coord = display * (uLutSize - 1.0) / uLutSize + 0.5 / uLutSize;
+  vec3 graded = texture(uLut, coord).rgb;
+  if (uLinear) graded = srgbToLinear(graded);
+
+  fragColor = finish(mix(color, graded, uIntensity));
+}
+"#;
+
+// Internal passes of the bloom.
+
+static BLOOM_PREFILTER_FS: &str = r#"
+uniform vec2 uTexel;
+uniform float uThreshold;
+uniform float uKnee;
+
+void main() {
+  vec2 uv = vNdc * 0.5 + 0.5;
+  vec3 c = 0.25 * (
+    texture(uInput, uv + vec2(-1.0, -1.0) * uTexel).rgb +
+    texture(uInput, uv + vec2(1.0, -1.0) * uTexel).rgb +
+    texture(uInput, uv + vec2(-1.0, 1.0) * uTexel).rgb +
+    texture(uInput, uv + vec2(1.0, 1.0) * uTexel).rgb
+  );
+
+  // soft threshold: a quadratic curve for `uKnee` below the threshold
+  float brightness = max(c.r, max(c.g, c.b));
+  float soft = clamp(brightness - uThreshold + uKnee, 0.0, 2.0 * uKnee);
+  soft = soft * soft / (4.0 * uKnee + 1e-5);
+  float contribution = max(soft, brightness - uThreshold) / max(brightness, 1e-5);
+
+  fragColor = vec4(c * contribution, 1.0);
+}
+"#;
+
+static BLOOM_DOWNSAMPLE_FS: &str = r#"
+uniform vec2 uTexel;
+
+void main() {
+  vec2 uv = vNdc * 0.5 + 0.5;
+  fragColor = vec4(0.25 * (
+    texture(uInput, uv + vec2(-1.0, -1.0) * uTexel).rgb +
+    texture(uInput, uv + vec2(1.0, -1.0) * uTexel).rgb +
+    texture(uInput, uv + vec2(-1.0, 1.0) * uTexel).rgb +
+    texture(uInput, uv + vec2(1.0, 1.0) * uTexel).rgb
+  ), 1.0);
+}
+"#;
+
+static BLOOM_UPSAMPLE_FS: &str = r#"
+uniform vec2 uTexel;
+uniform float uRadius;
+
+void main() {
+  vec2 uv = vNdc * 0.5 + 0.5;
+  vec2 d = uTexel * uRadius;
+
+  // 3x3 tent, added onto the level below
+  vec3 c = 4.0 * texture(uInput, uv).rgb;
+  c += 2.0 * (
+    texture(uInput, uv + vec2(-d.x, 0.0)).rgb + texture(uInput, uv + vec2(d.x, 0.0)).rgb +
+    texture(uInput, uv + vec2(0.0, -d.y)).rgb + texture(uInput, uv + vec2(0.0, d.y)).rgb
+  );
+  c += texture(uInput, uv + vec2(-d.x, -d.y)).rgb + texture(uInput, uv + vec2(d.x, -d.y)).rgb;
+  c += texture(uInput, uv + vec2(-d.x, d.y)).rgb + texture(uInput, uv + vec2(d.x, d.y)).rgb;
+
+  fragColor = vec4(c / 16.0, 1.0);
+}
+"#;
+
+// Puts a linear frame on the canvas when no effect is enabled.
+static ENCODE_FS: &str = r#"
+void main() {
+  fragColor = finish(texture(uInput, vNdc * 0.5 + 0.5).rgb);
+}
+"#;
+
+const MAX_BLOOM_LEVELS: usize = 6;
+const DEFAULT_LUT_SIZE: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EffectKind {
+    /// Bright areas bleed light into their surroundings.
+    Bloom,
+    /// HDR to displayable colors, with exposure and gamma.
+    ToneMapping,
+    /// Darkens the corners.
+    Vignette,
+    /// Splits red and blue apart towards the edges.
+    ChromaticAberration,
+    /// Smooths jagged edges.
+    Fxaa,
+    /// Animated noise.
+    FilmGrain,
+    /// Remaps colors through a 3D lookup table, see
+    /// `PostProcess::set_color_lut`.
+    ColorGrading,
+}
+
+impl EffectKind {
+    pub const ALL: [EffectKind; 7] = [
+        EffectKind::Bloom,
+        EffectKind::ToneMapping,
+        EffectKind::Vignette,
+        EffectKind::ChromaticAberration,
+        EffectKind::Fxaa,
+        EffectKind::FilmGrain,
+        EffectKind::ColorGrading,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectKind::Bloom => "bloom",
+            EffectKind::ToneMapping => "tone-mapping",
+            EffectKind::Vignette => "vignette",
+            EffectKind::ChromaticAberration => "chromatic-aberration",
+            EffectKind::Fxaa => "fxaa",
+            EffectKind::FilmGrain => "film-grain",
+            EffectKind::ColorGrading => "color-grading",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// The complete fragment shader.
+    pub fn source(&self) -> String {
+        let body = match self {
+            EffectKind::Bloom => BLOOM_FS,
+            EffectKind::ToneMapping => TONE_MAPPING_FS,
+            EffectKind::Vignette => VIGNETTE_FS,
+            EffectKind::ChromaticAberration => CHROMATIC_ABERRATION_FS,
+            EffectKind::Fxaa => FXAA_FS,
+            EffectKind::FilmGrain => FILM_GRAIN_FS,
+            EffectKind::ColorGrading => COLOR_GRADING_FS,
+        };
+
+        format!("{}{}", EFFECT_HEADER, body)
+    }
+}
+
+/// Resources of effects that need more than their own pass.
+enum Extra {
+    None,
+    Bloom(Bloom),
+    ColorGrading(Lut),
+}
+
+pub struct Effect {
+    kind: EffectKind,
+    program: WebGlProgram,
+    parameters: ShaderParameters,
+    enabled: bool,
+    loc_input: Option<WebGlUniformLocation>,
+    loc_resolution: Option<WebGlUniformLocation>,
+    loc_time: Option<WebGlUniformLocation>,
+    loc_linear: Option<WebGlUniformLocation>,
+    loc_encode: Option<WebGlUniformLocation>,
+    // uBloom or uLut
+    loc_extra: Option<WebGlUniformLocation>,
+    loc_lut_size: Option<WebGlUniformLocation>,
+    extra: Extra,
+}
+
+impl Effect {
+    fn new(state: &mut GlState, kind: EffectKind) -> Result<Self, String> {
+        let context = &state.context().clone();
+        let source = kind.source();
+        let program = fullscreen_program(context, &source)
+            .map_err(|err| format!("{}: {}", kind.name(), err))?;
+        let parameters = ShaderParameters::new(state, &program, &source)?;
+
+        let location = |name: &str| context.get_uniform_location(&program, name);
+
+        let extra = match kind {
+            EffectKind::Bloom => Extra::Bloom(Bloom::new(context)?),
+            EffectKind::ColorGrading => Extra::ColorGrading(Lut::new(
+                context,
+                DEFAULT_LUT_SIZE,
+                &identity_lut(DEFAULT_LUT_SIZE),
+            )?),
+            _ => Extra::None,
+        };
+
+        Ok(Effect {
+            kind,
+            loc_input: location("uInput"),
+            loc_resolution: location("uResolution"),
+            loc_time: location("uTime"),
+            loc_linear: location("uLinear"),
+            loc_encode: location("uEncodeSrgb"),
+            loc_extra: location("uBloom").or_else(|| location("uLut")),
+            loc_lut_size: location("uLutSize"),
+            program,
+            parameters,
+            enabled: true,
+            extra,
+        })
+    }
+
+    pub fn kind(&self) -> EffectKind {
+        self.kind
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn parameters(&self) -> &ShaderParameters {
+        &self.parameters
+    }
+
+    /// Draws into `output`, the canvas if `None`, which gets sRGB when the
+    /// chain is linear.
+    fn apply(
+        &mut self,
+        state: &mut GlState,
+        input: &WebGlTexture,
+        output: Option<&RenderTarget>,
+        frame: &Frame,
+    ) -> Result<(), String> {
+        let Frame {
+            width,
+            height,
+            linear,
+            time,
+        } = *frame;
+
+        let context = &state.context().clone();
+
+        if let Extra::Bloom(bloom) = &mut self.extra {
+            bloom.render(state, input, width, height, &self.parameters);
+        }
+
+        match output {
+            Some(target) => target.bind_cached(state),
+            None => {
+                context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+                state.viewport(0, 0, width as i32, height as i32);
+            }
+        }
+
+        state.use_program(Some(&self.program));
+        state.bind_texture(0, WebGl2RenderingContext::TEXTURE_2D, Some(input));
+        context.uniform1i(self.loc_input.as_ref(), 0);
+        context.uniform2f(self.loc_resolution.as_ref(), width as f32, height as f32);
+        context.uniform1f(self.loc_time.as_ref(), time);
+        context.uniform1i(self.loc_linear.as_ref(), linear as i32);
+        context.uniform1i(
+            self.loc_encode.as_ref(),
+            (linear && output.is_none()) as i32,
+        );
+
+        match &self.extra {
+            Extra::None => {}
+            Extra::Bloom(bloom) => {
+                state.bind_texture(1, WebGl2RenderingContext::TEXTURE_2D, Some(bloom.result()));
+                context.uniform1i(self.loc_extra.as_ref(), 1);
+            }
+            Extra::ColorGrading(lut) => {
+                state.bind_texture(1, WebGl2RenderingContext::TEXTURE_3D, Some(&lut.texture));
+                context.uniform1i(self.loc_extra.as_ref(), 1);
+                context.uniform1f(self.loc_lut_size.as_ref(), lut.size as f32);
+            }
+        }
+
+        context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+
+        Ok(())
+    }
+}
+
+/// What every pass of a frame shares.
+struct Frame {
+    width: u32,
+    height: u32,
+    linear: bool,
+    time: f32,
+}
+
+pub struct PostProcess {
+    scene: RenderTarget,
+    ping: RenderTarget,
+    pong: RenderTarget,
+    effects: Vec<Effect>,
+    encode: Encode,
+    vao: WebGlVertexArrayObject,
+    time: f32,
+}
+
+impl PostProcess {
+    pub fn new(context: &WebGl2RenderingContext, width: u32, height: u32) -> Result<Self, String> {
+        let format = WebGl2RenderingContext::RGBA8;
+
+        Ok(PostProcess {
+            scene: RenderTarget::new(context, width, height, format, true)?,
+            ping: RenderTarget::new(context, width, height, format, false)?,
+            pong: RenderTarget::new(context, width, height, format, false)?,
+            effects: vec![],
+            encode: Encode::new(context)?,
+            vao: context
+                .create_vertex_array()
+                .ok_or_else(|| String::from("Unable to create vertex array object"))?,
+            time: 0.0,
+        })
+    }
+
+    /// Appends an effect, enabled, to the end of the chain. Each kind can
+    /// appear once.
+    pub fn push(&mut self, state: &mut GlState, kind: EffectKind) -> Result<(), String> {
+        if self.effects.iter().any(|effect| effect.kind == kind) {
+            return Err(format!("{} is already in the chain", kind.name()));
+        }
+
+        let effect = Effect::new(state, kind);
+        // a lookup table is uploaded on the context itself
+        state.invalidate();
+        self.effects.push(effect?);
+
+        Ok(())
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    /// Switches the targets to `precision`, or the best supported below it,
+    /// and returns the one used. With float targets the chain is linear:
+    /// draw linear, unclamped colors after `begin` and add tone mapping.
+    pub fn set_precision(
+        &mut self,
+        state: &mut GlState,
+        precision: Precision,
+    ) -> Result<Precision, String> {
+        let context = state.context();
+        let precision = supported_precision(context, precision);
+
+        let reallocated = [&mut self.scene, &mut self.ping, &mut self.pong]
+            .into_iter()
+            .try_for_each(|target| target.set_format(context, precision.format()));
+        state.invalidate();
+        reallocated?;
+
+        Ok(precision)
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self.scene.format() {
+            WebGl2RenderingContext::RGBA16F => Precision::Half,
+            WebGl2RenderingContext::RGBA32F => Precision::Float,
+            _ => Precision::Byte,
+        }
+    }
+
+    /// Whether the frame holds linear values, to be encoded to sRGB at the
+    /// end of the chain.
+    pub fn is_linear(&self) -> bool {
+        self.precision() != Precision::Byte
+    }
+
+    /// Multisamples the scene target with up to `samples` samples per
+    /// pixel and returns the count used; 1 turns multisampling off.
+    pub fn set_samples(&mut self, state: &mut GlState, samples: u32) -> Result<u32, String> {
+        let samples = self.scene.set_samples(state.context(), samples);
+        state.invalidate();
+
+        samples
+    }
+
+    /// Where `begin` makes the scene render to.
+    pub fn scene_target(&self) -> &RenderTarget {
+        &self.scene
+    }
+
+    pub fn set_enabled(&mut self, effect: &str, enabled: bool) -> Result<(), String> {
+        self.effect_mut(effect)?.enabled = enabled;
+
+        Ok(())
+    }
+
+    /// The annotated uniforms of `effect`, as a JSON array.
+    pub fn parameter_schema(&self, effect: &str) -> Result<String, String> {
+        self.effects
+            .iter()
+            .find(|e| e.kind.name() == effect)
+            .map(|e| e.parameters.schema_json())
+            .ok_or_else(|| format!("Unknown effect: {}", effect))
+    }
+
+    pub fn set_parameter(
+        &mut self,
+        state: &mut GlState,
+        effect: &str,
+        name: &str,
+        values: &[f32],
+    ) -> Result<(), String> {
+        self.effect_mut(effect)?.parameters.set(state, name, values)
+    }
+
+    /// Replaces the lookup table of the color grading effect with a `size`³
+    /// RGBA8 volume, red varying fastest, then green, then blue.
+    pub fn set_color_lut(
+        &mut self,
+        state: &mut GlState,
+        size: u32,
+        rgba: &[u8],
+    ) -> Result<(), String> {
+        let context = &state.context().clone();
+        let effect = self.effect_mut(EffectKind::ColorGrading.name())?;
+        let lut = Lut::new(context, size, rgba);
+        state.invalidate();
+        let lut = lut?;
+
+        if let Extra::ColorGrading(old) = &effect.extra {
+            context.delete_texture(Some(&old.texture));
+        }
+        effect.extra = Extra::ColorGrading(lut);
+
+        Ok(())
+    }
+
+    /// Seconds, for animated effects such as film grain.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Resizes the targets to the canvas and binds the scene target. Draw
+    /// the frame, including clearing it, after this.
+    pub fn begin(&mut self, state: &mut GlState, width: u32, height: u32) -> Result<(), String> {
+        let resized = self.resize(state.context(), width, height);
+        // reallocating binds textures on the context itself
+        if resized != Ok(false) {
+            state.invalidate();
+        }
+        resized?;
+
+        self.scene.bind_cached(state);
+
+        Ok(())
+    }
+
+    /// Runs the enabled effects on the frame drawn since `begin` and puts
+    /// the result on the canvas, which is left bound, with `PASS_PIPELINE`
+    /// applied and no vertex array bound.
+    pub fn end(&mut self, state: &mut GlState) -> Result<(), String> {
+        let context = &state.context().clone();
+        let linear = self.is_linear();
+        let PostProcess {
+            scene,
+            ping,
+            pong,
+            effects,
+            encode,
+            vao,
+            time,
+        } = self;
+        let frame = Frame {
+            width: scene.width(),
+            height: scene.height(),
+            linear,
+            time: *time,
+        };
+
+        PASS_PIPELINE.apply(state);
+        state.bind_vertex_array(Some(vao));
+
+        let enabled = effects
+            .iter_mut()
+            .filter(|effect| effect.enabled)
+            .collect::<Vec<_>>();
+        let count = enabled.len();
+
+        let mut input = scene.texture_resolved(context).clone();
+        let mut result = Ok(());
+
+        for (i, effect) in enabled.into_iter().enumerate() {
+            let output = match i {
+                _ if i + 1 == count => None,
+                _ if i % 2 == 0 => Some(&*ping),
+                _ => Some(&*pong),
+            };
+
+            result = effect.apply(state, &input, output, &frame);
+            if result.is_err() {
+                break;
+            }
+
+            if let Some(output) = output {
+                input = output.texture().clone();
+            }
+        }
+
+        if count == 0 && linear {
+            encode.draw(state, scene);
+        } else if count == 0 {
+            scene.copy_to_canvas(context);
+        }
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        state.viewport(0, 0, frame.width as i32, frame.height as i32);
+        // so that attributes set up after it don't land in the empty one
+        state.bind_vertex_array(None);
+
+        result
+    }
+
+    /// The targets, and the bloom levels, for a frame of `width` by
+    /// `height`; returns whether any was reallocated.
+    fn resize(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+    ) -> Result<bool, String> {
+        let format = self.scene.format();
+        let mut resized = false;
+
+        for target in [&mut self.scene, &mut self.ping, &mut self.pong] {
+            resized |= target.resize(context, width, height)?;
+        }
+        for effect in &mut self.effects {
+            if let Extra::Bloom(bloom) = &mut effect.extra {
+                resized |= bloom.resize(context, width, height, format)?;
+            }
+        }
+
+        Ok(resized)
+    }
+
+    fn effect_mut(&mut self, name: &str) -> Result<&mut Effect, String> {
+        self.effects
+            .iter_mut()
+            .find(|e| e.kind.name() == name)
+            .ok_or_else(|| format!("Unknown effect: {}", name))
+    }
+}
+
+/// Copies a linear frame to the canvas as sRGB.
+struct Encode {
+    program: WebGlProgram,
+    loc_input: Option<WebGlUniformLocation>,
+    loc_encode: Option<WebGlUniformLocation>,
+}
+
+impl Encode {
+    fn new(context: &WebGl2RenderingContext) -> Result<Self, String> {
+        let program = fullscreen_program(context, &format!("{}{}", EFFECT_HEADER, ENCODE_FS))?;
+        let location = |name: &str| context.get_uniform_location(&program, name);
+
+        Ok(Encode {
+            loc_input: location("uInput"),
+            loc_encode: location("uEncodeSrgb"),
+            program,
+        })
+    }
+
+    fn draw(&self, state: &mut GlState, source: &RenderTarget) {
+        let context = &state.context().clone();
+        let texture = source.texture_resolved(context);
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        state.viewport(0, 0, source.width() as i32, source.height() as i32);
+        state.use_program(Some(&self.program));
+        state.bind_texture(0, WebGl2RenderingContext::TEXTURE_2D, Some(texture));
+        context.uniform1i(self.loc_input.as_ref(), 0);
+        context.uniform1i(self.loc_encode.as_ref(), 1);
+        context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+    }
+}
+
+struct BloomPass {
+    program: WebGlProgram,
+    loc_input: Option<WebGlUniformLocation>,
+    loc_texel: Option<WebGlUniformLocation>,
+    loc_threshold: Option<WebGlUniformLocation>,
+    loc_knee: Option<WebGlUniformLocation>,
+    loc_radius: Option<WebGlUniformLocation>,
+}
+
+impl BloomPass {
+    fn new(context: &WebGl2RenderingContext, body: &str) -> Result<Self, String> {
+        let program = fullscreen_program(context, &format!("{}{}", EFFECT_HEADER, body))?;
+        let location = |name: &str| context.get_uniform_location(&program, name);
+
+        Ok(BloomPass {
+            loc_input: location("uInput"),
+            loc_texel: location("uTexel"),
+            loc_threshold: location("uThreshold"),
+            loc_knee: location("uKnee"),
+            loc_radius: location("uRadius"),
+            program,
+        })
+    }
+
+    /// Reads `input`, of `size`, into `output`.
+    fn draw(
+        &self,
+        state: &mut GlState,
+        input: &WebGlTexture,
+        (width, height): (u32, u32),
+        output: &RenderTarget,
+    ) {
+        let context = &state.context().clone();
+        output.bind_cached(state);
+        state.use_program(Some(&self.program));
+        state.bind_texture(0, WebGl2RenderingContext::TEXTURE_2D, Some(input));
+        context.uniform1i(self.loc_input.as_ref(), 0);
+        context.uniform2f(
+            self.loc_texel.as_ref(),
+            1.0 / width as f32,
+            1.0 / height as f32,
+        );
+        context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+    }
+}
+
+/// Thresholded, then blurred by going down a chain of half sized targets
+/// and back up, adding each level onto the next larger one (Jimenez, "Next
+/// Generation Post Processing in Call of Duty: Advanced Warfare", 2014).
+struct Bloom {
+    prefilter: BloomPass,
+    downsample: BloomPass,
+    upsample: BloomPass,
+    levels: Vec<RenderTarget>,
+}
+
+impl Bloom {
+    fn new(context: &WebGl2RenderingContext) -> Result<Self, String> {
+        Ok(Bloom {
+            prefilter: BloomPass::new(context, BLOOM_PREFILTER_FS)?,
+            downsample: BloomPass::new(context, BLOOM_DOWNSAMPLE_FS)?,
+            upsample: BloomPass::new(context, BLOOM_UPSAMPLE_FS)?,
+            levels: vec![],
+        })
+    }
+
+    /// The blurred highlights, at half the size of the frame.
+    fn result(&self) -> &WebGlTexture {
+        self.levels[0].texture()
+    }
+
+    /// Sized by `PostProcess::begin`.
+    fn render(
+        &mut self,
+        state: &mut GlState,
+        input: &WebGlTexture,
+        width: u32,
+        height: u32,
+        parameters: &ShaderParameters,
+    ) {
+        let context = &state.context().clone();
+        let value = |name: &str, default: f32| {
+            parameters
+                .get(name)
+                .and_then(|values| values.first().copied())
+                .unwrap_or(default)
+        };
+        let size = |target: &RenderTarget| (target.width(), target.height());
+
+        state.use_program(Some(&self.prefilter.program));
+        context.uniform1f(
+            self.prefilter.loc_threshold.as_ref(),
+            value("uThreshold", 0.8),
+        );
+        context.uniform1f(self.prefilter.loc_knee.as_ref(), value("uKnee", 0.5));
+        self.prefilter
+            .draw(state, input, (width, height), &self.levels[0]);
+
+        for pair in self.levels.windows(2) {
+            self.downsample
+                .draw(state, pair[0].texture(), size(&pair[0]), &pair[1]);
+        }
+
+        state.use_program(Some(&self.upsample.program));
+        context.uniform1f(self.upsample.loc_radius.as_ref(), value("uRadius", 1.0));
+        UPSAMPLE_PIPELINE.apply(state);
+
+        for pair in self.levels.windows(2).rev() {
+            self.upsample
+                .draw(state, pair[1].texture(), size(&pair[1]), &pair[0]);
+        }
+
+        PASS_PIPELINE.apply(state);
+    }
+
+    /// Returns whether the levels were reallocated.
+    fn resize(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        format: u32,
+    ) -> Result<bool, String> {
+        let sizes = bloom_sizes(width, height);
+        let current = self
+            .levels
+            .iter()
+            .map(|level| (level.width(), level.height()))
+            .collect::<Vec<_>>();
+
+        if sizes == current && self.levels.iter().all(|level| level.format() == format) {
+            return Ok(false);
+        }
+
+        for level in self.levels.drain(..) {
+            level.delete(context);
+        }
+        for (width, height) in sizes {
+            self.levels
+                .push(RenderTarget::new(context, width, height, format, false)?);
+        }
+
+        Ok(true)
+    }
+}
+
+/// Halving from half the frame size, while both sides are at least 2.
+fn bloom_sizes(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let mut sizes = vec![((width / 2).max(1), (height / 2).max(1))];
+
+    while sizes.len() < MAX_BLOOM_LEVELS {
+        let (w, h) = sizes[sizes.len() - 1];
+        if w < 4 || h < 4 {
+            break;
+        }
+        sizes.push((w / 2, h / 2));
+    }
+
+    sizes
+}
+
+struct Lut {
+    texture: WebGlTexture,
+    size: u32,
+}
+
+impl Lut {
+    fn new(context: &WebGl2RenderingContext, size: u32, rgba: &[u8]) -> Result<Self, String> {
+        let expected = lut_bytes(size)?;
+        if rgba.len() != expected {
+            return Err(format!(
+                "A {0}x{0}x{0} LUT needs {1} bytes of RGBA, got {2}",
+                size,
+                expected,
+                rgba.len()
+            ));
+        }
+
+        let texture = context
+            .create_texture()
+            .ok_or_else(|| String::from("Unable to create texture"))?;
+        let target = WebGl2RenderingContext::TEXTURE_3D;
+
+        context.bind_texture(target, Some(&texture));
+        context.pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 1);
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/texImage3D
+        context
+            .tex_image_3d_with_opt_u8_array(
+                target,
+                0,
+                WebGl2RenderingContext::RGBA8 as i32,
+                size as i32,
+                size as i32,
+                size as i32,
+                0,
+                WebGl2RenderingContext::RGBA,
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(rgba),
+            )
+            .map_err(|_| String::from("Unable to upload LUT"))?;
+
+        crate::texture::Sampler::clamped().apply(context, target);
+        context.tex_parameteri(
+            target,
+            WebGl2RenderingContext::TEXTURE_WRAP_R,
+            WebGl2RenderingContext::CLAMP_TO_EDGE as i32,
+        );
+        context.bind_texture(target, None);
+
+        Ok(Lut { texture, size })
+    }
+}
+
+/// A lookup table that leaves colors as they are, laid out as
+/// `PostProcess::set_color_lut` expects.
+// MAX_3D_TEXTURE_SIZE is at least this everywhere, and 256³ texels is as
+// much as a LUT should ever take.
+const MAX_LUT_SIZE: u32 = 256;
+
+/// Bytes of a `size`³ RGBA8 volume, for sizes a LUT can have.
+fn lut_bytes(size: u32) -> Result<usize, String> {
+    if !(2..=MAX_LUT_SIZE).contains(&size) {
+        return Err(format!(
+            "LUT size {} is not within 2 to {}",
+            size, MAX_LUT_SIZE
+        ));
+    }
+
+    Ok((size as usize).pow(3) * 4)
+}
+
+pub fn identity_lut(size: u32) -> Vec<u8> {
+    let level = |i: u32| (i * 255 / (size - 1).max(1)) as u8;
+
+    (0..size)
+        .flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| (r, g, b))))
+        .flat_map(|(r, g, b)| [level(r), level(g), level(b), 255])
+        .collect()
+}
+
+/// Converts a LUT image, as exported by most grading tools, to a volume
+/// for `PostProcess::set_color_lut`. The image is a row of `size` squares
+/// of `size`² pixels, one per blue level; red grows to the right and green
+/// downwards within each square.
+pub fn lut_from_strip(width: u32, height: u32, rgba: &[u8]) -> Result<(u32, Vec<u8>), String> {
+    let size = height;
+    let expected = lut_bytes(size)?;
+    if width != size * size || rgba.len() != expected {
+        return Err(format!(
+            "A LUT strip is N² by N pixels of RGBA, got {}x{} and {} bytes",
+            width,
+            height,
+            rgba.len()
+        ));
+    }
+
+    let volume = (0..size)
+        .flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| (r, g, b))))
+        .flat_map(|(r, g, b)| {
+            let i = ((g * width + b * size + r) * 4) as usize;
+            [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+        })
+        .collect();
+
+    Ok((size, volume))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::parse_parameters;
+
+    #[test]
+    fn effect_parameters_parse() {
+        for kind in EffectKind::ALL {
+            let parameters = parse_parameters(&kind.source()).unwrap();
+            assert_eq!(EffectKind::from_name(kind.name()), Some(kind));
+            // the shared uniforms are not parameters
+            assert!(parameters.iter().all(|p| !p.name.starts_with("uInput")));
+            assert!(!parameters.is_empty(), "{} has no parameters", kind.name());
+        }
+    }
+
+    #[test]
+    fn identity_strip_matches_identity_volume() {
+        let size = 4;
+        let volume = identity_lut(size);
+
+        // lay the volume out as a strip, one square per blue level
+        let width = size * size;
+        let mut strip = vec![0; (width * size * 4) as usize];
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let from = (((b * size + g) * size + r) * 4) as usize;
+                    let to = ((g * width + b * size + r) * 4) as usize;
+                    strip[to..to + 4].copy_from_slice(&volume[from..from + 4]);
+                }
+            }
+        }
+
+        assert_eq!(lut_from_strip(width, size, &strip).unwrap(), (size, volume));
+        assert!(lut_from_strip(15, 4, &strip).is_err());
+        assert!(lut_from_strip(width, size, &strip[4..]).is_err());
+    }
+
+    #[test]
+    fn lut_sizes_are_bounded() {
+        assert_eq!(lut_bytes(2), Ok(32));
+        assert_eq!(lut_bytes(256), Ok(1 << 26));
+        assert!(lut_bytes(1).is_err());
+        assert!(lut_bytes(257).is_err());
+        // N² would overflow a u32
+        assert!(lut_from_strip(0, 65536, &[]).is_err());
+        assert!(lut_from_strip(u32::MAX, u32::MAX, &[]).is_err());
+    }
+
+    #[test]
+    fn bloom_levels_halve() {
+        assert_eq!(
+            bloom_sizes(800, 600),
+            vec![
+                (400, 300),
+                (200, 150),
+                (100, 75),
+                (50, 37),
+                (25, 18),
+                (12, 9)
+            ]
+        );
+        assert_eq!(bloom_sizes(6, 6), vec![(3, 3)]);
+    }
+}
@@ -0,0 +1,352 @@
+// Presets: snapshots of every parameter value of a shader (see params.rs),
+// stored as JSON and blended to animate between looks.
+//
+//   { "version": 1, "values": { "u_scale": [2.0], "u_tint": [1.0, 0.5, 0.0] } }
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::params::{Parameter, UniformType};
+
+const PRESET_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Preset {
+    #[serde(default = "preset_version")]
+    pub version: u32,
+    pub values: BTreeMap<String, Vec<f32>>,
+}
+
+fn preset_version() -> u32 {
+    PRESET_VERSION
+}
+
+impl Preset {
+    pub fn new(values: BTreeMap<String, Vec<f32>>) -> Self {
+        Preset {
+            version: PRESET_VERSION,
+            values,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let preset: Preset =
+            serde_json::from_str(json).map_err(|err| format!("Invalid preset: {}", err))?;
+
+        if preset.version > PRESET_VERSION {
+            return Err(format!("Unsupported preset version: {}", preset.version));
+        }
+
+        Ok(preset)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| String::from("{}"))
+    }
+
+    /// Blends `from` into `to` at `t` in [0, 1], per parameter type:
+    /// colors through OkLCh, ints rounded, bools switched halfway.
+    ///
+    /// Values only present in one preset, or whose sizes differ, are taken
+    /// from `to` as soon as `t` > 0. Values of declared parameters must have
+    /// as many components as the parameter.
+    pub fn interpolate(
+        from: &Preset,
+        to: &Preset,
+        t: f32,
+        parameters: &[Parameter],
+    ) -> Result<Preset, String> {
+        let t = t.clamp(0.0, 1.0);
+
+        let values = to
+            .values
+            .iter()
+            .map(|(name, b)| {
+                let parameter = parameters.iter().find(|p| &p.name == name);
+
+                if let Some(p) = parameter {
+                    let components = p.kind.components();
+                    let sizes = [from.values.get(name), Some(b)];
+
+                    if let Some(v) = sizes.iter().flatten().find(|v| v.len() != components) {
+                        return Err(format!(
+                            "Parameter {} needs {} value(s), got {}",
+                            name,
+                            components,
+                            v.len()
+                        ));
+                    }
+                }
+
+                let a = match from.values.get(name) {
+                    Some(a) if a.len() == b.len() => a,
+                    _ => {
+                        let value = if t > 0.0 {
+                            b
+                        } else {
+                            from.values.get(name).unwrap_or(b)
+                        };
+                        return Ok((name.clone(), value.clone()));
+                    }
+                };
+
+                let value = match parameter {
+                    Some(p) if p.color => lerp_color(a, b, t)?,
+                    Some(p) if p.kind == UniformType::Int => {
+                        lerp(a, b, t).iter().map(|v| v.round()).collect()
+                    }
+                    Some(p) if p.kind == UniformType::Bool => {
+                        if t < 0.5 {
+                            a.clone()
+                        } else {
+                            b.clone()
+                        }
+                    }
+                    _ => lerp(a, b, t),
+                };
+
+                Ok((name.clone(), value))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Preset::new(values))
+    }
+}
+
+/// An animated blend between two presets, driven by `tick` timestamps (sec).
+pub struct Transition {
+    from: Preset,
+    to: Preset,
+    duration: f64,
+    started_at: Option<f64>,
+}
+
+impl Transition {
+    pub fn new(from: Preset, to: Preset, duration: f64) -> Self {
+        Transition {
+            from,
+            to,
+            duration,
+            started_at: None,
+        }
+    }
+
+    /// The blended preset at `timestamp`; the first call starts the clock.
+    pub fn at(&mut self, timestamp: f64, parameters: &[Parameter]) -> Result<Preset, String> {
+        let started_at = *self.started_at.get_or_insert(timestamp);
+
+        let t = if self.duration <= 0.0 {
+            1.0
+        } else {
+            ((timestamp - started_at) / self.duration) as f32
+        };
+
+        Preset::interpolate(&self.from, &self.to, t, parameters)
+    }
+
+    pub fn is_finished(&self, timestamp: f64) -> bool {
+        match self.started_at {
+            None => false,
+            Some(started_at) => timestamp - started_at >= self.duration,
+        }
+    }
+}
+
+fn lerp(a: &[f32], b: &[f32], t: f32) -> Vec<f32> {
+    a.iter().zip(b).map(|(a, b)| a + (b - a) * t).collect()
+}
+
+/// Interpolates sRGB(A) colors in OkLCh, taking the shorter way around the
+/// hue circle. Alpha, if any, is interpolated linearly.
+fn lerp_color(a: &[f32], b: &[f32], t: f32) -> Result<Vec<f32>, String> {
+    let srgb = |c: &[f32]| match c {
+        [r, g, b] | [r, g, b, _] => Ok([*r, *g, *b]),
+        _ => Err(format!("A color needs 3 or 4 values, got {}", c.len())),
+    };
+
+    let (l0, c0, h0) = srgb_to_oklch(srgb(a)?);
+    let (l1, c1, h1) = srgb_to_oklch(srgb(b)?);
+
+    // the hue of a gray is meaningless, so borrow the other end's
+    let (h0, h1) = match (c0 < 1e-4, c1 < 1e-4) {
+        (true, false) => (h1, h1),
+        (false, true) => (h0, h0),
+        _ => (h0, h1),
+    };
+
+    let mut dh = h1 - h0;
+    if dh > std::f32::consts::PI {
+        dh -= std::f32::consts::TAU;
+    } else if dh < -std::f32::consts::PI {
+        dh += std::f32::consts::TAU;
+    }
+
+    let rgb = oklch_to_srgb((l0 + (l1 - l0) * t, c0 + (c1 - c0) * t, h0 + dh * t));
+
+    let mut color = rgb.to_vec();
+    color.extend(lerp(&a[3..], &b[3..], t));
+    Ok(color)
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// https://bottosson.github.io/posts/oklab/
+#[allow(clippy::excessive_precision)]
+fn srgb_to_oklch(rgb: [f32; 3]) -> (f32, f32, f32) {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    let lightness = 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s;
+    let a = 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s;
+    let b = 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s;
+
+    (lightness, a.hypot(b), b.atan2(a))
+}
+
+#[allow(clippy::excessive_precision)]
+fn oklch_to_srgb((lightness, chroma, hue): (f32, f32, f32)) -> [f32; 3] {
+    let a = chroma * hue.cos();
+    let b = chroma * hue.sin();
+
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+    .map(linear_to_srgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::parse_parameters;
+
+    fn parameters() -> Vec<Parameter> {
+        parse_parameters(
+            r#"
+uniform float u_scale; // @range(0.0, 10.0)
+uniform int u_steps;   // @range(0, 10)
+uniform bool u_invert; // @default(0)
+uniform vec3 u_tint;   // @color
+"#,
+        )
+        .unwrap()
+    }
+
+    fn preset(values: &[(&str, &[f32])]) -> Preset {
+        Preset::new(
+            values
+                .iter()
+                .map(|(name, v)| (name.to_string(), v.to_vec()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let original = preset(&[("u_scale", &[2.5]), ("u_tint", &[1.0, 0.5, 0.0])]);
+        let json = original.to_json();
+
+        assert_eq!(Preset::from_json(&json).unwrap(), original);
+        assert!(Preset::from_json(r#"{"values": {}}"#).is_ok());
+        assert!(Preset::from_json(r#"{"version": 2, "values": {}}"#).is_err());
+        assert!(Preset::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn blends_by_parameter_type() {
+        let parameters = parameters();
+        let from = preset(&[
+            ("u_scale", &[0.0]),
+            ("u_steps", &[0.0]),
+            ("u_invert", &[0.0]),
+        ]);
+        let to = preset(&[
+            ("u_scale", &[4.0]),
+            ("u_steps", &[3.0]),
+            ("u_invert", &[1.0]),
+        ]);
+
+        let at = |t| Preset::interpolate(&from, &to, t, &parameters).unwrap();
+
+        assert_eq!(at(0.25).values["u_scale"], [1.0]);
+        assert_eq!(at(0.4).values["u_steps"], [1.0]);
+        assert_eq!(at(0.4).values["u_invert"], [0.0]);
+        assert_eq!(at(0.6).values["u_invert"], [1.0]);
+        assert_eq!(at(2.0), to);
+    }
+
+    #[test]
+    fn colors_blend_between_their_ends() {
+        let parameters = parameters();
+        let from = preset(&[("u_tint", &[1.0, 0.0, 0.0])]);
+        let to = preset(&[("u_tint", &[0.0, 0.0, 1.0])]);
+
+        for (t, expected) in [(0.0, [1.0, 0.0, 0.0]), (1.0, [0.0, 0.0, 1.0])] {
+            let blended = Preset::interpolate(&from, &to, t, &parameters).unwrap();
+            let color = &blended.values["u_tint"];
+
+            for (c, e) in color.iter().zip(expected) {
+                assert!((c - e).abs() < 1e-3, "{:?} at {}", color, t);
+            }
+        }
+    }
+
+    #[test]
+    fn short_colors_are_errors() {
+        let parameters = parameters();
+        let from = preset(&[("u_tint", &[1.0])]);
+        let to = preset(&[("u_tint", &[0.5])]);
+
+        let err = Preset::interpolate(&from, &to, 0.5, &parameters).unwrap_err();
+        assert_eq!(err, "Parameter u_tint needs 3 value(s), got 1");
+
+        // no schema to check against, but still not a panic
+        assert!(lerp_color(&[1.0, 0.0], &[0.0, 1.0], 0.5).is_err());
+    }
+
+    #[test]
+    fn transitions_run_from_the_first_tick() {
+        let parameters = parameters();
+        let mut transition = Transition::new(
+            preset(&[("u_scale", &[0.0])]),
+            preset(&[("u_scale", &[10.0])]),
+            2.0,
+        );
+
+        assert_eq!(
+            transition.at(5.0, &parameters).unwrap().values["u_scale"],
+            [0.0]
+        );
+        assert_eq!(
+            transition.at(6.0, &parameters).unwrap().values["u_scale"],
+            [5.0]
+        );
+        assert!(!transition.is_finished(6.0));
+        assert!(transition.is_finished(7.0));
+    }
+}
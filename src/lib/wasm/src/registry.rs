@@ -0,0 +1,355 @@
+// Manifest of the fragment shaders shipped with the app, compiled into the
+// binary. The gallery and the `/rtg/[id]` route are generated from it:
+//
+//   shader_manifest()                      every entry, without sources
+//   find_shaders('{ "tags": ["noise"] }')  entries matching a `ShaderQuery`
+//   shader_source("perlin-noise")          the GLSL of one entry
+//
+// Default parameter values come from the `@default` annotations of each
+// source (see params.rs).
+
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::params::parse_parameters;
+
+/// What the page has to feed a shader for it to render as intended.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Input {
+    Time,
+    Resolution,
+    Mouse,
+    Texture,
+}
+
+const TIME: &[Input] = &[Input::Time, Input::Resolution];
+const MOUSE: &[Input] = &[Input::Time, Input::Resolution, Input::Mouse];
+
+#[derive(Serialize, Debug)]
+pub struct ShaderEntry {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub author: &'static str,
+    pub tags: &'static [&'static str],
+    pub inputs: &'static [Input],
+    #[serde(skip)]
+    pub source: &'static str,
+}
+
+pub static SHADERS: &[ShaderEntry] = &[
+    ShaderEntry {
+        id: "test",
+        title: "Test",
+        author: "areliq",
+        tags: &["basic"],
+        inputs: TIME,
+        source: include_str!("../../shaders/fragment_test_uniform.glsl"),
+    },
+    ShaderEntry {
+        id: "vg-noise",
+        title: "Value/Gradient Noise",
+        author: "areliq",
+        tags: &["noise"],
+        inputs: TIME,
+        source: include_str!("../../shaders/reference.glsl"),
+    },
+    ShaderEntry {
+        id: "perlin-noise",
+        title: "Perlin Noise",
+        author: "areliq",
+        tags: &["noise"],
+        inputs: TIME,
+        source: include_str!("../../shaders/perlin_noise.glsl"),
+    },
+    ShaderEntry {
+        id: "periodic-noise",
+        title: "Perlin Noise (Periodic)",
+        author: "areliq",
+        tags: &["noise"],
+        inputs: TIME,
+        source: include_str!("../../shaders/periodic_perlin_noise.glsl"),
+    },
+    ShaderEntry {
+        id: "gnoise-artifact",
+        title: "Gradient Noise Artifact",
+        author: "areliq",
+        tags: &["noise"],
+        inputs: TIME,
+        source: include_str!("../../shaders/grad_noise_artifact.glsl"),
+    },
+    ShaderEntry {
+        id: "fbm",
+        title: "Fractional Brownian Motion",
+        author: "areliq",
+        tags: &["noise", "fbm"],
+        inputs: TIME,
+        source: include_str!("../../shaders/frac_brownian_motion.glsl"),
+    },
+    ShaderEntry {
+        id: "domain-warping",
+        title: "Domain Warping",
+        author: "areliq",
+        tags: &["noise", "fbm", "warping"],
+        inputs: TIME,
+        source: include_str!("../../shaders/domain_warping.glsl"),
+    },
+    ShaderEntry {
+        id: "domain-warping-rot",
+        title: "Domain Warping (+Rot)",
+        author: "areliq",
+        tags: &["noise", "fbm", "warping"],
+        inputs: TIME,
+        source: include_str!("../../shaders/domain_warping_rot.glsl"),
+    },
+    ShaderEntry {
+        id: "conversion",
+        title: "Gradation Conversion",
+        author: "areliq",
+        tags: &["color"],
+        inputs: TIME,
+        source: include_str!("../../shaders/conversion.glsl"),
+    },
+    ShaderEntry {
+        id: "blending",
+        title: "Image Blending",
+        author: "areliq",
+        tags: &["blending"],
+        inputs: TIME,
+        source: include_str!("../../shaders/blending.glsl"),
+    },
+    ShaderEntry {
+        id: "bool",
+        title: "Boolean Operation",
+        author: "areliq",
+        tags: &["blending"],
+        inputs: TIME,
+        source: include_str!("../../shaders/bool.glsl"),
+    },
+    ShaderEntry {
+        id: "dist-first",
+        title: "First Nearest Neighbor Distance",
+        author: "areliq",
+        tags: &["cellular"],
+        inputs: TIME,
+        source: include_str!("../../shaders/dist_first.glsl"),
+    },
+    ShaderEntry {
+        id: "dist-first-improved",
+        title: "First Nearest Neighbor Distance (Calc Improved)",
+        author: "areliq",
+        tags: &["cellular"],
+        inputs: TIME,
+        source: include_str!("../../shaders/dist_first_improved.glsl"),
+    },
+    ShaderEntry {
+        id: "dist-first-grad",
+        title: "First Nearest Neighbor Distance (Grad)",
+        author: "areliq",
+        tags: &["cellular"],
+        inputs: TIME,
+        source: include_str!("../../shaders/dist_first_grad.glsl"),
+    },
+    ShaderEntry {
+        id: "voronoi",
+        title: "Voronoi Cell",
+        author: "areliq",
+        tags: &["cellular"],
+        inputs: TIME,
+        source: include_str!("../../shaders/voronoi.glsl"),
+    },
+    ShaderEntry {
+        id: "cellular-noise",
+        title: "Cellular Noise",
+        author: "areliq",
+        tags: &["cellular", "noise"],
+        inputs: TIME,
+        source: include_str!("../../shaders/cellular_noise.glsl"),
+    },
+    ShaderEntry {
+        id: "mouse",
+        title: "Mouse",
+        author: "areliq",
+        tags: &["interactive"],
+        inputs: MOUSE,
+        source: include_str!("../../shaders/mouse.glsl"),
+    },
+];
+
+/// Filters for `find_shaders`; every given field must match.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct ShaderQuery {
+    /// Entries having all of these tags.
+    pub tags: Vec<String>,
+    /// Entries needing only these inputs, e.g. `["time", "resolution"]`
+    /// for a page without mouse handling.
+    pub available: Option<Vec<Input>>,
+    pub author: Option<String>,
+    /// Case-insensitive substring of the id, title or a tag.
+    pub text: Option<String>,
+}
+
+impl ShaderQuery {
+    pub fn matches(&self, entry: &ShaderEntry) -> bool {
+        let tags = self
+            .tags
+            .iter()
+            .all(|tag| entry.tags.contains(&tag.as_str()));
+
+        let inputs = match &self.available {
+            None => true,
+            Some(available) => entry.inputs.iter().all(|i| available.contains(i)),
+        };
+
+        let author = match &self.author {
+            None => true,
+            Some(author) => entry.author == author,
+        };
+
+        let text = match &self.text {
+            None => true,
+            Some(text) => {
+                let text = text.to_lowercase();
+                [entry.id, entry.title]
+                    .iter()
+                    .chain(entry.tags)
+                    .any(|s| s.to_lowercase().contains(&text))
+            }
+        };
+
+        tags && inputs && author && text
+    }
+}
+
+pub fn find(id: &str) -> Option<&'static ShaderEntry> {
+    SHADERS.iter().find(|entry| entry.id == id)
+}
+
+#[derive(Serialize)]
+struct ManifestEntry {
+    #[serde(flatten)]
+    entry: &'static ShaderEntry,
+    parameters: BTreeMap<String, Vec<f32>>,
+}
+
+fn manifest_json(entries: &[&'static ShaderEntry]) -> String {
+    let entries = entries
+        .iter()
+        .map(|&entry| {
+            // a broken annotation only costs the entry its defaults
+            let parameters = parse_parameters(entry.source)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|p| (p.name, p.default))
+                .collect();
+
+            ManifestEntry { entry, parameters }
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string(&entries).unwrap_or_else(|_| String::from("[]"))
+}
+
+/// Every entry as a JSON array, in gallery order.
+#[wasm_bindgen]
+pub fn shader_manifest() -> String {
+    manifest_json(&SHADERS.iter().collect::<Vec<_>>())
+}
+
+/// The entries matching a JSON `ShaderQuery`, in gallery order.
+#[wasm_bindgen]
+pub fn find_shaders(query: &str) -> Result<String, JsValue> {
+    let query: ShaderQuery =
+        serde_json::from_str(query).map_err(|err| JsValue::from(err.to_string()))?;
+
+    let entries = SHADERS
+        .iter()
+        .filter(|entry| query.matches(entry))
+        .collect::<Vec<_>>();
+
+    Ok(manifest_json(&entries))
+}
+
+#[wasm_bindgen]
+pub fn shader_source(id: &str) -> Option<String> {
+    find(id).map(|entry| entry.source.to_string())
+}
+
+/// Every tag in use, sorted, as a JSON array.
+#[wasm_bindgen]
+pub fn shader_tags() -> String {
+    let mut tags = SHADERS
+        .iter()
+        .flat_map(|entry| entry.tags.iter().copied())
+        .collect::<Vec<_>>();
+    tags.sort_unstable();
+    tags.dedup();
+
+    serde_json::to_string(&tags).unwrap_or_else(|_| String::from("[]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uniforms::FRAME_BLOCK;
+
+    #[test]
+    fn every_entry_resolves_to_its_shader() {
+        for entry in SHADERS {
+            let found = find(entry.id).unwrap();
+            assert!(std::ptr::eq(found, entry), "duplicate id {}", entry.id);
+
+            let source = shader_source(entry.id).unwrap();
+            assert!(source.starts_with("#version 300 es"), "{}", entry.id);
+            assert!(source.contains("void main()"), "{}", entry.id);
+            parse_parameters(&source).unwrap_or_else(|err| panic!("{}: {}", entry.id, err));
+        }
+
+        assert!(find("no-such-shader").is_none());
+        assert!(shader_source("no-such-shader").is_none());
+    }
+
+    #[test]
+    fn inputs_match_the_uniforms() {
+        for entry in SHADERS {
+            let reads_mouse = entry.source.contains("frame.mouse");
+            assert_eq!(
+                entry.inputs.contains(&Input::Mouse),
+                reads_mouse,
+                "{}",
+                entry.id
+            );
+        }
+    }
+
+    #[test]
+    fn frame_is_read_from_the_shared_block() {
+        for entry in SHADERS {
+            // a copy drifting from `frame_layout` would read the wrong bytes
+            assert!(entry.source.contains(FRAME_BLOCK), "{}", entry.id);
+        }
+    }
+
+    #[test]
+    fn queries_filter_the_manifest() {
+        let ids = |query: &str| -> Vec<&str> {
+            let query: ShaderQuery = serde_json::from_str(query).unwrap();
+            SHADERS
+                .iter()
+                .filter(|entry| query.matches(entry))
+                .map(|entry| entry.id)
+                .collect()
+        };
+
+        assert_eq!(ids("{}").len(), SHADERS.len());
+        assert_eq!(ids(r#"{ "tags": ["interactive"] }"#), ["mouse"]);
+        assert!(!ids(r#"{ "available": ["time", "resolution"] }"#).contains(&"mouse"));
+        assert!(ids(r#"{ "text": "VORONOI" }"#).contains(&"voronoi"));
+        assert!(ids(r#"{ "author": "nobody" }"#).is_empty());
+    }
+}
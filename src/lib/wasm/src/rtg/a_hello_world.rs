@@ -9,7 +9,7 @@ extern crate nalgebra_glm as glm;
 use crate::utils::log;
 use crate::webgl::{compile_shader, get_context_with_canvas_by_id, link_shader_program};
 
-static VERTEX_SHADER_SOURCE: &'static str = r#"#version 300 es
+static VERTEX_SHADER_SOURCE: &str = r#"#version 300 es
 // The individual position vertex
 in vec2 position;
 
@@ -20,7 +20,7 @@ void main() {
 }
 "#;
 
-static FRAGMENT_SHADER_SOURCE: &'static str = r#"#version 300 es
+static FRAGMENT_SHADER_SOURCE: &str = r#"#version 300 es
 precision mediump float;
 out vec4 fragColor;
 uniform vec2 u_resolution;
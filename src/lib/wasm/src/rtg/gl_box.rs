@@ -3,20 +3,50 @@ use wasm_bindgen::prelude::*;
 // use wasm_bindgen::JsValue;
 // use wasm_bindgen::JsCast;
 
-use web_sys::{HtmlCanvasElement, WebGl2RenderingContext, WebGlUniformLocation};
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext, WebGlProgram, WebGlVertexArrayObject};
 extern crate console_error_panic_hook;
 extern crate nalgebra_glm as glm;
 
+use crate::params::ShaderParameters;
+use crate::pipeline::PipelineState;
+use crate::postprocess::{lut_from_strip, EffectKind, PostProcess};
+use crate::state::GlState;
+use crate::target::Precision;
+use crate::uniforms::{FrameUniforms, SharedFrame};
 use crate::utils::log;
 use crate::webgl::{compile_shader, get_context_with_canvas_by_id, link_shader_program};
 
+/// Depth tested as the WebGL default, for shaders drawing more than the quad.
+static PIPELINE: PipelineState = PipelineState::new().depth(WebGl2RenderingContext::LESS, true);
+
+static QUAD_POSITIONS: [f32; 12] = [
+    // Triangle 1
+    -1.0, -1.0, // left-bottom
+    1.0, -1.0, // right-bottom
+    -1.0, 1.0, // left-top
+    // Triangle 2
+    -1.0, 1.0, // left-top
+    1.0, -1.0, // right-bottom
+    1.0, 1.0, // right-top
+];
+
 #[wasm_bindgen]
 pub struct GlBox {
     context: WebGl2RenderingContext,
     canvas: HtmlCanvasElement,
-    loc_position: u32,
+    program: WebGlProgram,
+    // the quad, fed to `position`
+    vao: WebGlVertexArrayObject,
     // loc_color: WebGlUniformLocation,
-    loc_time: Option<WebGlUniformLocation>,
+    // whether `frame.time` follows `tick`
+    dynamic: bool,
+    parameters: ShaderParameters,
+    // created by the first `add_effect`
+    post: Option<PostProcess>,
+    // for shaders declaring `FRAME_BLOCK`, shared with whatever else draws
+    // on the context
+    frame: SharedFrame,
+    state: GlState,
 }
 
 #[wasm_bindgen]
@@ -26,17 +56,15 @@ impl GlBox {
         dynamic: bool,
         vertex_shader_source: &str,
         fragment_shader_source: &str,
-    ) -> Self {
+    ) -> Result<GlBox, JsValue> {
         console_error_panic_hook::set_once();
 
-        let (context, canvas) = get_context_with_canvas_by_id(id).unwrap_or_else(|err| {
-            log(&err);
-            panic!("Failed to compile vertex shader");
-        });
+        let (context, canvas) = get_context_with_canvas_by_id(id)?;
 
         log("GlBox.new: context ok");
 
-        resize_of(&context, &canvas);
+        let mut state = GlState::new(&context);
+        resize_of(&mut state, &canvas);
 
         log("GlBox.new: resize ok");
 
@@ -44,11 +72,7 @@ impl GlBox {
             &context,
             WebGl2RenderingContext::VERTEX_SHADER,
             vertex_shader_source,
-        )
-        .unwrap_or_else(|err| {
-            log(&err);
-            panic!("Failed to compile vertex shader");
-        });
+        )?;
 
         log("GlBox.new: vertex shader compiled");
 
@@ -56,62 +80,29 @@ impl GlBox {
             &context,
             WebGl2RenderingContext::FRAGMENT_SHADER,
             fragment_shader_source,
-        )
-        .unwrap_or_else(|err| {
-            log(&err);
-            panic!("Failed to compile fragment shader");
-        });
+        )?;
 
         log("GlBox.new: fragment shader compiled");
 
-        let program = link_shader_program(&context, &vertex_shader, &fragment_shader)
-            .unwrap_or_else(|err| {
-                log(&err);
-                panic!("Failed to compile link shader");
-            });
+        let program = link_shader_program(&context, &vertex_shader, &fragment_shader)?;
 
         log("GlBox.new: shaders linked to program");
 
-        context.use_program(Some(&program));
-
-        log("GlBox.new: use program ok");
-
-        // set resolution (if not, it will become [0.0, 0.0])
-        let loc_resolution = context.get_uniform_location(&program, "u_resolution");
-
-        log("GlBox.new: u_resolution location ok");
-
-        match loc_resolution {
-            None => {
-                log("no location for u_resolution");
+        // set resolution and time (if not, they will become 0.0)
+        let frame = FrameUniforms::shared(&context)?;
+        {
+            let mut frame = frame.borrow_mut();
+            if !frame.bind_program(&context, &program) {
+                log("no Frame block");
             }
-            Some(loc) => {
-                let viewport: [f32; 2] = [canvas.width() as f32, canvas.height() as f32];
-                log("GlBox.new: viewport ok");
-                context.uniform2fv_with_f32_array(Some(&loc), &viewport);
-                log("GlBox.new: set viewport to u_resolution ok");
+            frame.resolution = [canvas.width() as f32, canvas.height() as f32];
+            if dynamic {
+                frame.time = get_current_sec() as f32;
             }
+            frame.upload(&context)?;
         }
 
-        let loc_time = if dynamic {
-            // set time
-            let loc_time = context
-                .get_uniform_location(&program, "u_time")
-                .unwrap_or_else(|| {
-                    panic!("Failed to get uniform location: u_time");
-                });
-
-            log("GlBox.new: u_time location ok");
-
-            let current = get_current_sec() as f32;
-            context.uniform1f(Some(&loc_time), current);
-
-            log("GlBox.new: set time to u_time ok");
-
-            Some(loc_time)
-        } else {
-            None
-        };
+        log("GlBox.new: frame uniforms ok");
 
         // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.get_attrib_location
         // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/getAttribLocation
@@ -129,56 +120,30 @@ impl GlBox {
         // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/getUniformLocation
         // let loc_color = context.get_uniform_location(&program, "color").unwrap();
 
-        // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.enable
-        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/enable
-        context.enable(WebGl2RenderingContext::DEPTH_TEST);
-
-        log("GlBox.new: DEPTH_TEST ok");
-
-        GlBox {
-            context,
-            canvas,
-            loc_position,
-            loc_time,
-        }
-    }
+        PIPELINE.apply(&mut state);
 
-    fn bind_position_buffer(&self, positions: &[f32]) {
-        let position_buffer = self
-            .context
-            .create_buffer()
-            .ok_or("Failed to create buffer")
-            .unwrap();
+        log("GlBox.new: pipeline state ok");
 
-        self.context
-            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&position_buffer));
+        // leaves the program in use
+        let parameters = ShaderParameters::new(&mut state, &program, fragment_shader_source)?;
 
-        unsafe {
-            let positions_array_buffer_view = js_sys::Float32Array::view(positions);
+        log("GlBox.new: shader parameters ok");
 
-            self.context.buffer_data_with_array_buffer_view(
-                WebGl2RenderingContext::ARRAY_BUFFER,
-                &positions_array_buffer_view,
-                WebGl2RenderingContext::STATIC_DRAW,
-            );
-        }
+        let vao = quad_of(&mut state, loc_position)?;
 
-        self.context.enable_vertex_attrib_array(self.loc_position);
+        log("GlBox.new: quad ok");
 
-        let num_components = 2;
-        let data_type: u32 = WebGl2RenderingContext::FLOAT;
-        let normalize = false;
-        let stride = 0;
-        let offset = 0;
-
-        self.context.vertex_attrib_pointer_with_i32(
-            self.loc_position,
-            num_components,
-            data_type,
-            normalize,
-            stride,
-            offset,
-        );
+        Ok(GlBox {
+            context,
+            canvas,
+            program,
+            vao,
+            dynamic,
+            parameters,
+            post: None,
+            frame,
+            state,
+        })
     }
 
     // fn bind_color_buffer(&self, color: &[f32]) {
@@ -187,21 +152,20 @@ impl GlBox {
     //     self.context.uniform4fv_with_f32_array(Some(&self.loc_color), &color);
     // }
 
-    pub fn draw(&self) {
-        resize_of(&self.context, &self.canvas);
+    pub fn draw(&mut self) {
+        resize_of(&mut self.state, &self.canvas);
+
+        if let Some(post) = &mut self.post {
+            let (width, height) = (self.canvas.width(), self.canvas.height());
+            if let Err(err) = post.begin(&mut self.state, width, height) {
+                log(&err);
+            }
+        }
 
-        let positions = [
-            // Triangle 1
-            -1.0, -1.0, // left-bottom
-            1.0, -1.0, // right-bottom
-            -1.0, 1.0, // left-top
-            // Triangle 2
-            -1.0, 1.0, // left-top
-            1.0, -1.0, // right-bottom
-            1.0, 1.0, // right-top
-        ];
+        PIPELINE.apply(&mut self.state);
+        self.state.use_program(Some(&self.program));
 
-        self.bind_position_buffer(&positions);
+        self.state.bind_vertex_array(Some(&self.vao));
 
         // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.WebGlRenderingContext.html#method.draw_arrays
         // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/drawArrays
@@ -209,20 +173,213 @@ impl GlBox {
         let vertex_count = 6;
         self.context
             .draw_arrays(WebGl2RenderingContext::TRIANGLES, offset, vertex_count);
-    }
 
-    pub fn tick(&self, timestamp: f64) {
-        match &self.loc_time {
-            None => {}
-            Some(loc) => {
-                let current = timestamp as f32;
-                self.context.uniform1f(Some(&loc), current);
+        if let Some(post) = &mut self.post {
+            if let Err(err) = post.end(&mut self.state) {
+                log(&err);
             }
         }
     }
+
+    pub fn tick(&mut self, timestamp: f64) {
+        if let Some(post) = &mut self.post {
+            post.set_time(timestamp as f32);
+        }
+
+        // the size the next `draw` renders at
+        resize_of(&mut self.state, &self.canvas);
+        let mut frame = self.frame.borrow_mut();
+        frame.resolution = [self.canvas.width() as f32, self.canvas.height() as f32];
+
+        let uploaded = match self.dynamic {
+            true => frame.advance(&self.context, timestamp as f32),
+            false => frame.upload(&self.context),
+        };
+        if let Err(err) = uploaded {
+            log(&err);
+        }
+
+        if let Err(err) = self.parameters.advance(&mut self.state, timestamp) {
+            log(&err);
+        }
+    }
+
+    /// `frame.mouse` of `FRAME_BLOCK` from the next `tick` on: `x` and `y`
+    /// in canvas pixels from the bottom left.
+    pub fn set_mouse(&mut self, x: f32, y: f32, pressed: bool) {
+        self.frame.borrow_mut().mouse = [x, y, pressed as i32 as f32, 0.0];
+    }
+
+    /// The annotated uniforms of the fragment shader, as a JSON array.
+    pub fn parameter_schema(&self) -> String {
+        self.parameters.schema_json()
+    }
+
+    pub fn set_parameter(&mut self, name: &str, values: &[f32]) -> Result<(), JsValue> {
+        self.parameters
+            .set(&mut self.state, name, values)
+            .map_err(JsValue::from)
+    }
+
+    pub fn get_parameter(&self, name: &str) -> Option<Vec<f32>> {
+        self.parameters.get(name)
+    }
+
+    /// The current parameter values as a JSON preset.
+    pub fn save_preset(&self) -> String {
+        self.parameters.save_preset()
+    }
+
+    pub fn load_preset(&mut self, json: &str) -> Result<(), JsValue> {
+        self.parameters
+            .load_preset(&mut self.state, json)
+            .map_err(JsValue::from)
+    }
+
+    /// Applies the blend of two presets at `t` in [0, 1].
+    pub fn blend_presets(&mut self, from: &str, to: &str, t: f32) -> Result<(), JsValue> {
+        self.parameters
+            .blend_presets(&mut self.state, from, to, t)
+            .map_err(JsValue::from)
+    }
+
+    /// Blends from the current values to `json` over `duration` seconds,
+    /// starting at the next `tick`.
+    pub fn transition_to(&mut self, json: &str, duration: f64) -> Result<(), JsValue> {
+        self.parameters
+            .transition_to(json, duration)
+            .map_err(JsValue::from)
+    }
+
+    /// Appends a post-processing effect, e.g. "bloom" or "vignette", to the
+    /// end of the chain. See `postprocess::EffectKind` for the names.
+    pub fn add_effect(&mut self, name: &str) -> Result<(), JsValue> {
+        let kind =
+            EffectKind::from_name(name).ok_or_else(|| format!("Unknown effect: {}", name))?;
+        let (post, state) = self.post_or_create()?;
+
+        post.push(state, kind).map_err(JsValue::from)
+    }
+
+    /// Renders into "half" or "float" targets instead of 8-bit ("byte")
+    /// ones, so that values above 1 survive until a "tone-mapping" effect;
+    /// the output of the shader is then taken as linear and encoded to sRGB
+    /// at the end. Returns the precision the browser supports.
+    pub fn set_precision(&mut self, name: &str) -> Result<String, JsValue> {
+        let precision =
+            Precision::from_name(name).ok_or_else(|| format!("Unknown precision: {}", name))?;
+        let (post, state) = self.post_or_create()?;
+
+        post.set_precision(state, precision)
+            .map(|precision| precision.name().to_string())
+            .map_err(JsValue::from)
+    }
+
+    pub fn set_effect_enabled(&mut self, name: &str, enabled: bool) -> Result<(), JsValue> {
+        let (post, _) = self.post_mut()?;
+
+        post.set_enabled(name, enabled).map_err(JsValue::from)
+    }
+
+    pub fn set_effect_parameter(
+        &mut self,
+        effect: &str,
+        name: &str,
+        values: &[f32],
+    ) -> Result<(), JsValue> {
+        let (post, state) = self.post_mut()?;
+
+        post.set_parameter(state, effect, name, values)
+            .map_err(JsValue::from)
+    }
+
+    /// The annotated uniforms of an effect, as a JSON array.
+    pub fn effect_schema(&self, effect: &str) -> Result<String, JsValue> {
+        self.post
+            .as_ref()
+            .ok_or_else(|| JsValue::from("No effects"))?
+            .parameter_schema(effect)
+            .map_err(JsValue::from)
+    }
+
+    /// Replaces the lookup table of the "color-grading" effect with a LUT
+    /// strip image (N² by N pixels, RGBA).
+    pub fn set_color_lut(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<(), JsValue> {
+        let (size, volume) = lut_from_strip(width, height, rgba)?;
+        let (post, state) = self.post_mut()?;
+
+        post.set_color_lut(state, size, &volume)
+            .map_err(JsValue::from)
+    }
+
+    /// The chain, with the state its passes go through.
+    fn post_or_create(&mut self) -> Result<(&mut PostProcess, &mut GlState), String> {
+        if self.post.is_none() {
+            self.post = Some(PostProcess::new(
+                &self.context,
+                self.canvas.width(),
+                self.canvas.height(),
+            )?);
+        }
+
+        Ok((self.post.as_mut().unwrap(), &mut self.state))
+    }
+
+    fn post_mut(&mut self) -> Result<(&mut PostProcess, &mut GlState), JsValue> {
+        let post = self
+            .post
+            .as_mut()
+            .ok_or_else(|| JsValue::from("No effects"))?;
+
+        Ok((post, &mut self.state))
+    }
+}
+
+/// A vertex array feeding `QUAD_POSITIONS` to the attribute at
+/// `loc_position`, left bound.
+fn quad_of(state: &mut GlState, loc_position: u32) -> Result<WebGlVertexArrayObject, String> {
+    let context = &state.context().clone();
+    let vao = context
+        .create_vertex_array()
+        .ok_or_else(|| String::from("Unable to create vertex array object"))?;
+    let position_buffer = context
+        .create_buffer()
+        .ok_or_else(|| String::from("Failed to create buffer"))?;
+
+    state.bind_vertex_array(Some(&vao));
+    state.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&position_buffer));
+
+    unsafe {
+        let positions_array_buffer_view = js_sys::Float32Array::view(&QUAD_POSITIONS);
+
+        context.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &positions_array_buffer_view,
+            WebGl2RenderingContext::STATIC_DRAW,
+        );
+    }
+
+    context.enable_vertex_attrib_array(loc_position);
+
+    let num_components = 2;
+    let data_type: u32 = WebGl2RenderingContext::FLOAT;
+    let normalize = false;
+    let stride = 0;
+    let offset = 0;
+
+    context.vertex_attrib_pointer_with_i32(
+        loc_position,
+        num_components,
+        data_type,
+        normalize,
+        stride,
+        offset,
+    );
+
+    Ok(vao)
 }
 
-fn resize_of(context: &WebGl2RenderingContext, canvas: &HtmlCanvasElement) {
+fn resize_of(state: &mut GlState, canvas: &HtmlCanvasElement) {
     let display_width: u32 = canvas.client_width().try_into().unwrap_or_else(|_| {
         panic!("Failed to get display width");
     });
@@ -239,7 +396,7 @@ fn resize_of(context: &WebGl2RenderingContext, canvas: &HtmlCanvasElement) {
     let new_width: i32 = canvas.width().try_into().unwrap();
     let new_height: i32 = canvas.height().try_into().unwrap();
 
-    context.viewport(0, 0, new_width, new_height);
+    state.viewport(0, 0, new_width, new_height);
 }
 
 fn get_current_sec() -> f64 {
@@ -1,2 +1,3 @@
 pub mod a_hello_world;
 pub mod gl_box;
+pub mod tiled_render;
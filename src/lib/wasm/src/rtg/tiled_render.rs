@@ -0,0 +1,603 @@
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use std::io::{self, Write};
+
+use web_sys::{
+    WebGl2RenderingContext, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlTexture,
+    WebGlUniformLocation, WebGlVertexArrayObject,
+};
+extern crate console_error_panic_hook;
+
+use crate::uniforms::{FrameUniforms, SharedFrame};
+use crate::utils::log;
+use crate::webgl::{compile_shader, get_context_with_canvas_by_id, link_shader_program};
+
+// Every `gl_FragCoord` in the fragment shader is shifted by this offset,
+// so that a tile sees the same coordinates as the full-size image would.
+const TILE_OFFSET_UNIFORM: &str = "u_tile_offset";
+
+// A tile is read back whole, so keep it to 64 MiB of RGBA even where the
+// context could render to something larger.
+const MAX_TILE_SIZE: u32 = 4096;
+
+static QUAD_POSITIONS: [f32; 12] = [
+    // Triangle 1
+    -1.0, -1.0, // left-bottom
+    1.0, -1.0, // right-bottom
+    -1.0, 1.0, // left-top
+    // Triangle 2
+    -1.0, 1.0, // left-top
+    1.0, -1.0, // right-bottom
+    1.0, 1.0, // right-top
+];
+
+/// Renders a fragment shader into an image larger than `MAX_RENDERBUFFER_SIZE`
+/// by drawing it tile by tile into an offscreen framebuffer and streaming the
+/// stitched result out as PNG.
+///
+/// The canvas's context is usually shared with a box whose `GlState` this
+/// can't reach, so every binding it changes is put back afterwards.
+#[wasm_bindgen]
+pub struct TiledRenderer {
+    context: WebGl2RenderingContext,
+    program: WebGlProgram,
+    // the quad, fed to `position`
+    vao: WebGlVertexArrayObject,
+    framebuffer: WebGlFramebuffer,
+    loc_tile_offset: Option<WebGlUniformLocation>,
+    // for shaders declaring `FRAME_BLOCK`, shared with the boxes on the
+    // context
+    frame: SharedFrame,
+    tile_size: u32,
+}
+
+#[wasm_bindgen]
+impl TiledRenderer {
+    /// `tile_size` is clamped to what the context can render to at once, and
+    /// to at most 4096.
+    pub fn new(
+        id: &str,
+        vertex_shader_source: &str,
+        fragment_shader_source: &str,
+        tile_size: u32,
+    ) -> Self {
+        console_error_panic_hook::set_once();
+
+        let (context, _canvas) = get_context_with_canvas_by_id(id).unwrap_or_else(|err| {
+            log(&err);
+            panic!("Failed to get context");
+        });
+
+        let vertex_shader = compile_shader(
+            &context,
+            WebGl2RenderingContext::VERTEX_SHADER,
+            vertex_shader_source,
+        )
+        .unwrap_or_else(|err| {
+            log(&err);
+            panic!("Failed to compile vertex shader");
+        });
+
+        let fragment_shader = compile_shader(
+            &context,
+            WebGl2RenderingContext::FRAGMENT_SHADER,
+            &inject_tile_offset(fragment_shader_source),
+        )
+        .unwrap_or_else(|err| {
+            log(&err);
+            panic!("Failed to compile fragment shader");
+        });
+
+        let program = link_shader_program(&context, &vertex_shader, &fragment_shader)
+            .unwrap_or_else(|err| {
+                log(&err);
+                panic!("Failed to compile link shader");
+            });
+
+        let loc_position: u32 = context
+            .get_attrib_location(&program, "position")
+            .try_into()
+            .unwrap_or_else(|_| {
+                panic!("Failed to get attribute location: position");
+            });
+
+        let loc_tile_offset = context.get_uniform_location(&program, TILE_OFFSET_UNIFORM);
+
+        let tile_size = tile_size.clamp(1, max_tile_size_of(&context));
+
+        log(&format!("TiledRenderer.new: tile size {}", tile_size));
+
+        let bindings = Bindings::save(&context);
+
+        let vao = quad_of(&context, loc_position).unwrap_or_else(|err| {
+            log(&err);
+            panic!("Failed to create quad");
+        });
+
+        let framebuffer = create_tile_target(&context, tile_size).unwrap_or_else(|err| {
+            log(&err);
+            panic!("Failed to create tile framebuffer");
+        });
+
+        bindings.restore(&context);
+
+        let frame = FrameUniforms::shared(&context).unwrap_or_else(|err| {
+            log(&err);
+            panic!("Failed to create frame uniforms");
+        });
+        frame.borrow().bind_program(&context, &program);
+
+        TiledRenderer {
+            context,
+            program,
+            vao,
+            framebuffer,
+            loc_tile_offset,
+            frame,
+            tile_size,
+        }
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// Renders a `width` x `height` image at `time` and passes the encoded PNG
+    /// to `sink` as a sequence of `Uint8Array` chunks.
+    ///
+    /// Only one row of tiles is held in memory at a time. The context's
+    /// bindings and viewport are restored afterwards.
+    pub fn render(
+        &mut self,
+        width: u32,
+        height: u32,
+        time: f32,
+        sink: &js_sys::Function,
+    ) -> Result<(), JsValue> {
+        if width == 0 || height == 0 {
+            return Err(JsValue::from("Image size must not be zero"));
+        }
+
+        let grid = TileGrid::new(width, height, self.tile_size);
+
+        let mut encoder = png::Encoder::new(JsSink { sink: sink.clone() }, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let writer = encoder.write_header().map_err(to_js_error)?;
+        let stream = writer.into_stream_writer().map_err(to_js_error)?;
+
+        let shown = {
+            let frame = self.frame.borrow();
+            (frame.resolution, frame.time)
+        };
+        let bindings = Bindings::save(&self.context);
+
+        let result = self
+            .setup(width, height, time)
+            .map_err(JsValue::from)
+            .and_then(|_| self.render_bands(&grid, stream));
+
+        bindings.restore(&self.context);
+
+        // the boxes sharing the block go on from where they were
+        let mut frame = self.frame.borrow_mut();
+        (frame.resolution, frame.time) = shown;
+        frame.upload(&self.context)?;
+
+        result
+    }
+}
+
+impl TiledRenderer {
+    fn render_bands<W: Write>(
+        &self,
+        grid: &TileGrid,
+        mut stream: png::StreamWriter<W>,
+    ) -> Result<(), JsValue> {
+        let width = grid.width;
+        let row_bytes = rgba_len(width, 1);
+        let mut tile_pixels = vec![0u8; rgba_len(self.tile_size, self.tile_size)];
+
+        // PNG rows run top to bottom, while GL rows run bottom to top.
+        for band in (0..grid.rows).rev() {
+            let band_height = grid.tile_height(band);
+            let mut band_pixels = vec![0u8; row_bytes * band_height as usize];
+
+            for column in 0..grid.columns {
+                let tile = grid.tile(column, band);
+                let len = rgba_len(tile.width, tile.height);
+
+                self.render_tile(&tile, &mut tile_pixels[..len])?;
+
+                let tile_row_bytes = rgba_len(tile.width, 1);
+                let x_bytes = rgba_len(tile.x, 1);
+
+                for y in 0..tile.height as usize {
+                    let src = &tile_pixels[y * tile_row_bytes..(y + 1) * tile_row_bytes];
+                    let dst_row = band_height as usize - 1 - y;
+                    let dst = dst_row * row_bytes + x_bytes;
+                    band_pixels[dst..dst + tile_row_bytes].copy_from_slice(src);
+                }
+            }
+
+            stream.write_all(&band_pixels).map_err(to_js_error)?;
+        }
+
+        stream.finish().map_err(to_js_error)?;
+
+        Ok(())
+    }
+
+    fn setup(&mut self, width: u32, height: u32, time: f32) -> Result<(), String> {
+        self.context.use_program(Some(&self.program));
+
+        self.context
+            .bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&self.framebuffer));
+        self.context.bind_vertex_array(Some(&self.vao));
+
+        // the whole image, not the tile, is the resolution the shader sees
+        let mut frame = self.frame.borrow_mut();
+        frame.resolution = [width as f32, height as f32];
+        frame.time = time;
+        frame.upload(&self.context)
+    }
+
+    fn render_tile(&self, tile: &Tile, pixels: &mut [u8]) -> Result<(), JsValue> {
+        let width = tile.width as i32;
+        let height = tile.height as i32;
+
+        if let Some(loc) = &self.loc_tile_offset {
+            self.context
+                .uniform2f(Some(loc), tile.x as f32, tile.y as f32);
+        }
+
+        self.context.viewport(0, 0, width, height);
+        self.context
+            .draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 6);
+
+        self.context.read_pixels_with_opt_u8_array(
+            0,
+            0,
+            width,
+            height,
+            WebGl2RenderingContext::RGBA,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            Some(pixels),
+        )
+    }
+}
+
+/// A region of the full image, in GL coordinates (origin at the bottom-left).
+#[derive(Debug, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Splits a `width` x `height` image into square tiles of `tile_size`;
+/// tiles on the right and top edges are cropped to fit.
+pub struct TileGrid {
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: u32,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl TileGrid {
+    pub fn new(width: u32, height: u32, tile_size: u32) -> Self {
+        TileGrid {
+            width,
+            height,
+            tile_size,
+            columns: width.div_ceil(tile_size),
+            rows: height.div_ceil(tile_size),
+        }
+    }
+
+    pub fn tile_height(&self, row: u32) -> u32 {
+        (self.height - row * self.tile_size).min(self.tile_size)
+    }
+
+    pub fn tile(&self, column: u32, row: u32) -> Tile {
+        let x = column * self.tile_size;
+        let y = row * self.tile_size;
+
+        Tile {
+            x,
+            y,
+            width: (self.width - x).min(self.tile_size),
+            height: self.tile_height(row),
+        }
+    }
+}
+
+/// Declares `u_tile_offset` and rewrites every `gl_FragCoord` to include it.
+pub fn inject_tile_offset(fragment_shader_source: &str) -> String {
+    let shifted = format!("(gl_FragCoord + vec4({}, 0.0, 0.0))", TILE_OFFSET_UNIFORM);
+    let body = fragment_shader_source.replace("gl_FragCoord", &shifted);
+    let declaration = format!("uniform highp vec2 {};\n", TILE_OFFSET_UNIFORM);
+
+    // `#version` has to stay on the first line
+    match body.split_once('\n') {
+        Some((first, rest)) if first.trim_start().starts_with("#version") => {
+            format!("{}\n{}{}", first, declaration, rest)
+        }
+        _ => format!("{}{}", declaration, body),
+    }
+}
+
+fn max_tile_size_of(context: &WebGl2RenderingContext) -> u32 {
+    let parameter = |name: u32| {
+        context
+            .get_parameter(name)
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as u32
+    };
+
+    let size = parameter(WebGl2RenderingContext::MAX_RENDERBUFFER_SIZE)
+        .min(parameter(WebGl2RenderingContext::MAX_TEXTURE_SIZE));
+
+    if size == 0 {
+        // WebGL 2 guarantees at least this much
+        2048
+    } else {
+        size.min(MAX_TILE_SIZE)
+    }
+}
+
+/// Bytes in `width` x `height` RGBA8 pixels, counted in `usize` so that large
+/// tiles and images don't overflow `u32`.
+fn rgba_len(width: u32, height: u32) -> usize {
+    width as usize * height as usize * 4
+}
+
+/// The bindings `TiledRenderer` changes, as they were before it did.
+struct Bindings {
+    program: Option<WebGlProgram>,
+    vao: Option<WebGlVertexArrayObject>,
+    array_buffer: Option<WebGlBuffer>,
+    framebuffer: Option<WebGlFramebuffer>,
+    texture: Option<WebGlTexture>,
+    viewport: Option<Vec<i32>>,
+}
+
+impl Bindings {
+    fn save(context: &WebGl2RenderingContext) -> Self {
+        let parameter = |name: u32| context.get_parameter(name).ok();
+
+        Bindings {
+            program: parameter(WebGl2RenderingContext::CURRENT_PROGRAM)
+                .and_then(|p| p.dyn_into::<WebGlProgram>().ok()),
+            vao: parameter(WebGl2RenderingContext::VERTEX_ARRAY_BINDING)
+                .and_then(|v| v.dyn_into::<WebGlVertexArrayObject>().ok()),
+            array_buffer: parameter(WebGl2RenderingContext::ARRAY_BUFFER_BINDING)
+                .and_then(|b| b.dyn_into::<WebGlBuffer>().ok()),
+            framebuffer: parameter(WebGl2RenderingContext::FRAMEBUFFER_BINDING)
+                .and_then(|b| b.dyn_into::<WebGlFramebuffer>().ok()),
+            texture: parameter(WebGl2RenderingContext::TEXTURE_BINDING_2D)
+                .and_then(|t| t.dyn_into::<WebGlTexture>().ok()),
+            viewport: parameter(WebGl2RenderingContext::VIEWPORT)
+                .and_then(|v| v.dyn_into::<js_sys::Int32Array>().ok())
+                .map(|v| v.to_vec()),
+        }
+    }
+
+    fn restore(self, context: &WebGl2RenderingContext) {
+        context.use_program(self.program.as_ref());
+        context.bind_vertex_array(self.vao.as_ref());
+        context.bind_buffer(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            self.array_buffer.as_ref(),
+        );
+        context.bind_framebuffer(
+            WebGl2RenderingContext::FRAMEBUFFER,
+            self.framebuffer.as_ref(),
+        );
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, self.texture.as_ref());
+        if let Some(&[x, y, width, height]) = self.viewport.as_deref() {
+            context.viewport(x, y, width, height);
+        }
+    }
+}
+
+/// A vertex array with the full-screen quad fed to `loc_position`. Leaves it
+/// and its buffer bound.
+fn quad_of(
+    context: &WebGl2RenderingContext,
+    loc_position: u32,
+) -> Result<WebGlVertexArrayObject, String> {
+    let vao = context
+        .create_vertex_array()
+        .ok_or_else(|| String::from("Unable to create vertex array"))?;
+    let buffer = context
+        .create_buffer()
+        .ok_or_else(|| String::from("Unable to create buffer"))?;
+
+    context.bind_vertex_array(Some(&vao));
+    context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+
+    unsafe {
+        let positions_array_buffer_view = js_sys::Float32Array::view(&QUAD_POSITIONS);
+
+        context.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &positions_array_buffer_view,
+            WebGl2RenderingContext::STATIC_DRAW,
+        );
+    }
+
+    context.enable_vertex_attrib_array(loc_position);
+    context.vertex_attrib_pointer_with_i32(
+        loc_position,
+        2,
+        WebGl2RenderingContext::FLOAT,
+        false,
+        0,
+        0,
+    );
+
+    Ok(vao)
+}
+
+fn create_tile_target(
+    context: &WebGl2RenderingContext,
+    tile_size: u32,
+) -> Result<WebGlFramebuffer, String> {
+    let texture = context
+        .create_texture()
+        .ok_or_else(|| String::from("Unable to create texture"))?;
+
+    context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture));
+    context
+        .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            WebGl2RenderingContext::TEXTURE_2D,
+            0,
+            WebGl2RenderingContext::RGBA8 as i32,
+            tile_size as i32,
+            tile_size as i32,
+            0,
+            WebGl2RenderingContext::RGBA,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            None,
+        )
+        .map_err(|_| String::from("Unable to allocate tile texture"))?;
+    context.tex_parameteri(
+        WebGl2RenderingContext::TEXTURE_2D,
+        WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+        WebGl2RenderingContext::NEAREST as i32,
+    );
+
+    let framebuffer = context
+        .create_framebuffer()
+        .ok_or_else(|| String::from("Unable to create framebuffer"))?;
+
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&framebuffer));
+    context.framebuffer_texture_2d(
+        WebGl2RenderingContext::FRAMEBUFFER,
+        WebGl2RenderingContext::COLOR_ATTACHMENT0,
+        WebGl2RenderingContext::TEXTURE_2D,
+        Some(&texture),
+        0,
+    );
+
+    let status = context.check_framebuffer_status(WebGl2RenderingContext::FRAMEBUFFER);
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+
+    if status != WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+        return Err(format!("Tile framebuffer is incomplete: {:#x}", status));
+    }
+
+    Ok(framebuffer)
+}
+
+/// Forwards everything written to it to a JS callback.
+struct JsSink {
+    sink: js_sys::Function,
+}
+
+impl Write for JsSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk = js_sys::Uint8Array::from(buf);
+
+        self.sink
+            .call1(&JsValue::NULL, &chunk)
+            .map_err(|err| io::Error::other(format!("{:?}", err)))?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn to_js_error<E: std::fmt::Display>(err: E) -> JsValue {
+    JsValue::from(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_counts_partial_tiles() {
+        let grid = TileGrid::new(5000, 2048, 2048);
+        assert_eq!((grid.columns, grid.rows), (3, 1));
+
+        let exact = TileGrid::new(4096, 4096, 2048);
+        assert_eq!((exact.columns, exact.rows), (2, 2));
+
+        let small = TileGrid::new(10, 1, 2048);
+        assert_eq!((small.columns, small.rows), (1, 1));
+    }
+
+    #[test]
+    fn rgba_len_does_not_overflow_u32() {
+        assert_eq!(rgba_len(32768, 32768), 4 << 30);
+        assert_eq!(rgba_len(3, 2), 24);
+    }
+
+    #[test]
+    fn edge_tiles_are_cropped() {
+        let grid = TileGrid::new(5000, 3000, 2048);
+
+        assert_eq!(
+            grid.tile(0, 0),
+            Tile {
+                x: 0,
+                y: 0,
+                width: 2048,
+                height: 2048
+            }
+        );
+        assert_eq!(
+            grid.tile(2, 1),
+            Tile {
+                x: 4096,
+                y: 2048,
+                width: 904,
+                height: 952
+            }
+        );
+        assert_eq!(grid.tile_height(1), 952);
+    }
+
+    #[test]
+    fn tiles_cover_the_image_once() {
+        let grid = TileGrid::new(300, 170, 64);
+        let mut covered = vec![0u8; 300 * 170];
+
+        for row in 0..grid.rows {
+            for column in 0..grid.columns {
+                let tile = grid.tile(column, row);
+                assert!(tile.width > 0 && tile.height > 0);
+
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        covered[(y * 300 + x) as usize] += 1;
+                    }
+                }
+            }
+        }
+
+        assert!(covered.iter().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn tile_offset_goes_after_version() {
+        let source = "#version 300 es\nvoid main() { gl_FragCoord.xy; }";
+        let injected = inject_tile_offset(source);
+
+        assert!(injected.starts_with("#version 300 es\nuniform highp vec2 u_tile_offset;\n"));
+        assert!(injected.contains("(gl_FragCoord + vec4(u_tile_offset, 0.0, 0.0)).xy"));
+
+        let legacy = inject_tile_offset("void main() {}");
+        assert!(legacy.starts_with("uniform highp vec2 u_tile_offset;\n"));
+    }
+}
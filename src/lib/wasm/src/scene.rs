@@ -0,0 +1,478 @@
+// A scene graph: nodes with a local transform (translation, rotation,
+// scale), arranged in a hierarchy and optionally showing a mesh or
+// carrying a light.
+//
+//   let mut scene = Scene::new();
+//   let cube = scene.add_mesh(geometry::cylinder(1.0, 2.0, 32).to_mesh(&mut state)?);
+//   let arm = scene.add_node(None);
+//   let hand = scene.add_node(Some(arm));
+//   scene.attach_mesh(hand, cube);
+//   scene.set_translation(hand, glm::vec3(0.0, 2.0, 0.0));
+//   renderer.render(&mut state, &mut scene, &view, &projection)?;
+//
+// World matrices are cached and only recomputed for nodes whose transform,
+// or one of whose ancestors' transforms, changed since the last update.
+
+extern crate nalgebra_glm as glm;
+
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+use crate::lighting::{Light, PhongMaterial};
+use crate::mesh::Mesh;
+use crate::pbr::PbrMaterial;
+use crate::pipeline::PipelineState;
+use crate::state::GlState;
+
+pub type NodeId = usize;
+pub type MeshId = usize;
+pub type MaterialId = usize;
+
+/// How a node's mesh is shaded; each renderer reads its own kind and falls
+/// back to its default for the others.
+#[derive(Clone, Debug)]
+pub enum Material {
+    Phong(PhongMaterial),
+    Pbr(PbrMaterial),
+}
+
+impl From<PhongMaterial> for Material {
+    fn from(material: PhongMaterial) -> Self {
+        Material::Phong(material)
+    }
+}
+
+impl From<PbrMaterial> for Material {
+    fn from(material: PbrMaterial) -> Self {
+        Material::Pbr(material)
+    }
+}
+
+pub struct Node {
+    pub name: Option<String>,
+    translation: glm::Vec3,
+    rotation: glm::Quat,
+    scale: glm::Vec3,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    mesh: Option<MeshId>,
+    material: Option<MaterialId>,
+    light: Option<Light>,
+    world: glm::Mat4,
+    dirty: bool,
+}
+
+impl Node {
+    fn new(parent: Option<NodeId>) -> Self {
+        Node {
+            name: None,
+            translation: glm::Vec3::zeros(),
+            rotation: glm::quat_identity(),
+            scale: glm::vec3(1.0, 1.0, 1.0),
+            parent,
+            children: vec![],
+            mesh: None,
+            material: None,
+            light: None,
+            world: glm::Mat4::identity(),
+            dirty: true,
+        }
+    }
+
+    pub fn translation(&self) -> glm::Vec3 {
+        self.translation
+    }
+
+    pub fn rotation(&self) -> glm::Quat {
+        self.rotation
+    }
+
+    pub fn scale(&self) -> glm::Vec3 {
+        self.scale
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn mesh(&self) -> Option<MeshId> {
+        self.mesh
+    }
+
+    pub fn material(&self) -> Option<MaterialId> {
+        self.material
+    }
+
+    pub fn light(&self) -> Option<&Light> {
+        self.light.as_ref()
+    }
+
+    /// Where the node is, as of the last `Scene::update`.
+    pub fn world_position(&self) -> glm::Vec3 {
+        (self.world * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz()
+    }
+
+    /// Where the node's -Z axis points, as of the last `Scene::update`.
+    /// Lights shine this way.
+    pub fn world_direction(&self) -> glm::Vec3 {
+        glm::normalize(&(self.world * glm::vec4(0.0, 0.0, -1.0, 0.0)).xyz())
+    }
+
+    /// Translation * rotation * scale.
+    pub fn local_matrix(&self) -> glm::Mat4 {
+        glm::translation(&self.translation)
+            * glm::quat_to_mat4(&self.rotation)
+            * glm::scaling(&self.scale)
+    }
+}
+
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    meshes: Vec<Mesh>,
+    materials: Vec<Material>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    pub fn add_node(&mut self, parent: Option<NodeId>) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node::new(parent));
+
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len()).filter(|&id| self.nodes[id].parent.is_none())
+    }
+
+    pub fn set_name(&mut self, id: NodeId, name: &str) {
+        self.nodes[id].name = Some(name.to_string());
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node.name.as_deref() == Some(name))
+    }
+
+    /// Moves `id` under `parent` (or to the top level), keeping its local
+    /// transform. Fails if `parent` is `id` or one of its descendants.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), String> {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            if a == id {
+                return Err(format!("Node {} can't be its own ancestor", id));
+            }
+            ancestor = self.nodes[a].parent;
+        }
+
+        if let Some(old) = self.nodes[id].parent {
+            self.nodes[old].children.retain(|&child| child != id);
+        }
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+
+        self.nodes[id].parent = parent;
+        self.nodes[id].dirty = true;
+
+        Ok(())
+    }
+
+    pub fn set_translation(&mut self, id: NodeId, translation: glm::Vec3) {
+        self.nodes[id].translation = translation;
+        self.nodes[id].dirty = true;
+    }
+
+    pub fn set_rotation(&mut self, id: NodeId, rotation: glm::Quat) {
+        self.nodes[id].rotation = glm::quat_normalize(&rotation);
+        self.nodes[id].dirty = true;
+    }
+
+    pub fn set_scale(&mut self, id: NodeId, scale: glm::Vec3) {
+        self.nodes[id].scale = scale;
+        self.nodes[id].dirty = true;
+    }
+
+    pub fn add_mesh(&mut self, mesh: Mesh) -> MeshId {
+        self.meshes.push(mesh);
+        self.meshes.len() - 1
+    }
+
+    /// The same mesh may be shown by any number of nodes.
+    pub fn attach_mesh(&mut self, id: NodeId, mesh: MeshId) {
+        self.nodes[id].mesh = Some(mesh);
+    }
+
+    pub fn detach_mesh(&mut self, id: NodeId) {
+        self.nodes[id].mesh = None;
+    }
+
+    pub fn add_material(&mut self, material: impl Into<Material>) -> MaterialId {
+        self.materials.push(material.into());
+        self.materials.len() - 1
+    }
+
+    pub fn material(&self, id: MaterialId) -> Option<&Material> {
+        self.materials.get(id)
+    }
+
+    pub fn material_mut(&mut self, id: MaterialId) -> Option<&mut Material> {
+        self.materials.get_mut(id)
+    }
+
+    /// Lit renderers fall back to their default material for nodes without
+    /// one.
+    pub fn set_material(&mut self, id: NodeId, material: MaterialId) {
+        self.nodes[id].material = Some(material);
+    }
+
+    /// Lights shine along the node's -Z axis and sit at its origin.
+    pub fn attach_light(&mut self, id: NodeId, light: Light) {
+        self.nodes[id].light = Some(light);
+    }
+
+    pub fn detach_light(&mut self, id: NodeId) -> Option<Light> {
+        self.nodes[id].light.take()
+    }
+
+    pub fn light_mut(&mut self, id: NodeId) -> Option<&mut Light> {
+        self.nodes[id].light.as_mut()
+    }
+
+    /// Nodes carrying a light, in node order.
+    pub fn lights(&self) -> impl Iterator<Item = (&Node, &Light)> + '_ {
+        self.nodes
+            .iter()
+            .filter_map(|node| node.light.as_ref().map(|light| (node, light)))
+    }
+
+    /// The world matrix as of the last `update`.
+    pub fn world_matrix(&self, id: NodeId) -> glm::Mat4 {
+        self.nodes[id].world
+    }
+
+    /// Recomputes the world matrices of changed nodes and their descendants.
+    pub fn update(&mut self) {
+        let mut stack = self
+            .roots()
+            .map(|id| (id, glm::Mat4::identity(), false))
+            .collect::<Vec<_>>();
+
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let node = &mut self.nodes[id];
+            let changed = parent_changed || node.dirty;
+
+            if changed {
+                node.world = parent_world * node.local_matrix();
+                node.dirty = false;
+            }
+
+            let world = node.world;
+            stack.extend(node.children.iter().map(|&child| (child, world, changed)));
+        }
+    }
+}
+
+/// Draws every node with a mesh, with these uniforms set when the program
+/// declares them:
+///
+///   uniform mat4 uModelMatrix;
+///   uniform mat4 uViewMatrix;
+///   uniform mat4 uModelViewMatrix;
+///   uniform mat4 uProjectionMatrix;
+///   uniform mat3 uNormalMatrix;  // inverse transpose of the model-view
+///
+/// Fixed-function state comes from its `PipelineState`, by default depth
+/// tested and written without culling, as loaded models may wind either
+/// way.
+pub struct Renderer {
+    program: WebGlProgram,
+    pipeline: PipelineState,
+    loc_model: Option<WebGlUniformLocation>,
+    loc_view: Option<WebGlUniformLocation>,
+    loc_model_view: Option<WebGlUniformLocation>,
+    loc_projection: Option<WebGlUniformLocation>,
+    loc_normal: Option<WebGlUniformLocation>,
+}
+
+impl Renderer {
+    pub fn new(context: &WebGl2RenderingContext, program: &WebGlProgram) -> Self {
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/getUniformLocation
+        let location = |name: &str| context.get_uniform_location(program, name);
+
+        Renderer {
+            program: program.clone(),
+            pipeline: PipelineState::new().depth(WebGl2RenderingContext::LESS, true),
+            loc_model: location("uModelMatrix"),
+            loc_view: location("uViewMatrix"),
+            loc_model_view: location("uModelViewMatrix"),
+            loc_projection: location("uProjectionMatrix"),
+            loc_normal: location("uNormalMatrix"),
+        }
+    }
+
+    pub fn program(&self) -> &WebGlProgram {
+        &self.program
+    }
+
+    pub fn pipeline(&self) -> &PipelineState {
+        &self.pipeline
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: PipelineState) {
+        self.pipeline = pipeline;
+    }
+
+    /// Applies the pipeline and binds the program and the meshes through
+    /// `state`, so that nodes sharing a mesh, and frames drawing the same
+    /// scene, skip the calls.
+    pub fn render(
+        &self,
+        state: &mut GlState,
+        scene: &mut Scene,
+        view: &glm::Mat4,
+        projection: &glm::Mat4,
+    ) -> Result<(), String> {
+        self.render_with(state, scene, view, projection, |_, _, _| {})
+    }
+
+    /// Like `render`, calling `before_draw` for each node about to be drawn,
+    /// with the program in use, to set per-node uniforms such as materials
+    /// and bind their textures through the state.
+    pub fn render_with<F>(
+        &self,
+        state: &mut GlState,
+        scene: &mut Scene,
+        view: &glm::Mat4,
+        projection: &glm::Mat4,
+        mut before_draw: F,
+    ) -> Result<(), String>
+    where
+        F: FnMut(&mut GlState, &Scene, NodeId),
+    {
+        scene.update();
+
+        let context = state.context().clone();
+        self.pipeline.apply(state);
+        state.use_program(Some(&self.program));
+
+        context.uniform_matrix4fv_with_f32_array(self.loc_view.as_ref(), false, view.as_slice());
+        context.uniform_matrix4fv_with_f32_array(
+            self.loc_projection.as_ref(),
+            false,
+            projection.as_slice(),
+        );
+
+        for id in 0..scene.nodes.len() {
+            let mesh = match scene.nodes[id].mesh {
+                None => continue,
+                Some(mesh) => mesh,
+            };
+
+            let model = scene.nodes[id].world;
+            let model_view = view * model;
+
+            context.uniform_matrix4fv_with_f32_array(
+                self.loc_model.as_ref(),
+                false,
+                model.as_slice(),
+            );
+            context.uniform_matrix4fv_with_f32_array(
+                self.loc_model_view.as_ref(),
+                false,
+                model_view.as_slice(),
+            );
+
+            if self.loc_normal.is_some() {
+                let normal = glm::mat4_to_mat3(&glm::transpose(&glm::inverse(&model_view)));
+                context.uniform_matrix3fv_with_f32_array(
+                    self.loc_normal.as_ref(),
+                    false,
+                    normal.as_slice(),
+                );
+            }
+
+            before_draw(state, scene, id);
+
+            scene
+                .meshes
+                .get_mut(mesh)
+                .ok_or_else(|| format!("Mesh {} does not exist", mesh))?
+                .draw(state, &self.program)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin_of(scene: &Scene, id: NodeId) -> glm::Vec3 {
+        (scene.world_matrix(id) * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz()
+    }
+
+    #[test]
+    fn children_follow_parents() {
+        let mut scene = Scene::new();
+        let arm = scene.add_node(None);
+        let hand = scene.add_node(Some(arm));
+
+        scene.set_translation(arm, glm::vec3(1.0, 0.0, 0.0));
+        scene.set_rotation(
+            arm,
+            glm::quat_angle_axis(std::f32::consts::FRAC_PI_2, &glm::vec3(0.0, 0.0, 1.0)),
+        );
+        scene.set_translation(hand, glm::vec3(2.0, 0.0, 0.0));
+        scene.update();
+
+        assert!(glm::distance(&origin_of(&scene, hand), &glm::vec3(1.0, 2.0, 0.0)) < 1e-5);
+
+        // Only the parent changes; the child's cached matrix must still move.
+        scene.set_translation(arm, glm::vec3(0.0, 0.0, 3.0));
+        scene.update();
+
+        assert!(glm::distance(&origin_of(&scene, hand), &glm::vec3(0.0, 2.0, 3.0)) < 1e-5);
+    }
+
+    #[test]
+    fn reparenting() {
+        let mut scene = Scene::new();
+        let a = scene.add_node(None);
+        let b = scene.add_node(Some(a));
+        let c = scene.add_node(None);
+
+        scene.set_translation(c, glm::vec3(0.0, 5.0, 0.0));
+        scene.set_parent(b, Some(c)).unwrap();
+        scene.update();
+
+        assert!(scene.node(a).children().is_empty());
+        assert_eq!(scene.node(c).children(), &[b]);
+        assert!(glm::distance(&origin_of(&scene, b), &glm::vec3(0.0, 5.0, 0.0)) < 1e-5);
+
+        assert!(scene.set_parent(c, Some(b)).is_err());
+        assert!(scene.set_parent(c, Some(c)).is_err());
+        assert_eq!(scene.roots().collect::<Vec<_>>(), vec![a, c]);
+    }
+}
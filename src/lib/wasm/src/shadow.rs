@@ -0,0 +1,643 @@
+// Shadow maps for directional and spot lights.
+//
+// Lights with a `Shadow` (see `Light::with_shadow`) get layers of a depth
+// texture array: spot lights one, rendered with a perspective projection
+// through their cone; directional lights one per cascade, each an
+// orthographic projection around a slice of the camera's view volume.
+// Slices are split between the camera's near plane and `max_distance`,
+// blending logarithmic and uniform splits (Zhang et al., "Parallel-Split
+// Shadow Maps", 2006).
+//
+// Cascades are bounding spheres of their slice snapped to whole texels, so
+// that shadows don't shimmer as the camera moves.
+//
+// `ShadowMaps::render` draws the depth pass and returns a `ShadowFrame`;
+// lit shaders include `shadow_declarations` and multiply each light by
+// `shadowFactor`, with `ShadowUniforms` uploading the frame.
+//
+// https://learnopengl.com/Guest-Articles/2021/CSM
+
+extern crate nalgebra_glm as glm;
+
+use wasm_bindgen::JsCast;
+use web_sys::{WebGl2RenderingContext, WebGlFramebuffer, WebGlTexture, WebGlUniformLocation};
+
+use crate::camera::Camera;
+use crate::lighting::LightKind;
+use crate::pipeline::PipelineState;
+use crate::scene::{Renderer, Scene};
+use crate::state::GlState;
+use crate::webgl::{compile_shader, link_shader_program};
+
+pub const MAX_SHADOW_LAYERS: usize = 8;
+pub const MAX_CASCADES: usize = 4;
+pub const MAX_PCF_RADIUS: u32 = 3;
+
+/// Texture unit of the shadow map in lit shaders, clear of the units used
+/// for material and environment maps.
+pub const SHADOW_UNIT: u32 = 8;
+
+// Spot light shadows start this close to the light.
+const SPOT_NEAR: f32 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowOptions {
+    /// Width and height of every layer.
+    pub resolution: u32,
+    /// Layers of the depth texture, at most `MAX_SHADOW_LAYERS`. Lights
+    /// that don't fit, in node order, cast no shadows.
+    pub layers: usize,
+    /// Cascades of each directional light, at most `MAX_CASCADES`.
+    pub cascades: usize,
+    /// 0 splits the view uniformly, 1 logarithmically.
+    pub split_lambda: f32,
+    /// Nothing is shadowed farther than this from the camera, and spot
+    /// lights cast shadows this far.
+    pub max_distance: f32,
+    /// Polygon offset of the depth pass, scaled by the slope of each
+    /// triangle.
+    pub slope_bias: f32,
+}
+
+impl Default for ShadowOptions {
+    fn default() -> Self {
+        ShadowOptions {
+            resolution: 2048,
+            layers: MAX_SHADOW_LAYERS,
+            cascades: MAX_CASCADES,
+            split_lambda: 0.75,
+            max_distance: 50.0,
+            slope_bias: 2.0,
+        }
+    }
+}
+
+static DEPTH_VS_SRC: &str = r#"#version 300 es
+in vec3 position;
+
+uniform mat4 uModelViewMatrix;
+uniform mat4 uProjectionMatrix;
+
+void main() {
+  gl_Position = uProjectionMatrix * uModelViewMatrix * vec4(position, 1.0);
+}
+"#;
+
+static DEPTH_FS_SRC: &str = r#"#version 300 es
+precision mediump float;
+
+void main() {}
+"#;
+
+/// Declares, after `lighting::light_declarations`,
+///
+///   // 0 where light `i` is blocked, 1 where it isn't; view space arguments
+///   float shadowFactor(int i, vec3 position, vec3 normal);
+///
+/// for shaders whose shadows are set by `ShadowUniforms`.
+pub fn shadow_declarations() -> String {
+    format!(
+        r#"#define MAX_SHADOW_LAYERS {MAX_SHADOW_LAYERS}
+#define MAX_CASCADES {MAX_CASCADES}
+#define MAX_PCF_RADIUS {MAX_PCF_RADIUS}
+
+struct LightShadow {{
+  int layer;     // first layer, -1 without shadows
+  int cascades;  // layers used, from the nearest
+  float bias;
+  float normalBias;
+  int pcfRadius;
+}};
+
+uniform LightShadow uLightShadows[MAX_LIGHTS];
+uniform mat4 uShadowMatrices[MAX_SHADOW_LAYERS];  // view space to [0, 1]
+uniform float uCascadeSplits[MAX_CASCADES];       // far distance of each
+uniform float uShadowTexel;
+uniform highp sampler2DArrayShadow uShadowMap;
+
+float shadowFactor(int i, vec3 position, vec3 normal) {{
+  LightShadow s = uLightShadows[i];
+  if (s.layer < 0) return 1.0;
+
+  int layer = s.layer;
+  if (s.cascades > 1) {{
+    int cascade = -1;
+    for (int c = 0; c < MAX_CASCADES; c++) {{
+      if (c < s.cascades && -position.z <= uCascadeSplits[c]) {{
+        cascade = c;
+        break;
+      }}
+    }}
+    if (cascade < 0) return 1.0;
+    layer += cascade;
+  }}
+
+  vec4 p = uShadowMatrices[layer] * vec4(position + normal * s.normalBias, 1.0);
+  p.xyz /= p.w;
+  if (any(lessThan(p.xyz, vec3(0.0))) || any(greaterThan(p.xyz, vec3(1.0)))) return 1.0;
+
+  float depth = p.z - s.bias;
+  float lit = 0.0;
+  float taps = 0.0;
+
+  for (int x = -MAX_PCF_RADIUS; x <= MAX_PCF_RADIUS; x++) {{
+    for (int y = -MAX_PCF_RADIUS; y <= MAX_PCF_RADIUS; y++) {{
+      if (abs(x) > s.pcfRadius || abs(y) > s.pcfRadius) continue;
+
+      vec2 uv = p.xy + vec2(x, y) * uShadowTexel;
+      lit += texture(uShadowMap, vec4(uv, float(layer), depth));
+      taps += 1.0;
+    }}
+  }}
+
+  return lit / taps;
+}}
+"#
+    )
+}
+
+/// Layers of one light.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Layers {
+    first: usize,
+    count: usize,
+}
+
+/// The result of a depth pass, for `ShadowUniforms::upload`.
+pub struct ShadowFrame {
+    texture: WebGlTexture,
+    resolution: u32,
+    /// In `Scene::lights` order.
+    lights: Vec<Option<Layers>>,
+    /// World space to [0, 1], per layer.
+    matrices: Vec<glm::Mat4>,
+    splits: Vec<f32>,
+}
+
+impl ShadowFrame {
+    /// The matrix from world space to shadow map coordinates and depth of
+    /// each layer in use.
+    pub fn matrices(&self) -> &[glm::Mat4] {
+        &self.matrices
+    }
+
+    /// View space distance at which each cascade ends.
+    pub fn splits(&self) -> &[f32] {
+        &self.splits
+    }
+}
+
+pub struct ShadowMaps {
+    options: ShadowOptions,
+    depth: WebGlTexture,
+    framebuffer: WebGlFramebuffer,
+    renderer: Renderer,
+}
+
+impl ShadowMaps {
+    pub fn new(context: &WebGl2RenderingContext, options: ShadowOptions) -> Result<Self, String> {
+        let options = ShadowOptions {
+            layers: options.layers.clamp(1, MAX_SHADOW_LAYERS),
+            cascades: options.cascades.clamp(1, MAX_CASCADES),
+            ..options
+        };
+
+        let vs = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, DEPTH_VS_SRC)?;
+        let fs = compile_shader(
+            context,
+            WebGl2RenderingContext::FRAGMENT_SHADER,
+            DEPTH_FS_SRC,
+        )?;
+        let program = link_shader_program(context, &vs, &fs)?;
+
+        let depth = create_depth_array(
+            context,
+            options.resolution,
+            options.layers,
+            WebGl2RenderingContext::DEPTH_COMPONENT24,
+        )?;
+        let framebuffer = context
+            .create_framebuffer()
+            .ok_or_else(|| String::from("Unable to create framebuffer"))?;
+
+        let mut renderer = Renderer::new(context, &program);
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/polygonOffset
+        renderer.set_pipeline(
+            PipelineState::new()
+                .depth(WebGl2RenderingContext::LESS, true)
+                .polygon_offset(options.slope_bias, 1.0),
+        );
+
+        Ok(ShadowMaps {
+            options,
+            depth,
+            framebuffer,
+            renderer,
+        })
+    }
+
+    pub fn options(&self) -> &ShadowOptions {
+        &self.options
+    }
+
+    pub fn texture(&self) -> &WebGlTexture {
+        &self.depth
+    }
+
+    /// Draws the depth of every mesh as seen by each shadow casting light.
+    /// The framebuffer and viewport are restored afterwards; the depth
+    /// pass's `PipelineState`, with polygon offset, is left applied.
+    pub fn render(
+        &self,
+        state: &mut GlState,
+        scene: &mut Scene,
+        camera: &Camera,
+    ) -> Result<ShadowFrame, String> {
+        let context = &state.context().clone();
+        let options = &self.options;
+        let (near, far) = camera.depth_range();
+        let splits = cascade_splits(
+            near,
+            far.min(options.max_distance),
+            options.cascades,
+            options.split_lambda,
+        );
+
+        scene.update();
+
+        let mut lights = Vec::new();
+        let mut view_projections = Vec::new();
+
+        for (node, light) in scene.lights() {
+            let fits = |count: usize| view_projections.len() + count <= options.layers;
+
+            let layers = match (light.kind, light.shadow) {
+                (LightKind::Directional, Some(_)) if fits(splits.len()) => {
+                    let direction = node.world_direction();
+                    let first = view_projections.len();
+                    let mut start = near;
+
+                    for &end in &splits {
+                        let corners = camera.frustum_corners(start, end);
+                        view_projections.push(cascade_view_projection(
+                            &corners,
+                            &direction,
+                            options.resolution,
+                            options.max_distance,
+                        ));
+                        start = end;
+                    }
+
+                    Some(Layers {
+                        first,
+                        count: splits.len(),
+                    })
+                }
+                (LightKind::Spot { outer_angle, .. }, Some(_)) if fits(1) => {
+                    view_projections.push(spot_view_projection(
+                        &node.world_position(),
+                        &node.world_direction(),
+                        outer_angle,
+                        options.max_distance,
+                    ));
+
+                    Some(Layers {
+                        first: view_projections.len() - 1,
+                        count: 1,
+                    })
+                }
+                _ => None,
+            };
+
+            lights.push(layers);
+        }
+
+        let framebuffer = context
+            .get_parameter(WebGl2RenderingContext::FRAMEBUFFER_BINDING)
+            .ok()
+            .and_then(|binding| binding.dyn_into::<WebGlFramebuffer>().ok());
+        let viewport = context
+            .get_parameter(WebGl2RenderingContext::VIEWPORT)
+            .ok()
+            .and_then(|viewport| viewport.dyn_into::<js_sys::Int32Array>().ok())
+            .map(|viewport| viewport.to_vec());
+
+        let size = options.resolution as i32;
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&self.framebuffer));
+        state.viewport(0, 0, size, size);
+        // before clearing, which goes through the depth mask
+        self.renderer.pipeline().apply(state);
+
+        let mut result = Ok(());
+        for (layer, view_projection) in view_projections.iter().enumerate() {
+            // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/framebufferTextureLayer
+            context.framebuffer_texture_layer(
+                WebGl2RenderingContext::FRAMEBUFFER,
+                WebGl2RenderingContext::DEPTH_ATTACHMENT,
+                Some(&self.depth),
+                0,
+                layer as i32,
+            );
+            state.clear(WebGl2RenderingContext::DEPTH_BUFFER_BIT);
+
+            result = self
+                .renderer
+                .render(state, scene, view_projection, &glm::Mat4::identity());
+            if result.is_err() {
+                break;
+            }
+        }
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, framebuffer.as_ref());
+        if let Some(&[x, y, width, height]) = viewport.as_deref() {
+            state.viewport(x, y, width, height);
+        }
+        result?;
+
+        // from clip space to texture coordinates and [0, 1] depth
+        let bias =
+            glm::translation(&glm::vec3(0.5, 0.5, 0.5)) * glm::scaling(&glm::vec3(0.5, 0.5, 0.5));
+
+        Ok(ShadowFrame {
+            texture: self.depth.clone(),
+            resolution: options.resolution,
+            lights,
+            matrices: view_projections.iter().map(|m| bias * m).collect(),
+            splits,
+        })
+    }
+}
+
+struct LightShadowLocations {
+    layer: Option<WebGlUniformLocation>,
+    cascades: Option<WebGlUniformLocation>,
+    bias: Option<WebGlUniformLocation>,
+    normal_bias: Option<WebGlUniformLocation>,
+    pcf_radius: Option<WebGlUniformLocation>,
+}
+
+/// Uploads a `ShadowFrame` to the uniforms of `shadow_declarations`.
+pub struct ShadowUniforms {
+    lights: Vec<LightShadowLocations>,
+    loc_matrices: Vec<Option<WebGlUniformLocation>>,
+    loc_splits: Vec<Option<WebGlUniformLocation>>,
+    loc_texel: Option<WebGlUniformLocation>,
+    loc_shadow_map: Option<WebGlUniformLocation>,
+    // bound when there is no frame, as the sampler needs a depth texture
+    fallback: WebGlTexture,
+}
+
+impl ShadowUniforms {
+    pub fn new(
+        context: &WebGl2RenderingContext,
+        program: &web_sys::WebGlProgram,
+        max_lights: usize,
+    ) -> Result<Self, String> {
+        let location = |name: &str| context.get_uniform_location(program, name);
+
+        let lights = (0..max_lights)
+            .map(|i| {
+                let field = |field: &str| location(&format!("uLightShadows[{}].{}", i, field));
+
+                LightShadowLocations {
+                    layer: field("layer"),
+                    cascades: field("cascades"),
+                    bias: field("bias"),
+                    normal_bias: field("normalBias"),
+                    pcf_radius: field("pcfRadius"),
+                }
+            })
+            .collect();
+
+        Ok(ShadowUniforms {
+            lights,
+            loc_matrices: (0..MAX_SHADOW_LAYERS)
+                .map(|i| location(&format!("uShadowMatrices[{}]", i)))
+                .collect(),
+            loc_splits: (0..MAX_CASCADES)
+                .map(|i| location(&format!("uCascadeSplits[{}]", i)))
+                .collect(),
+            loc_texel: location("uShadowTexel"),
+            loc_shadow_map: location("uShadowMap"),
+            fallback: create_depth_array(context, 1, 1, WebGl2RenderingContext::DEPTH_COMPONENT16)?,
+        })
+    }
+
+    /// Without a frame, nothing is shadowed. The program must be in use.
+    pub fn upload(
+        &self,
+        state: &mut GlState,
+        scene: &Scene,
+        frame: Option<&ShadowFrame>,
+        view: &glm::Mat4,
+    ) {
+        let context = &state.context().clone();
+        state.bind_texture(
+            SHADOW_UNIT,
+            WebGl2RenderingContext::TEXTURE_2D_ARRAY,
+            Some(frame.map(|f| &f.texture).unwrap_or(&self.fallback)),
+        );
+        context.uniform1i(self.loc_shadow_map.as_ref(), SHADOW_UNIT as i32);
+
+        let assigned = |i: usize| frame.and_then(|f| f.lights.get(i).copied().flatten());
+
+        for (i, ((_, light), loc)) in scene.lights().zip(&self.lights).enumerate() {
+            let (layers, shadow) = match (assigned(i), light.shadow) {
+                (Some(layers), Some(shadow)) => (layers, shadow),
+                _ => {
+                    context.uniform1i(loc.layer.as_ref(), -1);
+                    continue;
+                }
+            };
+
+            context.uniform1i(loc.layer.as_ref(), layers.first as i32);
+            context.uniform1i(loc.cascades.as_ref(), layers.count as i32);
+            context.uniform1f(loc.bias.as_ref(), shadow.bias);
+            context.uniform1f(loc.normal_bias.as_ref(), shadow.normal_bias);
+            context.uniform1i(
+                loc.pcf_radius.as_ref(),
+                shadow.pcf_radius.min(MAX_PCF_RADIUS) as i32,
+            );
+        }
+
+        let frame = match frame {
+            None => return,
+            Some(frame) => frame,
+        };
+
+        // the lit shader works in view space
+        let inverse_view = glm::inverse(view);
+        for (matrix, loc) in frame.matrices.iter().zip(&self.loc_matrices) {
+            context.uniform_matrix4fv_with_f32_array(
+                loc.as_ref(),
+                false,
+                (matrix * inverse_view).as_slice(),
+            );
+        }
+        for (split, loc) in frame.splits.iter().zip(&self.loc_splits) {
+            context.uniform1f(loc.as_ref(), *split);
+        }
+        context.uniform1f(self.loc_texel.as_ref(), 1.0 / frame.resolution as f32);
+    }
+}
+
+/// Far distance of each of `count` slices between `near` and `far`.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    // the logarithmic split is undefined from 0
+    let near = near.max(1e-3);
+    let far = far.max(near);
+
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// An orthographic projection along `direction` around `corners`, which
+/// also catches casters up to `reach` behind them.
+fn cascade_view_projection(
+    corners: &[glm::Vec3; 8],
+    direction: &glm::Vec3,
+    resolution: u32,
+    reach: f32,
+) -> glm::Mat4 {
+    let center = corners.iter().sum::<glm::Vec3>() / 8.0;
+    let radius = corners
+        .iter()
+        .map(|corner| glm::distance(corner, &center))
+        .fold(0.0, f32::max);
+    // whole sixteenths, so that the size doesn't flicker from rounding
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let rotation = glm::look_at(&glm::Vec3::zeros(), direction, &up_for(direction));
+    let c = (rotation * center.push(1.0)).xyz();
+
+    // move in whole texels only
+    let texel = 2.0 * radius / resolution as f32;
+    let (x, y) = ((c.x / texel).floor() * texel, (c.y / texel).floor() * texel);
+    // snapping moves the center by up to a texel
+    let half = radius + texel;
+
+    glm::ortho(
+        x - half,
+        x + half,
+        y - half,
+        y + half,
+        -c.z - radius - reach,
+        -c.z + radius,
+    ) * rotation
+}
+
+fn spot_view_projection(
+    position: &glm::Vec3,
+    direction: &glm::Vec3,
+    outer_angle: f32,
+    far: f32,
+) -> glm::Mat4 {
+    let fov = (2.0 * outer_angle).clamp(0.01, 3.0);
+    let view = glm::look_at(position, &(position + direction), &up_for(direction));
+
+    glm::perspective(1.0, fov, SPOT_NEAR, far) * view
+}
+
+fn up_for(direction: &glm::Vec3) -> glm::Vec3 {
+    if direction.y.abs() > 0.99 {
+        glm::Vec3::z()
+    } else {
+        glm::Vec3::y()
+    }
+}
+
+fn create_depth_array(
+    context: &WebGl2RenderingContext,
+    size: u32,
+    layers: usize,
+    format: u32,
+) -> Result<WebGlTexture, String> {
+    let texture = context
+        .create_texture()
+        .ok_or_else(|| String::from("Unable to create texture"))?;
+    let target = WebGl2RenderingContext::TEXTURE_2D_ARRAY;
+
+    context.bind_texture(target, Some(&texture));
+    // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/texStorage3D
+    context.tex_storage_3d(target, 1, format, size as i32, size as i32, layers as i32);
+
+    // compare in the sampler, with bilinear filtering of the results
+    let parameters = [
+        (
+            WebGl2RenderingContext::TEXTURE_COMPARE_MODE,
+            WebGl2RenderingContext::COMPARE_REF_TO_TEXTURE,
+        ),
+        (
+            WebGl2RenderingContext::TEXTURE_COMPARE_FUNC,
+            WebGl2RenderingContext::LEQUAL,
+        ),
+        (
+            WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+            WebGl2RenderingContext::LINEAR,
+        ),
+        (
+            WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+            WebGl2RenderingContext::LINEAR,
+        ),
+        (
+            WebGl2RenderingContext::TEXTURE_WRAP_S,
+            WebGl2RenderingContext::CLAMP_TO_EDGE,
+        ),
+        (
+            WebGl2RenderingContext::TEXTURE_WRAP_T,
+            WebGl2RenderingContext::CLAMP_TO_EDGE,
+        ),
+    ];
+    for (name, value) in parameters {
+        context.tex_parameteri(target, name, value as i32);
+    }
+
+    context.bind_texture(target, None);
+
+    Ok(texture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_blend_uniform_and_logarithmic() {
+        let uniform = cascade_splits(1.0, 100.0, 4, 0.0);
+        assert_eq!(uniform, vec![25.75, 50.5, 75.25, 100.0]);
+
+        let logarithmic = cascade_splits(1.0, 100.0, 2, 1.0);
+        assert!((logarithmic[0] - 10.0).abs() < 1e-4);
+        assert!((logarithmic[1] - 100.0).abs() < 1e-3);
+
+        let blended = cascade_splits(0.0, 50.0, 4, 0.75);
+        assert!(blended.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn cascades_contain_their_slice() {
+        let mut camera =
+            Camera::default().look_at(glm::vec3(3.0, 2.0, 8.0), glm::vec3(0.0, 0.0, 0.0));
+        camera.resize(16, 9);
+        let direction = glm::normalize(&glm::vec3(-0.3, -1.0, -0.2));
+
+        let corners = camera.frustum_corners(2.0, 12.0);
+        let view_projection = cascade_view_projection(&corners, &direction, 1024, 10.0);
+
+        for corner in corners {
+            let p = view_projection * corner.push(1.0);
+            assert!(p.xyz().abs().max() <= 1.0, "{:?} is outside", p);
+        }
+
+        // the near face is 2 units in front of the camera
+        let near = corners[..4].iter().sum::<glm::Vec3>() / 4.0;
+        assert!((glm::distance(&near, &camera.position) - 2.0).abs() < 1e-3);
+    }
+}
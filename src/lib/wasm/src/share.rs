@@ -0,0 +1,377 @@
+// Shareable shader state, packed into a URL-safe string.
+//
+// Binary layout, before base64url (no padding):
+//
+//   [version: u8][flags: u8][field]*
+//   field = [tag: u8][length: varint][body: length bytes]
+//
+// The fields after the flags are deflated when `FLAG_DEFLATE` is set.
+// Readers skip (and keep) fields with tags they don't know, so new fields
+// can be added without bumping the version; the version only changes when
+// an existing field changes meaning.
+
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use std::collections::BTreeMap;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+const SHARE_VERSION: u8 = 1;
+
+const FLAG_DEFLATE: u8 = 0b0000_0001;
+
+// Far more than any real state; stops a small link inflating to gigabytes.
+const MAX_INFLATED_SIZE: usize = 1 << 20;
+
+const TAG_SHADER: u8 = 1;
+const TAG_TIME: u8 = 2;
+const TAG_RESOLUTION: u8 = 3;
+const TAG_CAMERA: u8 = 4;
+const TAG_PARAMETER: u8 = 5;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ShareState {
+    pub shader: String,
+    /// Parameter values by uniform name, as in a preset.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<[u32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraState>,
+    /// Fields written by a newer version, carried over when re-encoding.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unknown: Vec<UnknownField>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraState {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    /// Vertical field of view in radians.
+    pub fov: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UnknownField {
+    pub tag: u8,
+    pub data: Vec<u8>,
+}
+
+impl ShareState {
+    pub fn encode(&self, deflate: bool) -> String {
+        let mut fields = vec![];
+
+        write_field(&mut fields, TAG_SHADER, self.shader.as_bytes());
+
+        if let Some(time) = self.time {
+            write_field(&mut fields, TAG_TIME, &time.to_le_bytes());
+        }
+
+        if let Some([width, height]) = self.resolution {
+            let mut body = vec![];
+            write_varint(&mut body, width as u64);
+            write_varint(&mut body, height as u64);
+            write_field(&mut fields, TAG_RESOLUTION, &body);
+        }
+
+        if let Some(camera) = &self.camera {
+            let body = camera
+                .position
+                .iter()
+                .chain(&camera.target)
+                .chain([&camera.fov])
+                .flat_map(|v| v.to_le_bytes())
+                .collect::<Vec<_>>();
+            write_field(&mut fields, TAG_CAMERA, &body);
+        }
+
+        for (name, values) in &self.parameters {
+            let mut body = vec![];
+            write_varint(&mut body, name.len() as u64);
+            body.extend(name.as_bytes());
+            body.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+            write_field(&mut fields, TAG_PARAMETER, &body);
+        }
+
+        for field in &self.unknown {
+            write_field(&mut fields, field.tag, &field.data);
+        }
+
+        let mut flags = 0;
+
+        if deflate {
+            // only worth it for larger states; keep whichever is shorter
+            let compressed = miniz_oxide::deflate::compress_to_vec(&fields, 9);
+            if compressed.len() < fields.len() {
+                fields = compressed;
+                flags |= FLAG_DEFLATE;
+            }
+        }
+
+        let mut bytes = vec![SHARE_VERSION, flags];
+        bytes.extend(fields);
+
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, String> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded.trim())
+            .map_err(|err| format!("Invalid share string: {}", err))?;
+
+        let (version, flags, fields) = match &bytes[..] {
+            [version, flags, fields @ ..] => (*version, *flags, fields),
+            _ => return Err(String::from("Share string is too short")),
+        };
+
+        if version > SHARE_VERSION {
+            return Err(format!("Unsupported share version: {}", version));
+        }
+
+        let inflated;
+        let mut fields = if flags & FLAG_DEFLATE != 0 {
+            inflated =
+                miniz_oxide::inflate::decompress_to_vec_with_limit(fields, MAX_INFLATED_SIZE)
+                    .map_err(|err| format!("Invalid compressed share string: {:?}", err))?;
+            &inflated[..]
+        } else {
+            fields
+        };
+
+        let mut state = ShareState::default();
+
+        while !fields.is_empty() {
+            let tag = fields[0];
+            fields = &fields[1..];
+
+            let len = read_length(&mut fields)?;
+            if fields.len() < len {
+                return Err(format!("Field {} is truncated", tag));
+            }
+            let (body, rest) = fields.split_at(len);
+            fields = rest;
+
+            match tag {
+                TAG_SHADER => {
+                    state.shader = String::from_utf8(body.to_vec())
+                        .map_err(|_| String::from("Shader id is not UTF-8"))?;
+                }
+                TAG_TIME => state.time = Some(read_f32s(body, 1)?[0]),
+                TAG_RESOLUTION => {
+                    let mut body = body;
+                    let mut dimension = || {
+                        u32::try_from(read_varint(&mut body)?)
+                            .map_err(|_| String::from("Resolution is out of range"))
+                    };
+                    let width = dimension()?;
+                    let height = dimension()?;
+                    state.resolution = Some([width, height]);
+                }
+                TAG_CAMERA => {
+                    let v = read_f32s(body, 7)?;
+                    state.camera = Some(CameraState {
+                        position: [v[0], v[1], v[2]],
+                        target: [v[3], v[4], v[5]],
+                        fov: v[6],
+                    });
+                }
+                TAG_PARAMETER => {
+                    let mut body = body;
+                    let name_len = read_length(&mut body)?;
+                    if body.len() < name_len {
+                        return Err(String::from("Parameter name is truncated"));
+                    }
+                    let name = String::from_utf8(body[..name_len].to_vec())
+                        .map_err(|_| String::from("Parameter name is not UTF-8"))?;
+                    let values = &body[name_len..];
+                    let values = read_f32s(values, values.len() / 4)?;
+                    state.parameters.insert(name, values);
+                }
+                _ => state.unknown.push(UnknownField {
+                    tag,
+                    data: body.to_vec(),
+                }),
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+fn write_field(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+    out.push(tag);
+    write_varint(out, body.len() as u64);
+    out.extend(body);
+}
+
+// LEB128
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| String::from("Unexpected end of share string"))?;
+        *bytes = rest;
+
+        value |= ((byte & 0x7f) as u64) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(String::from("Varint is too long"))
+}
+
+fn read_length(bytes: &mut &[u8]) -> Result<usize, String> {
+    usize::try_from(read_varint(bytes)?).map_err(|_| String::from("Length is out of range"))
+}
+
+fn read_f32s(body: &[u8], count: usize) -> Result<Vec<f32>, String> {
+    if body.len() != count * 4 {
+        return Err(format!(
+            "Expected {} float(s), got {} byte(s)",
+            count,
+            body.len()
+        ));
+    }
+
+    Ok(body
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+/// Packs a JSON `ShareState` into a URL-safe string.
+#[wasm_bindgen]
+pub fn encode_share_state(json: &str, deflate: bool) -> Result<String, JsValue> {
+    let state: ShareState =
+        serde_json::from_str(json).map_err(|err| JsValue::from(err.to_string()))?;
+
+    Ok(state.encode(deflate))
+}
+
+/// Unpacks a string made by `encode_share_state` back into JSON.
+#[wasm_bindgen]
+pub fn decode_share_state(encoded: &str) -> Result<String, JsValue> {
+    let state = ShareState::decode(encoded)?;
+
+    serde_json::to_string(&state).map_err(|err| JsValue::from(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> ShareState {
+        ShareState {
+            shader: String::from("perlin_noise"),
+            parameters: [
+                (String::from("u_scale"), vec![2.5]),
+                (String::from("u_tint"), vec![1.0, 0.5, 0.0]),
+            ]
+            .into_iter()
+            .collect(),
+            time: Some(12.25),
+            resolution: Some([1920, 1080]),
+            camera: Some(CameraState {
+                position: [0.0, 1.0, 5.0],
+                target: [0.0; 3],
+                fov: 0.8,
+            }),
+            unknown: vec![UnknownField {
+                tag: 200,
+                data: vec![1, 2, 3],
+            }],
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trips() {
+        let state = state();
+
+        for deflate in [false, true] {
+            let encoded = state.encode(deflate);
+            assert_eq!(ShareState::decode(&encoded).unwrap(), state);
+        }
+
+        let minimal = ShareState {
+            shader: String::from("a"),
+            ..ShareState::default()
+        };
+        assert_eq!(ShareState::decode(&minimal.encode(true)).unwrap(), minimal);
+    }
+
+    #[test]
+    fn long_states_are_deflated() {
+        let mut state = state();
+        state.shader = "noise ".repeat(50);
+
+        let plain = state.encode(false);
+        let deflated = state.encode(true);
+
+        assert!(deflated.len() < plain.len());
+        assert_eq!(ShareState::decode(&deflated).unwrap(), state);
+    }
+
+    #[test]
+    fn malformed_strings_are_errors() {
+        let encode = |bytes: &[u8]| URL_SAFE_NO_PAD.encode(bytes);
+
+        assert!(ShareState::decode("not base64!").is_err());
+        assert!(ShareState::decode(&encode(&[SHARE_VERSION])).is_err());
+        assert!(ShareState::decode(&encode(&[SHARE_VERSION + 1, 0])).is_err());
+        // shader field claiming 5 bytes with 1 present
+        assert!(ShareState::decode(&encode(&[SHARE_VERSION, 0, TAG_SHADER, 5, b'a'])).is_err());
+        // time with 3 bytes instead of 4
+        assert!(ShareState::decode(&encode(&[SHARE_VERSION, 0, TAG_TIME, 3, 0, 0, 0])).is_err());
+        // width of 2^32, which doesn't fit a u32
+        let resolution = [
+            SHARE_VERSION,
+            0,
+            TAG_RESOLUTION,
+            6,
+            0x80,
+            0x80,
+            0x80,
+            0x80,
+            0x10,
+            1,
+        ];
+        assert!(ShareState::decode(&encode(&resolution)).is_err());
+    }
+
+    #[test]
+    fn inflating_is_limited() {
+        // a field of zeros that deflates to almost nothing
+        let mut fields = vec![];
+        write_field(&mut fields, 200, &vec![0; MAX_INFLATED_SIZE + 1]);
+
+        let mut bytes = vec![SHARE_VERSION, FLAG_DEFLATE];
+        bytes.extend(miniz_oxide::deflate::compress_to_vec(&fields, 9));
+        assert!(bytes.len() < 4096);
+
+        assert!(ShareState::decode(&URL_SAFE_NO_PAD.encode(bytes)).is_err());
+    }
+}
@@ -0,0 +1,490 @@
+// Simulations run on the GPU, one fragment per cell of a float texture.
+//
+// The state lives in a `PingPong` pair of render targets: every step reads
+// one and writes the other, then they swap. A step is a fragment shader
+// written after `STEP_HEADER`,
+//
+//   void main() {
+//     vec4 here = cell(vec2(0.0));
+//     vec4 right = cell(vec2(1.0, 0.0));
+//     fragColor = ...;
+//   }
+//
+// run `substeps` times per `step`, each with an equal share of the time
+// elapsed. A second shader, written after `DISPLAY_HEADER`, turns the state
+// into colors for the canvas. Both can declare annotated uniforms (see
+// params.rs), which become parameters of the simulation.
+//
+// The state can be seeded from Rust and read back, as RGBA floats; see
+// `RenderTarget::upload_f32` and `RenderTarget::read_f32`.
+//
+// Passes draw through a `GlState` with `PASS_PIPELINE`, which they leave
+// applied; fluid.rs runs its kernels the same way.
+
+use wasm_bindgen::JsCast;
+use web_sys::{
+    WebGl2RenderingContext, WebGlFramebuffer, WebGlProgram, WebGlUniformLocation,
+    WebGlVertexArrayObject,
+};
+
+use crate::params::{Parameter, ShaderParameters};
+use crate::pipeline::PipelineState;
+use crate::state::GlState;
+use crate::target::{supported_precision, Precision, RenderTarget};
+use crate::texture::Sampler;
+use crate::webgl::fullscreen_program;
+
+/// Fullscreen passes: no depth test, blending or culling.
+pub static PASS_PIPELINE: PipelineState = PipelineState::new();
+
+/// Declarations for a step shader. `cell` reads the previous state at an
+/// offset in cells, through the sampler of the state (wrapping or clamping
+/// at the edges).
+pub static STEP_HEADER: &str = r#"#version 300 es
+precision highp float;
+precision highp sampler2D;
+
+uniform sampler2D uState;
+// size of the state in cells
+uniform vec2 uResolution;
+uniform float uTime;
+// seconds per substep
+uniform float uDelta;
+// substeps run so far
+uniform int uFrame;
+// xy: position in [0, 1] of the state, z: 1 while pressed
+uniform vec4 uMouse;
+
+in vec2 vNdc;
+out vec4 fragColor;
+
+vec4 cell(vec2 offset) {
+  return texture(uState, (gl_FragCoord.xy + offset) / uResolution);
+}
+"#;
+
+/// Declarations for a display shader. `uResolution` is the size of the
+/// canvas here; the state is sampled over it with `vNdc`.
+pub static DISPLAY_HEADER: &str = r#"#version 300 es
+precision highp float;
+precision highp sampler2D;
+
+uniform sampler2D uState;
+uniform vec2 uStateResolution;
+uniform vec2 uResolution;
+uniform float uTime;
+
+in vec2 vNdc;
+out vec4 fragColor;
+"#;
+
+/// Shows the first three channels as they are.
+pub static DEFAULT_DISPLAY: &str = r#"
+void main() {
+  fragColor = vec4(texture(uState, vNdc * 0.5 + 0.5).rgb, 1.0);
+}
+"#;
+
+/// A pair of render targets of the same size, one read while the other is
+/// written.
+pub struct PingPong {
+    targets: [RenderTarget; 2],
+    // index of the target holding the latest state
+    current: usize,
+}
+
+impl PingPong {
+    pub fn new(
+        context: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        format: u32,
+        sampler: Sampler,
+    ) -> Result<Self, String> {
+        let targets = [
+            RenderTarget::new(context, width, height, format, false)?,
+            RenderTarget::new(context, width, height, format, false)?,
+        ];
+        for target in &targets {
+            target.set_sampler(context, sampler);
+        }
+
+        Ok(PingPong {
+            targets,
+            current: 0,
+        })
+    }
+
+    /// The latest state.
+    pub fn read(&self) -> &RenderTarget {
+        &self.targets[self.current]
+    }
+
+    /// Where the next state goes; call `swap` once it is drawn.
+    pub fn write(&self) -> &RenderTarget {
+        &self.targets[1 - self.current]
+    }
+
+    pub fn swap(&mut self) {
+        self.current = 1 - self.current;
+    }
+
+    pub fn width(&self) -> u32 {
+        self.read().width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.read().height()
+    }
+
+    /// Replaces the latest state, see `RenderTarget::upload_f32`.
+    pub fn seed(&self, context: &WebGl2RenderingContext, rgba: &[f32]) -> Result<(), String> {
+        self.read().upload_f32(context, rgba)
+    }
+
+    /// Sets every cell to `rgba`. The framebuffer, viewport and clear color
+    /// are restored afterwards.
+    pub fn fill(&self, context: &WebGl2RenderingContext, rgba: [f32; 4]) {
+        let parameter = |name: u32| context.get_parameter(name).ok();
+        let framebuffer = parameter(WebGl2RenderingContext::FRAMEBUFFER_BINDING)
+            .and_then(|b| b.dyn_into::<WebGlFramebuffer>().ok());
+        let viewport = parameter(WebGl2RenderingContext::VIEWPORT)
+            .and_then(|v| v.dyn_into::<js_sys::Int32Array>().ok())
+            .map(|v| v.to_vec());
+        let clear_color = parameter(WebGl2RenderingContext::COLOR_CLEAR_VALUE)
+            .and_then(|c| c.dyn_into::<js_sys::Float32Array>().ok())
+            .map(|c| c.to_vec());
+
+        context.clear_color(rgba[0], rgba[1], rgba[2], rgba[3]);
+        for target in &self.targets {
+            target.bind(context);
+            context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+        }
+
+        if let Some(&[r, g, b, a]) = clear_color.as_deref() {
+            context.clear_color(r, g, b, a);
+        }
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, framebuffer.as_ref());
+        if let Some(&[x, y, width, height]) = viewport.as_deref() {
+            context.viewport(x, y, width, height);
+        }
+    }
+
+    pub fn delete(self, context: &WebGl2RenderingContext) {
+        let [a, b] = self.targets;
+        a.delete(context);
+        b.delete(context);
+    }
+}
+
+pub struct SimulationOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Falls back as `supported_precision` does; `Simulation::precision`
+    /// tells which one is used.
+    pub precision: Precision,
+    pub substeps: u32,
+    /// Whether `cell` wraps around the edges (a torus) or clamps to them.
+    pub wrap: bool,
+    /// Nearest sampling reads cells exactly; linear interpolates between
+    /// them, e.g. for advection.
+    pub linear: bool,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        SimulationOptions {
+            width: 256,
+            height: 256,
+            precision: Precision::Float,
+            substeps: 1,
+            wrap: true,
+            linear: false,
+        }
+    }
+}
+
+impl SimulationOptions {
+    fn sampler(&self) -> Sampler {
+        let filter = match self.linear {
+            true => WebGl2RenderingContext::LINEAR,
+            false => WebGl2RenderingContext::NEAREST,
+        };
+        let wrap = match self.wrap {
+            true => WebGl2RenderingContext::REPEAT,
+            false => WebGl2RenderingContext::CLAMP_TO_EDGE,
+        };
+
+        Sampler {
+            mag_filter: filter,
+            min_filter: filter,
+            wrap_s: wrap,
+            wrap_t: wrap,
+        }
+    }
+}
+
+/// A fullscreen program with the uniforms of both headers.
+struct Pass {
+    program: WebGlProgram,
+    parameters: ShaderParameters,
+    loc_state: Option<WebGlUniformLocation>,
+    loc_resolution: Option<WebGlUniformLocation>,
+    loc_state_resolution: Option<WebGlUniformLocation>,
+    loc_time: Option<WebGlUniformLocation>,
+    loc_delta: Option<WebGlUniformLocation>,
+    loc_frame: Option<WebGlUniformLocation>,
+    loc_mouse: Option<WebGlUniformLocation>,
+}
+
+impl Pass {
+    fn new(state: &mut GlState, header: &str, body: &str) -> Result<Self, String> {
+        let context = &state.context().clone();
+        let source = format!("{}{}", header, body);
+        let program = fullscreen_program(context, &source)?;
+        let parameters = ShaderParameters::new(state, &program, body)?;
+
+        let location = |name: &str| context.get_uniform_location(&program, name);
+
+        Ok(Pass {
+            loc_state: location("uState"),
+            loc_resolution: location("uResolution"),
+            loc_state_resolution: location("uStateResolution"),
+            loc_time: location("uTime"),
+            loc_delta: location("uDelta"),
+            loc_frame: location("uFrame"),
+            loc_mouse: location("uMouse"),
+            program,
+            parameters,
+        })
+    }
+
+    /// Uses the program with `target` on texture unit 0.
+    fn begin(&self, state: &mut GlState, target: &RenderTarget) {
+        state.use_program(Some(&self.program));
+        state.bind_texture(
+            0,
+            WebGl2RenderingContext::TEXTURE_2D,
+            Some(target.texture()),
+        );
+        state.context().uniform1i(self.loc_state.as_ref(), 0);
+    }
+}
+
+pub struct Simulation {
+    state: PingPong,
+    precision: Precision,
+    step: Pass,
+    display: Pass,
+    vao: WebGlVertexArrayObject,
+    substeps: u32,
+    frame: i32,
+    time: f32,
+    mouse: [f32; 4],
+}
+
+impl Simulation {
+    /// `step_source` and `display_source` are written after `STEP_HEADER`
+    /// and `DISPLAY_HEADER`; see `DEFAULT_DISPLAY`.
+    pub fn new(
+        state: &mut GlState,
+        options: SimulationOptions,
+        step_source: &str,
+        display_source: &str,
+    ) -> Result<Self, String> {
+        let context = &state.context().clone();
+        let precision = supported_precision(context, options.precision);
+        let ping_pong = PingPong::new(
+            context,
+            options.width,
+            options.height,
+            precision.format(),
+            options.sampler(),
+        );
+        // allocating binds the textures on the context itself
+        state.invalidate();
+        let ping_pong = ping_pong?;
+        ping_pong.fill(context, [0.0; 4]);
+
+        Ok(Simulation {
+            step: Pass::new(state, STEP_HEADER, step_source)
+                .map_err(|err| format!("step: {}", err))?,
+            display: Pass::new(state, DISPLAY_HEADER, display_source)
+                .map_err(|err| format!("display: {}", err))?,
+            vao: context
+                .create_vertex_array()
+                .ok_or_else(|| String::from("Unable to create vertex array object"))?,
+            state: ping_pong,
+            precision,
+            substeps: options.substeps.max(1),
+            frame: 0,
+            time: 0.0,
+            mouse: [0.0; 4],
+        })
+    }
+
+    pub fn state(&self) -> &PingPong {
+        &self.state
+    }
+
+    /// Of the state, which is lower than asked for in `SimulationOptions`
+    /// when the browser can't render to float targets: with `Byte`, values
+    /// are clamped to [0, 1] and rounded to 1/255.
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    pub fn substeps(&self) -> u32 {
+        self.substeps
+    }
+
+    pub fn set_substeps(&mut self, substeps: u32) {
+        self.substeps = substeps.max(1);
+    }
+
+    /// Replaces the state with `rgba`, 4 floats per cell from the bottom
+    /// row up.
+    pub fn seed(&mut self, state: &mut GlState, rgba: &[f32]) -> Result<(), String> {
+        self.frame = 0;
+        let seeded = self.state.seed(state.context(), rgba);
+        // uploading binds the texture on the context itself
+        state.invalidate();
+
+        seeded
+    }
+
+    /// The state as RGBA floats, bottom row first.
+    pub fn read(&self, context: &WebGl2RenderingContext) -> Result<Vec<f32>, String> {
+        self.state.read().read_f32(context)
+    }
+
+    /// `x` and `y` in [0, 1] of the state, from the bottom left.
+    pub fn set_mouse(&mut self, x: f32, y: f32, pressed: bool) {
+        self.mouse = [x, y, pressed as i32 as f32, 0.0];
+    }
+
+    /// The annotated uniforms of both shaders, as a JSON array.
+    pub fn parameter_schema(&self) -> String {
+        let parameters = self
+            .step
+            .parameters
+            .parameters()
+            .iter()
+            .chain(self.display.parameters.parameters())
+            .collect::<Vec<&Parameter>>();
+
+        serde_json::to_string(&parameters).unwrap_or_else(|_| String::from("[]"))
+    }
+
+    /// Sets a parameter of the step shader or, failing that, of the display
+    /// shader.
+    pub fn set_parameter(
+        &mut self,
+        state: &mut GlState,
+        name: &str,
+        values: &[f32],
+    ) -> Result<(), String> {
+        for pass in [&mut self.step, &mut self.display] {
+            if pass.parameters.get(name).is_some() {
+                return pass.parameters.set(state, name, values);
+            }
+        }
+
+        Err(format!("Unknown parameter: {}", name))
+    }
+
+    /// Advances by `delta` seconds, split over the substeps. Leaves no
+    /// framebuffer bound.
+    pub fn step(&mut self, state: &mut GlState, time: f32, delta: f32) {
+        let context = &state.context().clone();
+        let delta = substep_delta(delta, self.substeps);
+        let (width, height) = (self.state.width(), self.state.height());
+
+        begin_passes(state, &self.vao);
+
+        for substep in 0..self.substeps {
+            self.state.write().bind_cached(state);
+            self.step.begin(state, self.state.read());
+
+            let pass = &self.step;
+            context.uniform2f(pass.loc_resolution.as_ref(), width as f32, height as f32);
+            context.uniform1f(pass.loc_time.as_ref(), time + delta * substep as f32);
+            context.uniform1f(pass.loc_delta.as_ref(), delta);
+            context.uniform1i(pass.loc_frame.as_ref(), self.frame);
+            context.uniform4fv_with_f32_array(pass.loc_mouse.as_ref(), &self.mouse);
+
+            context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+
+            self.state.swap();
+            self.frame += 1;
+        }
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        self.time = time;
+    }
+
+    /// Draws the state over the whole canvas of `width` by `height`.
+    pub fn display(&self, state: &mut GlState, width: u32, height: u32) {
+        let context = &state.context().clone();
+        begin_passes(state, &self.vao);
+
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        state.viewport(0, 0, width as i32, height as i32);
+        self.display.begin(state, self.state.read());
+
+        let pass = &self.display;
+        context.uniform2f(pass.loc_resolution.as_ref(), width as f32, height as f32);
+        context.uniform2f(
+            pass.loc_state_resolution.as_ref(),
+            self.state.width() as f32,
+            self.state.height() as f32,
+        );
+        context.uniform1f(pass.loc_time.as_ref(), self.time);
+
+        context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+    }
+}
+
+/// Applies `PASS_PIPELINE` and binds the empty `vao` fullscreen programs
+/// draw with.
+pub fn begin_passes(state: &mut GlState, vao: &WebGlVertexArrayObject) {
+    PASS_PIPELINE.apply(state);
+    state.bind_vertex_array(Some(vao));
+}
+
+/// Seconds per substep; large gaps (a hidden tab) are capped so that the
+/// simulation doesn't blow up.
+fn substep_delta(delta: f32, substeps: u32) -> f32 {
+    delta.clamp(0.0, 0.1) / substeps.max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substeps_share_the_delta() {
+        assert_eq!(substep_delta(0.04, 4), 0.01);
+        assert_eq!(substep_delta(1.0, 2), 0.05);
+        assert_eq!(substep_delta(-1.0, 2), 0.0);
+        assert_eq!(substep_delta(0.02, 0), 0.02);
+    }
+
+    #[test]
+    fn options_pick_the_sampler() {
+        let sampler = SimulationOptions::default().sampler();
+        assert_eq!(sampler.min_filter, WebGl2RenderingContext::NEAREST);
+        assert_eq!(sampler.wrap_s, WebGl2RenderingContext::REPEAT);
+
+        let options = SimulationOptions {
+            wrap: false,
+            linear: true,
+            ..SimulationOptions::default()
+        };
+        assert_eq!(options.sampler().mag_filter, WebGl2RenderingContext::LINEAR);
+        assert_eq!(
+            options.sampler().wrap_t,
+            WebGl2RenderingContext::CLAMP_TO_EDGE
+        );
+    }
+}
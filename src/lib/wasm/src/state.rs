@@ -0,0 +1,762 @@
+// A cache of the context's state, to skip calls that would change nothing.
+//
+// Every call into WebGL crosses from wasm into JavaScript and is validated by
+// the browser, so setting the same program, capability or viewport each frame
+// costs time for no effect. `GlState` remembers what it last set and only
+// forwards changes. It knows nothing of calls made on the context directly:
+// after code that bypasses it, `invalidate` makes it forget, and the next call
+// of each kind is issued again.
+//
+// The calls go through `GlContext`, implemented by the WebGL context, so that
+// tests can count what reaches a recording one instead.
+// https://developer.mozilla.org/en-US/docs/Web/API/WebGL_API/WebGL_best_practices
+
+use std::collections::HashMap;
+
+use web_sys::{
+    WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlTexture, WebGlVertexArrayObject,
+};
+
+/// The state-changing calls `GlState` forwards.
+pub trait GlContext {
+    type Program: Clone + PartialEq;
+    type VertexArray: Clone + PartialEq;
+    type Buffer: Clone + PartialEq;
+    type Texture: Clone + PartialEq;
+
+    fn use_program(&self, program: Option<&Self::Program>);
+    fn bind_vertex_array(&self, vao: Option<&Self::VertexArray>);
+    fn bind_buffer(&self, target: u32, buffer: Option<&Self::Buffer>);
+    fn active_texture(&self, unit: u32);
+    fn bind_texture(&self, target: u32, texture: Option<&Self::Texture>);
+    fn enable(&self, capability: u32);
+    fn disable(&self, capability: u32);
+    fn blend_equation_separate(&self, color: u32, alpha: u32);
+    fn blend_func_separate(&self, src_color: u32, dst_color: u32, src_alpha: u32, dst_alpha: u32);
+    fn depth_func(&self, func: u32);
+    fn depth_mask(&self, write: bool);
+    fn cull_face(&self, face: u32);
+    fn front_face(&self, winding: u32);
+    fn color_mask(&self, r: bool, g: bool, b: bool, a: bool);
+    fn polygon_offset(&self, factor: f32, units: f32);
+    fn stencil_func(&self, func: u32, reference: i32, mask: u32);
+    fn stencil_op(&self, fail: u32, depth_fail: u32, pass: u32);
+    fn stencil_mask(&self, mask: u32);
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32);
+    fn clear_depth(&self, depth: f32);
+    fn clear_stencil(&self, stencil: i32);
+    fn clear(&self, mask: u32);
+}
+
+// Each method calls the inherent one of the same name.
+impl GlContext for WebGl2RenderingContext {
+    type Program = WebGlProgram;
+    type VertexArray = WebGlVertexArrayObject;
+    type Buffer = WebGlBuffer;
+    type Texture = WebGlTexture;
+
+    fn use_program(&self, program: Option<&WebGlProgram>) {
+        self.use_program(program);
+    }
+
+    fn bind_vertex_array(&self, vao: Option<&WebGlVertexArrayObject>) {
+        self.bind_vertex_array(vao);
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>) {
+        self.bind_buffer(target, buffer);
+    }
+
+    fn active_texture(&self, unit: u32) {
+        self.active_texture(unit);
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<&WebGlTexture>) {
+        self.bind_texture(target, texture);
+    }
+
+    fn enable(&self, capability: u32) {
+        self.enable(capability);
+    }
+
+    fn disable(&self, capability: u32) {
+        self.disable(capability);
+    }
+
+    fn blend_equation_separate(&self, color: u32, alpha: u32) {
+        self.blend_equation_separate(color, alpha);
+    }
+
+    fn blend_func_separate(&self, src_color: u32, dst_color: u32, src_alpha: u32, dst_alpha: u32) {
+        self.blend_func_separate(src_color, dst_color, src_alpha, dst_alpha);
+    }
+
+    fn depth_func(&self, func: u32) {
+        self.depth_func(func);
+    }
+
+    fn depth_mask(&self, write: bool) {
+        self.depth_mask(write);
+    }
+
+    fn cull_face(&self, face: u32) {
+        self.cull_face(face);
+    }
+
+    fn front_face(&self, winding: u32) {
+        self.front_face(winding);
+    }
+
+    fn color_mask(&self, r: bool, g: bool, b: bool, a: bool) {
+        self.color_mask(r, g, b, a);
+    }
+
+    fn polygon_offset(&self, factor: f32, units: f32) {
+        self.polygon_offset(factor, units);
+    }
+
+    fn stencil_func(&self, func: u32, reference: i32, mask: u32) {
+        self.stencil_func(func, reference, mask);
+    }
+
+    fn stencil_op(&self, fail: u32, depth_fail: u32, pass: u32) {
+        self.stencil_op(fail, depth_fail, pass);
+    }
+
+    fn stencil_mask(&self, mask: u32) {
+        self.stencil_mask(mask);
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.viewport(x, y, width, height);
+    }
+
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.clear_color(r, g, b, a);
+    }
+
+    fn clear_depth(&self, depth: f32) {
+        self.clear_depth(depth);
+    }
+
+    fn clear_stencil(&self, stencil: i32) {
+        self.clear_stencil(stencil);
+    }
+
+    fn clear(&self, mask: u32) {
+        self.clear(mask);
+    }
+}
+
+/// Calls forwarded to the context and calls skipped as redundant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateStats {
+    pub issued: u32,
+    pub skipped: u32,
+}
+
+impl StateStats {
+    fn record(&mut self, changed: bool) -> bool {
+        if changed {
+            self.issued += 1;
+        } else {
+            self.skipped += 1;
+        }
+        changed
+    }
+}
+
+/// The last value set of one piece of state; `None` until set, or after
+/// `invalidate`.
+#[derive(Debug, Clone)]
+struct Cached<T>(Option<T>);
+
+impl<T> Default for Cached<T> {
+    fn default() -> Self {
+        Cached(None)
+    }
+}
+
+impl<T: PartialEq> Cached<T> {
+    /// Stores `value`; true if the context has to be told.
+    fn set(&mut self, value: T) -> bool {
+        if self.0.as_ref() == Some(&value) {
+            return false;
+        }
+        self.0 = Some(value);
+        true
+    }
+}
+
+/// Like `Cached`, for state kept per target, unit or capability.
+fn set_entry<K, V>(map: &mut HashMap<K, V>, key: K, value: V) -> bool
+where
+    K: std::hash::Hash + Eq,
+    V: PartialEq,
+{
+    if map.get(&key) == Some(&value) {
+        return false;
+    }
+    map.insert(key, value);
+    true
+}
+
+struct Cache<C: GlContext> {
+    program: Cached<Option<C::Program>>,
+    vertex_array: Cached<Option<C::VertexArray>>,
+    // by target, e.g. ARRAY_BUFFER
+    buffers: HashMap<u32, Option<C::Buffer>>,
+    active_texture: Cached<u32>,
+    // by (unit, target)
+    textures: HashMap<(u32, u32), Option<C::Texture>>,
+    // by capability, e.g. DEPTH_TEST
+    capabilities: HashMap<u32, bool>,
+    blend_equation: Cached<(u32, u32)>,
+    blend_func: Cached<(u32, u32, u32, u32)>,
+    depth_func: Cached<u32>,
+    depth_mask: Cached<bool>,
+    cull_face: Cached<u32>,
+    front_face: Cached<u32>,
+    color_mask: Cached<[bool; 4]>,
+    polygon_offset: Cached<(f32, f32)>,
+    stencil_func: Cached<(u32, i32, u32)>,
+    stencil_op: Cached<(u32, u32, u32)>,
+    stencil_mask: Cached<u32>,
+    viewport: Cached<[i32; 4]>,
+    clear_color: Cached<[f32; 4]>,
+    clear_depth: Cached<f32>,
+    clear_stencil: Cached<i32>,
+}
+
+// derived, it would ask for `C: Default`
+impl<C: GlContext> Default for Cache<C> {
+    fn default() -> Self {
+        Cache {
+            program: Cached::default(),
+            vertex_array: Cached::default(),
+            buffers: HashMap::new(),
+            active_texture: Cached::default(),
+            textures: HashMap::new(),
+            capabilities: HashMap::new(),
+            blend_equation: Cached::default(),
+            blend_func: Cached::default(),
+            depth_func: Cached::default(),
+            depth_mask: Cached::default(),
+            cull_face: Cached::default(),
+            front_face: Cached::default(),
+            color_mask: Cached::default(),
+            polygon_offset: Cached::default(),
+            stencil_func: Cached::default(),
+            stencil_op: Cached::default(),
+            stencil_mask: Cached::default(),
+            viewport: Cached::default(),
+            clear_color: Cached::default(),
+            clear_depth: Cached::default(),
+            clear_stencil: Cached::default(),
+        }
+    }
+}
+
+/// The context with a cache in front of its state-changing calls.
+pub struct GlState<C: GlContext = WebGl2RenderingContext> {
+    context: C,
+    cache: Cache<C>,
+    stats: StateStats,
+}
+
+impl<C: GlContext + Clone> GlState<C> {
+    pub fn new(context: &C) -> Self {
+        GlState {
+            context: context.clone(),
+            cache: Cache::default(),
+            stats: StateStats::default(),
+        }
+    }
+}
+
+impl<C: GlContext> GlState<C> {
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    /// Forgets everything cached, for after the context was used directly.
+    pub fn invalidate(&mut self) {
+        self.cache = Cache::default();
+    }
+
+    pub fn stats(&self) -> StateStats {
+        self.stats
+    }
+
+    /// The counts so far, starting over from zero, e.g. once per frame.
+    pub fn take_stats(&mut self) -> StateStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    pub fn use_program(&mut self, program: Option<&C::Program>) {
+        if self.stats.record(self.cache.program.set(program.cloned())) {
+            self.context.use_program(program);
+        }
+    }
+
+    pub fn bind_vertex_array(&mut self, vao: Option<&C::VertexArray>) {
+        if self.stats.record(self.cache.vertex_array.set(vao.cloned())) {
+            self.context.bind_vertex_array(vao);
+            self.cache
+                .buffers
+                .remove(&WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER);
+        }
+    }
+
+    /// Binding ELEMENT_ARRAY_BUFFER is part of the bound vertex array, so it is
+    /// forgotten whenever the vertex array changes.
+    pub fn bind_buffer(&mut self, target: u32, buffer: Option<&C::Buffer>) {
+        let changed = set_entry(&mut self.cache.buffers, target, buffer.cloned());
+        if self.stats.record(changed) {
+            self.context.bind_buffer(target, buffer);
+        }
+    }
+
+    /// Binds `texture` to `target` of texture unit `unit`, counted from 0,
+    /// switching the active unit only when needed.
+    pub fn bind_texture(&mut self, unit: u32, target: u32, texture: Option<&C::Texture>) {
+        let changed = set_entry(&mut self.cache.textures, (unit, target), texture.cloned());
+        if !self.stats.record(changed) {
+            return;
+        }
+
+        let active = WebGl2RenderingContext::TEXTURE0 + unit;
+        if self.stats.record(self.cache.active_texture.set(active)) {
+            self.context.active_texture(active);
+        }
+        self.context.bind_texture(target, texture);
+    }
+
+    /// `enable` or `disable` a capability such as DEPTH_TEST or BLEND.
+    pub fn set_enabled(&mut self, capability: u32, enabled: bool) {
+        let changed = set_entry(&mut self.cache.capabilities, capability, enabled);
+        if !self.stats.record(changed) {
+            return;
+        }
+
+        if enabled {
+            self.context.enable(capability);
+        } else {
+            self.context.disable(capability);
+        }
+    }
+
+    pub fn enable(&mut self, capability: u32) {
+        self.set_enabled(capability, true);
+    }
+
+    pub fn disable(&mut self, capability: u32) {
+        self.set_enabled(capability, false);
+    }
+
+    pub fn blend_equation(&mut self, color: u32, alpha: u32) {
+        if self
+            .stats
+            .record(self.cache.blend_equation.set((color, alpha)))
+        {
+            self.context.blend_equation_separate(color, alpha);
+        }
+    }
+
+    pub fn blend_func(&mut self, src_color: u32, dst_color: u32, src_alpha: u32, dst_alpha: u32) {
+        let factors = (src_color, dst_color, src_alpha, dst_alpha);
+        if self.stats.record(self.cache.blend_func.set(factors)) {
+            self.context
+                .blend_func_separate(src_color, dst_color, src_alpha, dst_alpha);
+        }
+    }
+
+    pub fn depth_func(&mut self, func: u32) {
+        if self.stats.record(self.cache.depth_func.set(func)) {
+            self.context.depth_func(func);
+        }
+    }
+
+    pub fn depth_mask(&mut self, write: bool) {
+        if self.stats.record(self.cache.depth_mask.set(write)) {
+            self.context.depth_mask(write);
+        }
+    }
+
+    pub fn cull_face(&mut self, face: u32) {
+        if self.stats.record(self.cache.cull_face.set(face)) {
+            self.context.cull_face(face);
+        }
+    }
+
+    pub fn front_face(&mut self, winding: u32) {
+        if self.stats.record(self.cache.front_face.set(winding)) {
+            self.context.front_face(winding);
+        }
+    }
+
+    pub fn color_mask(&mut self, rgba: [bool; 4]) {
+        if self.stats.record(self.cache.color_mask.set(rgba)) {
+            let [r, g, b, a] = rgba;
+            self.context.color_mask(r, g, b, a);
+        }
+    }
+
+    pub fn polygon_offset(&mut self, factor: f32, units: f32) {
+        if self
+            .stats
+            .record(self.cache.polygon_offset.set((factor, units)))
+        {
+            self.context.polygon_offset(factor, units);
+        }
+    }
+
+    pub fn stencil_func(&mut self, func: u32, reference: i32, mask: u32) {
+        if self
+            .stats
+            .record(self.cache.stencil_func.set((func, reference, mask)))
+        {
+            self.context.stencil_func(func, reference, mask);
+        }
+    }
+
+    pub fn stencil_op(&mut self, fail: u32, depth_fail: u32, pass: u32) {
+        if self
+            .stats
+            .record(self.cache.stencil_op.set((fail, depth_fail, pass)))
+        {
+            self.context.stencil_op(fail, depth_fail, pass);
+        }
+    }
+
+    pub fn stencil_mask(&mut self, mask: u32) {
+        if self.stats.record(self.cache.stencil_mask.set(mask)) {
+            self.context.stencil_mask(mask);
+        }
+    }
+
+    pub fn viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        if self
+            .stats
+            .record(self.cache.viewport.set([x, y, width, height]))
+        {
+            self.context.viewport(x, y, width, height);
+        }
+    }
+
+    pub fn clear_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        if self.stats.record(self.cache.clear_color.set([r, g, b, a])) {
+            self.context.clear_color(r, g, b, a);
+        }
+    }
+
+    pub fn clear_depth(&mut self, depth: f32) {
+        if self.stats.record(self.cache.clear_depth.set(depth)) {
+            self.context.clear_depth(depth);
+        }
+    }
+
+    pub fn clear_stencil(&mut self, stencil: i32) {
+        if self.stats.record(self.cache.clear_stencil.set(stencil)) {
+            self.context.clear_stencil(stencil);
+        }
+    }
+
+    /// Always issued: clearing is work, not state.
+    pub fn clear(&mut self, mask: u32) {
+        self.stats.record(true);
+        self.context.clear(mask);
+    }
+}
+
+/// A `GlContext` that writes down the calls reaching it, for tests.
+#[cfg(test)]
+pub(crate) mod recorder {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::GlContext;
+
+    /// Objects are numbered, as the tests only compare them.
+    #[derive(Clone, Default)]
+    pub struct Recorder {
+        calls: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Recorder {
+        /// The calls since the previous `take`.
+        pub fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.calls.borrow_mut())
+        }
+
+        fn record(&self, call: String) {
+            self.calls.borrow_mut().push(call);
+        }
+    }
+
+    impl GlContext for Recorder {
+        type Program = u32;
+        type VertexArray = u32;
+        type Buffer = u32;
+        type Texture = u32;
+
+        fn use_program(&self, program: Option<&u32>) {
+            self.record(format!("use_program {:?}", program));
+        }
+
+        fn bind_vertex_array(&self, vao: Option<&u32>) {
+            self.record(format!("bind_vertex_array {:?}", vao));
+        }
+
+        fn bind_buffer(&self, target: u32, buffer: Option<&u32>) {
+            self.record(format!("bind_buffer {:#x} {:?}", target, buffer));
+        }
+
+        fn active_texture(&self, unit: u32) {
+            self.record(format!("active_texture {:#x}", unit));
+        }
+
+        fn bind_texture(&self, target: u32, texture: Option<&u32>) {
+            self.record(format!("bind_texture {:#x} {:?}", target, texture));
+        }
+
+        fn enable(&self, capability: u32) {
+            self.record(format!("enable {:#x}", capability));
+        }
+
+        fn disable(&self, capability: u32) {
+            self.record(format!("disable {:#x}", capability));
+        }
+
+        fn blend_equation_separate(&self, color: u32, alpha: u32) {
+            self.record(format!("blend_equation {:#x} {:#x}", color, alpha));
+        }
+
+        fn blend_func_separate(
+            &self,
+            src_color: u32,
+            dst_color: u32,
+            src_alpha: u32,
+            dst_alpha: u32,
+        ) {
+            self.record(format!(
+                "blend_func {:#x} {:#x} {:#x} {:#x}",
+                src_color, dst_color, src_alpha, dst_alpha
+            ));
+        }
+
+        fn depth_func(&self, func: u32) {
+            self.record(format!("depth_func {:#x}", func));
+        }
+
+        fn depth_mask(&self, write: bool) {
+            self.record(format!("depth_mask {}", write));
+        }
+
+        fn cull_face(&self, face: u32) {
+            self.record(format!("cull_face {:#x}", face));
+        }
+
+        fn front_face(&self, winding: u32) {
+            self.record(format!("front_face {:#x}", winding));
+        }
+
+        fn color_mask(&self, r: bool, g: bool, b: bool, a: bool) {
+            self.record(format!("color_mask {} {} {} {}", r, g, b, a));
+        }
+
+        fn polygon_offset(&self, factor: f32, units: f32) {
+            self.record(format!("polygon_offset {} {}", factor, units));
+        }
+
+        fn stencil_func(&self, func: u32, reference: i32, mask: u32) {
+            self.record(format!(
+                "stencil_func {:#x} {} {:#x}",
+                func, reference, mask
+            ));
+        }
+
+        fn stencil_op(&self, fail: u32, depth_fail: u32, pass: u32) {
+            self.record(format!(
+                "stencil_op {:#x} {:#x} {:#x}",
+                fail, depth_fail, pass
+            ));
+        }
+
+        fn stencil_mask(&self, mask: u32) {
+            self.record(format!("stencil_mask {:#x}", mask));
+        }
+
+        fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+            self.record(format!("viewport {} {} {} {}", x, y, width, height));
+        }
+
+        fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+            self.record(format!("clear_color {} {} {} {}", r, g, b, a));
+        }
+
+        fn clear_depth(&self, depth: f32) {
+            self.record(format!("clear_depth {}", depth));
+        }
+
+        fn clear_stencil(&self, stencil: i32) {
+            self.record(format!("clear_stencil {}", stencil));
+        }
+
+        fn clear(&self, mask: u32) {
+            self.record(format!("clear {:#x}", mask));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use recorder::Recorder;
+
+    fn recorded() -> (GlState<Recorder>, Recorder) {
+        let recorder = Recorder::default();
+        (GlState::new(&recorder), recorder)
+    }
+
+    #[test]
+    fn repeated_calls_are_skipped() {
+        let (mut state, recorder) = recorded();
+
+        for _ in 0..3 {
+            state.use_program(Some(&1));
+            state.viewport(0, 0, 640, 480);
+        }
+        state.use_program(Some(&2));
+
+        assert_eq!(
+            recorder.take(),
+            [
+                "use_program Some(1)",
+                "viewport 0 0 640 480",
+                "use_program Some(2)"
+            ]
+        );
+        assert_eq!(
+            state.take_stats(),
+            StateStats {
+                issued: 3,
+                skipped: 4
+            }
+        );
+        assert_eq!(state.stats(), StateStats::default());
+    }
+
+    #[test]
+    fn textures_switch_the_active_unit_only_when_needed() {
+        let (mut state, recorder) = recorded();
+        let cube = WebGl2RenderingContext::TEXTURE_CUBE_MAP;
+        let flat = WebGl2RenderingContext::TEXTURE_2D;
+
+        state.bind_texture(0, flat, Some(&1));
+        state.bind_texture(0, cube, Some(&2));
+        state.bind_texture(3, flat, Some(&1));
+        // already bound: the active unit stays 3
+        state.bind_texture(0, flat, Some(&1));
+
+        assert_eq!(
+            recorder.take(),
+            [
+                "active_texture 0x84c0",
+                "bind_texture 0xde1 Some(1)",
+                "bind_texture 0x8513 Some(2)",
+                "active_texture 0x84c3",
+                "bind_texture 0xde1 Some(1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn vertex_arrays_take_their_element_buffer_along() {
+        let (mut state, recorder) = recorded();
+        let elements = WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER;
+        let array = WebGl2RenderingContext::ARRAY_BUFFER;
+
+        state.bind_vertex_array(Some(&1));
+        state.bind_buffer(elements, Some(&7));
+        state.bind_buffer(array, Some(&8));
+        state.bind_vertex_array(Some(&2));
+        // part of the new vertex array, so bound again; ARRAY_BUFFER isn't
+        state.bind_buffer(elements, Some(&7));
+        state.bind_buffer(array, Some(&8));
+
+        assert_eq!(
+            recorder.take(),
+            [
+                "bind_vertex_array Some(1)",
+                "bind_buffer 0x8893 Some(7)",
+                "bind_buffer 0x8892 Some(8)",
+                "bind_vertex_array Some(2)",
+                "bind_buffer 0x8893 Some(7)",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalidate_issues_everything_again() {
+        let (mut state, recorder) = recorded();
+
+        state.use_program(Some(&1));
+        state.enable(WebGl2RenderingContext::DEPTH_TEST);
+        state.invalidate();
+        state.use_program(Some(&1));
+        state.enable(WebGl2RenderingContext::DEPTH_TEST);
+        // clearing is never skipped
+        state.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+        state.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+
+        assert_eq!(recorder.take().len(), 6);
+        assert_eq!(state.stats().skipped, 0);
+    }
+
+    #[test]
+    fn cached_skips_repeats() {
+        let mut stats = StateStats::default();
+        let mut depth_func = Cached::default();
+
+        for func in [WebGl2RenderingContext::LEQUAL; 3] {
+            stats.record(depth_func.set(func));
+        }
+        stats.record(depth_func.set(WebGl2RenderingContext::LESS));
+
+        assert_eq!(
+            stats,
+            StateStats {
+                issued: 2,
+                skipped: 2
+            }
+        );
+    }
+
+    #[test]
+    fn entries_are_cached_per_key() {
+        let mut capabilities = HashMap::new();
+
+        assert!(set_entry(
+            &mut capabilities,
+            WebGl2RenderingContext::DEPTH_TEST,
+            true
+        ));
+        assert!(set_entry(
+            &mut capabilities,
+            WebGl2RenderingContext::BLEND,
+            true
+        ));
+        assert!(!set_entry(
+            &mut capabilities,
+            WebGl2RenderingContext::DEPTH_TEST,
+            true
+        ));
+        assert!(set_entry(
+            &mut capabilities,
+            WebGl2RenderingContext::DEPTH_TEST,
+            false
+        ));
+    }
+}
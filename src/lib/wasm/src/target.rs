@@ -0,0 +1,706 @@
+// Offscreen render targets: a framebuffer with a color texture and,
+// optionally, a depth renderbuffer.
+//
+// Storage is immutable (`texStorage2D`), so resizing reallocates.
+//
+// Allocating and uploading bind the texture on the context itself, behind
+// the back of a `GlState`, which has to be invalidated afterwards; drawing
+// code binds targets with `bind_cached`, which sets the viewport through it.
+//
+// With more than one sample, drawing goes into multisample renderbuffers
+// instead, which cannot be sampled: `resolve` blits them into the texture
+// (https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/renderbufferStorageMultisample).
+// `texture_resolved` does so when the target was drawn to since.
+//
+// Float color targets keep values above 1 (HDR) but need extensions: see
+// `supported_precision`. Their contents can also be written from and read
+// back to Rust, as RGBA floats, which is how simulations seed and inspect
+// their state.
+
+use std::cell::Cell;
+
+use wasm_bindgen::JsCast;
+use web_sys::{WebGl2RenderingContext, WebGlFramebuffer, WebGlRenderbuffer, WebGlTexture};
+
+use crate::state::GlState;
+use crate::texture::Sampler;
+use crate::utils::log;
+
+/// Per channel storage of a color target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precision {
+    /// RGBA8, clamped to [0, 1].
+    Byte,
+    /// RGBA16F.
+    Half,
+    /// RGBA32F.
+    Float,
+}
+
+impl Precision {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precision::Byte => "byte",
+            Precision::Half => "half",
+            Precision::Float => "float",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Precision::Byte, Precision::Half, Precision::Float]
+            .into_iter()
+            .find(|precision| precision.name() == name)
+    }
+
+    /// The sized internal format.
+    pub fn format(&self) -> u32 {
+        match self {
+            Precision::Byte => WebGl2RenderingContext::RGBA8,
+            Precision::Half => WebGl2RenderingContext::RGBA16F,
+            Precision::Float => WebGl2RenderingContext::RGBA32F,
+        }
+    }
+}
+
+/// The highest precision up to `wanted` that can be rendered to, enabling
+/// the extensions it needs.
+///
+/// Rendering to any float format needs EXT_color_buffer_float; RGBA32F
+/// targets are also filtered and blended, which needs
+/// OES_texture_float_linear and EXT_float_blend.
+pub fn supported_precision(context: &WebGl2RenderingContext, wanted: Precision) -> Precision {
+    let has = |name: &str| context.get_extension(name).ok().flatten().is_some();
+
+    let color_buffer_float = wanted > Precision::Byte && has("EXT_color_buffer_float");
+    let float_linear = wanted == Precision::Float
+        && color_buffer_float
+        && has("OES_texture_float_linear")
+        && has("EXT_float_blend");
+
+    let precision = fallback(wanted, color_buffer_float, float_linear);
+    if precision != wanted {
+        log(&format!(
+            "{} color targets are not supported; using {}",
+            wanted.name(),
+            precision.name()
+        ));
+    }
+
+    precision
+}
+
+fn fallback(wanted: Precision, color_buffer_float: bool, float_linear: bool) -> Precision {
+    match wanted {
+        Precision::Float if color_buffer_float && float_linear => Precision::Float,
+        Precision::Float | Precision::Half if color_buffer_float => Precision::Half,
+        _ => Precision::Byte,
+    }
+}
+
+pub struct RenderTarget {
+    // holds `texture`; drawn to unless multisampled
+    framebuffer: WebGlFramebuffer,
+    texture: WebGlTexture,
+    depth: Option<WebGlRenderbuffer>,
+    multisample: Option<Multisample>,
+    format: u32,
+    width: u32,
+    height: u32,
+    with_depth: bool,
+    samples: u32,
+    // drawn to since the last resolve
+    dirty: Cell<bool>,
+}
+
+struct Multisample {
+    framebuffer: WebGlFramebuffer,
+    color: WebGlRenderbuffer,
+    depth: Option<WebGlRenderbuffer>,
+}
+
+impl RenderTarget {
+    /// `format` is a sized internal format, e.g. `RGBA8`.
+    pub fn new(
+        context: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        format: u32,
+        depth: bool,
+    ) -> Result<Self, String> {
+        Self::with_samples(context, width, height, format, depth, 1)
+    }
+
+    /// Multisampled with up to `samples` samples per pixel, as many as the
+    /// format supports (see `samples`). 0 and 1 mean no multisampling.
+    pub fn with_samples(
+        context: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        format: u32,
+        depth: bool,
+        samples: u32,
+    ) -> Result<Self, String> {
+        let framebuffer = context
+            .create_framebuffer()
+            .ok_or_else(|| String::from("Unable to create framebuffer"))?;
+        let (width, height) = (width.max(1), height.max(1));
+        let samples = supported_samples(context, format, samples);
+
+        let mut target = RenderTarget {
+            texture: allocate_texture(context, &framebuffer, width, height, format)?,
+            framebuffer,
+            depth: None,
+            multisample: None,
+            format,
+            width,
+            height,
+            with_depth: depth,
+            samples,
+            dirty: Cell::new(false),
+        };
+        target.allocate_attachments(context)?;
+
+        Ok(target)
+    }
+
+    /// The framebuffer `bind` draws into.
+    pub fn framebuffer(&self) -> &WebGlFramebuffer {
+        match &self.multisample {
+            Some(multisample) => &multisample.framebuffer,
+            None => &self.framebuffer,
+        }
+    }
+
+    /// The color texture, as of the last `resolve` when multisampled.
+    pub fn texture(&self) -> &WebGlTexture {
+        &self.texture
+    }
+
+    /// The color texture, resolved first if needed.
+    pub fn texture_resolved(&self, context: &WebGl2RenderingContext) -> &WebGlTexture {
+        self.resolve(context);
+
+        &self.texture
+    }
+
+    pub fn format(&self) -> u32 {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Samples per pixel, 1 without multisampling.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Reallocates when the size changes, returning whether it did; the
+    /// contents are lost then.
+    pub fn resize(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+    ) -> Result<bool, String> {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) == (self.width, self.height) {
+            return Ok(false);
+        }
+
+        self.width = width;
+        self.height = height;
+        self.reallocate(context)?;
+
+        Ok(true)
+    }
+
+    /// Reallocates with another internal format.
+    pub fn set_format(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        format: u32,
+    ) -> Result<(), String> {
+        if format == self.format {
+            return Ok(());
+        }
+
+        self.format = format;
+        self.samples = supported_samples(context, format, self.samples);
+        self.reallocate(context)
+    }
+
+    /// Reallocates with up to `samples` samples per pixel and returns the
+    /// count used.
+    pub fn set_samples(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        samples: u32,
+    ) -> Result<u32, String> {
+        let samples = supported_samples(context, self.format, samples);
+
+        if samples != self.samples {
+            self.samples = samples;
+            self.reallocate(context)?;
+        }
+
+        Ok(samples)
+    }
+
+    /// Draws into this target from now on, over all of it.
+    pub fn bind(&self, context: &WebGl2RenderingContext) {
+        context.bind_framebuffer(
+            WebGl2RenderingContext::FRAMEBUFFER,
+            Some(self.framebuffer()),
+        );
+        context.viewport(0, 0, self.width as i32, self.height as i32);
+
+        self.dirty.set(self.multisample.is_some());
+    }
+
+    /// Like `bind`, setting the viewport through `state`.
+    pub fn bind_cached(&self, state: &mut GlState) {
+        state.context().bind_framebuffer(
+            WebGl2RenderingContext::FRAMEBUFFER,
+            Some(self.framebuffer()),
+        );
+        state.viewport(0, 0, self.width as i32, self.height as i32);
+
+        self.dirty.set(self.multisample.is_some());
+    }
+
+    /// Copies the multisample renderbuffer into the texture, if it was drawn
+    /// to since. Leaves no framebuffer bound.
+    pub fn resolve(&self, context: &WebGl2RenderingContext) {
+        let multisample = match &self.multisample {
+            Some(multisample) if self.dirty.get() => multisample,
+            _ => return,
+        };
+
+        blit(
+            context,
+            Some(&multisample.framebuffer),
+            Some(&self.framebuffer),
+            self.width,
+            self.height,
+        );
+        self.dirty.set(false);
+    }
+
+    /// Copies the color to the canvas, of the same size. Leaves no
+    /// framebuffer bound.
+    pub fn copy_to_canvas(&self, context: &WebGl2RenderingContext) {
+        self.resolve(context);
+
+        blit(
+            context,
+            Some(&self.framebuffer),
+            None,
+            self.width,
+            self.height,
+        );
+    }
+
+    /// Filtering and wrapping of the color texture; `Sampler::clamped` by
+    /// default. Mipmapped filters are not supported.
+    pub fn set_sampler(&self, context: &WebGl2RenderingContext, sampler: Sampler) {
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.texture));
+        sampler.apply(context, WebGl2RenderingContext::TEXTURE_2D);
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+    }
+
+    /// Overwrites the color texture with `rgba`, 4 floats per pixel from the
+    /// bottom row up. `RGBA8` targets clamp them to [0, 1].
+    pub fn upload_f32(&self, context: &WebGl2RenderingContext, rgba: &[f32]) -> Result<(), String> {
+        let expected = (self.width * self.height * 4) as usize;
+        if rgba.len() != expected {
+            return Err(format!(
+                "Expected {} floats of RGBA for {}x{}, got {}",
+                expected,
+                self.width,
+                self.height,
+                rgba.len()
+            ));
+        }
+
+        let bytes;
+        // `view` is only valid until the next allocation, so upload right away
+        let (kind, view): (u32, js_sys::Object) = match self.format {
+            WebGl2RenderingContext::RGBA8 => {
+                bytes = to_unorm8(rgba);
+                (WebGl2RenderingContext::UNSIGNED_BYTE, unsafe {
+                    js_sys::Uint8Array::view(&bytes).into()
+                })
+            }
+            _ => (WebGl2RenderingContext::FLOAT, unsafe {
+                js_sys::Float32Array::view(rgba).into()
+            }),
+        };
+
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.texture));
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/texSubImage2D
+        let result = context
+            .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_array_buffer_view(
+                WebGl2RenderingContext::TEXTURE_2D,
+                0,
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                WebGl2RenderingContext::RGBA,
+                kind,
+                Some(&view),
+            );
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+
+        result.map_err(|_| String::from("Unable to upload render target"))
+    }
+
+    /// The color texture as RGBA floats, bottom row first. Stalls until the
+    /// GPU has caught up, so keep it out of the frame loop.
+    pub fn read_f32(&self, context: &WebGl2RenderingContext) -> Result<Vec<f32>, String> {
+        let length = self.width * self.height * 4;
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        self.resolve(context);
+        context.bind_framebuffer(
+            WebGl2RenderingContext::READ_FRAMEBUFFER,
+            Some(&self.framebuffer),
+        );
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/readPixels
+        // float buffers are read as FLOAT, with EXT_color_buffer_float
+        let result = match self.format {
+            WebGl2RenderingContext::RGBA8 => {
+                let pixels = js_sys::Uint8Array::new_with_length(length);
+                context
+                    .read_pixels_with_opt_array_buffer_view(
+                        0,
+                        0,
+                        width,
+                        height,
+                        WebGl2RenderingContext::RGBA,
+                        WebGl2RenderingContext::UNSIGNED_BYTE,
+                        Some(&pixels),
+                    )
+                    .map(|_| from_unorm8(&pixels.to_vec()))
+            }
+            _ => {
+                let pixels = js_sys::Float32Array::new_with_length(length);
+                context
+                    .read_pixels_with_opt_array_buffer_view(
+                        0,
+                        0,
+                        width,
+                        height,
+                        WebGl2RenderingContext::RGBA,
+                        WebGl2RenderingContext::FLOAT,
+                        Some(&pixels),
+                    )
+                    .map(|_| pixels.to_vec())
+            }
+        };
+        context.bind_framebuffer(WebGl2RenderingContext::READ_FRAMEBUFFER, None);
+
+        result.map_err(|_| String::from("Unable to read render target"))
+    }
+
+    pub fn delete(mut self, context: &WebGl2RenderingContext) {
+        self.delete_attachments(context);
+        context.delete_framebuffer(Some(&self.framebuffer));
+        context.delete_texture(Some(&self.texture));
+    }
+
+    fn reallocate(&mut self, context: &WebGl2RenderingContext) -> Result<(), String> {
+        self.delete_attachments(context);
+        context.delete_texture(Some(&self.texture));
+
+        self.texture = allocate_texture(
+            context,
+            &self.framebuffer,
+            self.width,
+            self.height,
+            self.format,
+        )?;
+        self.allocate_attachments(context)
+    }
+
+    /// The depth buffer and multisample renderbuffers.
+    fn allocate_attachments(&mut self, context: &WebGl2RenderingContext) -> Result<(), String> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let depth_format = WebGl2RenderingContext::DEPTH_COMPONENT24;
+
+        if self.samples <= 1 {
+            self.depth = match self.with_depth {
+                true => Some(attach_renderbuffer(
+                    context,
+                    &self.framebuffer,
+                    WebGl2RenderingContext::DEPTH_ATTACHMENT,
+                    depth_format,
+                    1,
+                    (width, height),
+                )?),
+                false => None,
+            };
+
+            return check_complete(context, &self.framebuffer);
+        }
+
+        let framebuffer = context
+            .create_framebuffer()
+            .ok_or_else(|| String::from("Unable to create framebuffer"))?;
+        let color = attach_renderbuffer(
+            context,
+            &framebuffer,
+            WebGl2RenderingContext::COLOR_ATTACHMENT0,
+            self.format,
+            self.samples,
+            (width, height),
+        )?;
+        let depth = match self.with_depth {
+            true => Some(attach_renderbuffer(
+                context,
+                &framebuffer,
+                WebGl2RenderingContext::DEPTH_ATTACHMENT,
+                depth_format,
+                self.samples,
+                (width, height),
+            )?),
+            false => None,
+        };
+
+        check_complete(context, &framebuffer)?;
+
+        self.multisample = Some(Multisample {
+            framebuffer,
+            color,
+            depth,
+        });
+
+        Ok(())
+    }
+
+    fn delete_attachments(&mut self, context: &WebGl2RenderingContext) {
+        if let Some(depth) = self.depth.take() {
+            context.delete_renderbuffer(Some(&depth));
+        }
+
+        if let Some(multisample) = self.multisample.take() {
+            context.delete_framebuffer(Some(&multisample.framebuffer));
+            context.delete_renderbuffer(Some(&multisample.color));
+            context.delete_renderbuffer(multisample.depth.as_ref());
+        }
+    }
+}
+
+/// The largest sample count up to `wanted` that `format` supports, 1 for
+/// none.
+pub fn supported_samples(context: &WebGl2RenderingContext, format: u32, wanted: u32) -> u32 {
+    if wanted <= 1 {
+        return 1;
+    }
+
+    // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/getInternalformatParameter
+    let counts = context
+        .get_internalformat_parameter(
+            WebGl2RenderingContext::RENDERBUFFER,
+            format,
+            WebGl2RenderingContext::SAMPLES,
+        )
+        .ok()
+        .and_then(|counts| counts.dyn_into::<js_sys::Int32Array>().ok())
+        .map(|counts| counts.to_vec())
+        .unwrap_or_default();
+    let max = context
+        .get_parameter(WebGl2RenderingContext::MAX_SAMPLES)
+        .ok()
+        .and_then(|max| max.as_f64())
+        .unwrap_or(0.0) as i32;
+
+    pick_samples(wanted, &counts, max)
+}
+
+/// `counts` are the counts a format supports, in any order.
+fn pick_samples(wanted: u32, counts: &[i32], max: i32) -> u32 {
+    counts
+        .iter()
+        .copied()
+        .filter(|&count| count > 1 && count <= max && count as u32 <= wanted)
+        .max()
+        .unwrap_or(1) as u32
+}
+
+fn to_unorm8(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect()
+}
+
+fn from_unorm8(bytes: &[u8]) -> Vec<f32> {
+    bytes.iter().map(|&b| b as f32 / 255.0).collect()
+}
+
+fn allocate_texture(
+    context: &WebGl2RenderingContext,
+    framebuffer: &WebGlFramebuffer,
+    width: u32,
+    height: u32,
+    format: u32,
+) -> Result<WebGlTexture, String> {
+    let texture = context
+        .create_texture()
+        .ok_or_else(|| String::from("Unable to create texture"))?;
+    context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture));
+    // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/texStorage2D
+    context.tex_storage_2d(
+        WebGl2RenderingContext::TEXTURE_2D,
+        1,
+        format,
+        width as i32,
+        height as i32,
+    );
+    Sampler::clamped().apply(context, WebGl2RenderingContext::TEXTURE_2D);
+    context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(framebuffer));
+    context.framebuffer_texture_2d(
+        WebGl2RenderingContext::FRAMEBUFFER,
+        WebGl2RenderingContext::COLOR_ATTACHMENT0,
+        WebGl2RenderingContext::TEXTURE_2D,
+        Some(&texture),
+        0,
+    );
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+
+    Ok(texture)
+}
+
+fn attach_renderbuffer(
+    context: &WebGl2RenderingContext,
+    framebuffer: &WebGlFramebuffer,
+    attachment: u32,
+    format: u32,
+    samples: u32,
+    (width, height): (i32, i32),
+) -> Result<WebGlRenderbuffer, String> {
+    let renderbuffer = context
+        .create_renderbuffer()
+        .ok_or_else(|| String::from("Unable to create renderbuffer"))?;
+
+    context.bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, Some(&renderbuffer));
+    if samples > 1 {
+        context.renderbuffer_storage_multisample(
+            WebGl2RenderingContext::RENDERBUFFER,
+            samples as i32,
+            format,
+            width,
+            height,
+        );
+    } else {
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/renderbufferStorage
+        context.renderbuffer_storage(WebGl2RenderingContext::RENDERBUFFER, format, width, height);
+    }
+    context.bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, None);
+
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(framebuffer));
+    context.framebuffer_renderbuffer(
+        WebGl2RenderingContext::FRAMEBUFFER,
+        attachment,
+        WebGl2RenderingContext::RENDERBUFFER,
+        Some(&renderbuffer),
+    );
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+
+    Ok(renderbuffer)
+}
+
+fn check_complete(
+    context: &WebGl2RenderingContext,
+    framebuffer: &WebGlFramebuffer,
+) -> Result<(), String> {
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(framebuffer));
+    // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/checkFramebufferStatus
+    let status = context.check_framebuffer_status(WebGl2RenderingContext::FRAMEBUFFER);
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+
+    if status != WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+        return Err(format!("Framebuffer is incomplete (0x{:x})", status));
+    }
+
+    Ok(())
+}
+
+/// Copies the color of `from` to `to`, the canvas if `None`.
+fn blit(
+    context: &WebGl2RenderingContext,
+    from: Option<&WebGlFramebuffer>,
+    to: Option<&WebGlFramebuffer>,
+    width: u32,
+    height: u32,
+) {
+    let (width, height) = (width as i32, height as i32);
+
+    // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/blitFramebuffer
+    context.bind_framebuffer(WebGl2RenderingContext::READ_FRAMEBUFFER, from);
+    context.bind_framebuffer(WebGl2RenderingContext::DRAW_FRAMEBUFFER, to);
+    context.blit_framebuffer(
+        0,
+        0,
+        width,
+        height,
+        0,
+        0,
+        width,
+        height,
+        WebGl2RenderingContext::COLOR_BUFFER_BIT,
+        WebGl2RenderingContext::NEAREST,
+    );
+    context.bind_framebuffer(WebGl2RenderingContext::READ_FRAMEBUFFER, None);
+    context.bind_framebuffer(WebGl2RenderingContext::DRAW_FRAMEBUFFER, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precision_falls_back() {
+        assert_eq!(fallback(Precision::Float, true, true), Precision::Float);
+        assert_eq!(fallback(Precision::Float, true, false), Precision::Half);
+        assert_eq!(fallback(Precision::Float, false, false), Precision::Byte);
+        assert_eq!(fallback(Precision::Half, true, true), Precision::Half);
+        assert_eq!(fallback(Precision::Half, false, false), Precision::Byte);
+        assert_eq!(fallback(Precision::Byte, true, true), Precision::Byte);
+
+        assert_eq!(Precision::from_name("half"), Some(Precision::Half));
+        assert_eq!(Precision::from_name("rgba16f"), None);
+    }
+
+    #[test]
+    fn samples_clamp_to_supported_counts() {
+        let counts = [8, 4, 2];
+
+        assert_eq!(pick_samples(4, &counts, 8), 4);
+        assert_eq!(pick_samples(16, &counts, 8), 8);
+        assert_eq!(pick_samples(16, &counts, 4), 4);
+        assert_eq!(pick_samples(3, &counts, 8), 2);
+        assert_eq!(pick_samples(4, &[], 8), 1);
+    }
+
+    #[test]
+    fn unorm8_round_trips() {
+        let bytes = to_unorm8(&[-1.0, 0.0, 0.5, 1.0, 2.0]);
+        assert_eq!(bytes, [0, 0, 128, 255, 255]);
+
+        let values = from_unorm8(&bytes);
+        assert_eq!(values[0], 0.0);
+        assert!((values[2] - 0.5).abs() < 1.0 / 255.0);
+        assert_eq!(values[3], 1.0);
+    }
+}
@@ -0,0 +1,296 @@
+// 2D textures uploaded from pixels decoded in Rust.
+//
+// PNG is decoded with the `png` crate; other formats (e.g. JPEG) have to be
+// decoded by the browser and uploaded with `Texture::from_rgba8`.
+
+use web_sys::{WebGl2RenderingContext, WebGlTexture};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampler {
+    pub mag_filter: u32,
+    pub min_filter: u32,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+}
+
+impl Default for Sampler {
+    /// Trilinear, repeating.
+    fn default() -> Self {
+        Sampler {
+            mag_filter: WebGl2RenderingContext::LINEAR,
+            min_filter: WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR,
+            wrap_s: WebGl2RenderingContext::REPEAT,
+            wrap_t: WebGl2RenderingContext::REPEAT,
+        }
+    }
+}
+
+impl Sampler {
+    /// Bilinear, clamped; for lookup tables and render targets.
+    pub fn clamped() -> Self {
+        Sampler {
+            mag_filter: WebGl2RenderingContext::LINEAR,
+            min_filter: WebGl2RenderingContext::LINEAR,
+            wrap_s: WebGl2RenderingContext::CLAMP_TO_EDGE,
+            wrap_t: WebGl2RenderingContext::CLAMP_TO_EDGE,
+        }
+    }
+
+    fn uses_mipmaps(&self) -> bool {
+        matches!(
+            self.min_filter,
+            WebGl2RenderingContext::NEAREST_MIPMAP_NEAREST
+                | WebGl2RenderingContext::LINEAR_MIPMAP_NEAREST
+                | WebGl2RenderingContext::NEAREST_MIPMAP_LINEAR
+                | WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR
+        )
+    }
+
+    /// Applies to the texture bound to `target`.
+    pub fn apply(&self, context: &WebGl2RenderingContext, target: u32) {
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/texParameter
+        let parameters = [
+            (WebGl2RenderingContext::TEXTURE_MAG_FILTER, self.mag_filter),
+            (WebGl2RenderingContext::TEXTURE_MIN_FILTER, self.min_filter),
+            (WebGl2RenderingContext::TEXTURE_WRAP_S, self.wrap_s),
+            (WebGl2RenderingContext::TEXTURE_WRAP_T, self.wrap_t),
+        ];
+
+        for (name, value) in parameters {
+            context.tex_parameteri(target, name, value as i32);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Texture {
+    texture: WebGlTexture,
+    width: u32,
+    height: u32,
+}
+
+impl Texture {
+    /// `srgb` is for color data (base color, emissive), so that sampling
+    /// returns linear values; leave it off for normals, roughness etc.
+    pub fn from_rgba8(
+        context: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        srgb: bool,
+        sampler: Sampler,
+    ) -> Result<Self, String> {
+        let expected = pixel_count(width, height, 4)?;
+        if pixels.len() != expected {
+            return Err(format!(
+                "Expected {} bytes of RGBA for {}x{}, got {}",
+                expected,
+                width,
+                height,
+                pixels.len()
+            ));
+        }
+
+        let internal_format = if srgb {
+            WebGl2RenderingContext::SRGB8_ALPHA8
+        } else {
+            WebGl2RenderingContext::RGBA8
+        };
+
+        let texture = Self::create(context)?;
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/texImage2D
+        context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                WebGl2RenderingContext::TEXTURE_2D,
+                0,
+                internal_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                WebGl2RenderingContext::RGBA,
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(pixels),
+            )
+            .map_err(|_| String::from("Unable to upload texture"))?;
+
+        Ok(Self::finish(context, texture, width, height, sampler))
+    }
+
+    /// Linear RGB floats, stored as half floats.
+    pub fn from_rgb_f32(
+        context: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        pixels: &[f32],
+        sampler: Sampler,
+    ) -> Result<Self, String> {
+        let expected = pixel_count(width, height, 3)?;
+        if pixels.len() != expected {
+            return Err(format!(
+                "Expected {} floats of RGB for {}x{}, got {}",
+                expected,
+                width,
+                height,
+                pixels.len()
+            ));
+        }
+
+        let texture = Self::create(context)?;
+
+        // https://rustwasm.github.io/wasm-bindgen/api/js_sys/struct.Float32Array.html#method.view
+        // `view` is only valid until the next allocation, so upload right away
+        let view = unsafe { js_sys::Float32Array::view(pixels) };
+
+        context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+                WebGl2RenderingContext::TEXTURE_2D,
+                0,
+                WebGl2RenderingContext::RGB16F as i32,
+                width as i32,
+                height as i32,
+                0,
+                WebGl2RenderingContext::RGB,
+                WebGl2RenderingContext::FLOAT,
+                Some(&view),
+            )
+            .map_err(|_| String::from("Unable to upload texture"))?;
+
+        Ok(Self::finish(context, texture, width, height, sampler))
+    }
+
+    pub fn from_png(
+        context: &WebGl2RenderingContext,
+        bytes: &[u8],
+        srgb: bool,
+        sampler: Sampler,
+    ) -> Result<Self, String> {
+        let (width, height, pixels) = decode_png(bytes)?;
+        Self::from_rgba8(context, width, height, &pixels, srgb, sampler)
+    }
+
+    /// A 1x1 texture, e.g. a stand-in for a missing map.
+    pub fn solid(context: &WebGl2RenderingContext, rgba: [u8; 4]) -> Result<Self, String> {
+        let sampler = Sampler {
+            min_filter: WebGl2RenderingContext::NEAREST,
+            mag_filter: WebGl2RenderingContext::NEAREST,
+            ..Sampler::default()
+        };
+
+        Self::from_rgba8(context, 1, 1, &rgba, false, sampler)
+    }
+
+    pub fn texture(&self) -> &WebGlTexture {
+        &self.texture
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Binds to texture unit `unit` and points `sampler` (a uniform
+    /// location) at it.
+    pub fn bind(
+        &self,
+        context: &WebGl2RenderingContext,
+        unit: u32,
+        sampler: Option<&web_sys::WebGlUniformLocation>,
+    ) {
+        context.active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.texture));
+        context.uniform1i(sampler, unit as i32);
+    }
+
+    fn create(context: &WebGl2RenderingContext) -> Result<WebGlTexture, String> {
+        let texture = context
+            .create_texture()
+            .ok_or_else(|| String::from("Unable to create texture"))?;
+
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture));
+        // rows are tightly packed, whatever the width
+        context.pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 1);
+
+        Ok(texture)
+    }
+
+    fn finish(
+        context: &WebGl2RenderingContext,
+        texture: WebGlTexture,
+        width: u32,
+        height: u32,
+        sampler: Sampler,
+    ) -> Self {
+        sampler.apply(context, WebGl2RenderingContext::TEXTURE_2D);
+
+        if sampler.uses_mipmaps() {
+            context.generate_mipmap(WebGl2RenderingContext::TEXTURE_2D);
+        }
+
+        context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+
+        Texture {
+            texture,
+            width,
+            height,
+        }
+    }
+}
+
+/// `width * height * channels`, for sizes `texImage2D` can take.
+fn pixel_count(width: u32, height: u32, channels: usize) -> Result<usize, String> {
+    let too_large = || format!("Texture too large: {}x{}", width, height);
+
+    if i32::try_from(width).is_err() || i32::try_from(height).is_err() {
+        return Err(too_large());
+    }
+
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or_else(too_large)
+}
+
+/// Width, height and RGBA8 pixels, top row first.
+pub fn decode_png(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    let mut decoder = png::Decoder::new(bytes);
+    // palettes and 16-bit channels become 8-bit RGB(A) or gray
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    let buffer = &buffer[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer.to_vec(),
+        png::ColorType::Rgb => buffer
+            .chunks(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return Err(String::from("Palette was not expanded")),
+    };
+
+    Ok((info.width, info.height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_counts_are_checked() {
+        assert_eq!(pixel_count(4, 2, 4), Ok(32));
+        assert_eq!(pixel_count(0, 2, 3), Ok(0));
+        assert!(pixel_count(u32::MAX, 2, 4).is_err());
+        assert!(pixel_count(1 << 31, 1, 1).is_err());
+    }
+}
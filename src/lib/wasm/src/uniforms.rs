@@ -0,0 +1,572 @@
+// Uniform buffer objects: blocks of uniforms stored in a buffer, shared by
+// every program bound to the same binding point.
+//
+// `Std140Layout` computes where each member of a `layout(std140)` block
+// lives (https://registry.khronos.org/OpenGL/specs/es/3.0/es_spec_3.0.pdf,
+// section 2.12.6.4), and `UniformData` writes values there with the padding
+// the rules call for: vec3 aligned like vec4, array elements and matrix
+// columns 16 bytes apart.
+//
+// `FrameUniforms` is the block every program can read per frame data from,
+// the camera matrices included. There is one per context, shared by every
+// box and program drawing on it, and uploaded once per frame:
+//
+//   // in the shader, after FRAME_BLOCK
+//   float t = frame.time;
+//   gl_Position = frame.viewProjection * uModelMatrix * aVertexPosition;
+
+extern crate nalgebra_glm as glm;
+extern crate wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlProgram};
+
+use crate::camera::Camera;
+
+/// Types of block members.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Std140 {
+    Float,
+    Int,
+    UInt,
+    Bool,
+    Vec2,
+    Vec3,
+    Vec4,
+    IVec2,
+    IVec3,
+    IVec4,
+    Mat2,
+    Mat3,
+    Mat4,
+}
+
+impl Std140 {
+    /// Columns, and components per column.
+    fn shape(&self) -> (usize, usize) {
+        match self {
+            Std140::Float | Std140::Int | Std140::UInt | Std140::Bool => (1, 1),
+            Std140::Vec2 | Std140::IVec2 => (1, 2),
+            Std140::Vec3 | Std140::IVec3 => (1, 3),
+            Std140::Vec4 | Std140::IVec4 => (1, 4),
+            Std140::Mat2 => (2, 2),
+            Std140::Mat3 => (3, 3),
+            Std140::Mat4 => (4, 4),
+        }
+    }
+
+    /// 4-byte components in one value.
+    pub fn components(&self) -> usize {
+        let (columns, rows) = self.shape();
+        columns * rows
+    }
+
+    /// Base alignment in bytes, outside of an array.
+    pub fn alignment(&self) -> usize {
+        match self.shape() {
+            (1, 1) => 4,
+            (1, 2) => 8,
+            // vec3 aligns like vec4, and matrices like arrays of vec4
+            _ => 16,
+        }
+    }
+
+    /// Bytes taken by one value, outside of an array.
+    pub fn size(&self) -> usize {
+        match self.shape() {
+            (1, rows) => rows * 4,
+            (columns, _) => columns * 16,
+        }
+    }
+}
+
+fn round_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub kind: Std140,
+    /// Elements, 1 unless an array.
+    pub length: usize,
+    pub array: bool,
+    /// Bytes from the start of the block.
+    pub offset: usize,
+}
+
+impl Member {
+    /// Bytes from one array element to the next.
+    pub fn stride(&self) -> usize {
+        match self.array {
+            true => round_up(self.kind.size(), 16),
+            false => self.kind.size(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.stride() * self.length
+    }
+}
+
+/// Offsets of the members of a `layout(std140)` block, in declaration
+/// order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Std140Layout {
+    members: Vec<Member>,
+    end: usize,
+}
+
+impl Std140Layout {
+    pub fn new() -> Self {
+        Std140Layout::default()
+    }
+
+    pub fn member(self, name: &str, kind: Std140) -> Self {
+        self.push(name, kind, 1, false)
+    }
+
+    /// `kind name[length]`, every element aligned to 16 bytes.
+    pub fn array(self, name: &str, kind: Std140, length: usize) -> Self {
+        self.push(name, kind, length, true)
+    }
+
+    fn push(mut self, name: &str, kind: Std140, length: usize, array: bool) -> Self {
+        let alignment = match array {
+            true => 16,
+            false => kind.alignment(),
+        };
+
+        let member = Member {
+            name: name.to_string(),
+            kind,
+            length,
+            array,
+            offset: round_up(self.end, alignment),
+        };
+
+        // arrays and matrices are whole vec4s, so whatever follows them
+        // starts on the next 16 bytes as it should
+        self.end = member.offset + member.size();
+        self.members.push(member);
+        self
+    }
+
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    /// Bytes of the whole block, a multiple of 16.
+    pub fn size(&self) -> usize {
+        round_up(self.end, 16)
+    }
+}
+
+/// The bytes of a block, laid out as `Std140Layout` says.
+#[derive(Clone, Debug)]
+pub struct UniformData {
+    layout: Std140Layout,
+    bytes: Vec<u8>,
+}
+
+impl UniformData {
+    pub fn new(layout: Std140Layout) -> Self {
+        UniformData {
+            bytes: vec![0; layout.size()],
+            layout,
+        }
+    }
+
+    pub fn layout(&self) -> &Std140Layout {
+        &self.layout
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Sets `name` from `values`: the components of each value, matrices
+    /// column-major, for as many elements as given.
+    pub fn set_f32(&mut self, name: &str, values: &[f32]) -> Result<(), String> {
+        self.set_f32_at(name, 0, values)
+    }
+
+    /// Like `set_f32`, from array element `index` on.
+    pub fn set_f32_at(&mut self, name: &str, index: usize, values: &[f32]) -> Result<(), String> {
+        let words = values.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        self.write(name, index, &words)
+    }
+
+    /// For int, uint and bool members (bools as 0 or 1).
+    pub fn set_i32(&mut self, name: &str, values: &[i32]) -> Result<(), String> {
+        let words = values.iter().map(|&v| v as u32).collect::<Vec<_>>();
+        self.write(name, 0, &words)
+    }
+
+    fn write(&mut self, name: &str, index: usize, words: &[u32]) -> Result<(), String> {
+        let member = self
+            .layout
+            .get(name)
+            .ok_or_else(|| format!("Unknown block member: {}", name))?;
+
+        let components = member.kind.components();
+        let count = words.len() / components;
+        if words.is_empty()
+            || !words.len().is_multiple_of(components)
+            || index + count > member.length
+        {
+            return Err(format!(
+                "{} takes {} value(s) of {} component(s), got {} from element {}",
+                name,
+                member.length,
+                components,
+                words.len(),
+                index
+            ));
+        }
+
+        let (columns, rows) = member.kind.shape();
+        for (element, value) in words.chunks(components).enumerate() {
+            let start = member.offset + (index + element) * member.stride();
+
+            for column in 0..columns {
+                for row in 0..rows {
+                    // matrix columns are padded to 16 bytes
+                    let at = start + column * 16 + row * 4;
+                    let word = value[column * rows + row];
+                    self.bytes[at..at + 4].copy_from_slice(&word.to_le_bytes());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A uniform buffer holding one block, attached to a binding point.
+pub struct UniformBlock {
+    name: String,
+    binding: u32,
+    buffer: WebGlBuffer,
+    pub data: UniformData,
+}
+
+impl UniformBlock {
+    /// `name` is the block name in GLSL, e.g. `Frame` for
+    /// `uniform Frame { ... } frame;`.
+    pub fn new(
+        context: &WebGl2RenderingContext,
+        name: &str,
+        binding: u32,
+        layout: Std140Layout,
+    ) -> Result<Self, String> {
+        let buffer = context
+            .create_buffer()
+            .ok_or_else(|| String::from("Unable to create uniform buffer"))?;
+
+        context.bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, Some(&buffer));
+        context.buffer_data_with_i32(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            layout.size() as i32,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+        context.bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, None);
+
+        Ok(UniformBlock {
+            name: name.to_string(),
+            binding,
+            buffer,
+            data: UniformData::new(layout),
+        })
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    /// Points the program's block at this buffer. Returns false when the
+    /// program doesn't use the block.
+    pub fn bind_program(&self, context: &WebGl2RenderingContext, program: &WebGlProgram) -> bool {
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/getUniformBlockIndex
+        let index = context.get_uniform_block_index(program, &self.name);
+        if index == WebGl2RenderingContext::INVALID_INDEX {
+            return false;
+        }
+
+        context.uniform_block_binding(program, index, self.binding);
+        true
+    }
+
+    /// Sends `data` to the buffer, for every program bound to the block.
+    pub fn upload(&self, context: &WebGl2RenderingContext) {
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/bufferSubData
+        context.bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, Some(&self.buffer));
+        context.buffer_sub_data_with_i32_and_u8_array(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            0,
+            self.data.bytes(),
+        );
+        context.bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, None);
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/bindBufferBase
+        context.bind_buffer_base(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            self.binding,
+            Some(&self.buffer),
+        );
+    }
+}
+
+/// Paste into a shader to read the per frame data:
+///
+///   float t = frame.time;
+///   gl_Position = frame.viewProjection * uModelMatrix * aVertexPosition;
+///
+/// `mouse` is in pixels from the bottom left, with z 1 while pressed.
+pub const FRAME_BLOCK: &str = "layout(std140) uniform Frame {
+  float time;
+  float delta;
+  int count;
+  vec2 resolution;
+  vec4 mouse;
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 cameraPosition;
+} frame;
+";
+
+pub const FRAME_BLOCK_NAME: &str = "Frame";
+
+/// `FRAME_BLOCK`, for pages to paste into the shaders they pass to
+/// `GlBox`.
+#[wasm_bindgen]
+pub fn frame_block() -> String {
+    FRAME_BLOCK.to_string()
+}
+
+/// Uniform buffer binding point of `FRAME_BLOCK`.
+pub const FRAME_BINDING: u32 = 0;
+
+/// The layout of `FRAME_BLOCK`.
+pub fn frame_layout() -> Std140Layout {
+    Std140Layout::new()
+        .member("time", Std140::Float)
+        .member("delta", Std140::Float)
+        .member("count", Std140::Int)
+        .member("resolution", Std140::Vec2)
+        .member("mouse", Std140::Vec4)
+        .member("view", Std140::Mat4)
+        .member("projection", Std140::Mat4)
+        .member("viewProjection", Std140::Mat4)
+        .member("cameraPosition", Std140::Vec4)
+}
+
+/// The `FrameUniforms` of a context, see `FrameUniforms::shared`.
+pub type SharedFrame = Rc<RefCell<FrameUniforms>>;
+
+thread_local! {
+    // by context; weak, so that the buffer goes with the last box using it
+    static SHARED_FRAMES: RefCell<Vec<(WebGl2RenderingContext, Weak<RefCell<FrameUniforms>>)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// The buffer behind `FRAME_BLOCK`. Set the fields, then `advance` once per
+/// frame.
+///
+/// Every `FrameUniforms` binds its buffer to `FRAME_BINDING`, so a context
+/// should have just one, from `shared`.
+pub struct FrameUniforms {
+    block: UniformBlock,
+    /// Seconds.
+    pub time: f32,
+    /// Seconds since the previous frame.
+    pub delta: f32,
+    /// Frames since the start.
+    pub count: i32,
+    /// Of the canvas, in pixels.
+    pub resolution: [f32; 2],
+    /// In pixels from the bottom left, with z 1 while pressed.
+    pub mouse: [f32; 4],
+    /// World to view space; identity until `set_camera`.
+    pub view: glm::Mat4,
+    pub projection: glm::Mat4,
+    /// In world space.
+    pub camera_position: glm::Vec3,
+    started: bool,
+}
+
+impl FrameUniforms {
+    pub fn new(context: &WebGl2RenderingContext) -> Result<Self, String> {
+        Ok(FrameUniforms {
+            block: UniformBlock::new(context, FRAME_BLOCK_NAME, FRAME_BINDING, frame_layout())?,
+            time: 0.0,
+            delta: 0.0,
+            count: 0,
+            resolution: [1.0, 1.0],
+            mouse: [0.0; 4],
+            view: glm::Mat4::identity(),
+            projection: glm::Mat4::identity(),
+            camera_position: glm::Vec3::zeros(),
+            started: false,
+        })
+    }
+
+    /// The one of `context`, created by the first caller. Boxes drawing on
+    /// the same context get the same buffer.
+    pub fn shared(context: &WebGl2RenderingContext) -> Result<SharedFrame, String> {
+        SHARED_FRAMES.with(|frames| {
+            let mut frames = frames.borrow_mut();
+            frames.retain(|(_, frame)| frame.strong_count() > 0);
+
+            if let Some(frame) = frames
+                .iter()
+                .find(|(owner, _)| owner == context)
+                .and_then(|(_, frame)| frame.upgrade())
+            {
+                return Ok(frame);
+            }
+
+            let frame = Rc::new(RefCell::new(FrameUniforms::new(context)?));
+            frames.push((context.clone(), Rc::downgrade(&frame)));
+            Ok(frame)
+        })
+    }
+
+    /// Takes the matrices and the position of `camera`.
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.view = camera.view_matrix();
+        self.projection = camera.projection_matrix();
+        self.camera_position = camera.position;
+    }
+
+    /// See `UniformBlock::bind_program`.
+    pub fn bind_program(&self, context: &WebGl2RenderingContext, program: &WebGlProgram) -> bool {
+        self.block.bind_program(context, program)
+    }
+
+    /// Moves to the frame at `time`, in seconds, and uploads it. Already at
+    /// `time`, it only uploads, so that everything sharing the block may
+    /// call it each frame.
+    pub fn advance(&mut self, context: &WebGl2RenderingContext, time: f32) -> Result<(), String> {
+        self.step(time);
+        self.upload(context)
+    }
+
+    /// Uploads the fields without moving to the next frame, e.g. before the
+    /// first one.
+    pub fn upload(&mut self, context: &WebGl2RenderingContext) -> Result<(), String> {
+        let data = &mut self.block.data;
+        data.set_f32("time", &[self.time])?;
+        data.set_f32("delta", &[self.delta])?;
+        data.set_i32("count", &[self.count])?;
+        data.set_f32("resolution", &self.resolution)?;
+        data.set_f32("mouse", &self.mouse)?;
+        data.set_f32("view", self.view.as_slice())?;
+        data.set_f32("projection", self.projection.as_slice())?;
+        data.set_f32("viewProjection", (self.projection * self.view).as_slice())?;
+        let position = &self.camera_position;
+        data.set_f32("cameraPosition", &[position.x, position.y, position.z, 1.0])?;
+
+        self.block.upload(context);
+        Ok(())
+    }
+
+    fn step(&mut self, time: f32) {
+        if self.started && time == self.time {
+            return;
+        }
+        if self.started {
+            self.delta = (time - self.time).max(0.0);
+            self.count += 1;
+        }
+        self.time = time;
+        self.started = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(layout: &Std140Layout) -> Vec<usize> {
+        layout.members().iter().map(|m| m.offset).collect()
+    }
+
+    fn word(data: &UniformData, offset: usize) -> f32 {
+        f32::from_le_bytes(data.bytes()[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn vec3_aligns_like_vec4() {
+        // a float fits in the padding after a vec3, a vec3 after a float doesn't
+        let layout = Std140Layout::new()
+            .member("a", Std140::Vec3)
+            .member("b", Std140::Float)
+            .member("c", Std140::Vec3)
+            .member("d", Std140::Vec2);
+
+        assert_eq!(offsets(&layout), [0, 12, 16, 32]);
+        assert_eq!(layout.size(), 48);
+    }
+
+    #[test]
+    fn array_elements_pad_to_16_bytes() {
+        let layout = Std140Layout::new()
+            .array("weights", Std140::Float, 3)
+            .member("after", Std140::Float)
+            .array("points", Std140::Vec3, 2);
+
+        assert_eq!(offsets(&layout), [0, 48, 64]);
+        assert_eq!(layout.get("weights").unwrap().stride(), 16);
+        assert_eq!(layout.size(), 96);
+
+        let mut data = UniformData::new(layout);
+        data.set_f32("weights", &[1.0, 2.0, 3.0]).unwrap();
+        data.set_f32_at("points", 1, &[4.0, 5.0, 6.0]).unwrap();
+
+        assert_eq!(word(&data, 16), 2.0);
+        assert_eq!(word(&data, 32), 3.0);
+        assert_eq!(word(&data, 80), 4.0);
+        assert_eq!(word(&data, 88), 6.0);
+        assert!(data.set_f32("weights", &[1.0; 4]).is_err());
+        assert!(data.set_f32_at("points", 2, &[0.0; 3]).is_err());
+    }
+
+    #[test]
+    fn mat3_columns_pad_to_vec4() {
+        let layout = Std140Layout::new()
+            .member("before", Std140::Float)
+            .member("normal", Std140::Mat3)
+            .member("after", Std140::Float);
+
+        assert_eq!(offsets(&layout), [0, 16, 64]);
+        assert_eq!(layout.size(), 80);
+
+        let mut data = UniformData::new(layout);
+        let columns = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0];
+        data.set_f32("normal", &columns).unwrap();
+
+        assert_eq!(word(&data, 16 + 8), 3.0);
+        // padding after the first column
+        assert_eq!(word(&data, 16 + 12), 0.0);
+        assert_eq!(word(&data, 32), 4.0);
+        assert_eq!(word(&data, 48 + 8), 9.0);
+    }
+
+    #[test]
+    fn frame_block_layout() {
+        let layout = frame_layout();
+
+        // the matrices start on the 16 bytes after mouse, packed
+        assert_eq!(offsets(&layout), [0, 4, 8, 16, 32, 48, 112, 176, 240]);
+        assert_eq!(layout.size(), 256);
+    }
+}
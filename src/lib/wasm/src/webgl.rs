@@ -1,8 +1,11 @@
-use wasm_bindgen::JsCast;
+use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{
     WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader, WebGlUniformLocation,
 };
 
+use crate::pipeline::PipelineState;
+use crate::state::GlState;
+
 pub struct CanvasProperties {
     pub height: f32,
     pub width: f32,
@@ -84,6 +87,27 @@ pub fn link_shader_program(
     context: &WebGl2RenderingContext,
     vertex_shader: &WebGlShader,
     fragment_shader: &WebGlShader,
+) -> Result<WebGlProgram, String> {
+    link_program(context, vertex_shader, fragment_shader, &[])
+}
+
+/// Links a program whose vertex shader outputs `varyings` are captured,
+/// interleaved in that order, into the buffer bound to transform feedback
+/// binding 0.
+pub fn link_transform_feedback_program(
+    context: &WebGl2RenderingContext,
+    vertex_shader: &WebGlShader,
+    fragment_shader: &WebGlShader,
+    varyings: &[&str],
+) -> Result<WebGlProgram, String> {
+    link_program(context, vertex_shader, fragment_shader, varyings)
+}
+
+fn link_program(
+    context: &WebGl2RenderingContext,
+    vertex_shader: &WebGlShader,
+    fragment_shader: &WebGlShader,
+    varyings: &[&str],
 ) -> Result<WebGlProgram, String> {
     let program = context
         .create_program()
@@ -91,6 +115,21 @@ pub fn link_shader_program(
 
     context.attach_shader(&program, vertex_shader);
     context.attach_shader(&program, fragment_shader);
+
+    if !varyings.is_empty() {
+        let names = varyings
+            .iter()
+            .map(|name| JsValue::from_str(name))
+            .collect::<js_sys::Array>();
+
+        // https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/transformFeedbackVaryings
+        context.transform_feedback_varyings(
+            &program,
+            &names,
+            WebGl2RenderingContext::INTERLEAVED_ATTRIBS,
+        );
+    }
+
     context.link_program(&program);
 
     let link_success = context
@@ -118,22 +157,24 @@ pub struct ShaderInfo<'a> {
     pub program_model_view_matrix: &'a WebGlUniformLocation,
 }
 
+/// Cleared to black, depth tested with LEQUAL.
+static SQUARE_PIPELINE: PipelineState = PipelineState::new()
+    .depth(WebGl2RenderingContext::LEQUAL, true)
+    .clear_color([0.0, 0.0, 0.0, 1.0]) // black, fully-opaque
+    .clear_depth(1.0);
+
 pub fn draw(
-    context: &WebGl2RenderingContext,
+    state: &mut GlState,
     info: &ShaderInfo,
     position_buffer: &WebGlBuffer,
     color_buffer: &WebGlBuffer,
     vec_projection_matrix: &[f32],
     vec_model_view_matrix: &[f32],
 ) {
-    context.clear_color(0.0, 0.0, 0.0, 1.0); // black, fully-opaque
-    context.clear_depth(1.0);
-    context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
-    context.enable(WebGl2RenderingContext::DEPTH_TEST);
-    context.depth_func(WebGl2RenderingContext::LEQUAL);
+    SQUARE_PIPELINE.clear(state);
+    SQUARE_PIPELINE.apply(state);
 
-    context
-        .clear(WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT);
+    let context = state.context().clone();
 
     // Tell WebGL how to pull out the positions from the position
     // buffer into the VertexPosition attribute
@@ -144,7 +185,7 @@ pub fn draw(
         let stride = 0;
         let offset = 0;
 
-        context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&position_buffer));
+        state.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(position_buffer));
         context.vertex_attrib_pointer_with_i32(
             info.vertex_position,
             num_components,
@@ -165,7 +206,7 @@ pub fn draw(
         let normalize = false;
         let stride = 0;
         let offset = 0;
-        context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&color_buffer));
+        state.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(color_buffer));
         context.vertex_attrib_pointer_with_i32(
             info.vertex_color,
             num_components,
@@ -178,19 +219,19 @@ pub fn draw(
     }
 
     // Tell WebGL to use our program when drawing
-    context.use_program(Some(info.program));
+    state.use_program(Some(info.program));
 
     // Set the shader uniforms
     context.uniform_matrix4fv_with_f32_array(
         Some(info.program_projection_matrix),
         false,
-        &vec_projection_matrix,
+        vec_projection_matrix,
     );
 
     context.uniform_matrix4fv_with_f32_array(
         Some(info.program_model_view_matrix),
         false,
-        &vec_model_view_matrix,
+        vec_model_view_matrix,
     );
 
     let offset = 0;
@@ -198,3 +239,95 @@ pub fn draw(
     // let data_type = WebGl2RenderingContext::UNSIGNED_SHORT;
     context.draw_arrays(WebGl2RenderingContext::TRIANGLE_STRIP, offset, vertex_count);
 }
+
+/// Draws one triangle covering the viewport; no vertex buffers needed.
+pub static FULLSCREEN_VS: &str = r#"#version 300 es
+out vec2 vNdc;
+
+void main() {
+  vec2 p = vec2(float((gl_VertexID & 1) << 2), float((gl_VertexID & 2) << 1)) - 1.0;
+  vNdc = p;
+  gl_Position = vec4(p, 0.0, 1.0);
+}
+"#;
+
+/// A program drawing `FULLSCREEN_VS` with `fragment_shader_source`, which
+/// gets `in vec2 vNdc`.
+pub fn fullscreen_program(
+    context: &WebGl2RenderingContext,
+    fragment_shader_source: &str,
+) -> Result<WebGlProgram, String> {
+    let vs = compile_shader(
+        context,
+        WebGl2RenderingContext::VERTEX_SHADER,
+        FULLSCREEN_VS,
+    )?;
+    let fs = compile_shader(
+        context,
+        WebGl2RenderingContext::FRAGMENT_SHADER,
+        fragment_shader_source,
+    )?;
+
+    link_shader_program(context, &vs, &fs)
+}
+
+/// Words GLSL ES 3.00 reserves for future use (section 3.7), found in
+/// `source`. A shader naming anything after one of them fails to compile.
+#[cfg(test)]
+pub(crate) fn reserved_words_in(source: &str) -> Vec<&str> {
+    const RESERVED: &[&str] = &[
+        "attribute", "varying", "coherent", "volatile", "restrict", "readonly", "writeonly",
+        "resource", "atomic_uint", "noperspective", "patch", "sample", "subroutine", "common",
+        "partition", "active", "asm", "class", "union", "enum", "typedef", "template", "this",
+        "goto", "inline", "noinline", "public", "static", "extern", "external", "interface",
+        "long", "short", "double", "half", "fixed", "unsigned", "superp", "input", "output",
+        "hvec2", "hvec3", "hvec4", "dvec2", "dvec3", "dvec4", "fvec2", "fvec3", "fvec4",
+        "sampler3DRect", "filter", "image1D", "image2D", "image3D", "imageCube", "iimage1D",
+        "iimage2D", "iimage3D", "iimageCube", "uimage1D", "uimage2D", "uimage3D", "uimageCube",
+        "image1DArray", "image2DArray", "iimage1DArray", "iimage2DArray", "uimage1DArray",
+        "uimage2DArray", "imageBuffer", "iimageBuffer", "uimageBuffer", "sampler1D",
+        "sampler1DShadow", "sampler1DArray", "sampler1DArrayShadow", "isampler1D",
+        "isampler1DArray", "usampler1D", "usampler1DArray", "sampler2DRect",
+        "sampler2DRectShadow", "isampler2DRect", "usampler2DRect", "samplerBuffer",
+        "isamplerBuffer", "usamplerBuffer", "sampler2DMS", "isampler2DMS", "usampler2DMS",
+        "sampler2DMSArray", "isampler2DMSArray", "usampler2DMSArray", "sizeof", "cast",
+        "namespace", "using",
+    ];
+
+    // comments may say anything
+    let code = source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>();
+
+    let mut found = code
+        .iter()
+        .flat_map(|line| line.split(|c: char| !c.is_ascii_alphanumeric() && c != '_'))
+        .filter(|word| RESERVED.contains(word) || word.contains("__"))
+        .collect::<Vec<_>>();
+    found.dedup();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_words_are_found() {
+        let source = "float sample = 1.0; // a sample\nint x__y;\nfloat samples;\n";
+        assert_eq!(reserved_words_in(source), ["sample", "x__y"]);
+    }
+
+    #[test]
+    fn shader_files_avoid_reserved_words() {
+        assert!(reserved_words_in(FULLSCREEN_VS).is_empty());
+
+        let dir = format!("{}/../shaders", env!("CARGO_MANIFEST_DIR"));
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(reserved_words_in(&source), Vec::<&str>::new(), "{:?}", path);
+        }
+    }
+}
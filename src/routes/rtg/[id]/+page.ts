@@ -0,0 +1,27 @@
+import { error } from '@sveltejs/kit';
+import type { PageLoad } from './$types';
+import init, { shader_manifest } from '$lib/wasm/pkg';
+
+// the shader manifest lives in the wasm binary, so render in the browser
+export const ssr = false;
+
+// e.g. /rtg/perlin-noise?effects=bloom,vignette
+//      /rtg/fbm?precision=half&effects=tone-mapping
+export const load: PageLoad = async ({ params, url }) => {
+  await init();
+
+  const manifest: { id: string; inputs: string[] }[] = JSON.parse(shader_manifest());
+  const entry = manifest.find((e) => e.id === params.id);
+  if (entry === undefined) {
+    throw error(404, `Unknown shader: ${params.id}`);
+  }
+
+  const effects = url.searchParams.get('effects');
+
+  return {
+    id: entry.id,
+    mouse: entry.inputs.includes('mouse'),
+    effects: effects ? effects.split(',') : [],
+    precision: url.searchParams.get('precision') ?? 'byte'
+  };
+};